@@ -2,7 +2,7 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion, Benchmark
 use imaginary::image::operations::*;
 use imaginary::image::pipeline_executor::execute_pipeline;
 use imaginary::image::pipeline_types::{PipelineOperationSpec, SupportedOperation};
-use imaginary::image::params::{ResizeParams, CropParams, RotateParams, BlurParams, FormatConversionParams};
+use imaginary::image::params::{ResizeParams, CropParams, RotateParams, BlurParams, FormatConversionParams, ResampleFilter, ThumbnailParams};
 use image::{DynamicImage, ImageBuffer, RgbImage};
 use serde_json::json;
 
@@ -64,6 +64,36 @@ fn bench_resize(c: &mut Criterion) {
     group.finish();
 }
 
+// Benchmark thumbnail resampling filters (speed vs quality)
+fn bench_thumbnail_filters(c: &mut Criterion) {
+    let mut group = c.benchmark_group("thumbnail_filters");
+    
+    let img = create_test_image(1920, 1080);
+    
+    let filters = vec![
+        (ResampleFilter::Nearest, "nearest"),
+        (ResampleFilter::Lanczos3, "lanczos3"),
+    ];
+    
+    for (filter, filter_name) in filters {
+        group.bench_with_input(
+            BenchmarkId::new("thumbnail", filter_name),
+            &img,
+            |b, img| {
+                let params = ThumbnailParams { width: 200, height: 200, filter, ..Default::default() };
+                b.iter(|| {
+                    black_box(thumbnail(
+                        black_box(img.clone()),
+                        black_box(&params),
+                    ))
+                })
+            },
+        );
+    }
+    
+    group.finish();
+}
+
 // Benchmark crop operations
 fn bench_crop(c: &mut Criterion) {
     let mut group = c.benchmark_group("crop_operations");
@@ -108,7 +138,11 @@ fn bench_rotate(c: &mut Criterion) {
             BenchmarkId::new("rotate", format!("{}_degrees", angle)),
             &img,
             |b, img| {
-                let params = RotateParams { degrees: angle };
+                let params = RotateParams {
+                    degrees: angle,
+                    // Only right angles are lossless turns; others rotate within the canvas
+                    keep_canvas: angle % 90.0 != 0.0,
+                };
                 b.iter(|| {
                     black_box(rotate(
                         black_box(img.clone()),
@@ -209,7 +243,8 @@ fn bench_format_conversion(c: &mut Criterion) {
                 |b, img| {
                     let params = FormatConversionParams { 
                         format: format.to_string(), 
-                        quality: Some(*quality) 
+                        quality: Some(*quality),
+                        ..Default::default()
                     };
                     b.iter(|| {
                         black_box(convert_format(
@@ -237,11 +272,13 @@ fn bench_pipeline(c: &mut Criterion) {
             operation: SupportedOperation::Resize,
             params: json!({"width": 400, "height": 300}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Grayscale,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         },
     ];
     
@@ -251,26 +288,31 @@ fn bench_pipeline(c: &mut Criterion) {
             operation: SupportedOperation::Resize,
             params: json!({"width": 800, "height": 600}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Crop,
             params: json!({"x": 100, "y": 100, "width": 600, "height": 400}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Rotate,
             params: json!({"degrees": 90.0}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Blur,
             params: json!({"sigma": 1.5}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::AdjustBrightness,
             params: json!({"value": 10}),
             ignore_failure: false,
+            region: None,
         },
     ];
     
@@ -298,6 +340,7 @@ fn bench_pipeline(c: &mut Criterion) {
 criterion_group!(
     benches,
     bench_resize,
+    bench_thumbnail_filters,
     bench_crop,
     bench_rotate,
     bench_color_operations,
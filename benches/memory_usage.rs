@@ -35,11 +35,13 @@ fn bench_memory_by_image_size(c: &mut Criterion) {
             operation: SupportedOperation::Resize,
             params: json!({"width": 400, "height": 300}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Grayscale,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         },
     ];
     
@@ -75,6 +77,7 @@ fn bench_memory_by_operation_count(c: &mut Criterion) {
                 operation: SupportedOperation::Resize,
                 params: json!({"width": 400, "height": 300}),
                 ignore_failure: false,
+                region: None,
             },
         ]),
         (3, "three_ops", vec![
@@ -82,16 +85,19 @@ fn bench_memory_by_operation_count(c: &mut Criterion) {
                 operation: SupportedOperation::Resize,
                 params: json!({"width": 400, "height": 300}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Grayscale,
                 params: json!({}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Blur,
                 params: json!({"sigma": 1.0}),
                 ignore_failure: false,
+                region: None,
             },
         ]),
         (5, "five_ops", vec![
@@ -99,26 +105,31 @@ fn bench_memory_by_operation_count(c: &mut Criterion) {
                 operation: SupportedOperation::Resize,
                 params: json!({"width": 600, "height": 400}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Crop,
                 params: json!({"x": 50, "y": 50, "width": 500, "height": 300}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Rotate,
                 params: json!({"degrees": 90.0}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::AdjustBrightness,
                 params: json!({"value": 10}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Sharpen,
                 params: json!({}),
                 ignore_failure: false,
+                region: None,
             },
         ]),
     ];
@@ -162,6 +173,7 @@ fn bench_memory_by_format(c: &mut Criterion) {
                 operation: SupportedOperation::Convert,
                 params,
                 ignore_failure: false,
+                region: None,
             },
         ];
         
@@ -194,11 +206,13 @@ fn bench_memory_cloning_patterns(c: &mut Criterion) {
             operation: SupportedOperation::Resize,
             params: json!({"width": 400, "height": 300}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Grayscale,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         },
     ];
     
@@ -238,11 +252,13 @@ fn bench_memory_concurrent_load(c: &mut Criterion) {
             operation: SupportedOperation::Resize,
             params: json!({"width": 300, "height": 200}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Blur,
             params: json!({"sigma": 1.0}),
             ignore_failure: false,
+            region: None,
         },
     ];
     
@@ -31,6 +31,7 @@ fn bench_pipeline_operations_count(c: &mut Criterion) {
                 operation: SupportedOperation::Resize,
                 params: json!({"width": 400, "height": 300}),
                 ignore_failure: false,
+                region: None,
             },
         ]),
         (3, "three_operations", vec![
@@ -38,16 +39,19 @@ fn bench_pipeline_operations_count(c: &mut Criterion) {
                 operation: SupportedOperation::Resize,
                 params: json!({"width": 400, "height": 300}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Grayscale,
                 params: json!({}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Blur,
                 params: json!({"sigma": 1.0}),
                 ignore_failure: false,
+                region: None,
             },
         ]),
         (5, "five_operations", vec![
@@ -55,26 +59,31 @@ fn bench_pipeline_operations_count(c: &mut Criterion) {
                 operation: SupportedOperation::Resize,
                 params: json!({"width": 600, "height": 400}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Crop,
                 params: json!({"x": 50, "y": 50, "width": 500, "height": 300}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Rotate,
                 params: json!({"degrees": 90.0}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::AdjustBrightness,
                 params: json!({"value": 10}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Sharpen,
                 params: json!({}),
                 ignore_failure: false,
+                region: None,
             },
         ]),
     ];
@@ -114,16 +123,19 @@ fn bench_memory_usage_patterns(c: &mut Criterion) {
             operation: SupportedOperation::Resize,
             params: json!({"width": 400, "height": 300}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Grayscale,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Blur,
             params: json!({"sigma": 2.0}),
             ignore_failure: false,
+            region: None,
         },
     ];
     
@@ -157,16 +169,19 @@ fn bench_concurrent_processing(c: &mut Criterion) {
             operation: SupportedOperation::Resize,
             params: json!({"width": 400, "height": 300}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Grayscale,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Blur,
             params: json!({"sigma": 1.0}),
             ignore_failure: false,
+            region: None,
         },
     ]);
     
@@ -240,6 +255,7 @@ fn bench_format_performance(c: &mut Criterion) {
                 operation: SupportedOperation::Convert,
                 params,
                 ignore_failure: false,
+                region: None,
             },
         ];
         
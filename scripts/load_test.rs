@@ -344,7 +344,7 @@ async fn make_pipeline_request(
     
     // Make request
     let response = client
-        .post(&format!("{}/pipeline", base_url))
+        .post(format!("{}/pipeline", base_url))
         .multipart(form)
         .send()
         .await?;
@@ -0,0 +1,324 @@
+//! Typed Rust client for the `/pipeline` API.
+//!
+//! [`PipelineBuilder`] assembles the operations from the same parameter types the server
+//! parses, so a pipeline that compiles serializes to JSON the server accepts.
+//! [`PipelineClient`] submits it with an uploaded image or an image URL.
+//!
+//! Example usage:
+//! ```no_run
+//! # async fn run(image: Vec<u8>) -> Result<(), imaginary::client::ClientError> {
+//! use imaginary::client::{PipelineBuilder, PipelineClient};
+//!
+//! let client = PipelineClient::new("http://localhost:8080").with_api_key("secret");
+//! let pipeline = PipelineBuilder::new()
+//!     .resize(800, 600)
+//!     .grayscale()
+//!     .convert("webp", Some(80));
+//! let processed = client.process(image, &pipeline).await?;
+//! println!("{} bytes of {}", processed.data.len(), processed.content_type);
+//! # Ok(())
+//! # }
+//! ```
+
+use reqwest::{
+    header::CONTENT_TYPE,
+    multipart::{Form, Part},
+    Client, RequestBuilder,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::image::params::{
+    BlurParams, CropParams, FormatConversionParams, ResizeParams, RotateParams, ThumbnailParams,
+};
+use crate::image::pipeline_types::{PipelineOperationSpec, SupportedOperation};
+
+/// Errors returned by [`PipelineClient`].
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The server rejected the request; `message` is its `error` field when it sent one.
+    #[error("Server returned {status}: {message}")]
+    Server { status: u16, message: String },
+    /// Operation parameters given to [`PipelineBuilder::operation`] could not be serialized.
+    #[error("Invalid operation parameters: {0}")]
+    Params(#[from] serde_json::Error),
+}
+
+/// A processed image returned by the server.
+#[derive(Debug, Clone)]
+pub struct ProcessedImage {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Builds the operations of a pipeline in order.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineBuilder {
+    operations: Vec<PipelineOperationSpec>,
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends any operation with its parameters, for operations without a shorthand below.
+    ///
+    /// Fails if `params` cannot be serialized to JSON (e.g. a map with non-string keys).
+    pub fn operation<P: Serialize>(
+        self,
+        operation: SupportedOperation,
+        params: P,
+    ) -> Result<Self, ClientError> {
+        Ok(self.push(operation, serde_json::to_value(params)?))
+    }
+
+    pub fn resize(self, width: u32, height: u32) -> Self {
+        self.typed(SupportedOperation::Resize, ResizeParams { width, height })
+    }
+
+    pub fn crop(self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        self.typed(
+            SupportedOperation::Crop,
+            CropParams {
+                x,
+                y,
+                width,
+                height,
+            },
+        )
+    }
+
+    pub fn thumbnail(self, width: u32, height: u32) -> Self {
+        self.typed(
+            SupportedOperation::Thumbnail,
+            ThumbnailParams {
+                width,
+                height,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn rotate(self, degrees: f32) -> Self {
+        self.typed(
+            SupportedOperation::Rotate,
+            RotateParams {
+                degrees,
+                keep_canvas: false,
+            },
+        )
+    }
+
+    pub fn blur(self, sigma: f32) -> Self {
+        self.typed(
+            SupportedOperation::Blur,
+            BlurParams {
+                sigma,
+                minampl: None,
+            },
+        )
+    }
+
+    pub fn grayscale(self) -> Self {
+        self.push(SupportedOperation::Grayscale, Value::Null)
+    }
+
+    /// Converts to `format` (`png`, `jpeg`, `webp`, ...), optionally at a JPEG `quality`.
+    pub fn convert(self, format: &str, quality: Option<u8>) -> Self {
+        self.typed(
+            SupportedOperation::Convert,
+            FormatConversionParams {
+                format: format.to_string(),
+                quality,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Lets the server skip the last added operation if it fails instead of failing the request.
+    pub fn ignore_failure(mut self) -> Self {
+        if let Some(spec) = self.operations.last_mut() {
+            spec.ignore_failure = true;
+        }
+        self
+    }
+
+    pub fn operations(&self) -> &[PipelineOperationSpec] {
+        &self.operations
+    }
+
+    pub fn build(self) -> Vec<PipelineOperationSpec> {
+        self.operations
+    }
+
+    /// Appends an operation with one of the server's parameter types.
+    fn typed<P: Serialize>(self, operation: SupportedOperation, params: P) -> Self {
+        let params = serde_json::to_value(params)
+            .expect("operation parameter types serialize to JSON objects");
+        self.push(operation, params)
+    }
+
+    fn push(mut self, operation: SupportedOperation, params: Value) -> Self {
+        self.operations.push(PipelineOperationSpec {
+            operation,
+            ignore_failure: false,
+            params,
+            region: None,
+        });
+        self
+    }
+}
+
+/// Client for a running imaginary-rs server.
+#[derive(Debug, Clone)]
+pub struct PipelineClient {
+    http: Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl PipelineClient {
+    /// Creates a client for the server at `base_url`, e.g. `http://localhost:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+        }
+    }
+
+    /// Sends `key` in the `x-api-key` header, for servers with a security key configured.
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Uses `http` instead of a default `reqwest::Client`, e.g. to set timeouts.
+    pub fn with_http_client(mut self, http: Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Uploads `image` as multipart/form-data and applies `pipeline` to it.
+    pub async fn process(
+        &self,
+        image: Vec<u8>,
+        pipeline: &PipelineBuilder,
+    ) -> Result<ProcessedImage, ClientError> {
+        let form = Form::new()
+            .part("image", Part::bytes(image).file_name("image"))
+            .text("operations", operations_json(pipeline));
+        self.send(self.http.post(self.pipeline_url()).multipart(form))
+            .await
+    }
+
+    /// Has the server fetch the image at `url` and apply `pipeline` to it.
+    pub async fn process_url(
+        &self,
+        url: &str,
+        pipeline: &PipelineBuilder,
+    ) -> Result<ProcessedImage, ClientError> {
+        let body = json!({"url": url, "operations": pipeline.operations()});
+        self.send(self.http.post(self.pipeline_url()).json(&body))
+            .await
+    }
+
+    fn pipeline_url(&self) -> String {
+        format!("{}/pipeline", self.base_url)
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<ProcessedImage, ClientError> {
+        let request = match &self.api_key {
+            Some(key) => request.header("x-api-key", key),
+            None => request,
+        };
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|error| error["error"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            return Err(ClientError::Server {
+                status: status.as_u16(),
+                message,
+            });
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let data = response.bytes().await?.to_vec();
+        Ok(ProcessedImage { content_type, data })
+    }
+}
+
+/// The `operations` JSON array of `pipeline`.
+fn operations_json(pipeline: &PipelineBuilder) -> String {
+    serde_json::to_string(pipeline.operations()).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::pipeline_executor::validate_operation;
+
+    #[test]
+    fn test_builder_serializes_to_server_json() {
+        let pipeline = PipelineBuilder::new()
+            .resize(800, 600)
+            .grayscale()
+            .convert("webp", Some(80));
+        let json: Value = serde_json::from_str(&operations_json(&pipeline)).unwrap();
+        assert_eq!(
+            json,
+            json!([
+                {"operation": "resize", "ignoreFailure": false, "params": {"width": 800, "height": 600}, "region": null},
+                {"operation": "grayscale", "ignoreFailure": false, "params": null, "region": null},
+                {"operation": "convert", "ignoreFailure": false, "params": {"format": "webp", "quality": 80, "strict": false}, "region": null}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unserializable_params_are_an_error() {
+        let params = std::collections::BTreeMap::from([((1, 2), 3)]);
+        assert!(matches!(
+            PipelineBuilder::new().operation(SupportedOperation::Zoom, params),
+            Err(ClientError::Params(_))
+        ));
+    }
+
+    #[test]
+    fn test_built_operations_parse_and_validate_on_the_server() {
+        let pipeline = PipelineBuilder::new()
+            .crop(10, 20, 100, 50)
+            .thumbnail(40, 40)
+            .rotate(90.0)
+            .blur(1.5)
+            .ignore_failure()
+            .convert("jpeg", None);
+        let parsed: Vec<PipelineOperationSpec> =
+            serde_json::from_str(&operations_json(&pipeline)).unwrap();
+        assert_eq!(parsed.len(), 5);
+        assert!(parsed[3].ignore_failure);
+        for spec in &parsed {
+            validate_operation(spec).unwrap();
+        }
+        assert_eq!(
+            parsed.iter().map(|spec| spec.operation).collect::<Vec<_>>(),
+            pipeline
+                .build()
+                .iter()
+                .map(|spec| spec.operation)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -47,9 +47,8 @@ pub fn build_cli() -> Command {
             Arg::new("concurrency")
                 .long("concurrency")
                 .value_name("N")
-                .help("Maximum number of concurrent HTTP requests to process (0 = unlimited, default: 0)")
-                .value_parser(clap::value_parser!(u32))
-                .default_value("0"),
+                .help("Maximum number of concurrent HTTP requests to process (0 = unlimited); overrides server.concurrency from the config file")
+                .value_parser(clap::value_parser!(u32)),
         )
         .arg(
             Arg::new("max-body-size")
@@ -58,6 +57,12 @@ pub fn build_cli() -> Command {
                 .help("Sets the maximum request body size in bytes")
                 .default_value("10485760"),
         )
+        .arg(
+            Arg::new("max-operation-duration")
+                .long("max-operation-duration")
+                .value_name("SECONDS")
+                .help("Sets the maximum time a single pipeline operation may run (0 = unlimited); overrides server.max_operation_duration from the config file"),
+        )
         .arg(
             Arg::new("key")
                 .long("key")
@@ -1,16 +1,19 @@
 use crate::http::errors::AppError;
+use crate::image::operations::format::EncodingConfig;
 use crate::security::SecurityConfig;
 use crate::server::ServerConfig;
 use crate::storage::StorageConfig;
 use anyhow::Result;
-use clap::ArgMatches;
-use serde::Deserialize;
+use arc_swap::ArcSwap;
+use clap::{parser::ValueSource, ArgMatches};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use toml::Value;
 pub mod cli;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Config {
     #[serde(default)]
     pub server: ServerConfig,
@@ -18,50 +21,114 @@ pub struct Config {
     pub security: SecurityConfig,
     #[serde(default)]
     pub storage: StorageConfig,
-    #[serde(default = "default_data")]
-    pub data: Vec<u8>,
+    #[serde(default)]
+    pub encoding: EncodingConfig,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            server: ServerConfig::default(),
-            security: SecurityConfig::default(),
-            storage: StorageConfig::default(),
-            data: default_data(),
-        }
-    }
-}
+/// Configuration shared with the running server and replaced in place by [`reload_config`].
+pub type LiveConfig = Arc<ArcSwap<Config>>;
 
-fn default_data() -> Vec<u8> {
-    b"example data".to_vec()
+/// The current configuration: `live` when the router serves one (it is an extension of every
+/// request routed by [`create_live_router`](crate::server::create_live_router)), otherwise
+/// the handler's `config`.
+pub fn current_config(config: &Arc<Config>, live: Option<&LiveConfig>) -> Arc<Config> {
+    live.map_or_else(|| config.clone(), |live| live.load_full())
 }
 
+/// Prefix of environment variables overriding config values, e.g. `IMAGINARY_SERVER__PORT`.
+const ENV_PREFIX: &str = "IMAGINARY";
+
+/// Loads the configuration in layers: the config file, then `IMAGINARY_*` environment
+/// variables, then the CLI flags actually passed on the command line.
+///
+/// The file format follows the extension of the `--config` path (`.toml`, `.json`, `.yaml`/`.yml`;
+/// no extension means TOML). A missing TOML file is created with the defaults.
 pub fn load_config(matches: &ArgMatches) -> Result<Config, AppError> {
+    load_config_with_env(matches, None)
+}
+
+/// Re-runs [`load_config`] and swaps the result into `live`.
+///
+/// Only flags given on the command line are re-applied over the file, so a reload picks up
+/// any setting the command line left out.
+///
+/// On error the running configuration is left untouched. The server reads the CORS origins,
+/// the API key (for authentication, URL signatures and `/sign`) and the configuration reported
+/// by `/config` from `live` on every request; every other setting is fixed at startup.
+pub fn reload_config(matches: &ArgMatches, live: &ArcSwap<Config>) -> Result<(), AppError> {
+    let config = load_config(matches)?;
+    live.store(Arc::new(config));
+    Ok(())
+}
+
+/// [`load_config`] with an explicit set of environment variables; `None` reads the process
+/// environment.
+fn load_config_with_env(
+    matches: &ArgMatches,
+    env: Option<::config::Map<String, String>>,
+) -> Result<Config, AppError> {
     let config_path = matches
         .get_one::<String>("config")
         .map(|s| s.as_str())
         .unwrap_or("config/default.toml");
     let config_path = Path::new(config_path);
+    let format = config_format(config_path)?;
 
     if !config_path.exists() {
+        if !matches!(format, ::config::FileFormat::Toml) {
+            return Err(AppError::FileSystemError(format!(
+                "Config file {} does not exist",
+                config_path.display()
+            )));
+        }
         create_default_config(config_path)?;
     }
 
-    let config_content = fs::read_to_string(config_path)
-        .map_err(|_| AppError::FileSystemError("Failed to read config file".to_string()))?;
-    let mut config: Value = toml::from_str(&config_content)
-        .map_err(|_| AppError::FileSystemError("Failed to parse config file".to_string()))?;
+    // Env vars are nested with `__`, e.g. IMAGINARY_SERVER__HEALTH_CHECKS__MEMORY=false
+    let environment = ::config::Environment::with_prefix(ENV_PREFIX)
+        .prefix_separator("_")
+        .separator("__")
+        .try_parsing(true)
+        .list_separator(",")
+        .with_list_parse_key("security.allowed_origins")
+        .with_list_parse_key("security.allowed_fetch_hosts")
+        .with_list_parse_key("server.allowed_input_formats")
+        .source(env);
+    let mut config: Value = ::config::Config::builder()
+        .add_source(::config::File::from(config_path).format(format))
+        .add_source(environment)
+        .build()
+        .and_then(|layered| layered.try_deserialize())
+        .map_err(|e| {
+            AppError::FileSystemError(format!(
+                "Failed to parse config file {}: {}",
+                config_path.display(),
+                e
+            ))
+        })?;
 
     override_with_cli_args(&mut config, matches)
         .map_err(|e| AppError::BadRequest(format!("Configuration error: {}", e)))?;
 
     let config: Config = config
         .try_into()
-        .map_err(|_| AppError::FileSystemError("Failed to deserialize config".to_string()))?;
+        .map_err(|e| AppError::FileSystemError(format!("Failed to deserialize config: {}", e)))?;
     Ok(config)
 }
 
+/// Picks the config file format from the path's extension.
+fn config_format(config_path: &Path) -> Result<::config::FileFormat, AppError> {
+    match config_path.extension().and_then(|ext| ext.to_str()) {
+        None | Some("toml") => Ok(::config::FileFormat::Toml),
+        Some("json") => Ok(::config::FileFormat::Json),
+        Some("yaml") | Some("yml") => Ok(::config::FileFormat::Yaml),
+        Some(other) => Err(AppError::BadRequest(format!(
+            "Unsupported config file extension '.{}'; use .toml, .json, .yaml or .yml",
+            other
+        ))),
+    }
+}
+
 fn create_default_config(config_path: &Path) -> Result<(), AppError> {
     let default_config = r#"
 [server]
@@ -70,19 +137,51 @@ host = "127.0.0.1"
 read_timeout = 30
 write_timeout = 30
 concurrency = 4
+cpu_queue_size = 64
 max_body_size = 10485760
+max_operation_duration = 10
+animated_input_policy = "first_frame"
+max_input_pixels = 100000000
+max_frames = 200
+max_output_pixels = 100000000
+max_output_dimension = 16384
+max_megapixels_per_second = 0
+rate_limit_per_second = 0
+rate_limit_burst = 20
+trust_forwarded_for = false
+timings_header = false
+shutdown_timeout = 30
+max_redirects = 5
+fetch_retries = 2
+fetch_retry_backoff_ms = 100
+max_concurrent_fetches = 32
+max_pipeline_operations = 25
+allowed_input_formats = ["png", "jpeg", "gif", "webp", "bmp", "tiff"]
+strict_content_type = false
+fonts_dir = ""
+
+[server.health_checks]
+memory = true
+disk = true
 
 [security]
 key = ""
 salt = ""
 allowed_origins = ["*"]
+allowed_fetch_hosts = []
+
+[security.headers]
+enabled = true
+hsts_max_age = 31536000
+hsts_include_subdomains = false
+referrer_policy = "no-referrer"
 
 [storage]
 temp_dir = "temp"
 max_cache_size = 1073741824
 
-[data]
-value = "example data"
+[encoding]
+default_jpeg_quality = 75
 "#;
 
     fs::create_dir_all(config_path.parent().unwrap())
@@ -93,8 +192,30 @@ value = "example data"
     Ok(())
 }
 
+/// Sets `section.key`, creating the section if the config sources did not define it.
+fn set_value(config: &mut Value, section: &str, key: &str, value: Value) {
+    if let Value::Table(root) = config {
+        if let Value::Table(table) = root
+            .entry(section)
+            .or_insert_with(|| Value::Table(Default::default()))
+        {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// The value of the flag `id` if it was given on the command line.
+///
+/// Clap defaults are left out so they do not override the config file or the environment.
+fn cli_value<'a>(matches: &'a ArgMatches, id: &str) -> Option<&'a String> {
+    match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => matches.get_one::<String>(id),
+        _ => None,
+    }
+}
+
 fn override_with_cli_args(config: &mut Value, matches: &ArgMatches) -> Result<(), String> {
-    if let Some(port) = matches.get_one::<String>("port") {
+    if let Some(port) = cli_value(matches, "port") {
         let port_val = port
             .parse::<i64>()
             .map_err(|_| format!("Invalid port value: {}", port))?;
@@ -104,12 +225,12 @@ fn override_with_cli_args(config: &mut Value, matches: &ArgMatches) -> Result<()
                 port_val
             ));
         }
-        config["server"]["port"] = Value::Integer(port_val);
+        set_value(config, "server", "port", Value::Integer(port_val));
     }
-    if let Some(host) = matches.get_one::<String>("host") {
-        config["server"]["host"] = Value::String(host.clone());
+    if let Some(host) = cli_value(matches, "host") {
+        set_value(config, "server", "host", Value::String(host.clone()));
     }
-    if let Some(read_timeout) = matches.get_one::<String>("read-timeout") {
+    if let Some(read_timeout) = cli_value(matches, "read-timeout") {
         let timeout_val = read_timeout
             .parse::<i64>()
             .map_err(|_| format!("Invalid read timeout value: {}", read_timeout))?;
@@ -119,9 +240,14 @@ fn override_with_cli_args(config: &mut Value, matches: &ArgMatches) -> Result<()
                 timeout_val
             ));
         }
-        config["server"]["read_timeout"] = Value::Integer(timeout_val);
+        set_value(
+            config,
+            "server",
+            "read_timeout",
+            Value::Integer(timeout_val),
+        );
     }
-    if let Some(write_timeout) = matches.get_one::<String>("write-timeout") {
+    if let Some(write_timeout) = cli_value(matches, "write-timeout") {
         let timeout_val = write_timeout
             .parse::<i64>()
             .map_err(|_| format!("Invalid write timeout value: {}", write_timeout))?;
@@ -131,12 +257,22 @@ fn override_with_cli_args(config: &mut Value, matches: &ArgMatches) -> Result<()
                 timeout_val
             ));
         }
-        config["server"]["write_timeout"] = Value::Integer(timeout_val);
+        set_value(
+            config,
+            "server",
+            "write_timeout",
+            Value::Integer(timeout_val),
+        );
     }
     if let Some(concurrency) = matches.get_one::<u32>("concurrency") {
-        config["server"]["concurrency"] = Value::Integer(*concurrency as i64);
+        set_value(
+            config,
+            "server",
+            "concurrency",
+            Value::Integer(*concurrency as i64),
+        );
     }
-    if let Some(max_body_size) = matches.get_one::<String>("max-body-size") {
+    if let Some(max_body_size) = cli_value(matches, "max-body-size") {
         let size_val = max_body_size
             .parse::<i64>()
             .map_err(|_| format!("Invalid max body size value: {}", max_body_size))?;
@@ -146,32 +282,62 @@ fn override_with_cli_args(config: &mut Value, matches: &ArgMatches) -> Result<()
                 size_val
             ));
         }
-        config["server"]["max_body_size"] = Value::Integer(size_val);
+        set_value(config, "server", "max_body_size", Value::Integer(size_val));
     }
-    if let Some(key) = matches.get_one::<String>("key") {
+    if let Some(max_operation_duration) = matches.get_one::<String>("max-operation-duration") {
+        let duration_val = max_operation_duration.parse::<i64>().map_err(|_| {
+            format!(
+                "Invalid max operation duration value: {}",
+                max_operation_duration
+            )
+        })?;
+        if duration_val < 0 {
+            return Err(format!(
+                "Max operation duration must not be negative, got: {}",
+                duration_val
+            ));
+        }
+        set_value(
+            config,
+            "server",
+            "max_operation_duration",
+            Value::Integer(duration_val),
+        );
+    }
+    if let Some(key) = cli_value(matches, "key") {
         if key.len() < 32 {
             return Err("Security key must be at least 32 characters long".to_string());
         }
-        config["security"]["key"] = Value::String(key.clone());
+        set_value(config, "security", "key", Value::String(key.clone()));
     }
-    if let Some(salt) = matches.get_one::<String>("salt") {
+    if let Some(salt) = cli_value(matches, "salt") {
         if salt.len() < 32 {
             return Err("Security salt must be at least 32 characters long".to_string());
         }
-        config["security"]["salt"] = Value::String(salt.clone());
+        set_value(config, "security", "salt", Value::String(salt.clone()));
     }
-    if let Some(allowed_origins) = matches.get_one::<String>("allowed-origins") {
-        config["security"]["allowed_origins"] = Value::Array(
-            allowed_origins
-                .split(',')
-                .map(|s| Value::String(s.trim().to_string()))
-                .collect(),
+    if let Some(allowed_origins) = cli_value(matches, "allowed-origins") {
+        set_value(
+            config,
+            "security",
+            "allowed_origins",
+            Value::Array(
+                allowed_origins
+                    .split(',')
+                    .map(|s| Value::String(s.trim().to_string()))
+                    .collect(),
+            ),
         );
     }
-    if let Some(temp_dir) = matches.get_one::<String>("temp-dir") {
-        config["storage"]["temp_dir"] = Value::String(temp_dir.clone());
+    if let Some(temp_dir) = cli_value(matches, "temp-dir") {
+        set_value(
+            config,
+            "storage",
+            "temp_dir",
+            Value::String(temp_dir.clone()),
+        );
     }
-    if let Some(max_cache_size) = matches.get_one::<String>("max-cache-size") {
+    if let Some(max_cache_size) = cli_value(matches, "max-cache-size") {
         let cache_size_val = max_cache_size
             .parse::<i64>()
             .map_err(|_| format!("Invalid max cache size value: {}", max_cache_size))?;
@@ -181,7 +347,206 @@ fn override_with_cli_args(config: &mut Value, matches: &ArgMatches) -> Result<()
                 cache_size_val
             ));
         }
-        config["storage"]["max_cache_size"] = Value::Integer(cache_size_val);
+        set_value(
+            config,
+            "storage",
+            "max_cache_size",
+            Value::Integer(cache_size_val),
+        );
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    /// Loads a config file with `server.concurrency` set to `concurrency`.
+    fn load_with_concurrency(concurrency: usize, extra_args: &[&str]) -> Config {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let content = format!(
+            r#"
+[server]
+port = 8080
+host = "127.0.0.1"
+read_timeout = 30
+write_timeout = 30
+concurrency = {}
+max_body_size = 10485760
+max_operation_duration = 10
+
+[security]
+key = ""
+salt = ""
+allowed_origins = ["*"]
+
+[storage]
+temp_dir = "temp"
+max_cache_size = 1073741824
+"#,
+            concurrency
+        );
+        fs::write(&path, content).unwrap();
+
+        let path = path.to_str().unwrap();
+        let mut args = vec![
+            "imaginary-rs",
+            "--config",
+            path,
+            "--key",
+            TEST_SECRET,
+            "--salt",
+            TEST_SECRET,
+        ];
+        args.extend_from_slice(extra_args);
+        let matches = cli::build_cli().try_get_matches_from(args).unwrap();
+        load_config(&matches).unwrap()
+    }
+
+    #[test]
+    fn test_config_concurrency_applies_without_cli_flag() {
+        let config = load_with_concurrency(7, &[]);
+        assert_eq!(config.server.concurrency, 7);
+        let semaphore = crate::server::concurrency_semaphore(&config.server).unwrap();
+        assert_eq!(semaphore.available_permits(), 7);
+    }
+
+    #[test]
+    fn test_config_max_operation_duration_applies_without_cli_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[server]\nmax_operation_duration = 42\n").unwrap();
+        let config = load_config_with_env(&matches_for(&path, &[]), env(&[])).unwrap();
+        assert_eq!(config.server.max_operation_duration, 42);
+
+        let matches = matches_for(&path, &["--max-operation-duration", "0"]);
+        let config = load_config_with_env(&matches, env(&[])).unwrap();
+        assert_eq!(config.server.max_operation_duration, 0);
+    }
+
+    #[test]
+    fn test_cli_concurrency_overrides_config() {
+        let config = load_with_concurrency(7, &["--concurrency", "0"]);
+        assert_eq!(config.server.concurrency, 0);
+        assert!(crate::server::concurrency_semaphore(&config.server).is_none());
+    }
+
+    fn matches_for(path: &Path, extra_args: &[&str]) -> ArgMatches {
+        let mut args = vec!["imaginary-rs", "--config", path.to_str().unwrap()];
+        args.extend_from_slice(extra_args);
+        cli::build_cli().try_get_matches_from(args).unwrap()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Option<::config::Map<String, String>> {
+        Some(
+            vars.iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_env_vars_override_file_and_cli_overrides_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "[server]\nport = 8080\nhost = \"127.0.0.1\"\nconcurrency = 4\n",
+        )
+        .unwrap();
+        let vars = env(&[
+            ("IMAGINARY_SERVER__PORT", "9000"),
+            ("IMAGINARY_SERVER__CONCURRENCY", "16"),
+            ("IMAGINARY_SERVER__HEALTH_CHECKS__MEMORY", "false"),
+            (
+                "IMAGINARY_SECURITY__ALLOWED_ORIGINS",
+                "https://a.test,https://b.test",
+            ),
+            ("OTHER_SERVER__PORT", "1"),
+        ]);
+
+        let config = load_config_with_env(&matches_for(&path, &[]), vars.clone()).unwrap();
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.concurrency, 16);
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert!(!config.server.health_checks.memory);
+        assert!(config.security.is_origin_allowed("https://b.test"));
+        assert!(!config.security.is_origin_allowed("https://c.test"));
+
+        let config = load_config_with_env(&matches_for(&path, &["--port", "7000"]), vars).unwrap();
+        assert_eq!(config.server.port, 7000);
+        assert_eq!(config.server.concurrency, 16);
+    }
+
+    #[test]
+    fn test_yaml_and_json_configs_are_detected_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = dir.path().join("config.yaml");
+        fs::write(
+            &yaml,
+            "server:\n  port: 8181\n  concurrency: 3\nstorage:\n  temp_dir: yaml-temp\n",
+        )
+        .unwrap();
+        let config = load_config_with_env(&matches_for(&yaml, &[]), env(&[])).unwrap();
+        assert_eq!(config.server.port, 8181);
+        assert_eq!(config.server.concurrency, 3);
+        assert_eq!(config.storage.temp_dir, Path::new("yaml-temp"));
+
+        let json = dir.path().join("config.json");
+        fs::write(&json, r#"{"server": {"port": 8282}}"#).unwrap();
+        let config = load_config_with_env(&matches_for(&json, &[]), env(&[])).unwrap();
+        assert_eq!(config.server.port, 8282);
+
+        let ini = dir.path().join("config.ini");
+        fs::write(&ini, "").unwrap();
+        assert!(matches!(
+            load_config_with_env(&matches_for(&ini, &[]), env(&[])),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_reload_reads_origins_and_key_from_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let write_security = |origin: &str, key: &str| {
+            fs::write(
+                &path,
+                format!(
+                    "[security]\nallowed_origins = [\"{}\"]\nkey = \"{}\"\n",
+                    origin, key
+                ),
+            )
+            .unwrap();
+        };
+        write_security("https://old.test", TEST_SECRET);
+        let matches = matches_for(&path, &[]);
+        let live = ArcSwap::from_pointee(load_config(&matches).unwrap());
+
+        let new_key = "fedcba9876543210fedcba9876543210";
+        write_security("https://new.test", new_key);
+        reload_config(&matches, &live).unwrap();
+
+        let config = live.load();
+        assert!(config.security.is_origin_allowed("https://new.test"));
+        assert!(!config.security.is_origin_allowed("https://old.test"));
+        assert!(config.security.verify_api_key(new_key));
+        assert!(!config.security.verify_api_key(TEST_SECRET));
+    }
+
+    #[test]
+    fn test_invalid_config_reports_the_parse_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[server\nport = ").unwrap();
+        match load_config_with_env(&matches_for(&path, &[]), env(&[])) {
+            Err(AppError::FileSystemError(msg)) => {
+                assert!(msg.starts_with("Failed to parse config file"), "{}", msg)
+            }
+            other => panic!("Expected FileSystemError, got {:?}", other),
+        }
+    }
+}
@@ -1,13 +1,15 @@
 use axum::{
-    http::StatusCode,
+    extract::multipart::MultipartError,
+    http::{header::ALLOW, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
 use thiserror::Error;
 use tracing::error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum AppError {
     #[error("Internal Server Error: {0}")]
     InternalServerError(String),
@@ -20,8 +22,10 @@ pub enum AppError {
     #[error("Payload Too Large: {0}")]
     PayloadTooLarge(String),
     #[error("Rate Limit Exceeded: {0}")]
-    #[allow(dead_code)]
     RateLimitExceeded(String),
+    /// The server is out of capacity for the request right now (not the client's fault).
+    #[error("Service Unavailable: {0}")]
+    ServiceUnavailable(String),
     #[error("Invalid Operation: {0}")]
     #[allow(dead_code)]
     InvalidOperation(String),
@@ -31,9 +35,95 @@ pub enum AppError {
     MultipartError(String),
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
+    #[error("Bad Request: {context}: {source}")]
+    InvalidParams { context: String, source: ImageError },
+    #[error("Bad Request: {0}")]
+    SsrfBlocked(String),
+    /// The operations JSON doesn't match what the pipeline expects; `pointer` is a JSON
+    /// pointer to the offending value in the JSON the operations were sent in (the operations
+    /// array, or the whole body of a JSON request).
+    #[error("Bad Request: Invalid operations at {pointer}: {message}")]
+    InvalidOperationsJson { pointer: String, message: String },
+    /// The request method is not supported; `allow` is sent as the `Allow` header.
+    #[error("Method Not Allowed: {method} (allowed: {allow})")]
+    MethodNotAllowed { method: String, allow: &'static str },
+}
+
+/// Stable, machine-readable error identifiers returned as `error_code` in JSON error bodies.
+///
+/// Clients should branch on these rather than on the human-readable `error` message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InternalError,
+    BadRequest,
+    ImageProcessingFailed,
+    UnsupportedFormat,
+    PayloadTooLarge,
+    RateLimited,
+    Overloaded,
+    InvalidOperation,
+    FileSystemError,
+    MultipartError,
+    Unauthorized,
+    SsrfBlocked,
+    MethodNotAllowed,
+    InvalidOperationsJson,
+    InvalidDimensions,
+    InvalidDegrees,
+    InvalidOpacity,
+    InvalidQuality,
+    InvalidParameters,
+}
+
+impl AppError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            AppError::InternalServerError(_) => ErrorCode::InternalError,
+            AppError::BadRequest(_) => ErrorCode::BadRequest,
+            AppError::ImageProcessingError(_) => ErrorCode::ImageProcessingFailed,
+            AppError::UnsupportedMediaType(_) => ErrorCode::UnsupportedFormat,
+            AppError::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+            AppError::RateLimitExceeded(_) => ErrorCode::RateLimited,
+            AppError::ServiceUnavailable(_) => ErrorCode::Overloaded,
+            AppError::InvalidOperation(_) => ErrorCode::InvalidOperation,
+            AppError::FileSystemError(_) => ErrorCode::FileSystemError,
+            AppError::MultipartError(_) => ErrorCode::MultipartError,
+            AppError::Unauthorized(_) => ErrorCode::Unauthorized,
+            AppError::InvalidParams { source, .. } => source.error_code(),
+            AppError::SsrfBlocked(_) => ErrorCode::SsrfBlocked,
+            AppError::MethodNotAllowed { .. } => ErrorCode::MethodNotAllowed,
+            AppError::InvalidOperationsJson { .. } => ErrorCode::InvalidOperationsJson,
+        }
+    }
+}
+
+impl ImageError {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            ImageError::InvalidDimensions(_) => ErrorCode::InvalidDimensions,
+            ImageError::InvalidDegrees(_) => ErrorCode::InvalidDegrees,
+            ImageError::InvalidOpacity(_) => ErrorCode::InvalidOpacity,
+            ImageError::InvalidQuality(_) => ErrorCode::InvalidQuality,
+            ImageError::InvalidParameters(_) => ErrorCode::InvalidParameters,
+        }
+    }
+}
+
+fn error_body(
+    error_message: String,
+    status: StatusCode,
+    error_code: ErrorCode,
+) -> Json<serde_json::Value> {
+    Json(json!({
+        "error": error_message,
+        "code": status.as_u16(),
+        "error_code": error_code,
+        "status": "error"
+    }))
 }
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 #[allow(clippy::enum_variant_names)]
 pub enum ImageError {
     #[error("Invalid dimensions: {0}")]
@@ -52,6 +142,17 @@ pub enum ImageError {
     InvalidParameters(String),
 }
 
+impl From<MultipartError> for AppError {
+    /// Bodies cut off by the request body limit are `413`; other upload errors stay `400`.
+    fn from(error: MultipartError) -> Self {
+        if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            AppError::PayloadTooLarge(error.body_text())
+        } else {
+            AppError::MultipartError(error.body_text())
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_message) = match &self {
@@ -76,6 +177,10 @@ impl IntoResponse for AppError {
                 StatusCode::TOO_MANY_REQUESTS,
                 format!("Rate Limit Exceeded: {}", msg),
             ),
+            AppError::ServiceUnavailable(msg) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("Service Unavailable: {}", msg),
+            ),
             AppError::InvalidOperation(msg) => (
                 StatusCode::BAD_REQUEST,
                 format!("Invalid Operation: {}", msg),
@@ -90,18 +195,27 @@ impl IntoResponse for AppError {
             AppError::Unauthorized(msg) => {
                 (StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", msg))
             }
+            AppError::InvalidParams { .. }
+            | AppError::SsrfBlocked(_)
+            | AppError::InvalidOperationsJson { .. } => (StatusCode::BAD_REQUEST, self.to_string()),
+            AppError::MethodNotAllowed { .. } => (StatusCode::METHOD_NOT_ALLOWED, self.to_string()),
         };
 
         // Log the error
         error!("Error occurred: {}", error_message);
 
-        let body = Json(json!({
-            "error": error_message,
-            "code": status.as_u16(),
-            "status": "error"
-        }));
+        let mut body = error_body(error_message, status, self.error_code());
+        if let AppError::InvalidOperationsJson { pointer, .. } = &self {
+            body.0["pointer"] = json!(pointer);
+        }
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let AppError::MethodNotAllowed { allow, .. } = &self {
+            response
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::from_static(allow));
+        }
+        response
     }
 }
 
@@ -130,12 +244,99 @@ impl IntoResponse for ImageError {
         // Log the error
         error!("Image error occurred: {}", error_message);
 
-        let body = Json(json!({
-            "error": error_message,
-            "code": status.as_u16(),
-            "status": "error"
-        }));
+        let body = error_body(error_message, status, self.error_code());
 
         (status, body).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    async fn body_json(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_invalid_params_report_image_error_code() {
+        let error = AppError::InvalidParams {
+            context: "Invalid Resize params".to_string(),
+            source: ImageError::InvalidDimensions("Width must be positive".to_string()),
+        };
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_json(response).await;
+        assert_eq!(body["error_code"], "invalid_dimensions");
+        assert_eq!(body["code"], 400);
+        assert_eq!(
+            body["error"],
+            "Bad Request: Invalid Resize params: Invalid dimensions: Width must be positive"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_app_error_codes_are_stable() {
+        let cases = [
+            (
+                AppError::UnsupportedMediaType("image/tiff".to_string()),
+                415,
+                "unsupported_format",
+            ),
+            (
+                AppError::SsrfBlocked("resolves to a private address".to_string()),
+                400,
+                "ssrf_blocked",
+            ),
+            (
+                AppError::RateLimitExceeded("too many requests".to_string()),
+                429,
+                "rate_limited",
+            ),
+            (
+                AppError::ServiceUnavailable("CPU work queue is full".to_string()),
+                503,
+                "overloaded",
+            ),
+            (
+                AppError::Unauthorized("missing key".to_string()),
+                401,
+                "unauthorized",
+            ),
+            (
+                AppError::MethodNotAllowed {
+                    method: "PUT".to_string(),
+                    allow: "GET, POST",
+                },
+                405,
+                "method_not_allowed",
+            ),
+            (
+                AppError::InvalidOperationsJson {
+                    pointer: "/0/params/width".to_string(),
+                    message: "invalid type".to_string(),
+                },
+                400,
+                "invalid_operations_json",
+            ),
+        ];
+        for (error, status, code) in cases {
+            let body = body_json(error.into_response()).await;
+            assert_eq!(body["code"], status);
+            assert_eq!(body["error_code"], code);
+            assert_eq!(body["status"], "error");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_image_error_response_includes_code() {
+        let response = ImageError::InvalidOpacity("must be in 0..=1".to_string()).into_response();
+        let body = body_json(response).await;
+        assert_eq!(body["error_code"], "invalid_opacity");
+        assert_eq!(body["code"], 400);
+    }
+}
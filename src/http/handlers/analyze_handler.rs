@@ -0,0 +1,280 @@
+//! HTTP handler for the /analyze endpoint.
+//!
+//! Accepts multipart/form-data with an `image` field and returns per-channel statistics and
+//! histograms as JSON (see `image::analysis`). A `fields` query parameter selects a subset
+//! of the top-level keys, e.g. `?fields=width,height` to skip the histograms.
+//!
+//! Example usage:
+//!   POST /analyze?fields=width,height,luma
+//!   - image: file
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Multipart, Query, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    config::Config,
+    http::{
+        errors::AppError,
+        handlers::pipeline_handler::{check_animated_input, first_frame_only_header},
+    },
+    image::{
+        analysis::analyze,
+        format_support::{decode_error, detect_input_format, format_name, format_support},
+        limits::ImageLimits,
+    },
+    server::cpu_pool::run_cpu,
+    utils::image_utils::probe_dimensions,
+};
+
+/// Top-level keys of the `/analyze` response, in response order.
+const ANALYSIS_FIELDS: [&str; 6] = ["width", "height", "red", "green", "blue", "luma"];
+
+#[derive(Deserialize)]
+pub struct AnalyzeQuery {
+    /// Comma-separated response keys to keep; all of them when absent.
+    fields: Option<String>,
+}
+
+/// Handles POST /analyze requests.
+pub async fn analyze_image(
+    State(config): State<Arc<Config>>,
+    Query(query): Query<AnalyzeQuery>,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    let fields = query.fields.as_deref().map(parse_fields).transpose()?;
+    let mut image_data = None;
+    while let Some(field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+        match name.as_str() {
+            "image" | "file" => {
+                let data = field.bytes().await?;
+                if data.len() > config.server.max_body_size {
+                    return Err(AppError::PayloadTooLarge(format!(
+                        "Image size {} exceeds limit of {} bytes",
+                        data.len(),
+                        config.server.max_body_size
+                    )));
+                }
+                image_data = Some(data);
+            }
+            _ => {
+                tracing::debug!("Ignoring unknown multipart field: {}", name);
+            }
+        }
+    }
+    let bytes = image_data.ok_or_else(|| {
+        AppError::BadRequest("Missing image data in multipart request".to_string())
+    })?;
+
+    let format = detect_input_format(&bytes, &config.server.allowed_input_formats)?;
+    if !format_support().can_decode(format) {
+        return Err(AppError::UnsupportedMediaType(format!(
+            "Decoding {} is not supported by this build",
+            format_name(format)
+        )));
+    }
+    let dimensions = probe_dimensions(&bytes, format)?;
+    ImageLimits::from_config(&config.server).check_input(dimensions)?;
+    let first_frame_only =
+        check_animated_input(&bytes, format, config.server.animated_input_policy)?;
+
+    let analysis = run_cpu("Analysis", move || {
+        image::load_from_memory_with_format(&bytes, format)
+            .map(|image| analyze(&image))
+            .map_err(|e| decode_error(format, e))
+    })
+    .await?;
+
+    let body = serde_json::to_value(analysis).map_err(|e| {
+        AppError::InternalServerError(format!("Failed to serialize analysis: {}", e))
+    })?;
+    let body = match (fields, body) {
+        (Some(fields), Value::Object(mut all)) => Value::Object(
+            fields
+                .into_iter()
+                .filter_map(|field| all.remove_entry(field))
+                .collect::<Map<_, _>>(),
+        ),
+        (_, body) => body,
+    };
+    Ok((first_frame_only_header(first_frame_only), Json(body)).into_response())
+}
+
+/// Parses the `fields` selector, rejecting names that are not response keys.
+fn parse_fields(fields: &str) -> Result<Vec<&'static str>, AppError> {
+    let mut selected = Vec::new();
+    for name in fields
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let field = ANALYSIS_FIELDS
+            .iter()
+            .find(|field| **field == name)
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Unknown field '{}' in 'fields', expected any of: {}",
+                    name,
+                    ANALYSIS_FIELDS.join(", ")
+                ))
+            })?;
+        if !selected.contains(field) {
+            selected.push(*field);
+        }
+    }
+    if selected.is_empty() {
+        return Err(AppError::BadRequest(
+            "'fields' must name at least one field".to_string(),
+        ));
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{AnimatedInputPolicy, ServerConfig};
+    use axum::{body::Body, http::StatusCode, routing::post, Router};
+    use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+    use serde_json::Value;
+    use std::io::Cursor;
+    use tower::ServiceExt;
+
+    const BOUNDARY: &str = "imaginary-analyze-boundary";
+
+    fn analyze_request(uri: &str, image: &[u8]) -> axum::http::Request<Body> {
+        let mut body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"a.png\"\r\nContent-Type: image/png\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(image);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+        axum::http::Request::post(uri)
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    /// A two-frame 8x4 GIF, red then green.
+    fn animated_gif() -> Vec<u8> {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, Rgba, RgbaImage};
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            let frames = [Rgba([255u8, 0, 0, 255]), Rgba([0u8, 255, 0, 255])]
+                .into_iter()
+                .map(|color| {
+                    Frame::from_parts(
+                        RgbaImage::from_pixel(8, 4, color),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(50, 1),
+                    )
+                });
+            encoder.encode_frames(frames).unwrap();
+        }
+        bytes
+    }
+
+    fn app_with(animated_input_policy: AnimatedInputPolicy) -> Router {
+        let config = Config {
+            server: ServerConfig {
+                max_body_size: 1024 * 1024,
+                animated_input_policy,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Router::new()
+            .route("/analyze", post(analyze_image))
+            .with_state(Arc::new(config))
+    }
+
+    /// Posts an 8x4 solid-color PNG to `uri`.
+    async fn analyze(uri: &str) -> (StatusCode, Value) {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 4, Rgb([10, 20, 30])))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let response = app_with(AnimatedInputPolicy::default())
+            .oneshot(analyze_request(uri, &png))
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_returns_channel_stats() {
+        let (status, body) = analyze("/analyze").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["width"], 8);
+        assert_eq!(body["blue"]["min"], 30);
+        assert_eq!(body["blue"]["stddev"], 0.0);
+        assert_eq!(body["red"]["histogram"][10], 32);
+        assert_eq!(body["luma"]["histogram"].as_array().unwrap().len(), 256);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_fields_selects_keys() {
+        let (status, body) = analyze("/analyze?fields=width,%20height,width").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({"width": 8, "height": 4}));
+
+        let (status, body) = analyze("/analyze?fields=luma").await;
+        assert_eq!(status, StatusCode::OK);
+        let keys: Vec<&String> = body.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["luma"]);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_rejects_unknown_fields() {
+        let (status, body) = analyze("/analyze?fields=width,exif").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let message = body["error"].as_str().unwrap();
+        assert!(message.contains("Unknown field 'exif'"), "{}", message);
+
+        let (status, _) = analyze("/analyze?fields=").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_applies_the_animated_input_policy() {
+        let response = app_with(AnimatedInputPolicy::FirstFrame)
+            .oneshot(analyze_request("/analyze", &animated_gif()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-first-frame-only"], "true");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        // Only the red first frame is analyzed
+        assert_eq!(body["green"]["max"], 0);
+
+        let response = app_with(AnimatedInputPolicy::Reject)
+            .oneshot(analyze_request("/analyze", &animated_gif()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let (status, _) = analyze("/analyze").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
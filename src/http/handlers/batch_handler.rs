@@ -0,0 +1,551 @@
+//! HTTP handler for the /batch endpoint.
+//!
+//! Accepts multipart/form-data with several `image` fields and a single `operations` field,
+//! applies the same pipeline to every image concurrently and returns a JSON array with one
+//! result per image, in upload order. A failing image does not fail the whole batch.
+//!
+//! `/batch/stream` takes the same input and answers with server-sent events instead: an
+//! `event: progress` with `{done, total}` as each image completes, then an `event: result`
+//! carrying the same array `/batch` returns.
+//!
+//! Example usage:
+//!   POST /batch
+//!   - image: file (repeated)
+//!   - operations: '[{"operation": "thumbnail", "params": {"width": 100, "height": 100}}]'
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Multipart, State},
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use serde_json::json;
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task::JoinHandle,
+};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    config::Config,
+    http::{
+        errors::{AppError, ErrorCode},
+        handlers::pipeline_handler::{determine_output_format, render},
+    },
+    image::{
+        format_support::{check_declared_type, detect_input_format, format_support},
+        limits::ImageLimits,
+        metadata::MetadataOptions,
+        pipeline_executor::parse_operations,
+        pipeline_types::PipelineOperationSpec,
+    },
+};
+
+/// An uploaded image waiting to be processed.
+struct BatchImage {
+    filename: Option<String>,
+    /// The part's `Content-Type`, kept only when `strict_content_type` is on.
+    declared_type: Option<String>,
+    bytes: Vec<u8>,
+}
+
+/// A validated batch request, ready to be processed.
+struct BatchJob {
+    images: Vec<BatchImage>,
+    operations_spec: Vec<PipelineOperationSpec>,
+    config: Arc<Config>,
+}
+
+/// Payload of the `progress` events sent by `/batch/stream`.
+#[derive(Debug, Serialize)]
+struct BatchProgress {
+    done: usize,
+    total: usize,
+}
+
+/// Per-image outcome returned by `/batch`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status")]
+pub enum BatchItemResult {
+    #[serde(rename = "ok")]
+    Processed {
+        index: usize,
+        filename: Option<String>,
+        content_type: String,
+        /// Applied/skipped operations, as in the `X-Imaginary-Operations` header.
+        operations: String,
+        /// Base64-encoded processed image.
+        data: String,
+    },
+    #[serde(rename = "error")]
+    Failed {
+        index: usize,
+        filename: Option<String>,
+        error: String,
+        error_code: ErrorCode,
+    },
+}
+
+/// Handles POST /batch requests.
+///
+/// The combined size of all images is capped by `max_body_size`, and at most one image per
+/// CPU is processed at a time.
+pub async fn process_batch(
+    State(config): State<Arc<Config>>,
+    multipart: Multipart,
+) -> Result<Json<Vec<BatchItemResult>>, AppError> {
+    let job = read_batch(config, multipart).await?;
+    let tasks = spawn_batch(job, None);
+    Ok(Json(collect_results(tasks).await?))
+}
+
+/// Handles POST /batch/stream requests.
+///
+/// Validation errors are returned as plain JSON errors before the stream starts; once it
+/// has started, a failed worker ends it with an `event: error` instead of the result.
+pub async fn process_batch_stream(
+    State(config): State<Arc<Config>>,
+    multipart: Multipart,
+) -> Result<Sse<ReceiverStream<Result<Event, Infallible>>>, AppError> {
+    let job = read_batch(config, multipart).await?;
+    let total = job.images.len();
+    let (completed_tx, mut completed_rx) = mpsc::channel(total);
+    let tasks = spawn_batch(job, Some(completed_tx));
+
+    let (events_tx, events_rx) = mpsc::channel(total + 1);
+    tokio::spawn(async move {
+        let mut done = 0;
+        // Ends once every worker has finished and dropped its sender
+        while completed_rx.recv().await.is_some() {
+            done += 1;
+            let progress = json_event("progress", &BatchProgress { done, total });
+            if events_tx.send(Ok(progress)).await.is_err() {
+                tracing::debug!("Batch stream client disconnected");
+            }
+        }
+        let event = match collect_results(tasks).await {
+            Ok(results) => json_event("result", &results),
+            Err(e) => json_event(
+                "error",
+                &json!({"error": e.to_string(), "error_code": e.error_code()}),
+            ),
+        };
+        let _ = events_tx.send(Ok(event)).await;
+    });
+
+    Ok(Sse::new(ReceiverStream::new(events_rx)).keep_alive(KeepAlive::default()))
+}
+
+/// Reads the uploaded images and the pipeline, and validates the pipeline.
+async fn read_batch(config: Arc<Config>, mut multipart: Multipart) -> Result<BatchJob, AppError> {
+    let mut images = Vec::new();
+    let mut total_size = 0usize;
+    let mut operations_json_str: Option<String> = None;
+
+    while let Some(field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+        match name.as_str() {
+            "image" | "file" => {
+                let filename = field.file_name().map(str::to_string);
+                let declared_type = field
+                    .content_type()
+                    .filter(|_| config.server.strict_content_type)
+                    .map(str::to_string);
+                let data = field.bytes().await?;
+                total_size += data.len();
+                if total_size > config.server.max_body_size {
+                    return Err(AppError::PayloadTooLarge(format!(
+                        "Batch size {} exceeds limit of {} bytes",
+                        total_size, config.server.max_body_size
+                    )));
+                }
+                images.push(BatchImage {
+                    filename,
+                    declared_type,
+                    bytes: data.into(),
+                });
+            }
+            "operations" => {
+                operations_json_str = Some(field.text().await?);
+            }
+            _ => {
+                tracing::debug!("Ignoring unknown multipart field: {}", name);
+            }
+        }
+    }
+
+    if images.is_empty() {
+        return Err(AppError::BadRequest(
+            "Missing image data in multipart request".to_string(),
+        ));
+    }
+    let ops_str = operations_json_str.ok_or_else(|| {
+        AppError::BadRequest("Missing 'operations' JSON string in multipart request".to_string())
+    })?;
+    let operations_spec = parse_operations(&ops_str)?;
+    if operations_spec.is_empty() {
+        return Err(AppError::BadRequest(
+            "'operations' array cannot be empty".to_string(),
+        ));
+    }
+    ImageLimits::check_operation_count(&operations_spec, config.server.max_pipeline_operations)?;
+
+    Ok(BatchJob {
+        images,
+        operations_spec,
+        config,
+    })
+}
+
+/// Starts one task per image, at most one per CPU running at a time.
+///
+/// Each task sends its image index on `completed`, when given, as soon as it finishes.
+fn spawn_batch(
+    job: BatchJob,
+    completed: Option<mpsc::Sender<usize>>,
+) -> Vec<JoinHandle<BatchItemResult>> {
+    let BatchJob {
+        images,
+        operations_spec,
+        config,
+    } = job;
+    let semaphore = Arc::new(Semaphore::new(num_cpus::get().max(1)));
+    images
+        .into_iter()
+        .enumerate()
+        .map(|(index, image)| {
+            let semaphore = semaphore.clone();
+            let operations_spec = operations_spec.clone();
+            let config = config.clone();
+            let completed = completed.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("batch semaphore is never closed");
+                let BatchImage {
+                    filename,
+                    declared_type,
+                    bytes,
+                } = image;
+                let outcome = process_image(bytes, declared_type, operations_spec, config).await;
+                let result = match outcome {
+                    Ok((bytes, content_type, operations)) => BatchItemResult::Processed {
+                        index,
+                        filename,
+                        content_type,
+                        operations,
+                        data: STANDARD.encode(bytes),
+                    },
+                    Err(e) => {
+                        tracing::warn!(index, error = %e, "Batch image failed");
+                        BatchItemResult::Failed {
+                            index,
+                            filename,
+                            error_code: e.error_code(),
+                            error: e.to_string(),
+                        }
+                    }
+                };
+                if let Some(completed) = completed {
+                    // Capacity covers every image, so this never waits
+                    let _ = completed.send(index).await;
+                }
+                result
+            })
+        })
+        .collect()
+}
+
+/// Waits for every task and returns the results in upload order.
+async fn collect_results(
+    tasks: Vec<JoinHandle<BatchItemResult>>,
+) -> Result<Vec<BatchItemResult>, AppError> {
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(
+            task.await
+                .map_err(|e| AppError::InternalServerError(format!("Batch task failed: {}", e)))?,
+        );
+    }
+    Ok(results)
+}
+
+/// A server-sent event named `name` with `data` as its JSON payload.
+fn json_event(name: &str, data: &impl Serialize) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()))
+}
+
+/// Checks, processes and re-encodes a single image with [`render`], so every image gets the
+/// same limits as a `/pipeline` request: input and output size, pixel throughput, operation
+/// timeout and the animated-input policy.
+///
+/// Returns the encoded bytes, their MIME type and the operations summary.
+async fn process_image(
+    bytes: Vec<u8>,
+    declared_type: Option<String>,
+    operations_spec: Vec<PipelineOperationSpec>,
+    config: Arc<Config>,
+) -> Result<(Bytes, String, String), AppError> {
+    check_declared_type(declared_type.as_deref(), &bytes)?;
+    let original_format = detect_input_format(&bytes, &config.server.allowed_input_formats)?;
+    let output_format = determine_output_format(&operations_spec, original_format);
+    format_support().ensure_supported(original_format, output_format)?;
+    let rendered = render(
+        bytes,
+        operations_spec,
+        original_format,
+        output_format,
+        MetadataOptions::default(),
+        BTreeMap::new(),
+        config,
+    )
+    .await?;
+    Ok((
+        rendered.bytes.clone(),
+        output_format.to_mime_type().to_string(),
+        rendered.summary.clone(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ServerConfig;
+    use axum::{body::Body, http::StatusCode, routing::post, Router};
+    use image::{GenericImageView, ImageFormat};
+    use serde_json::Value;
+    use std::io::Cursor;
+    use tower::ServiceExt;
+
+    const BOUNDARY: &str = "imaginary-batch-boundary";
+
+    fn app(max_body_size: usize) -> Router {
+        app_with(ServerConfig {
+            max_body_size,
+            ..Default::default()
+        })
+    }
+
+    fn app_with(server: ServerConfig) -> Router {
+        let config = Config {
+            server,
+            ..Default::default()
+        };
+        Router::new()
+            .route("/batch", post(process_batch))
+            .route("/batch/stream", post(process_batch_stream))
+            .with_state(Arc::new(config))
+    }
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn batch_request(images: &[(&str, Vec<u8>)], operations: &str) -> axum::http::Request<Body> {
+        let mut body = Vec::new();
+        for (filename, bytes) in images {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"{filename}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(bytes);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"operations\"\r\n\r\n{operations}\r\n--{BOUNDARY}--\r\n"
+            )
+            .as_bytes(),
+        );
+        axum::http::Request::post("/batch")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn body_json(response: axum::response::Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_batch_processes_each_image_independently() {
+        let images = [
+            ("a.png", png_bytes(40, 40)),
+            ("broken.png", b"definitely not an image".to_vec()),
+            ("c.png", png_bytes(60, 30)),
+        ];
+        let operations = r#"[{"operation": "resize", "params": {"width": 20, "height": 10}}]"#;
+
+        let response = app(1024 * 1024)
+            .oneshot(batch_request(&images, operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        let results = body.as_array().unwrap();
+        assert_eq!(results.len(), 3);
+
+        for (index, filename) in [(0, "a.png"), (2, "c.png")] {
+            let result = &results[index];
+            assert_eq!(result["status"], "ok");
+            assert_eq!(result["index"], index);
+            assert_eq!(result["filename"], filename);
+            assert_eq!(result["content_type"], "image/png");
+            assert_eq!(result["operations"], "resize");
+            let decoded = STANDARD.decode(result["data"].as_str().unwrap()).unwrap();
+            let image = image::load_from_memory(&decoded).unwrap();
+            assert_eq!(image.dimensions(), (20, 10));
+        }
+
+        assert_eq!(results[1]["status"], "error");
+        assert_eq!(results[1]["filename"], "broken.png");
+        assert_eq!(results[1]["error_code"], "unsupported_format");
+    }
+
+    #[tokio::test]
+    async fn test_batch_applies_the_animated_input_policy() {
+        use crate::server::AnimatedInputPolicy;
+        use image::{codecs::gif::GifEncoder, Delay, Frame, Rgba, RgbaImage};
+
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            let frames = [Rgba([255u8, 0, 0, 255]), Rgba([0u8, 255, 0, 255])]
+                .into_iter()
+                .map(|color| {
+                    Frame::from_parts(
+                        RgbaImage::from_pixel(8, 8, color),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(50, 1),
+                    )
+                });
+            encoder.encode_frames(frames).unwrap();
+        }
+        let images = [("animated.gif", gif), ("still.png", png_bytes(8, 8))];
+
+        let response = app_with(ServerConfig {
+            max_body_size: 1024 * 1024,
+            animated_input_policy: AnimatedInputPolicy::Reject,
+            ..Default::default()
+        })
+        .oneshot(batch_request(&images, r#"[{"operation": "grayscale"}]"#))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        let results = body.as_array().unwrap();
+        assert_eq!(results[0]["status"], "error");
+        assert_eq!(results[0]["error_code"], "bad_request");
+        assert_eq!(results[1]["status"], "ok");
+    }
+
+    #[tokio::test]
+    async fn test_batch_total_size_is_capped() {
+        let image = png_bytes(40, 40);
+        let max_body_size = image.len() * 2 + image.len() / 2;
+        let images = [
+            ("a.png", image.clone()),
+            ("b.png", image.clone()),
+            ("c.png", image),
+        ];
+
+        let response = app(max_body_size)
+            .oneshot(batch_request(&images, r#"[{"operation": "grayscale"}]"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_batch_stream_reports_progress_then_result() {
+        let images = [
+            ("a.png", png_bytes(40, 40)),
+            ("b.png", png_bytes(20, 20)),
+            ("c.png", png_bytes(60, 30)),
+        ];
+        let mut request = batch_request(&images, r#"[{"operation": "grayscale"}]"#);
+        *request.uri_mut() = "/batch/stream".parse().unwrap();
+
+        let response = app(1024 * 1024).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        // Each event is an `event:` line and a `data:` line, separated by blank lines
+        let events: Vec<(String, Value)> = std::str::from_utf8(&bytes)
+            .unwrap()
+            .split("\n\n")
+            .filter_map(|block| {
+                let field = |prefix: &str| {
+                    block
+                        .lines()
+                        .find_map(|line| line.strip_prefix(prefix))
+                        .map(str::to_string)
+                };
+                Some((
+                    field("event: ")?,
+                    serde_json::from_str(&field("data: ")?).unwrap(),
+                ))
+            })
+            .collect();
+
+        let progress: Vec<&Value> = events
+            .iter()
+            .filter(|(name, _)| name == "progress")
+            .map(|(_, data)| data)
+            .collect();
+        assert_eq!(progress.len(), 3);
+        for (done, data) in progress.iter().enumerate() {
+            assert_eq!(data["done"], done + 1);
+            assert_eq!(data["total"], 3);
+        }
+
+        let (last_name, result) = events.last().unwrap();
+        assert_eq!(last_name, "result");
+        assert_eq!(
+            events.iter().filter(|(name, _)| name == "result").count(),
+            1
+        );
+        let results = result.as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        for (index, item) in results.iter().enumerate() {
+            assert_eq!(item["status"], "ok");
+            assert_eq!(item["index"], index);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_stream_rejects_invalid_input_before_streaming() {
+        let mut request = batch_request(&[("a.png", png_bytes(4, 4))], "[]");
+        *request.uri_mut() = "/batch/stream".parse().unwrap();
+        let response = app(1024 * 1024).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,38 @@
+//! Endpoint describing what this build of the service can process.
+
+use crate::image::format_support::format_support;
+use crate::image::pipeline_types::SupportedOperation;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde_json::json;
+
+/// Lists the image formats this binary can decode/encode and the supported pipeline operations.
+pub async fn capabilities() -> impl IntoResponse {
+    Json(json!({
+        "formats": format_support().formats(),
+        "operations": &SupportedOperation::ALL[..],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_capabilities_lists_formats_and_operations() {
+        let response = capabilities().await.into_response();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        let formats = body["formats"].as_array().unwrap();
+        let png = formats.iter().find(|f| f["format"] == "png").unwrap();
+        assert_eq!(png["mime_type"], "image/png");
+        assert_eq!(png["decode"], true);
+        assert_eq!(png["encode"], true);
+
+        let operations = body["operations"].as_array().unwrap();
+        assert!(operations.iter().any(|op| op == "smartCrop"));
+    }
+}
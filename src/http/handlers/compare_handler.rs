@@ -0,0 +1,248 @@
+//! HTTP handler for the /compare endpoint.
+//!
+//! Accepts multipart/form-data with two images of the same size, `a` and `b`, and returns how
+//! different they are as JSON (see `image::compare`).
+//!
+//! Example usage:
+//!   POST /compare
+//!   - a: file
+//!   - b: file
+
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Multipart, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use image::{DynamicImage, ImageFormat};
+
+use crate::{
+    config::Config,
+    http::{
+        errors::AppError,
+        handlers::pipeline_handler::{check_animated_input, first_frame_only_header},
+    },
+    image::{
+        compare::compare,
+        format_support::{decode_error, detect_input_format, format_name, format_support},
+        limits::ImageLimits,
+    },
+    server::cpu_pool::run_cpu,
+    utils::image_utils::probe_dimensions,
+};
+
+/// Handles POST /compare requests.
+pub async fn compare_images(
+    State(config): State<Arc<Config>>,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    let (mut a, mut b) = (None, None);
+    while let Some(field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+        let slot = match name.as_str() {
+            "a" => &mut a,
+            "b" => &mut b,
+            _ => {
+                tracing::debug!("Ignoring unknown multipart field: {}", name);
+                continue;
+            }
+        };
+        let data = field.bytes().await?;
+        if data.len() > config.server.max_body_size {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Image '{}' size {} exceeds limit of {} bytes",
+                name,
+                data.len(),
+                config.server.max_body_size
+            )));
+        }
+        *slot = Some(data);
+    }
+    let missing =
+        |name: &str| AppError::BadRequest(format!("Missing image '{}' in multipart request", name));
+    let a = checked_image(a.ok_or_else(|| missing("a"))?, &config)?;
+    let b = checked_image(b.ok_or_else(|| missing("b"))?, &config)?;
+    let first_frame_only = a.2 || b.2;
+
+    let comparison = run_cpu("Comparison", move || {
+        compare(&decode(&a.0, a.1)?, &decode(&b.0, b.1)?)
+    })
+    .await?;
+    Ok((first_frame_only_header(first_frame_only), Json(comparison)).into_response())
+}
+
+/// Detects the image's format and checks it is allowed, decodable and within the input limits,
+/// and whether the animated-input policy keeps only its first frame.
+fn checked_image(bytes: Bytes, config: &Config) -> Result<(Bytes, ImageFormat, bool), AppError> {
+    let format = detect_input_format(&bytes, &config.server.allowed_input_formats)?;
+    if !format_support().can_decode(format) {
+        return Err(AppError::UnsupportedMediaType(format!(
+            "Decoding {} is not supported by this build",
+            format_name(format)
+        )));
+    }
+    let dimensions = probe_dimensions(&bytes, format)?;
+    ImageLimits::from_config(&config.server).check_input(dimensions)?;
+    let first_frame_only =
+        check_animated_input(&bytes, format, config.server.animated_input_policy)?;
+    Ok((bytes, format, first_frame_only))
+}
+
+fn decode(bytes: &[u8], format: ImageFormat) -> Result<DynamicImage, AppError> {
+    image::load_from_memory_with_format(bytes, format).map_err(|e| decode_error(format, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{AnimatedInputPolicy, ServerConfig};
+    use axum::{body::Body, http::StatusCode, routing::post, Router};
+    use image::{Rgb, RgbImage};
+    use serde_json::Value;
+    use std::io::Cursor;
+    use tower::ServiceExt;
+
+    const BOUNDARY: &str = "imaginary-compare-boundary";
+
+    fn png(image: RgbImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    /// A two-frame 8x4 GIF, red then green.
+    fn animated_gif() -> Vec<u8> {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, Rgba, RgbaImage};
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            let frames = [Rgba([255u8, 0, 0, 255]), Rgba([0u8, 255, 0, 255])]
+                .into_iter()
+                .map(|color| {
+                    Frame::from_parts(
+                        RgbaImage::from_pixel(8, 4, color),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(50, 1),
+                    )
+                });
+            encoder.encode_frames(frames).unwrap();
+        }
+        bytes
+    }
+
+    fn compare_request(a: &[u8], b: &[u8]) -> axum::http::Request<Body> {
+        let mut body = Vec::new();
+        for (name, image) in [("a", a), ("b", b)] {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{name}.png\"\r\nContent-Type: image/png\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(image);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+        axum::http::Request::post("/compare")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn app() -> Router {
+        app_with(AnimatedInputPolicy::default())
+    }
+
+    fn app_with(animated_input_policy: AnimatedInputPolicy) -> Router {
+        let config = Config {
+            server: ServerConfig {
+                max_body_size: 1024 * 1024,
+                animated_input_policy,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Router::new()
+            .route("/compare", post(compare_images))
+            .with_state(Arc::new(config))
+    }
+
+    #[tokio::test]
+    async fn test_compare_reports_differences() {
+        let a = png(RgbImage::from_pixel(8, 4, Rgb([10, 20, 30])));
+        let b = png(RgbImage::from_fn(8, 4, |x, _| {
+            if x < 2 {
+                Rgb([14, 20, 30])
+            } else {
+                Rgb([10, 20, 30])
+            }
+        }));
+
+        let response = app().oneshot(compare_request(&a, &b)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["width"], 8);
+        // A quarter of the pixels are 4 levels brighter in red
+        assert_eq!(body["mean_absolute_error"]["red"], 1.0);
+        assert_eq!(body["mean_absolute_error"]["green"], 0.0);
+        assert_eq!(body["different_pixels_percent"], 25.0);
+
+        let response = app().oneshot(compare_request(&a, &a)).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["ssim"], 1.0);
+        assert_eq!(body["different_pixels_percent"], 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_compare_rejects_different_sizes() {
+        let a = png(RgbImage::new(8, 4));
+        let b = png(RgbImage::new(4, 8));
+        let response = app().oneshot(compare_request(&a, &b)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_compare_applies_the_animated_input_policy() {
+        let red = png(RgbImage::from_pixel(8, 4, Rgb([255, 0, 0])));
+        let response = app_with(AnimatedInputPolicy::FirstFrame)
+            .oneshot(compare_request(&animated_gif(), &red))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-first-frame-only"], "true");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["different_pixels_percent"], 0.0);
+
+        let response = app_with(AnimatedInputPolicy::Reject)
+            .oneshot(compare_request(&red, &animated_gif()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app_with(AnimatedInputPolicy::Reject)
+            .oneshot(compare_request(&red, &red))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("x-first-frame-only").is_none());
+    }
+}
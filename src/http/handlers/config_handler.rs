@@ -0,0 +1,175 @@
+//! Endpoint exposing the effective runtime configuration for debugging deployments.
+
+use crate::config::{current_config, Config, LiveConfig};
+use crate::http::errors::AppError;
+use axum::extract::{ConnectInfo, State};
+use axum::http::HeaderMap;
+use axum::{Extension, Json};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Returns the configuration the server loaded after merging the config file and CLI flags,
+/// as of the last reload.
+///
+/// Secrets are serialized as `<redacted ...>` placeholders. Only loopback clients, or clients
+/// presenting the configured key in `x-api-key`, may read it. With `trust_forwarded_for` on the
+/// server sits behind a proxy whose connections all look local, so the key is always required.
+pub async fn effective_config(
+    State(config): State<Arc<Config>>,
+    live: Option<Extension<LiveConfig>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let config = current_config(&config, live.as_ref().map(|Extension(live)| live));
+    let is_loopback = !config.server.trust_forwarded_for
+        && peer.is_some_and(|ConnectInfo(addr)| addr.ip().is_loopback());
+    let has_valid_key = headers
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|key| config.security.verify_api_key(key));
+    if !is_loopback && !has_valid_key {
+        return Err(AppError::Unauthorized(
+            "Configuration is only available from localhost or with a valid API key".to_string(),
+        ));
+    }
+
+    serde_json::to_value(config.as_ref())
+        .map(Json)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to serialize config: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::{ApiKey, ApiSalt};
+    use crate::server::ServerConfig;
+    use axum::{body::Body, http::Request, http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    const SECRET_KEY: &str = "real-key-0123456789abcdef0123456789";
+    const SECRET_SALT: &str = "real-salt-0123456789abcdef012345678";
+
+    fn app() -> Router {
+        app_with(ServerConfig {
+            concurrency: 12,
+            ..Default::default()
+        })
+    }
+
+    fn app_with(server: ServerConfig) -> Router {
+        let mut config = Config {
+            server,
+            ..Default::default()
+        };
+        config
+            .security
+            .set_key(ApiKey::from(SECRET_KEY.to_string()));
+        config
+            .security
+            .set_salt(ApiSalt::from(SECRET_SALT.to_string()));
+        Router::new()
+            .route("/config", get(effective_config))
+            .with_state(Arc::new(config))
+    }
+
+    fn request(peer: &str, api_key: Option<&str>) -> Request<Body> {
+        let mut builder = Request::get("/config");
+        if let Some(api_key) = api_key {
+            builder = builder.header("x-api-key", api_key);
+        }
+        let mut req = builder.body(Body::empty()).unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        req
+    }
+
+    async fn body_text(response: axum::response::Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_config_endpoint_redacts_secrets() {
+        let response = app()
+            .oneshot(request("127.0.0.1:5000", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = body_text(response).await;
+        assert!(!text.contains(SECRET_KEY));
+        assert!(!text.contains(SECRET_SALT));
+
+        let body: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(body["server"]["concurrency"], 12);
+        assert_eq!(body["security"]["key"], "<redacted api key>");
+        assert_eq!(body["security"]["salt"], "<redacted api salt>");
+    }
+
+    #[tokio::test]
+    async fn test_config_endpoint_requires_key_for_remote_clients() {
+        let response = app()
+            .oneshot(request("203.0.113.5:5000", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app()
+            .oneshot(request("203.0.113.5:5000", Some("wrong-key")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app()
+            .oneshot(request("203.0.113.5:5000", Some(SECRET_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!body_text(response).await.contains(SECRET_KEY));
+    }
+
+    #[tokio::test]
+    async fn test_config_endpoint_requires_key_behind_a_trusted_proxy() {
+        let app = || {
+            app_with(ServerConfig {
+                trust_forwarded_for: true,
+                ..Default::default()
+            })
+        };
+        let response = app()
+            .oneshot(request("127.0.0.1:5000", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app()
+            .oneshot(request("127.0.0.1:5000", Some(SECRET_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_config_endpoint_empty_key_does_not_admit_remote_clients() {
+        let app = || {
+            let mut config = Config::default();
+            config.security.set_key(ApiKey::from(String::new()));
+            Router::new()
+                .route("/config", get(effective_config))
+                .with_state(Arc::new(config))
+        };
+        let response = app()
+            .oneshot(request("203.0.113.5:5000", Some("")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app()
+            .oneshot(request("127.0.0.1:5000", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -1,14 +1,59 @@
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use crate::config::Config;
+use crate::http::errors::AppError;
+use crate::image::pipeline_executor::operation_counts;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use sysinfo::{Disks, System};
-use tracing::info;
+use tracing::{info, warn};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Usage percentage at or above which a resource is reported unhealthy.
+const MAX_USAGE_PERCENTAGE: f64 = 90.0;
+
+/// Which system checks `/ready` performs (`[server.health_checks]`).
+///
+/// Disable checks that cannot work on the host, e.g. memory in containers without `/proc` access.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HealthChecksConfig {
+    #[serde(default = "default_check_enabled")]
+    pub memory: bool,
+    #[serde(default = "default_check_enabled")]
+    pub disk: bool,
+}
+
+impl Default for HealthChecksConfig {
+    fn default() -> Self {
+        Self {
+            memory: default_check_enabled(),
+            disk: default_check_enabled(),
+        }
+    }
+}
+
+fn default_check_enabled() -> bool {
+    true
+}
+
+/// Outcome of a single readiness check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Healthy,
+    Unhealthy,
+    /// The platform did not report the data needed (e.g. restricted `/proc`).
+    Unknown,
+    /// The check is turned off in configuration.
+    Disabled,
+}
+
 // Global counters for metrics (in production, use proper metrics library like prometheus)
 static REQUEST_COUNT: AtomicU64 = AtomicU64::new(0);
 static ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
@@ -44,14 +89,32 @@ pub async fn health_check() -> impl IntoResponse {
 }
 
 /// Detailed readiness check endpoint
-pub async fn readiness_check() -> impl IntoResponse {
+///
+/// Only checks that are `unhealthy` make the service not ready; checks whose data is
+/// unavailable are reported as `unknown` and logged instead.
+pub async fn readiness_check(State(config): State<Arc<Config>>) -> impl IntoResponse {
     info!("Readiness check endpoint called");
 
     // Perform basic system checks
-    let memory_check = check_memory_usage();
-    let disk_check = check_disk_space();
+    let checks = &config.server.health_checks;
+    let memory_check = if checks.memory {
+        check_memory_usage()
+    } else {
+        CheckStatus::Disabled
+    };
+    let disk_check = if checks.disk {
+        check_disk_space()
+    } else {
+        CheckStatus::Disabled
+    };
+
+    for (name, status) in [("memory", memory_check), ("disk", disk_check)] {
+        if status == CheckStatus::Unknown {
+            warn!("Readiness check '{}' could not be determined", name);
+        }
+    }
 
-    let is_ready = memory_check && disk_check;
+    let is_ready = memory_check != CheckStatus::Unhealthy && disk_check != CheckStatus::Unhealthy;
     let status_code = if is_ready {
         StatusCode::OK
     } else {
@@ -75,67 +138,157 @@ pub async fn readiness_check() -> impl IntoResponse {
     )
 }
 
-/// Metrics endpoint for monitoring
-pub async fn metrics() -> impl IntoResponse {
+/// `Content-Type` of the Prometheus text exposition format.
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Deserialize)]
+pub struct MetricsQuery {
+    /// `json` or `prometheus`; overrides the `Accept` header.
+    format: Option<String>,
+}
+
+/// Metrics endpoint for monitoring.
+///
+/// Responds with JSON unless `?format=prometheus` is given or the `Accept` header asks for
+/// `text/plain`, in which case the Prometheus text exposition format is used.
+pub async fn metrics(
+    Query(query): Query<MetricsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     info!("Metrics endpoint called");
 
+    let prometheus = match query.format.as_deref() {
+        Some("prometheus") => true,
+        Some("json") => false,
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown metrics format '{}'; use 'json' or 'prometheus'",
+                other
+            )))
+        }
+        None => headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/plain")),
+    };
+
     let uptime_seconds = START_TIME
         .get()
         .and_then(|start| SystemTime::now().duration_since(*start).ok())
         .map(|duration| duration.as_secs())
         .unwrap_or(0);
+    let requests_total = REQUEST_COUNT.load(Ordering::Relaxed);
+    let errors_total = ERROR_COUNT.load(Ordering::Relaxed);
+    let memory_bytes = get_memory_usage();
 
-    Json(json!({
+    if prometheus {
+        let body = prometheus_metrics(&[
+            (
+                "imaginary_requests_total",
+                "counter",
+                "Requests handled since startup.",
+                requests_total,
+            ),
+            (
+                "imaginary_errors_total",
+                "counter",
+                "Requests answered with a 4xx or 5xx status since startup.",
+                errors_total,
+            ),
+            (
+                "imaginary_uptime_seconds",
+                "gauge",
+                "Seconds since the server started.",
+                uptime_seconds,
+            ),
+            (
+                "imaginary_memory_bytes",
+                "gauge",
+                "Memory in use on the host, in bytes.",
+                memory_bytes,
+            ),
+        ]) + &prometheus_operation_counts();
+        return Ok(([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], body).into_response());
+    }
+
+    Ok(Json(json!({
         "version": VERSION,
         "uptime_seconds": uptime_seconds,
-        "requests_total": REQUEST_COUNT.load(Ordering::Relaxed),
-        "errors_total": ERROR_COUNT.load(Ordering::Relaxed),
-        "memory_usage_bytes": get_memory_usage(),
+        "requests_total": requests_total,
+        "errors_total": errors_total,
+        "memory_usage_bytes": memory_bytes,
+        "operations": operation_counts()
+            .into_iter()
+            .map(|(operation, succeeded, failed)| {
+                (operation.to_string(), json!({"ok": succeeded, "error": failed}))
+            })
+            .collect::<serde_json::Map<_, _>>(),
         "timestamp": SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs()
     }))
+    .into_response())
 }
 
-/// Check memory usage - returns true if usage is reasonable (less than 90%)
-fn check_memory_usage() -> bool {
-    let mut system = System::new();
-    system.refresh_memory();
+/// Renders `(name, type, help, value)` samples in the Prometheus text exposition format.
+fn prometheus_metrics(samples: &[(&str, &str, &str, u64)]) -> String {
+    samples
+        .iter()
+        .map(|(name, kind, help, value)| {
+            format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n")
+        })
+        .collect()
+}
 
-    let total_memory = system.total_memory();
-    let used_memory = system.used_memory();
+/// Renders the per-operation run counts as the `imaginary_operation_total` counter, labelled by
+/// `operation` and `result` (`ok` or `error`).
+fn prometheus_operation_counts() -> String {
+    let name = "imaginary_operation_total";
+    let mut body = format!(
+        "# HELP {name} Pipeline operations run since startup, by outcome.\n# TYPE {name} counter\n"
+    );
+    for (operation, succeeded, failed) in operation_counts() {
+        for (result, value) in [("ok", succeeded), ("error", failed)] {
+            body += &format!("{name}{{operation=\"{operation}\",result=\"{result}\"}} {value}\n");
+        }
+    }
+    body
+}
 
-    if total_memory == 0 {
-        return true; // Can't determine, assume healthy
+/// Classifies resource usage; a zero total means the platform did not report it.
+fn usage_status(used: u64, total: u64) -> CheckStatus {
+    if total == 0 {
+        return CheckStatus::Unknown;
+    }
+    let usage_percentage = (used as f64 / total as f64) * 100.0;
+    if usage_percentage < MAX_USAGE_PERCENTAGE {
+        CheckStatus::Healthy
+    } else {
+        CheckStatus::Unhealthy
     }
+}
 
-    let usage_percentage = (used_memory as f64 / total_memory as f64) * 100.0;
-    usage_percentage < 90.0
+/// Check memory usage - healthy if less than 90% is used
+fn check_memory_usage() -> CheckStatus {
+    let mut system = System::new();
+    system.refresh_memory();
+    usage_status(system.used_memory(), system.total_memory())
 }
 
-/// Check disk space - returns true if available space is more than 10%
-fn check_disk_space() -> bool {
+/// Check disk space on the root filesystem - healthy if more than 10% is available
+fn check_disk_space() -> CheckStatus {
     let disks = Disks::new_with_refreshed_list();
-
-    // Check the root filesystem
-    for disk in &disks {
-        if disk.mount_point().to_str().unwrap_or("") == "/" {
+    disks
+        .iter()
+        .find(|disk| disk.mount_point().to_str() == Some("/"))
+        .map_or(CheckStatus::Unknown, |disk| {
             let total_space = disk.total_space();
-            let available_space = disk.available_space();
-
-            if total_space == 0 {
-                return true; // Can't determine, assume healthy
-            }
-
-            let usage_percentage =
-                ((total_space - available_space) as f64 / total_space as f64) * 100.0;
-            return usage_percentage < 90.0;
-        }
-    }
-
-    // If we can't find root filesystem, assume healthy
-    true
+            usage_status(
+                total_space.saturating_sub(disk.available_space()),
+                total_space,
+            )
+        })
 }
 
 /// Get current memory usage in bytes
@@ -146,3 +299,138 @@ fn get_memory_usage() -> u64 {
     // Return used memory in bytes
     system.used_memory()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ServerConfig;
+
+    async fn ready_body(checks: HealthChecksConfig) -> (StatusCode, serde_json::Value) {
+        let config = Arc::new(Config {
+            server: ServerConfig {
+                health_checks: checks,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let response = readiness_check(State(config)).await.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    async fn metrics_response(format: Option<&str>, accept: Option<&str>) -> Response {
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(header::ACCEPT, accept.parse().unwrap());
+        }
+        let query = MetricsQuery {
+            format: format.map(str::to_string),
+        };
+        metrics(Query(query), headers).await.into_response()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_metrics_render_prometheus_when_asked() {
+        for response in [
+            metrics_response(Some("prometheus"), None).await,
+            metrics_response(None, Some("text/plain")).await,
+        ] {
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                PROMETHEUS_CONTENT_TYPE
+            );
+            let body = body_text(response).await;
+            for (name, kind) in [
+                ("imaginary_requests_total", "counter"),
+                ("imaginary_errors_total", "counter"),
+                ("imaginary_uptime_seconds", "gauge"),
+                ("imaginary_memory_bytes", "gauge"),
+                ("imaginary_operation_total", "counter"),
+            ] {
+                assert!(body.contains(&format!("# HELP {} ", name)), "{}", body);
+                assert!(
+                    body.contains(&format!("# TYPE {} {}\n", name, kind)),
+                    "{}",
+                    body
+                );
+                assert!(
+                    body.lines().any(|line| line
+                        .strip_prefix(name)
+                        .and_then(|sample| sample.rsplit_once(' '))
+                        .is_some_and(|(_, value)| value.parse::<u64>().is_ok())),
+                    "{}",
+                    body
+                );
+            }
+            assert!(
+                body.contains(
+                    "imaginary_operation_total{operation=\"smartCrop\",result=\"error\"} "
+                ),
+                "{}",
+                body
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_default_to_json() {
+        for response in [
+            metrics_response(None, None).await,
+            metrics_response(None, Some("application/json")).await,
+            metrics_response(Some("json"), Some("text/plain")).await,
+        ] {
+            let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+            assert!(body["requests_total"].is_u64());
+        }
+        assert_eq!(
+            metrics_response(Some("xml"), None).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_usage_status_unknown_when_total_unavailable() {
+        assert_eq!(usage_status(0, 0), CheckStatus::Unknown);
+        assert_eq!(usage_status(512, 0), CheckStatus::Unknown);
+        assert_eq!(usage_status(50, 100), CheckStatus::Healthy);
+        assert_eq!(usage_status(95, 100), CheckStatus::Unhealthy);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_checks_are_reported_and_ready() {
+        let (status, body) = ready_body(HealthChecksConfig {
+            memory: false,
+            disk: false,
+        })
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["checks"]["memory"], "disabled");
+        assert_eq!(body["checks"]["disk"], "disabled");
+    }
+
+    #[tokio::test]
+    async fn test_enabled_checks_report_a_status() {
+        let (_, body) = ready_body(HealthChecksConfig::default()).await;
+        for check in ["memory", "disk"] {
+            let value = body["checks"][check].as_str().unwrap();
+            assert!(
+                ["healthy", "unhealthy", "unknown"].contains(&value),
+                "{}: {}",
+                check,
+                value
+            );
+        }
+    }
+}
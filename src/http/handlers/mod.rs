@@ -1,2 +1,11 @@
+pub mod analyze_handler;
+pub mod batch_handler;
+pub mod capabilities_handler;
+pub mod compare_handler;
+pub mod config_handler;
 pub mod health_handler;
 pub mod pipeline_handler;
+pub mod sign_handler;
+pub mod srcset_handler;
+pub mod validate_handler;
+pub mod version_handler;
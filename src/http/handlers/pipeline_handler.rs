@@ -1,53 +1,256 @@
 //! HTTP handler for the /pipeline endpoint.
 //!
-//! Accepts multipart/form-data with an image and a JSON array of operations.
+//! Accepts multipart/form-data with an image and a JSON array of operations, or a JSON body
+//! with an image URL or base64-encoded image plus the operations.
 //! Applies the operations in sequence and returns the processed image.
+//! Animated GIFs whose output format stays GIF are processed frame by frame.
 //!
 //! Example usage:
 //!   POST /pipeline
 //!   - image: file
 //!   - operations: '[{"operation": "resize", "params": {"width": 200, "height": 200}}]'
-
-use std::io::Cursor;
-use std::net::{IpAddr, Ipv4Addr};
+//!
+//!   POST /pipeline (Content-Type: application/json)
+//!   {"image_base64": "iVBORw0...", "operations": [{"operation": "grayscale"}]}
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::{self, Cursor};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
-    extract::{Multipart, Query, State},
-    http::Method,
-    response::Response,
+    body::{Body, Bytes},
+    extract::{FromRequest, Multipart, Query, Request, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, ETAG, IF_NONE_MATCH, X_CONTENT_TYPE_OPTIONS},
+        HeaderMap, Method, StatusCode, Uri,
+    },
+    response::{AppendHeaders, IntoResponse, Response},
+    Extension, Json,
 };
-use image::ImageFormat;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::{AnimationDecoder, DynamicImage, Frame, ImageFormat};
 use once_cell::sync::Lazy;
-use serde::Deserialize;
-use serde_json::{from_str, from_value};
+use serde::{Deserialize, Serialize};
+use serde_json::from_value;
+use sha2::{Digest, Sha256};
 use url::Url;
 
 use crate::{
-    config::Config, // Assuming Config is at crate::config
+    config::{current_config, Config, LiveConfig},
     http::errors::AppError,
     image::{
+        format_support::{
+            check_declared_type, decode_error, detect_input_format, format_name, format_support,
+        },
+        heif,
+        icc::{embed_icc_profile, jpeg_embedding_size, read_icc_profile},
+        limits::{exact_output_dimensions, ImageLimits},
+        metadata::{
+            apply_orientation, embed_orientation, read_orientation, strip_metadata, MetadataOptions,
+        },
+        operations::format::encode_image_with_quality,
         params::FormatConversionParams, // For parsing convert params
-        pipeline_executor::execute_pipeline,
-        pipeline_types::{PipelineOperationSpec, SupportedOperation}, // For checking op type
+        pipeline_executor::{
+            check_operation_params, execute_pipeline_with_timeout, format_timings,
+            parse_operations, source_asset, PipelineAssets, OVERLAY_ASSET, WATERMARK_ASSET,
+        },
+        pipeline_types::{json_pointer, PipelineOperationSpec, SupportedOperation}, // For checking op type
+    },
+    security::SecurityConfig,
+    server::{cpu_pool::run_cpu, governor::throttle_pixels, AnimatedInputPolicy, ServerConfig},
+    utils::{
+        image_utils::{is_animated, probe_dimensions},
+        single_flight::SingleFlight,
     },
 };
 
 const MAX_IMAGE_SIZE: usize = 10 * 1024 * 1024; // 10 MB, consistent with server config default
 
+/// Response header listing the operations that were applied (and skipped) by the pipeline.
+const OPERATIONS_HEADER: &str = "X-Imaginary-Operations";
+
+/// Response header with the JPEG quality chosen to meet a `max_bytes` budget.
+const QUALITY_HEADER: &str = "X-Imaginary-Quality";
+
+/// Response header flagging that only the first frame of an animated input was used.
+const FIRST_FRAME_ONLY_HEADER: &str = "X-First-Frame-Only";
+
+/// Response header with per-operation durations, sent when `server.timings_header` is set.
+const TIMINGS_HEADER: &str = "X-Imaginary-Timings";
+
+/// Request header with the SHA-256 of an uploaded image, checked before processing.
+const CHECKSUM_HEADER: &str = "X-Checksum-SHA256";
+
+/// Pipeline renders currently in progress, keyed by `coalescing_key`.
+static PIPELINE_FLIGHTS: Lazy<SingleFlight<Result<Arc<RenderedImage>, AppError>>> =
+    Lazy::new(SingleFlight::new);
+
+/// Resolver used for URL fetches; see [`SafeResolver`].
+static SAFE_RESOLVER: Lazy<SafeResolver> = Lazy::new(SafeResolver::system);
+
 // Reusable HTTP client for performance
-static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| fetch_client(SAFE_RESOLVER.clone()));
+
+/// Pending result of a [`HostLookup`].
+type LookupFuture = Pin<Box<dyn Future<Output = io::Result<Vec<IpAddr>>> + Send>>;
+
+/// Looks up the IP addresses of a host name.
+type HostLookup = dyn Fn(String) -> LookupFuture + Send + Sync;
+
+/// DNS resolver for URL fetches that only returns addresses passing `allow` ([`is_safe_ip`]).
+///
+/// The HTTP client connects to exactly the addresses this resolver returned, so an answer that
+/// changes between the SSRF check and the connection (DNS rebinding) can't reach a private
+/// address.
+#[derive(Clone)]
+struct SafeResolver {
+    lookup: Arc<HostLookup>,
+    allow: fn(IpAddr) -> bool,
+}
+
+impl SafeResolver {
+    fn system() -> Self {
+        Self {
+            lookup: Arc::new(|host: String| -> LookupFuture {
+                Box::pin(async move {
+                    let addrs = tokio::net::lookup_host((host.as_str(), 0)).await?;
+                    Ok(addrs.map(|addr| addr.ip()).collect())
+                })
+            }),
+            allow: is_safe_ip,
+        }
+    }
+
+    /// Resolves `host`, keeping only the allowed addresses; fails if none remain.
+    async fn resolve_safe(&self, host: &str) -> Result<Vec<IpAddr>, AppError> {
+        let ips = (self.lookup)(host.to_string()).await.map_err(|e| {
+            AppError::BadRequest(format!("Failed to resolve hostname '{}': {}", host, e))
+        })?;
+        self.filter_allowed(host, ips)
+    }
+
+    /// Checks the host of a URL about to be requested.
+    ///
+    /// Host names are resolved here to fail early with a clear error; the client repeats the
+    /// lookup through this resolver when connecting, since the answer may have changed by then.
+    /// IP literals never reach the resolver, so this is their only check.
+    async fn check_url(&self, url: &Url) -> Result<(), AppError> {
+        match url.scheme() {
+            "http" | "https" => {}
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Only HTTP and HTTPS URLs are supported".to_string(),
+                ))
+            }
+        }
+        match url.host() {
+            Some(url::Host::Domain(domain)) => self.resolve_safe(domain).await.map(drop),
+            Some(url::Host::Ipv4(ip)) => self
+                .filter_allowed(&ip.to_string(), vec![IpAddr::V4(ip)])
+                .map(drop),
+            Some(url::Host::Ipv6(ip)) => self
+                .filter_allowed(&ip.to_string(), vec![IpAddr::V6(ip)])
+                .map(drop),
+            None => Err(AppError::BadRequest(
+                "URL must contain a valid hostname".to_string(),
+            )),
+        }
+    }
+
+    /// Filters `ips` down to the allowed ones, failing with `SsrfBlocked` if none are left.
+    fn filter_allowed(&self, host: &str, ips: Vec<IpAddr>) -> Result<Vec<IpAddr>, AppError> {
+        let allowed: Vec<_> = ips.into_iter().filter(|ip| (self.allow)(*ip)).collect();
+        if allowed.is_empty() {
+            return Err(AppError::SsrfBlocked(format!(
+                "URL '{}' resolves to private/internal IP addresses and is not allowed for security reasons",
+                host
+            )));
+        }
+        Ok(allowed)
+    }
+}
+
+impl reqwest::dns::Resolve for SafeResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let ips = resolver.resolve_safe(name.as_str()).await?;
+            // Port 0 is replaced with the URL's port by the connector
+            let addrs: reqwest::dns::Addrs =
+                Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// Builds the client for URL fetches.
+///
+/// Host names only resolve through `resolver`. Redirects are not followed by the client but by
+/// [`fetch_image`], which checks every hop. System proxies are not used: a proxy would resolve
+/// the host itself and bypass the checks.
+fn fetch_client(resolver: SafeResolver) -> reqwest::Client {
     reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
         .user_agent("imaginary-rs/0.1.0")
+        .no_proxy()
+        .dns_resolver(Arc::new(resolver))
+        .redirect(reqwest::redirect::Policy::none())
         .build()
         .expect("Failed to create HTTP client")
-});
+}
 
 #[derive(Deserialize)]
 pub struct PipelineQuery {
     url: Option<String>,
     operations: String,
+    format: Option<String>,
+    filename: Option<String>,
+    strip_metadata: Option<bool>,
+    keep_orientation: Option<bool>,
+    dry_run: Option<bool>,
+}
+
+/// JSON body accepted by POST /pipeline; exactly one of `url` and `image_base64` must be set.
+#[derive(Deserialize)]
+pub struct PipelineJsonBody {
+    url: Option<String>,
+    image_base64: Option<String>,
+    operations: Vec<PipelineOperationSpec>,
+    format: Option<String>,
+    filename: Option<String>,
+    strip_metadata: Option<bool>,
+    keep_orientation: Option<bool>,
+    dry_run: Option<bool>,
+}
+
+/// Image and operations read from a /pipeline request.
+struct PipelineInput {
+    image_bytes: Vec<u8>,
+    operations_spec: Vec<PipelineOperationSpec>,
+    original_format: ImageFormat,
+    /// Output format requested by the client, overriding any `convert` operation.
+    format: Option<String>,
+    /// Download filename requested by the client, as sent.
+    filename: Option<String>,
+    metadata: MetadataOptions,
+    /// Report the output size and format instead of the image.
+    dry_run: bool,
+    /// Encoded auxiliary images by name, e.g. the `watermark` multipart field.
+    assets: BTreeMap<String, Vec<u8>>,
+}
+
+/// Metadata handling from the optional `strip_metadata` and `keep_orientation` parameters.
+fn metadata_options(strip: Option<bool>, keep_orientation: Option<bool>) -> MetadataOptions {
+    let defaults = MetadataOptions::default();
+    MetadataOptions {
+        strip: strip.unwrap_or(defaults.strip),
+        keep_orientation: keep_orientation.unwrap_or(defaults.keep_orientation),
+    }
 }
 
 /// Handles both POST and GET /pipeline requests
@@ -55,50 +258,544 @@ pub struct PipelineQuery {
 /// POST: Accepts multipart/form-data with fields:
 /// - `image`: the image file
 /// - `operations`: JSON array of operation specs
+/// - `format` (optional): output format, see below
+/// - `filename` (optional): download filename, see below
+/// - `strip_metadata`, `keep_orientation` (optional): metadata handling, see below
+/// - `dry_run` (optional): see below
+///
+/// POST with `Content-Type: application/json`: Accepts a body with:
+/// - `url` or `image_base64`: where to fetch the image from, or the image itself
+/// - `operations`: array of operation specs
+/// - `format`, `filename`, `strip_metadata`, `keep_orientation`, `dry_run` (optional)
 ///
 /// GET: Accepts query parameters:
 /// - `url`: URL of the image to process
 /// - `operations`: JSON-encoded array of operation specs
+/// - `sign`: HMAC-SHA256 of the path and query (without `sign`), required when a key is configured
+/// - `format`, `filename`, `strip_metadata`, `keep_orientation`, `dry_run` (optional)
+///
+/// Returns the processed image as binary data, encoded as `format` when given and otherwise as
+/// the last `convert` operation asks (or in the input format). When `filename` is given, the response carries
+/// `Content-Disposition: attachment` with that name, sanitized and with the extension of the
+/// output format.
+///
+/// The input's EXIF orientation is applied to the pixels and, unless `strip_metadata` is
+/// `false`, the output is scrubbed of EXIF/XMP/IPTC/text metadata (see `image::metadata`).
+/// `keep_orientation` keeps the pixels as stored and writes only the orientation tag back
+/// (JPEG output).
 ///
-/// Returns the processed image as binary data.
+/// With `dry_run=true` nothing is encoded: the response is JSON `{width, height, format}`
+/// describing the image the request would produce. See [`dry_run_outcome`].
 pub async fn process_pipeline(
     method: Method,
+    uri: Uri,
     State(config): State<Arc<Config>>,
+    live: Option<Extension<LiveConfig>>,
+    headers: HeaderMap,
     query: Option<Query<PipelineQuery>>,
-    multipart: Option<Multipart>,
+    request: Request,
 ) -> Result<Response, AppError> {
-    let (image_bytes, operations_spec, original_format) = match method {
-        Method::GET => handle_get_request(query, &config).await?,
-        Method::POST => handle_post_request(multipart, &config).await?,
-        _ => return Err(AppError::BadRequest("Method not allowed".to_string())),
+    let PipelineInput {
+        image_bytes,
+        operations_spec,
+        original_format,
+        format,
+        filename,
+        metadata,
+        dry_run,
+        mut assets,
+    } = match method {
+        Method::GET => {
+            // Signatures follow key rotations by a reload
+            let current = current_config(&config, live.as_ref().map(|Extension(live)| live));
+            verify_url_signature(&uri, &current.security)?;
+            handle_get_request(query, &config).await?
+        }
+        Method::POST if is_json(&headers) => handle_json_request(request, &config).await?,
+        Method::POST => {
+            let multipart = Multipart::from_request(request, &config).await.ok();
+            handle_post_request(multipart, &headers, &config).await?
+        }
+        _ => {
+            return Err(AppError::MethodNotAllowed {
+                method: method.to_string(),
+                allow: "GET, POST",
+            })
+        }
     };
+    ImageLimits::check_operation_count(&operations_spec, config.server.max_pipeline_operations)?;
+    fetch_overlay_assets(&operations_spec, &mut assets, &config).await?;
+
+    // An explicit `format` wins; otherwise the last convert operation or the original format
+    let output_format = match &format {
+        Some(name) => format_from_name(name)
+            .ok_or_else(|| AppError::BadRequest(format!("Unsupported output format '{}'", name)))?,
+        None => determine_output_format(&operations_spec, original_format),
+    };
+    format_support().ensure_supported(original_format, output_format)?;
+
+    if dry_run {
+        return dry_run_outcome(
+            image_bytes,
+            operations_spec,
+            original_format,
+            output_format,
+            metadata,
+            assets,
+            config,
+        )
+        .await
+        .map(|outcome| Json(outcome).into_response());
+    }
 
-    let dynamic_image = image::load_from_memory_with_format(&image_bytes, original_format)
-        .map_err(|e| AppError::ImageProcessingError(format!("Failed to load image: {}", e)))?;
-
-    let processed_image = execute_pipeline(dynamic_image, operations_spec.clone())?;
+    // Identical concurrent requests (same image, operations and output) share one render, and
+    // the same key is the ETag since the output is determined by those inputs
+    let key = coalescing_key(
+        &image_bytes,
+        &operations_spec,
+        output_format,
+        metadata,
+        &assets,
+    );
+    let etag = format!("\"{}\"", key);
+    if if_none_match(&headers, &etag) {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(ETAG, etag)
+            .body(Body::empty())
+            .map_err(|e| {
+                AppError::InternalServerError(format!("Failed to build response: {}", e))
+            });
+    }
+    let rendered = PIPELINE_FLIGHTS
+        .run(&key, || {
+            render(
+                image_bytes,
+                operations_spec,
+                original_format,
+                output_format,
+                metadata,
+                assets,
+                config.clone(),
+            )
+        })
+        .await?;
 
-    // Determine output format - default to original format unless convert operation specifies otherwise
-    let output_format = determine_output_format(&operations_spec, original_format);
     let content_type = output_format.to_mime_type();
 
-    let mut final_image_bytes = Vec::new();
-    processed_image
-        .write_to(&mut Cursor::new(&mut final_image_bytes), output_format)
-        .map_err(|e| {
-            AppError::ImageProcessingError(format!("Failed to write processed image: {}", e))
-        })?;
-
-    Response::builder()
+    // The type comes from the encoder's output format alone, never from the upload, and
+    // `nosniff` stops browsers from second-guessing it (e.g. rendering a polyglot as HTML)
+    let mut response = Response::builder()
         .header("Content-Type", content_type)
-        .body(axum::body::Body::from(final_image_bytes))
+        .header(X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(ETAG, etag)
+        .header(OPERATIONS_HEADER, rendered.summary.as_str());
+    if rendered.first_frame_only {
+        response = response.header(FIRST_FRAME_ONLY_HEADER, "true");
+    }
+    if let Some(quality) = rendered.quality {
+        response = response.header(QUALITY_HEADER, quality.to_string());
+    }
+    if config.server.timings_header {
+        response = response.header(TIMINGS_HEADER, rendered.timings.as_str());
+    }
+    if let Some(filename) = filename {
+        response = response.header(
+            CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}\"",
+                download_filename(&filename, output_format)
+            ),
+        );
+    }
+    // `Bytes` is reference counted: coalesced responses share one encoded buffer, no copies
+    response
+        .body(Body::from(rendered.bytes.clone()))
         .map_err(|e| AppError::InternalServerError(format!("Failed to build response: {}", e)))
 }
 
+/// Returns true if the `If-None-Match` header lists `etag` or is `*`.
+///
+/// Uses the weak comparison RFC 9110 prescribes for `If-None-Match`, so `W/` prefixes are
+/// ignored.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Sanitizes a client-supplied download filename and gives it the extension of `format`.
+///
+/// Directory components, quotes, backslashes and control characters (CR/LF included) are
+/// removed, as is anything outside ASCII, so the name can't break out of the header value.
+fn download_filename(requested: &str, format: ImageFormat) -> String {
+    let name: String = requested
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii() && !c.is_ascii_control() && *c != '"')
+        .collect();
+    // A dot-file like `.png` has an empty stem and falls back to `image`
+    let stem = match name.rsplit_once('.') {
+        Some((stem, _)) => stem,
+        None => name.as_str(),
+    };
+    let stem = match stem.trim() {
+        "" => "image",
+        stem => stem,
+    };
+    let extension = format.extensions_str().first().copied().unwrap_or("bin");
+    format!("{}.{}", stem, extension)
+}
+
+/// Result of a dry run: what `/pipeline` would return, without the pixels.
+#[derive(Debug, Serialize)]
+struct DryRunOutcome {
+    width: u32,
+    height: u32,
+    format: String,
+}
+
+/// Works out the size and format of the pipeline's output without encoding it.
+///
+/// When every operation's output size follows from its parameters, only the image header is
+/// read. Otherwise the image is decoded and the operations run, as they would for a real
+/// request. Either way the input limits apply and EXIF orientation is taken into account.
+async fn dry_run_outcome(
+    image_bytes: Vec<u8>,
+    operations_spec: Vec<PipelineOperationSpec>,
+    original_format: ImageFormat,
+    output_format: ImageFormat,
+    metadata: MetadataOptions,
+    assets: BTreeMap<String, Vec<u8>>,
+    config: Arc<Config>,
+) -> Result<DryRunOutcome, AppError> {
+    let (width, height) = probe_dimensions(&image_bytes, original_format)?;
+    let limits = ImageLimits::from_config(&config.server);
+    limits.check_input((width, height))?;
+    limits.check_pipeline((width, height), &operations_spec)?;
+
+    let keep_orientation = metadata.keep_orientation && output_format == ImageFormat::Jpeg;
+    let orientation = read_orientation(&image_bytes, original_format)
+        .filter(|&orientation| orientation != 1 && !keep_orientation);
+    // Orientations 5-8 turn the image by a quarter
+    let oriented = match orientation {
+        Some(5..=8) => (height, width),
+        _ => (width, height),
+    };
+
+    let (width, height) = match exact_output_dimensions(oriented, &operations_spec) {
+        Some(dimensions) => dimensions,
+        None => {
+            let image = run_cpu("Decoding", move || {
+                let image = image::load_from_memory_with_format(&image_bytes, original_format)
+                    .map_err(|e| decode_error(original_format, e))?;
+                Ok(match orientation {
+                    Some(orientation) => apply_orientation(image, orientation),
+                    None => image,
+                })
+            })
+            .await?;
+            let max_operation_duration = match config.server.max_operation_duration {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            };
+            let assets = run_cpu("Asset decoding", move || decode_assets(&assets, &config)).await?;
+            let image = execute_pipeline_with_timeout(
+                image,
+                operations_spec,
+                max_operation_duration,
+                assets,
+            )
+            .await?
+            .image;
+            (image.width(), image.height())
+        }
+    };
+    Ok(DryRunOutcome {
+        width,
+        height,
+        format: format_name(output_format),
+    })
+}
+
+/// Output of a pipeline run, shared between coalesced requests.
+#[derive(Debug)]
+pub(crate) struct RenderedImage {
+    pub(crate) bytes: Bytes,
+    pub(crate) summary: String,
+    /// Per-operation durations, see [`format_timings`].
+    timings: String,
+    first_frame_only: bool,
+    /// JPEG quality picked for a `max_bytes` budget.
+    quality: Option<u8>,
+}
+
+/// The input image as decoded by [`render`].
+enum DecodedInput {
+    /// An animated GIF kept as GIF, processed frame by frame from its encoded bytes.
+    AnimatedGif(Vec<u8>),
+    /// A single image, with what the encoder needs to carry over from the input.
+    Still {
+        image: DynamicImage,
+        icc_profile: Option<Vec<u8>>,
+        kept_orientation: Option<u8>,
+        first_frame_only: bool,
+    },
+}
+
+/// Decodes, processes and encodes the image, honouring the image size limits, the pixel
+/// throughput limit, the operation timeout and the metadata options.
+///
+/// Decoding and encoding run on the CPU pool, as does each operation, so the handler only
+/// awaits while the pixels are worked on.
+pub(crate) async fn render(
+    image_bytes: Vec<u8>,
+    operations_spec: Vec<PipelineOperationSpec>,
+    original_format: ImageFormat,
+    output_format: ImageFormat,
+    metadata: MetadataOptions,
+    assets: BTreeMap<String, Vec<u8>>,
+    config: Arc<Config>,
+) -> Result<Arc<RenderedImage>, AppError> {
+    let (width, height) = probe_dimensions(&image_bytes, original_format)?;
+    let limits = ImageLimits::from_config(&config.server);
+    limits.check_input((width, height))?;
+    limits.check_pipeline((width, height), &operations_spec)?;
+    throttle_pixels(width as u64 * height as u64).await?;
+
+    let max_operation_duration = match config.server.max_operation_duration {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    let encoding = config
+        .encoding
+        .encoder_params(last_convert_params(&operations_spec), output_format);
+    let assets = {
+        let config = config.clone();
+        run_cpu("Asset decoding", move || decode_assets(&assets, &config)).await?
+    };
+
+    // Animated GIFs that stay GIFs are processed frame by frame, unless the policy rejects
+    // animated inputs; everything else is single-frame
+    let animated_input_policy = config.server.animated_input_policy;
+    let decoded = run_cpu("Decoding", move || {
+        let animated = check_animated_input(&image_bytes, original_format, animated_input_policy)?;
+        if animated && original_format == ImageFormat::Gif && output_format == ImageFormat::Gif {
+            return Ok(DecodedInput::AnimatedGif(image_bytes));
+        }
+        let icc_profile = read_icc_profile(&image_bytes, original_format);
+        let mut image = image::load_from_memory_with_format(&image_bytes, original_format)
+            .map_err(|e| decode_error(original_format, e))?;
+        // Orientation is either applied to the pixels or written back as the only EXIF tag
+        let orientation =
+            read_orientation(&image_bytes, original_format).filter(|&orientation| orientation != 1);
+        let kept_orientation =
+            orientation.filter(|_| metadata.keep_orientation && output_format == ImageFormat::Jpeg);
+        if let (Some(orientation), None) = (orientation, kept_orientation) {
+            image = apply_orientation(image, orientation);
+        }
+        Ok(DecodedInput::Still {
+            image,
+            icc_profile,
+            kept_orientation,
+            first_frame_only: animated,
+        })
+    })
+    .await?;
+
+    let (bytes, summary, timings, first_frame_only, quality) = match decoded {
+        DecodedInput::AnimatedGif(image_bytes) => {
+            let (bytes, summary, timings) = process_animated_gif(
+                image_bytes,
+                &operations_spec,
+                max_operation_duration,
+                &assets,
+                FrameLimits::from_config(&config.server),
+            )
+            .await?;
+            (bytes, summary, timings, false, None)
+        }
+        DecodedInput::Still {
+            image: dynamic_image,
+            icc_profile,
+            kept_orientation,
+            first_frame_only,
+        } => {
+            let result = execute_pipeline_with_timeout(
+                dynamic_image,
+                operations_spec,
+                max_operation_duration,
+                assets,
+            )
+            .await?;
+
+            let mut encoding = encoding;
+            // A byte budget covers the whole file, including the profile embedded after encoding
+            if let (Some(params), Some(profile)) = (encoding.as_mut(), &icc_profile) {
+                if output_format == ImageFormat::Jpeg {
+                    params.max_bytes = params
+                        .max_bytes
+                        .map(|max| max.saturating_sub(jpeg_embedding_size(profile)).max(1));
+                }
+            }
+            let summary = result.summary();
+            let timings = format_timings(&result.timings);
+            let (final_image_bytes, quality) = run_cpu("Encoding", move || {
+                let (mut final_image_bytes, quality) =
+                    encode_image_with_quality(&result.image, output_format, encoding.as_ref())?;
+                if metadata.strip {
+                    final_image_bytes = strip_metadata(final_image_bytes, output_format);
+                }
+                if let Some(profile) = icc_profile {
+                    final_image_bytes =
+                        embed_icc_profile(final_image_bytes, output_format, &profile);
+                }
+                if let Some(orientation) = kept_orientation {
+                    final_image_bytes = embed_orientation(final_image_bytes, orientation);
+                }
+                Ok((final_image_bytes, quality))
+            })
+            .await?;
+            (
+                final_image_bytes,
+                summary,
+                timings,
+                first_frame_only,
+                quality,
+            )
+        }
+    };
+
+    Ok(Arc::new(RenderedImage {
+        bytes: Bytes::from(bytes),
+        summary,
+        timings,
+        first_frame_only,
+        quality,
+    }))
+}
+
+/// Key identifying requests that produce the same output: hex SHA-256 over the input image,
+/// the operations, the output format, the metadata options and the uploaded assets.
+fn coalescing_key(
+    image_bytes: &[u8],
+    operations_spec: &[PipelineOperationSpec],
+    output_format: ImageFormat,
+    metadata: MetadataOptions,
+    assets: &BTreeMap<String, Vec<u8>>,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(image_bytes);
+    hasher.update(serde_json::to_vec(operations_spec).unwrap_or_default());
+    hasher.update(output_format.to_mime_type().as_bytes());
+    hasher.update([metadata.strip as u8, metadata.keep_orientation as u8]);
+    for (name, bytes) in assets {
+        // Length-prefix each part so different splits cannot hash alike
+        for part in [name.as_bytes(), bytes.as_slice()] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Fetches the images `overlay` operations name by `url`, through the same SSRF checks and
+/// size cap as `url` inputs, and adds them to `assets` keyed by the URL.
+///
+/// A failed fetch fails the request, unless the operation has `ignoreFailure`: then it is left
+/// out and the operation is skipped when it runs.
+async fn fetch_overlay_assets(
+    operations_spec: &[PipelineOperationSpec],
+    assets: &mut BTreeMap<String, Vec<u8>>,
+    config: &Config,
+) -> Result<(), AppError> {
+    for spec in operations_spec
+        .iter()
+        .filter(|spec| spec.operation == SupportedOperation::Overlay)
+    {
+        let Some(url) = spec.params.get("url").and_then(serde_json::Value::as_str) else {
+            continue;
+        };
+        if assets.contains_key(url) {
+            continue;
+        }
+        match fetch_image_from_url(url, config).await {
+            Ok(bytes) => {
+                assets.insert(url.to_string(), bytes);
+            }
+            Err(e) if spec.ignore_failure => {
+                tracing::warn!(url, error = %e, "Overlay fetch failed, operation will be skipped");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the auxiliary images uploaded next to the main one, with the same format allowlist
+/// and input size limit as the main image.
+fn decode_assets(
+    assets: &BTreeMap<String, Vec<u8>>,
+    config: &Config,
+) -> Result<Arc<PipelineAssets>, AppError> {
+    let limits = ImageLimits::from_config(&config.server);
+    assets
+        .iter()
+        .map(|(name, bytes)| {
+            let format = detect_input_format(bytes, &config.server.allowed_input_formats)?;
+            let dimensions = probe_dimensions(bytes, format)?;
+            limits.check_input(dimensions)?;
+            let image = image::load_from_memory_with_format(bytes, format)
+                .map_err(|e| decode_error(format, e))?;
+            Ok((name.clone(), image))
+        })
+        .collect::<Result<PipelineAssets, AppError>>()
+        .map(Arc::new)
+}
+
+/// Verifies the `sign` query parameter of a GET request against the configured key.
+///
+/// The signature is the hex HMAC-SHA256 of the request path plus the raw query string with the
+/// `sign` parameter removed (e.g. `/pipeline?url=...&operations=...`). Verification is skipped
+/// when no key is configured.
+pub(crate) fn verify_url_signature(uri: &Uri, security: &SecurityConfig) -> Result<(), AppError> {
+    if security.key().is_none_or(|key| key.is_empty()) {
+        return Ok(());
+    }
+
+    let mut signature = None;
+    let mut signed_params = Vec::new();
+    for param in uri.query().unwrap_or("").split('&') {
+        match param.strip_prefix("sign=") {
+            Some(value) => signature = Some(value),
+            None if !param.is_empty() => signed_params.push(param),
+            None => {}
+        }
+    }
+    let signature =
+        signature.ok_or_else(|| AppError::Unauthorized("Missing 'sign' parameter".to_string()))?;
+
+    let signed_data = if signed_params.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), signed_params.join("&"))
+    };
+    match security.validate_signature(signed_data.as_bytes(), signature) {
+        Ok(true) => Ok(()),
+        _ => Err(AppError::Unauthorized("Invalid URL signature".to_string())),
+    }
+}
+
 async fn handle_get_request(
     query: Option<Query<PipelineQuery>>,
     config: &Config,
-) -> Result<(Vec<u8>, Vec<PipelineOperationSpec>, ImageFormat), AppError> {
+) -> Result<PipelineInput, AppError> {
     let Query(params) =
         query.ok_or_else(|| AppError::BadRequest("Missing query parameters".to_string()))?;
 
@@ -110,8 +807,7 @@ async fn handle_get_request(
     let image_bytes = fetch_image_from_url(&url, config).await?;
 
     // Parse operations
-    let operations_spec: Vec<PipelineOperationSpec> = from_str(&params.operations)
-        .map_err(|e| AppError::BadRequest(format!("Failed to parse 'operations' JSON: {}", e)))?;
+    let operations_spec = parse_operations(&params.operations)?;
 
     if operations_spec.is_empty() {
         return Err(AppError::BadRequest(
@@ -119,50 +815,88 @@ async fn handle_get_request(
         ));
     }
 
-    let original_format = image::guess_format(&image_bytes).map_err(|_| {
-        AppError::UnsupportedMediaType("Could not determine image format".to_string())
-    })?;
-
-    Ok((image_bytes, operations_spec, original_format))
+    let (image_bytes, original_format) = detect_pipeline_input(image_bytes, config).await?;
+
+    Ok(PipelineInput {
+        image_bytes,
+        operations_spec,
+        original_format,
+        format: params.format,
+        filename: params.filename,
+        metadata: metadata_options(params.strip_metadata, params.keep_orientation),
+        dry_run: params.dry_run.unwrap_or(false),
+        assets: BTreeMap::new(),
+    })
 }
 
+/// Reads a multipart upload. The first `image` (or `file`) part is the image processed; any
+/// later ones are sources 1, 2, ... that `overlay` and `watermarkImage` select with `source`.
 async fn handle_post_request(
     multipart: Option<Multipart>,
+    headers: &HeaderMap,
     config: &Config,
-) -> Result<(Vec<u8>, Vec<PipelineOperationSpec>, ImageFormat), AppError> {
+) -> Result<PipelineInput, AppError> {
     let mut multipart =
         multipart.ok_or_else(|| AppError::BadRequest("Missing multipart data".to_string()))?;
 
-    let mut image_data: Option<Vec<u8>> = None;
+    // Every `image` (or `file`) part, in upload order; the first is the one processed
+    let mut images: Vec<Vec<u8>> = Vec::new();
+    let mut image_content_type: Option<String> = None;
     let mut operations_json_str: Option<String> = None;
-
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|e| AppError::MultipartError(e.to_string()))?
-    {
+    let mut format: Option<String> = None;
+    let mut filename: Option<String> = None;
+    let mut strip_metadata: Option<bool> = None;
+    let mut keep_orientation: Option<bool> = None;
+    let mut dry_run = false;
+    let mut assets = BTreeMap::new();
+    let max_image_size = config.server.max_body_size.min(MAX_IMAGE_SIZE);
+
+    while let Some(field) = multipart.next_field().await? {
         let name = field.name().unwrap_or("").to_string();
         match name.as_str() {
             "image" | "file" => {
-                let data = field
-                    .bytes()
-                    .await
-                    .map_err(|e| AppError::MultipartError(e.to_string()))?;
-                if data.len() > config.server.max_body_size.min(MAX_IMAGE_SIZE) {
+                if images.is_empty() {
+                    image_content_type = field.content_type().map(str::to_string);
+                }
+                let data = field.bytes().await?;
+                if data.len() > max_image_size {
                     return Err(AppError::PayloadTooLarge(format!(
                         "Image size {} exceeds limit",
                         data.len()
                     )));
                 }
-                image_data = Some(data.into());
+                images.push(data.into());
+            }
+            WATERMARK_ASSET | OVERLAY_ASSET => {
+                let data = field.bytes().await?;
+                if data.len() > max_image_size {
+                    return Err(AppError::PayloadTooLarge(format!(
+                        "'{}' image size {} exceeds limit",
+                        name,
+                        data.len()
+                    )));
+                }
+                assets.insert(name, data.into());
             }
             "operations" => {
-                operations_json_str = Some(
-                    field
-                        .text()
-                        .await
-                        .map_err(|e| AppError::MultipartError(e.to_string()))?,
-                );
+                operations_json_str = Some(field.text().await?);
+            }
+            "format" => {
+                format = Some(field.text().await?);
+            }
+            "filename" => {
+                filename = Some(field.text().await?);
+            }
+            "strip_metadata" | "keep_orientation" | "dry_run" => {
+                let text = field.text().await?;
+                let value = text.trim().parse::<bool>().map_err(|_| {
+                    AppError::BadRequest(format!("'{}' must be true or false", name))
+                })?;
+                match name.as_str() {
+                    "strip_metadata" => strip_metadata = Some(value),
+                    "keep_orientation" => keep_orientation = Some(value),
+                    _ => dry_run = value,
+                }
             }
             _ => {
                 tracing::debug!("Ignoring unknown multipart field: {}", name);
@@ -170,15 +904,23 @@ async fn handle_post_request(
         }
     }
 
-    let image_bytes = image_data.ok_or_else(|| {
+    let mut images = images.into_iter();
+    let image_bytes = images.next().ok_or_else(|| {
         AppError::BadRequest("Missing image data in multipart request".to_string())
     })?;
+    // Further images are available to `overlay` and `watermarkImage` through `source`
+    for (index, bytes) in images.enumerate() {
+        assets.insert(source_asset(index + 1), bytes);
+    }
+    verify_checksum(headers, &image_bytes)?;
+    if config.server.strict_content_type {
+        check_declared_type(image_content_type.as_deref(), &image_bytes)?;
+    }
     let ops_str = operations_json_str.ok_or_else(|| {
         AppError::BadRequest("Missing 'operations' JSON string in multipart request".to_string())
     })?;
 
-    let operations_spec: Vec<PipelineOperationSpec> = from_str(&ops_str)
-        .map_err(|e| AppError::BadRequest(format!("Failed to parse 'operations' JSON: {}", e)))?;
+    let operations_spec = parse_operations(&ops_str)?;
 
     if operations_spec.is_empty() {
         return Err(AppError::BadRequest(
@@ -186,171 +928,606 @@ async fn handle_post_request(
         ));
     }
 
-    let original_format = image::guess_format(&image_bytes).map_err(|_| {
-        AppError::UnsupportedMediaType("Could not determine image format".to_string())
-    })?;
-
-    Ok((image_bytes, operations_spec, original_format))
+    let (image_bytes, original_format) = detect_pipeline_input(image_bytes, config).await?;
+
+    Ok(PipelineInput {
+        image_bytes,
+        operations_spec,
+        original_format,
+        format,
+        filename,
+        metadata: metadata_options(strip_metadata, keep_orientation),
+        dry_run,
+        assets,
+    })
 }
 
-/// Checks if an IP address is safe for external requests (not private/internal)
-fn is_safe_ip(ip: IpAddr) -> bool {
-    match ip {
-        IpAddr::V4(ipv4) => {
-            // Use De Morgan's law to simplify boolean expression
-            !(ipv4.is_private()
-                || ipv4.is_loopback()
-                || ipv4.is_link_local()
-                || ipv4.is_broadcast()
-                || ipv4.is_multicast()
-                || (ipv4.octets()[0] == 100 && (64..128).contains(&ipv4.octets()[1]))
-                || ipv4 == Ipv4Addr::new(169, 254, 169, 254)
-                || (ipv4.octets()[0] == 192 && ipv4.octets()[1] == 0 && ipv4.octets()[2] == 2)
-                || (ipv4.octets()[0] == 198 && ipv4.octets()[1] == 51 && ipv4.octets()[2] == 100)
-                || (ipv4.octets()[0] == 203 && ipv4.octets()[1] == 0 && ipv4.octets()[2] == 113)
-                || (ipv4.octets()[0] == 192 && ipv4.octets()[1] == 88 && ipv4.octets()[2] == 99))
-        }
-        IpAddr::V6(ipv6) => {
-            // Use De Morgan's law and simplified expressions
-            !(ipv6.is_loopback()
-                || ipv6.is_multicast()
-                || ipv6.segments()[0] & 0xffc0 == 0xfe80
-                || ipv6.segments()[0] & 0xfe00 == 0xfc00
-                || (ipv6.segments()[0] == 0x2001 && ipv6.segments()[1] == 0x0db8))
-        }
+/// Checks an uploaded image against the `X-Checksum-SHA256` request header, when one is sent.
+///
+/// The header holds the SHA-256 digest of the image bytes (not the whole multipart body),
+/// hex or base64 encoded. A mismatch, or a value that is not a digest, is a bad request.
+fn verify_checksum(headers: &HeaderMap, image_bytes: &[u8]) -> Result<(), AppError> {
+    let Some(value) = headers.get(CHECKSUM_HEADER) else {
+        return Ok(());
+    };
+    let expected = value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .and_then(|value| {
+            hex::decode(value)
+                .ok()
+                .filter(|digest| digest.len() == 32)
+                .or_else(|| STANDARD.decode(value).ok())
+        })
+        .filter(|digest| digest.len() == 32)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "{} must be a hex or base64 SHA-256 digest",
+                CHECKSUM_HEADER
+            ))
+        })?;
+    if Sha256::digest(image_bytes).as_slice() != expected.as_slice() {
+        return Err(AppError::BadRequest(format!(
+            "Image does not match {}",
+            CHECKSUM_HEADER
+        )));
     }
+    Ok(())
 }
 
-async fn fetch_image_from_url(url_str: &str, config: &Config) -> Result<Vec<u8>, AppError> {
-    // Parse and validate URL
-    let url =
-        Url::parse(url_str).map_err(|e| AppError::BadRequest(format!("Invalid URL: {}", e)))?;
-
-    // Validate URL scheme
-    match url.scheme() {
-        "http" | "https" => {}
-        _ => {
-            return Err(AppError::BadRequest(
-                "Only HTTP and HTTPS URLs are supported".to_string(),
-            ))
-        }
+/// Detects the format of the uploaded or fetched image. HEIF inputs are decoded up front and
+/// continue through the pipeline as PNG (see `image::heif`).
+async fn detect_pipeline_input(
+    image_bytes: Vec<u8>,
+    config: &Config,
+) -> Result<(Vec<u8>, ImageFormat), AppError> {
+    let allowed = &config.server.allowed_input_formats;
+    if !heif::is_heif(&image_bytes) {
+        let format = detect_input_format(&image_bytes, allowed)?;
+        return Ok((image_bytes, format));
     }
+    if !heif::is_allowed(allowed) {
+        return Err(AppError::UnsupportedMediaType(
+            "Input format heif is not allowed".to_string(),
+        ));
+    }
+    let limits = ImageLimits::from_config(&config.server);
+    let png = run_cpu("HEIF decoding", move || {
+        heif::transcode_to_png(&image_bytes, &limits)
+    })
+    .await?;
+    Ok((png, ImageFormat::Png))
+}
 
-    // Validate hostname exists
-    let hostname = url
-        .host_str()
-        .ok_or_else(|| AppError::BadRequest("URL must contain a valid hostname".to_string()))?;
-
-    // Resolve hostname to IP addresses
-    let addrs = tokio::net::lookup_host((
-        hostname,
-        url.port()
-            .unwrap_or(if url.scheme() == "https" { 443 } else { 80 }),
-    ))
-    .await
-    .map_err(|e| {
-        AppError::BadRequest(format!("Failed to resolve hostname '{}': {}", hostname, e))
-    })?;
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
 
-    // Check if any resolved IP is safe
-    let safe_ips: Vec<_> = addrs
-        .filter_map(|addr| {
-            let ip = addr.ip();
-            if is_safe_ip(ip) {
-                Some(ip)
+async fn handle_json_request(request: Request, config: &Config) -> Result<PipelineInput, AppError> {
+    let max_image_size = config.server.max_body_size.min(MAX_IMAGE_SIZE);
+    // Base64 inflates the image by 4/3; leave some room for the operations
+    let max_json_size = max_image_size / 3 * 4 + 64 * 1024;
+    let body = axum::body::to_bytes(request.into_body(), max_json_size)
+        .await
+        .map_err(|_| {
+            AppError::PayloadTooLarge(format!(
+                "Request body exceeds limit of {} bytes",
+                max_json_size
+            ))
+        })?;
+    let PipelineJsonBody {
+        url,
+        image_base64,
+        operations: operations_spec,
+        format,
+        filename,
+        strip_metadata,
+        keep_orientation,
+        dry_run,
+    } = serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_slice(&body))
+        .map_err(|e| {
+            let pointer = json_pointer(e.path());
+            if pointer.starts_with("/operations/") {
+                AppError::InvalidOperationsJson {
+                    pointer,
+                    message: e.inner().to_string(),
+                }
             } else {
-                None
+                AppError::BadRequest(format!("Failed to parse JSON body: {}", e.inner()))
             }
-        })
-        .collect();
+        })?;
+    check_operation_params(&operations_spec).map_err(|e| match e {
+        AppError::InvalidOperationsJson { pointer, message } => AppError::InvalidOperationsJson {
+            pointer: format!("/operations{}", pointer),
+            message,
+        },
+        e => e,
+    })?;
 
-    if safe_ips.is_empty() {
-        return Err(AppError::BadRequest(format!(
-            "URL '{}' resolves to private/internal IP addresses and is not allowed for security reasons", 
-            hostname
-        )));
+    if operations_spec.is_empty() {
+        return Err(AppError::BadRequest(
+            "'operations' array cannot be empty".to_string(),
+        ));
     }
 
-    // Make the HTTP request using the reusable client
-    let response = HTTP_CLIENT
-        .get(url_str)
-        .send()
-        .await
-        .map_err(|e| AppError::BadRequest(format!("Failed to fetch image from URL: {}", e)))?;
+    let image_bytes = match (url, image_base64) {
+        (Some(url), None) => fetch_image_from_url(&url, config).await?,
+        (None, Some(encoded)) => {
+            let image_bytes = STANDARD
+                .decode(encoded.trim())
+                .map_err(|e| AppError::BadRequest(format!("Invalid 'image_base64' data: {}", e)))?;
+            if image_bytes.len() > max_image_size {
+                return Err(AppError::PayloadTooLarge(format!(
+                    "Image size {} exceeds limit of {} bytes",
+                    image_bytes.len(),
+                    max_image_size
+                )));
+            }
+            image_bytes
+        }
+        (Some(_), Some(_)) => {
+            return Err(AppError::BadRequest(
+                "Provide either 'url' or 'image_base64', not both".to_string(),
+            ))
+        }
+        (None, None) => {
+            return Err(AppError::BadRequest(
+                "Missing 'url' or 'image_base64' in JSON body".to_string(),
+            ))
+        }
+    };
 
-    if !response.status().is_success() {
-        return Err(AppError::BadRequest(format!(
-            "HTTP error when fetching image: {}",
-            response.status()
-        )));
+    let (image_bytes, original_format) = detect_pipeline_input(image_bytes, config).await?;
+
+    Ok(PipelineInput {
+        image_bytes,
+        operations_spec,
+        original_format,
+        format,
+        filename,
+        metadata: metadata_options(strip_metadata, keep_orientation),
+        dry_run: dry_run.unwrap_or(false),
+        assets: BTreeMap::new(),
+    })
+}
+
+/// Limits on the frames of an animated GIF; 0 disables a limit.
+#[derive(Debug, Clone, Copy, Default)]
+struct FrameLimits {
+    max_frames: usize,
+    /// Pixels of all decoded frames together.
+    max_total_pixels: u64,
+}
+
+impl FrameLimits {
+    fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            max_frames: config.max_frames,
+            max_total_pixels: config.max_input_pixels,
+        }
     }
+}
 
-    // Check content length
-    let content_length = response.content_length().unwrap_or(0);
-    let max_size = config.server.max_body_size.min(MAX_IMAGE_SIZE) as u64;
-    if content_length > max_size {
-        return Err(AppError::PayloadTooLarge(format!(
-            "Image size {} exceeds limit of {} bytes",
-            content_length, max_size
-        )));
+/// Applies the pipeline to every frame of an animated GIF and re-encodes it as GIF.
+///
+/// Frame delays are preserved and the output loops forever. Returns the encoded GIF, the
+/// operations summary of the first frame (every frame runs the same operations) and the
+/// operation timings summed over all frames. Decoding stops with `AppError::BadRequest` as
+/// soon as the next frame would exceed `limits`.
+async fn process_animated_gif(
+    image_bytes: Vec<u8>,
+    operations_spec: &[PipelineOperationSpec],
+    max_operation_duration: Option<Duration>,
+    assets: &Arc<PipelineAssets>,
+    limits: FrameLimits,
+) -> Result<(Vec<u8>, String, String), AppError> {
+    let frames = run_cpu("Decoding", move || {
+        let decoder = GifDecoder::new(Cursor::new(image_bytes))
+            .map_err(|e| decode_error(ImageFormat::Gif, e))?;
+        let mut frames = Vec::new();
+        let mut total_pixels = 0u64;
+        for frame in decoder.into_frames() {
+            if limits.max_frames > 0 && frames.len() >= limits.max_frames {
+                return Err(AppError::BadRequest(format!(
+                    "Animated GIF has more than {} frames",
+                    limits.max_frames
+                )));
+            }
+            let frame = frame.map_err(|e| decode_error(ImageFormat::Gif, e))?;
+            let (width, height) = frame.buffer().dimensions();
+            total_pixels += width as u64 * height as u64;
+            if limits.max_total_pixels > 0 && total_pixels > limits.max_total_pixels {
+                return Err(AppError::BadRequest(format!(
+                    "Animated GIF frames exceed the limit of {} pixels in total",
+                    limits.max_total_pixels
+                )));
+            }
+            frames.push(frame);
+        }
+        Ok(frames)
+    })
+    .await?;
+    tracing::info!(
+        frames = frames.len(),
+        "Processing animated GIF frame by frame"
+    );
+
+    let mut processed_frames = Vec::with_capacity(frames.len());
+    let mut summary = None;
+    let mut timings: Vec<(SupportedOperation, Duration)> = Vec::new();
+    for frame in frames {
+        let delay = frame.delay();
+        let processed = execute_pipeline_with_timeout(
+            DynamicImage::ImageRgba8(frame.into_buffer()),
+            operations_spec.to_vec(),
+            max_operation_duration,
+            assets.clone(),
+        )
+        .await?;
+        summary.get_or_insert_with(|| processed.summary());
+        for (index, &(operation, elapsed)) in processed.timings.iter().enumerate() {
+            match timings.get_mut(index) {
+                Some((_, total)) => *total += elapsed,
+                None => timings.push((operation, elapsed)),
+            }
+        }
+        processed_frames.push(Frame::from_parts(processed.image.to_rgba8(), 0, 0, delay));
     }
 
-    // Read response body with size limit
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| AppError::BadRequest(format!("Failed to read image data: {}", e)))?;
+    let output = run_cpu("Encoding", move || {
+        let mut output = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut output);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .and_then(|_| encoder.encode_frames(processed_frames))
+                .map_err(|e| {
+                    AppError::ImageProcessingError(format!("Failed to encode animated GIF: {}", e))
+                })?;
+        }
+        Ok(output)
+    })
+    .await?;
+    Ok((
+        output,
+        summary.unwrap_or_default(),
+        format_timings(&timings),
+    ))
+}
 
-    if bytes.len() > config.server.max_body_size.min(MAX_IMAGE_SIZE) {
-        return Err(AppError::PayloadTooLarge(format!(
-            "Image size {} exceeds limit of {} bytes",
-            bytes.len(),
-            config.server.max_body_size.min(MAX_IMAGE_SIZE)
-        )));
+/// Applies the deployment's animated-input policy to the uploaded image.
+///
+/// Returns `Ok(true)` when the input is animated and only its first frame will be processed,
+/// `Ok(false)` for static inputs, or `AppError::BadRequest` when animated inputs are rejected.
+pub(crate) fn check_animated_input(
+    image_bytes: &[u8],
+    format: ImageFormat,
+    policy: AnimatedInputPolicy,
+) -> Result<bool, AppError> {
+    if !is_animated(image_bytes, format) {
+        return Ok(false);
+    }
+    match policy {
+        AnimatedInputPolicy::Reject => Err(AppError::BadRequest(
+            "animated input not supported on this endpoint".to_string(),
+        )),
+        AnimatedInputPolicy::FirstFrame => {
+            tracing::info!("Animated input detected; processing first frame only");
+            Ok(true)
+        }
     }
+}
 
-    Ok(bytes.to_vec())
+/// `X-First-Frame-Only: true` when [`check_animated_input`] kept only the first frame.
+pub(crate) fn first_frame_only_header(
+    first_frame_only: bool,
+) -> AppendHeaders<Option<(&'static str, &'static str)>> {
+    AppendHeaders(first_frame_only.then_some((FIRST_FRAME_ONLY_HEADER, "true")))
 }
 
-fn determine_output_format(
-    operations_spec: &[PipelineOperationSpec],
-    original_format: ImageFormat,
-) -> ImageFormat {
-    // Check the last convert operation to determine output format
-    for spec in operations_spec.iter().rev() {
-        if spec.operation == SupportedOperation::Convert {
-            if let Ok(convert_params) = from_value::<FormatConversionParams>(spec.params.clone()) {
-                match convert_params.format.to_lowercase().as_str() {
-                    "png" => return ImageFormat::Png,
-                    "jpeg" | "jpg" => return ImageFormat::Jpeg,
-                    "gif" => return ImageFormat::Gif,
-                    "webp" => return ImageFormat::WebP,
-                    "bmp" => return ImageFormat::Bmp,
-                    "tiff" | "tif" => return ImageFormat::Tiff,
-                    _ => {
-                        tracing::warn!(
-                            "Unsupported format in convert operation: {}, using original format",
-                            convert_params.format
-                        );
-                        return original_format;
-                    }
-                }
+/// Checks if an IP address is safe for external requests (not private/internal)
+///
+/// IPv6 addresses embedding an IPv4 address (`::ffff:10.0.0.1`, `::127.0.0.1`) are judged by
+/// that address, since connecting to them reaches it.
+fn is_safe_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ipv4) => is_safe_ipv4(ipv4),
+        IpAddr::V6(ipv6) => match ipv6.to_ipv4() {
+            Some(ipv4) => is_safe_ipv4(ipv4),
+            // Use De Morgan's law and simplified expressions
+            None => {
+                !(ipv6.is_unspecified()
+                    || ipv6.is_loopback()
+                    || ipv6.is_multicast()
+                    || ipv6.segments()[0] & 0xffc0 == 0xfe80
+                    || ipv6.segments()[0] & 0xfe00 == 0xfc00
+                    || (ipv6.segments()[0] == 0x2001 && ipv6.segments()[1] == 0x0db8))
             }
-        }
+        },
     }
+}
 
-    // Default to original format if no convert operation found
-    original_format
+/// [`is_safe_ip`] for IPv4; `0.0.0.0/8` is rejected because `0.0.0.0` connects to the local
+/// host.
+fn is_safe_ipv4(ipv4: Ipv4Addr) -> bool {
+    // Use De Morgan's law to simplify boolean expression
+    !(ipv4.is_unspecified()
+        || ipv4.octets()[0] == 0
+        || ipv4.is_private()
+        || ipv4.is_loopback()
+        || ipv4.is_link_local()
+        || ipv4.is_broadcast()
+        || ipv4.is_multicast()
+        || (ipv4.octets()[0] == 100 && (64..128).contains(&ipv4.octets()[1]))
+        || ipv4 == Ipv4Addr::new(169, 254, 169, 254)
+        || (ipv4.octets()[0] == 192 && ipv4.octets()[1] == 0 && ipv4.octets()[2] == 2)
+        || (ipv4.octets()[0] == 198 && ipv4.octets()[1] == 51 && ipv4.octets()[2] == 100)
+        || (ipv4.octets()[0] == 203 && ipv4.octets()[1] == 0 && ipv4.octets()[2] == 113)
+        || (ipv4.octets()[0] == 192 && ipv4.octets()[1] == 88 && ipv4.octets()[2] == 99))
+}
+
+async fn fetch_image_from_url(url_str: &str, config: &Config) -> Result<Vec<u8>, AppError> {
+    fetch_image(url_str, config, &HTTP_CLIENT, &SAFE_RESOLVER).await
+}
+
+/// A failed fetch attempt.
+struct FetchError {
+    error: AppError,
+    /// Connection errors, timeouts and 5xx responses, which a later attempt may not hit.
+    retryable: bool,
+}
+
+impl From<AppError> for FetchError {
+    fn from(error: AppError) -> Self {
+        Self {
+            error,
+            retryable: false,
+        }
+    }
+}
+
+/// Longest a URL fetch waits for one of the `max_concurrent_fetches` slots before the request
+/// is rejected.
+const FETCH_SLOT_WAIT: Duration = Duration::from_millis(500);
+
+/// Fetches `url_str` with `client`, which must resolve host names through `resolver`.
+///
+/// The fetch holds one of the `max_concurrent_fetches` slots, retries included; if none frees
+/// up within [`FETCH_SLOT_WAIT`] it fails with `AppError::ServiceUnavailable` (503).
+/// Transient failures are retried up to `fetch_retries` times, waiting `fetch_retry_backoff_ms`
+/// before the first retry and twice as long before each further one. 4xx responses and
+/// rejected hosts or addresses fail right away.
+async fn fetch_image(
+    url_str: &str,
+    config: &Config,
+    client: &reqwest::Client,
+    resolver: &SafeResolver,
+) -> Result<Vec<u8>, AppError> {
+    let _slot = match config.server.fetch_slots() {
+        Some(slots) => Some(
+            tokio::time::timeout(FETCH_SLOT_WAIT, slots.acquire())
+                .await
+                .map_err(|_| {
+                    AppError::ServiceUnavailable(format!(
+                        "Too many image fetches in progress (limit {})",
+                        config.server.max_concurrent_fetches
+                    ))
+                })?
+                .expect("fetch semaphore is never closed"),
+        ),
+        None => None,
+    };
+    let mut retries = 0;
+    loop {
+        match fetch_image_once(url_str, config, client, resolver).await {
+            Err(failure) if failure.retryable && retries < config.server.fetch_retries => {
+                let delay = Duration::from_millis(
+                    config
+                        .server
+                        .fetch_retry_backoff_ms
+                        .saturating_mul(2u64.saturating_pow(retries)),
+                );
+                retries += 1;
+                tracing::warn!(
+                    url = %url_str,
+                    error = %failure.error,
+                    retry = retries,
+                    delay_ms = delay.as_millis() as u64,
+                    "Image fetch failed, retrying"
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result.map_err(|failure| failure.error),
+        }
+    }
+}
+
+/// One attempt of [`fetch_image`], following redirects.
+async fn fetch_image_once(
+    url_str: &str,
+    config: &Config,
+    client: &reqwest::Client,
+    resolver: &SafeResolver,
+) -> Result<Vec<u8>, FetchError> {
+    // Parse and validate URL
+    let mut url =
+        Url::parse(url_str).map_err(|e| AppError::BadRequest(format!("Invalid URL: {}", e)))?;
+
+    // Follow redirects by hand so every hop gets the same checks as the original URL
+    let mut redirects = 0;
+    let response = loop {
+        let host = url.host_str().unwrap_or_default();
+        if !config.security.is_fetch_host_allowed(host) {
+            return Err(AppError::Unauthorized(format!(
+                "Fetching images from host '{}' is not allowed",
+                host
+            ))
+            .into());
+        }
+        resolver.check_url(&url).await?;
+        let response =
+            client
+                .get(url.as_str())
+                .send()
+                .await
+                .map_err(|e| match fetch_error_cause(&e) {
+                    Some(cause) => FetchError::from(cause),
+                    None => FetchError {
+                        retryable: e.is_connect() || e.is_timeout(),
+                        error: AppError::BadRequest(format!(
+                            "Failed to fetch image from URL: {}",
+                            e
+                        )),
+                    },
+                })?;
+        if !is_followed_redirect(response.status()) {
+            break response;
+        }
+        if redirects >= config.server.max_redirects {
+            return Err(AppError::BadRequest(format!(
+                "Too many redirects when fetching image (limit {})",
+                config.server.max_redirects
+            ))
+            .into());
+        }
+        redirects += 1;
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                AppError::BadRequest("Redirect without a valid Location header".to_string())
+            })?;
+        url = url
+            .join(location)
+            .map_err(|e| AppError::BadRequest(format!("Invalid redirect URL: {}", e)))?;
+    };
+
+    if !response.status().is_success() {
+        return Err(FetchError {
+            retryable: response.status().is_server_error(),
+            error: AppError::BadRequest(format!(
+                "HTTP error when fetching image: {}",
+                response.status()
+            )),
+        });
+    }
+
+    let max_size = config.server.max_body_size.min(MAX_IMAGE_SIZE);
+    read_body_capped(response, max_size)
+        .await
+        .map_err(FetchError::from)
+}
+
+/// Reads a response body, failing as soon as more than `max_size` bytes have arrived.
+///
+/// `Content-Length` is only used to reject early; it can be absent or wrong, so the running
+/// total of the streamed chunks is what enforces the cap.
+async fn read_body_capped(
+    mut response: reqwest::Response,
+    max_size: usize,
+) -> Result<Vec<u8>, AppError> {
+    let too_large = |size: u64| {
+        AppError::PayloadTooLarge(format!(
+            "Image size {} exceeds limit of {} bytes",
+            size, max_size
+        ))
+    };
+    let content_length = response.content_length();
+    if let Some(length) = content_length.filter(|&length| length > max_size as u64) {
+        return Err(too_large(length));
+    }
+
+    let mut bytes = Vec::with_capacity(content_length.unwrap_or(0) as usize);
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Failed to read image data: {}", e)))?
+    {
+        let size = bytes.len() + chunk.len();
+        if size > max_size {
+            return Err(too_large(size as u64));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Redirect statuses [`fetch_image`] follows.
+fn is_followed_redirect(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 301 | 302 | 303 | 307 | 308)
+}
+
+/// Finds the `AppError` raised by [`SafeResolver`] inside a client error.
+fn fetch_error_cause(error: &reqwest::Error) -> Option<AppError> {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(err) = source {
+        if let Some(app_error) = err.downcast_ref::<AppError>() {
+            return Some(app_error.clone());
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// Picks the output format from the last `convert` operation, defaulting to the input format.
+pub(crate) fn determine_output_format(
+    operations_spec: &[PipelineOperationSpec],
+    original_format: ImageFormat,
+) -> ImageFormat {
+    // The last convert operation determines the output format
+    let Some(convert_params) = last_convert_params(operations_spec) else {
+        // Default to original format if no convert operation found
+        return original_format;
+    };
+    format_from_name(&convert_params.format).unwrap_or_else(|| {
+        tracing::warn!(
+            "Unsupported format in convert operation: {}, using original format",
+            convert_params.format
+        );
+        original_format
+    })
+}
+
+/// Output format for a name accepted by `convert` and the `format` parameter.
+pub(crate) fn format_from_name(name: &str) -> Option<ImageFormat> {
+    match name.to_lowercase().as_str() {
+        "png" => Some(ImageFormat::Png),
+        "jpeg" | "jpg" => Some(ImageFormat::Jpeg),
+        "gif" => Some(ImageFormat::Gif),
+        "webp" => Some(ImageFormat::WebP),
+        "bmp" => Some(ImageFormat::Bmp),
+        "tiff" | "tif" => Some(ImageFormat::Tiff),
+        _ => None,
+    }
+}
+
+/// Parameters of the last `convert` operation, whose encoder options apply to the response.
+pub(crate) fn last_convert_params(
+    operations_spec: &[PipelineOperationSpec],
+) -> Option<FormatConversionParams> {
+    operations_spec
+        .iter()
+        .rev()
+        .filter(|spec| spec.operation == SupportedOperation::Convert)
+        .find_map(|spec| from_value::<FormatConversionParams>(spec.params.clone()).ok())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::Config;
-    use crate::server::ServerConfig;
+    use crate::image::operations::format::EncodingConfig;
+    use crate::security::ApiKey;
+    use axum::{body::Body, routing::post, Router};
     use serde_json::json;
+    use tower::ServiceExt;
 
-    #[allow(dead_code)]
     fn create_test_config() -> Arc<Config> {
         Arc::new(Config {
             server: ServerConfig {
@@ -368,11 +1545,13 @@ mod tests {
                 operation: SupportedOperation::Resize,
                 params: json!({"width": 100, "height": 100}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Convert,
                 params: json!({"format": "jpeg", "quality": 85}),
                 ignore_failure: false,
+                region: None,
             },
         ];
 
@@ -386,6 +1565,7 @@ mod tests {
             operation: SupportedOperation::Resize,
             params: json!({"width": 100, "height": 100}),
             ignore_failure: false,
+            region: None,
         }];
 
         let result = determine_output_format(&operations, ImageFormat::Png);
@@ -399,16 +1579,19 @@ mod tests {
                 operation: SupportedOperation::Convert,
                 params: json!({"format": "png"}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Resize,
                 params: json!({"width": 100, "height": 100}),
                 ignore_failure: false,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Convert,
                 params: json!({"format": "webp"}),
                 ignore_failure: false,
+                region: None,
             },
         ];
 
@@ -483,4 +1666,1904 @@ mod tests {
             0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888
         ))));
     }
+
+    fn create_animated_gif() -> Vec<u8> {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, Rgba, RgbaImage};
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            let frames = [Rgba([255u8, 0, 0, 255]), Rgba([0u8, 255, 0, 255])]
+                .into_iter()
+                .map(|color| {
+                    Frame::from_parts(
+                        RgbaImage::from_pixel(8, 8, color),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(50, 1),
+                    )
+                });
+            encoder.encode_frames(frames).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_animated_input_rejected() {
+        let gif = create_animated_gif();
+        let result = check_animated_input(&gif, ImageFormat::Gif, AnimatedInputPolicy::Reject);
+        match result {
+            Err(AppError::BadRequest(msg)) => {
+                assert_eq!(msg, "animated input not supported on this endpoint")
+            }
+            other => panic!("Expected BadRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_animated_input_first_frame_only() {
+        let gif = create_animated_gif();
+        let result = check_animated_input(&gif, ImageFormat::Gif, AnimatedInputPolicy::FirstFrame);
+        assert!(result.unwrap());
+    }
+
+    #[test]
+    fn test_static_input_passes_any_policy() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(8, 8)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        for policy in [AnimatedInputPolicy::Reject, AnimatedInputPolicy::FirstFrame] {
+            assert!(!check_animated_input(&png, ImageFormat::Png, policy).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_animated_gif_preserves_frames() {
+        let gif = create_animated_gif();
+        let operations = vec![PipelineOperationSpec {
+            operation: SupportedOperation::Resize,
+            params: json!({"width": 4, "height": 4}),
+            ignore_failure: false,
+            region: None,
+        }];
+
+        let (output, summary, timings) = process_animated_gif(
+            gif,
+            &operations,
+            None,
+            &Arc::default(),
+            FrameLimits::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary, "resize");
+        // Timings are summed over the frames, one entry per operation
+        assert!(timings.starts_with("resize=") && !timings.contains(','));
+
+        let frames = GifDecoder::new(Cursor::new(&output))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        for frame in &frames {
+            assert_eq!(frame.buffer().dimensions(), (4, 4));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_animated_gif_enforces_frame_limits() {
+        let cases = [
+            (
+                FrameLimits {
+                    max_frames: 1,
+                    max_total_pixels: 0,
+                },
+                "more than 1 frames",
+            ),
+            // Each 8x8 frame has 64 pixels, so the second one goes over
+            (
+                FrameLimits {
+                    max_frames: 0,
+                    max_total_pixels: 100,
+                },
+                "100 pixels in total",
+            ),
+        ];
+        for (limits, expected) in cases {
+            match process_animated_gif(create_animated_gif(), &[], None, &Arc::default(), limits)
+                .await
+            {
+                Err(AppError::BadRequest(msg)) => assert!(msg.contains(expected), "{}", msg),
+                other => panic!("Expected BadRequest, got {:?}", other.map(|_| ())),
+            }
+        }
+        let limits = FrameLimits {
+            max_frames: 2,
+            max_total_pixels: 128,
+        };
+        assert!(
+            process_animated_gif(create_animated_gif(), &[], None, &Arc::default(), limits)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_animated_gif_output_honours_reject_policy() {
+        let render_gif = |policy| {
+            render(
+                create_animated_gif(),
+                Vec::new(),
+                ImageFormat::Gif,
+                ImageFormat::Gif,
+                MetadataOptions::default(),
+                BTreeMap::new(),
+                Arc::new(Config {
+                    server: ServerConfig {
+                        animated_input_policy: policy,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }),
+            )
+        };
+        assert!(matches!(
+            render_gif(AnimatedInputPolicy::Reject).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(render_gif(AnimatedInputPolicy::FirstFrame).await.is_ok());
+    }
+
+    /// `#[tokio::test]` runs a single-threaded runtime, so a task spawned next to `render` only
+    /// makes progress while `render` is awaiting. An empty pipeline leaves nothing to await
+    /// but decoding and encoding: done inline, the other task would never run until the end.
+    #[tokio::test]
+    async fn test_render_keeps_the_runtime_responsive() {
+        let image = DynamicImage::ImageRgb8(image::ImageBuffer::from_fn(1024, 1024, |x, y| {
+            image::Rgb([(x * 7 + y) as u8, (x ^ y) as u8, (x * y) as u8])
+        }));
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let done = Arc::new(AtomicBool::new(false));
+        let ticker = tokio::spawn({
+            let done = done.clone();
+            async move {
+                let mut ticks = 0u64;
+                while !done.load(Ordering::Relaxed) {
+                    ticks += 1;
+                    tokio::task::yield_now().await;
+                }
+                ticks
+            }
+        });
+        let rendered = render(
+            png,
+            Vec::new(),
+            ImageFormat::Png,
+            ImageFormat::Png,
+            MetadataOptions::default(),
+            BTreeMap::new(),
+            create_test_config(),
+        )
+        .await
+        .unwrap();
+        done.store(true, Ordering::Relaxed);
+        let ticks = ticker.await.unwrap();
+
+        let output = image::load_from_memory(&rendered.bytes).unwrap();
+        assert_eq!((output.width(), output.height()), (1024, 1024));
+        assert!(ticks > 10, "other tasks ran only {} times", ticks);
+    }
+
+    fn multipart_request(image_bytes: &[u8], operations: &str) -> axum::http::Request<Body> {
+        multipart_request_with_fields::<&str>(image_bytes, operations, &[])
+    }
+
+    /// Like `multipart_request`, with extra (text or binary) fields after `operations`.
+    fn multipart_request_with_fields<V: AsRef<[u8]>>(
+        image_bytes: &[u8],
+        operations: &str,
+        fields: &[(&str, V)],
+    ) -> axum::http::Request<Body> {
+        let boundary = "imaginary-test-boundary";
+        let mut body = Vec::new();
+        body.extend_from_slice(
+            format!(
+                "--{b}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"test.png\"\r\nContent-Type: image/png\r\n\r\n",
+                b = boundary
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(image_bytes);
+        body.extend_from_slice(
+            format!(
+                "\r\n--{b}\r\nContent-Disposition: form-data; name=\"operations\"\r\n\r\n{ops}",
+                b = boundary,
+                ops = operations
+            )
+            .as_bytes(),
+        );
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!(
+                    "\r\n--{b}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n",
+                    b = boundary
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(value.as_ref());
+        }
+        body.extend_from_slice(format!("\r\n--{b}--\r\n", b = boundary).as_bytes());
+        axum::http::Request::post("/pipeline")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_operations_header_lists_applied_and_skipped() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let operations = r#"[
+            {"operation": "resize", "params": {"width": 20, "height": 20}},
+            {"operation": "crop", "ignoreFailure": true, "params": {"width": 0, "height": 5}},
+            {"operation": "grayscale"}
+        ]"#;
+
+        let response = app
+            .oneshot(multipart_request(&png_bytes(40, 40), operations))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(
+            response.headers()[OPERATIONS_HEADER],
+            "resize,grayscale; skipped=crop"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checksum_header_is_verified() {
+        let image = png_bytes(20, 20);
+        let digest = Sha256::digest(&image);
+        let send = |checksum: String| {
+            let app = Router::new()
+                .route("/pipeline", post(process_pipeline))
+                .with_state(create_test_config());
+            let mut request = multipart_request(&image, r#"[{"operation": "grayscale"}]"#);
+            request
+                .headers_mut()
+                .insert(CHECKSUM_HEADER, checksum.parse().unwrap());
+            app.oneshot(request)
+        };
+
+        for matching in [hex::encode(digest), STANDARD.encode(digest)] {
+            let response = send(matching).await.unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+        }
+
+        let response = send(hex::encode(Sha256::digest(b"other bytes")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("does not match"));
+
+        let response = send("not-a-digest".to_string()).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_second_uploaded_image_is_composited_by_source() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let mut red = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(4, 4, image::Rgb([255, 0, 0])))
+            .write_to(&mut Cursor::new(&mut red), ImageFormat::Png)
+            .unwrap();
+        let request = |operations: &str| {
+            multipart_request_with_fields(&png_bytes(20, 20), operations, &[("image", &red)])
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request(
+                r#"[{"operation": "overlay", "params": {"source": 1, "x": 2, "y": 2}}]"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let output = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let output = image::load_from_memory(&output).unwrap().to_rgb8();
+        // The first image is the base, the second lands on it at (2, 2)
+        assert_eq!(output.dimensions(), (20, 20));
+        assert_eq!(output.get_pixel(3, 3), &image::Rgb([255, 0, 0]));
+        assert_eq!(output.get_pixel(0, 0), &image::Rgb([0, 0, 0]));
+        assert_eq!(output.get_pixel(10, 10), &image::Rgb([0, 0, 0]));
+
+        let response = app
+            .oneshot(request(
+                r#"[{"operation": "overlay", "params": {"source": 2}}]"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("source 2 was not uploaded"));
+    }
+
+    #[tokio::test]
+    async fn test_watermark_field_is_composited() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let mut logo = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            10,
+            10,
+            image::Rgb([255, 0, 0]),
+        ))
+        .write_to(&mut Cursor::new(&mut logo), ImageFormat::Png)
+        .unwrap();
+        let operations = r#"[{"operation": "watermarkImage", "params": {"opacity": 1.0, "position": "TopLeft"}}]"#;
+
+        let response = app
+            .oneshot(multipart_request_with_fields(
+                &png_bytes(40, 40),
+                operations,
+                &[("watermark", logo.as_slice())],
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let output = image::load_from_memory(&body).unwrap().to_rgba8();
+        assert_eq!(output.dimensions(), (40, 40));
+        // The uploaded red logo covers the top-left corner; the black base shows elsewhere
+        assert_eq!(output.get_pixel(5, 5).0, [255, 0, 0, 255]);
+        assert_eq!(output.get_pixel(30, 30).0, [0, 0, 0, 255]);
+    }
+
+    #[tokio::test]
+    async fn test_overlay_field_is_blended() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let mut top = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            10,
+            10,
+            image::Rgb([128, 128, 128]),
+        ))
+        .write_to(&mut Cursor::new(&mut top), ImageFormat::Png)
+        .unwrap();
+        let mut base = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            40,
+            40,
+            image::Rgb([200, 200, 200]),
+        ))
+        .write_to(&mut Cursor::new(&mut base), ImageFormat::Png)
+        .unwrap();
+        let operations =
+            r#"[{"operation": "overlay", "params": {"x": 20, "y": 5, "blend_mode": "multiply"}}]"#;
+
+        let response = app
+            .clone()
+            .oneshot(multipart_request_with_fields(
+                &base,
+                operations,
+                &[("overlay", top.as_slice())],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let output = image::load_from_memory(&body).unwrap().to_rgba8();
+        assert_eq!(output.get_pixel(25, 10).0, [100, 100, 100, 255]);
+        assert_eq!(output.get_pixel(5, 10).0, [200, 200, 200, 255]);
+
+        // Without an upload or URL there is nothing to overlay
+        let response = app
+            .oneshot(multipart_request(&base, operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_overlay_url_is_fetched_through_ssrf_checks() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let operations =
+            r#"[{"operation": "overlay", "params": {"url": "http://127.0.0.1:9/logo.png"}}]"#;
+        let response = app
+            .oneshot(multipart_request(&png_bytes(4, 4), operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error_code"], "ssrf_blocked");
+    }
+
+    #[tokio::test]
+    async fn test_watermark_asset_must_be_uploaded_when_named() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let operations = r#"[{"operation": "watermarkImage", "params": {"asset": "watermark"}}]"#;
+
+        let response = app
+            .oneshot(multipart_request(&png_bytes(40, 40), operations))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_timings_header_requires_config_flag() {
+        let operations = r#"[
+            {"operation": "resize", "params": {"width": 20, "height": 20}},
+            {"operation": "grayscale"}
+        ]"#;
+        let response = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config())
+            .oneshot(multipart_request(&png_bytes(40, 40), operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert!(response.headers().get(TIMINGS_HEADER).is_none());
+
+        let config = Arc::new(Config {
+            server: ServerConfig {
+                max_body_size: 1024 * 1024,
+                timings_header: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let response = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(config)
+            .oneshot(multipart_request(&png_bytes(40, 40), operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let timings = response.headers()[TIMINGS_HEADER].to_str().unwrap();
+        let operations: Vec<&str> = timings
+            .split(',')
+            .map(|timing| timing.split('=').next().unwrap())
+            .collect();
+        assert_eq!(operations, ["resize", "grayscale"]);
+        assert!(timings.ends_with("ms"));
+    }
+
+    #[test]
+    fn test_download_filename_is_sanitized_and_matches_format() {
+        assert_eq!(
+            download_filename("photo.jpg", ImageFormat::Png),
+            "photo.png"
+        );
+        assert_eq!(download_filename("photo", ImageFormat::Jpeg), "photo.jpg");
+        assert_eq!(
+            download_filename("a\"b\r\nSet-Cookie: x.png", ImageFormat::WebP),
+            "abSet-Cookie: x.webp"
+        );
+        assert_eq!(
+            download_filename("../../etc\\passwd.gif", ImageFormat::Gif),
+            "passwd.gif"
+        );
+        assert_eq!(download_filename(".png", ImageFormat::Png), "image.png");
+    }
+
+    #[tokio::test]
+    async fn test_content_disposition_only_when_filename_requested() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let image = STANDARD.encode(png_bytes(4, 4));
+
+        let response = app
+            .clone()
+            .oneshot(json_request(json!({
+                "image_base64": image,
+                "filename": "holiday\r\n\"shot\".png",
+                "operations": [{"operation": "convert", "params": {"format": "jpeg"}}]
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(
+            response.headers()[CONTENT_DISPOSITION],
+            "attachment; filename=\"holidayshot.jpg\""
+        );
+
+        let response = app
+            .oneshot(json_request(json!({
+                "image_base64": image,
+                "operations": [{"operation": "grayscale"}]
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert!(response.headers().get(CONTENT_DISPOSITION).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_format_parameter_overrides_output_format() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+
+        // Overrides a trailing convert
+        let response = app
+            .clone()
+            .oneshot(json_request(json!({
+                "image_base64": STANDARD.encode(png_bytes(4, 4)),
+                "format": "webp",
+                "operations": [{"operation": "convert", "params": {"format": "jpeg"}}]
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "image/webp");
+
+        // Applies without any convert
+        let response = app
+            .clone()
+            .oneshot(multipart_request_with_fields(
+                &png_bytes(4, 4),
+                r#"[{"operation": "grayscale"}]"#,
+                &[("format", "JPG")],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "image/jpeg");
+        let output = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(image::guess_format(&output).unwrap(), ImageFormat::Jpeg);
+
+        let response = app
+            .oneshot(multipart_request_with_fields(
+                &png_bytes(4, 4),
+                r#"[{"operation": "grayscale"}]"#,
+                &[("format", "svg")],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_format_parameter_jpeg_uses_the_configured_default_quality() {
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8])
+        }))
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .unwrap();
+        let output_size = |default_jpeg_quality| {
+            let config = Config {
+                server: ServerConfig {
+                    max_body_size: 1024 * 1024,
+                    ..Default::default()
+                },
+                encoding: EncodingConfig {
+                    default_jpeg_quality,
+                },
+                ..Default::default()
+            };
+            let app = Router::new()
+                .route("/pipeline", post(process_pipeline))
+                .with_state(Arc::new(config));
+            let request = json_request(json!({
+                "image_base64": STANDARD.encode(&bytes),
+                "format": "jpeg",
+                "operations": [{"operation": "grayscale"}]
+            }));
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), axum::http::StatusCode::OK);
+                axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap()
+                    .len()
+            }
+        };
+        assert!(output_size(10).await < output_size(95).await);
+    }
+
+    #[tokio::test]
+    async fn test_content_type_matches_the_encoded_output() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let formats = [
+            ("png", ImageFormat::Png, "image/png"),
+            ("jpeg", ImageFormat::Jpeg, "image/jpeg"),
+            ("gif", ImageFormat::Gif, "image/gif"),
+            ("webp", ImageFormat::WebP, "image/webp"),
+            ("bmp", ImageFormat::Bmp, "image/bmp"),
+            ("tiff", ImageFormat::Tiff, "image/tiff"),
+        ];
+        for (name, format, mime) in formats {
+            let response = app
+                .clone()
+                .oneshot(multipart_request_with_fields(
+                    &png_bytes(8, 8),
+                    r#"[{"operation": "grayscale"}]"#,
+                    &[("format", name)],
+                ))
+                .await
+                .unwrap();
+            if !format_support().can_encode(format) {
+                assert_eq!(
+                    response.status(),
+                    axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "{}",
+                    name
+                );
+                continue;
+            }
+            assert_eq!(response.status(), axum::http::StatusCode::OK, "{}", name);
+            assert_eq!(response.headers()[CONTENT_TYPE], mime, "{}", name);
+            let output = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(image::guess_format(&output).unwrap(), format, "{}", name);
+            assert!(image::load_from_memory_with_format(&output, format).is_ok());
+        }
+
+        // Animated GIFs stay animated GIFs, under the GIF content type
+        let response = app
+            .oneshot(multipart_request(
+                &create_animated_gif(),
+                r#"[{"operation": "grayscale"}]"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "image/gif");
+        let output = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let frames = GifDecoder::new(Cursor::new(&output))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_dimensions_and_format() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let json_body = |response: axum::response::Response| async move {
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+            assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        // Answered from the header alone
+        let response = app
+            .clone()
+            .oneshot(json_request(json!({
+                "image_base64": STANDARD.encode(png_bytes(40, 20)),
+                "dry_run": true,
+                "operations": [
+                    {"operation": "resize", "params": {"width": 30, "height": 10}},
+                    {"operation": "rotate", "params": {"degrees": 90}},
+                    {"operation": "convert", "params": {"format": "webp"}}
+                ]
+            })))
+            .await
+            .unwrap();
+        assert_eq!(
+            json_body(response).await,
+            json!({"width": 10, "height": 30, "format": "webp"})
+        );
+
+        // `thumbnail` needs the operations to run
+        let response = app
+            .oneshot(multipart_request_with_fields(
+                &png_bytes(40, 20),
+                r#"[{"operation": "thumbnail", "params": {"width": 10, "height": 10}}]"#,
+                &[("dry_run", "true")],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            json_body(response).await,
+            json!({"width": 10, "height": 5, "format": "png"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_bytes_reports_chosen_quality() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let operations =
+            r#"[{"operation": "convert", "params": {"format": "jpeg", "max_bytes": 2000}}]"#;
+
+        let response = app
+            .clone()
+            .oneshot(multipart_request(&png_bytes(64, 64), operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let quality: u8 = response.headers()[QUALITY_HEADER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((20..=95).contains(&quality));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.len() <= 2000);
+
+        let response = app
+            .oneshot(multipart_request(
+                &png_bytes(64, 64),
+                r#"[{"operation": "convert", "params": {"format": "jpeg"}}]"#,
+            ))
+            .await
+            .unwrap();
+        assert!(response.headers().get(QUALITY_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_icc_profile_survives_resize_and_convert() {
+        let mut profile = vec![0u8; 200];
+        profile[0..4].copy_from_slice(&200u32.to_be_bytes());
+        profile[12..16].copy_from_slice(b"mntr");
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile[36..40].copy_from_slice(b"acsp");
+        let input = embed_icc_profile(png_bytes(40, 40), ImageFormat::Png, &profile);
+        assert_eq!(
+            read_icc_profile(&input, ImageFormat::Png).as_deref(),
+            Some(&profile[..])
+        );
+
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let operations = r#"[
+            {"operation": "resize", "params": {"width": 20, "height": 20}},
+            {"operation": "convert", "params": {"format": "jpeg", "quality": 85}}
+        ]"#;
+        let response = app
+            .clone()
+            .oneshot(multipart_request(&input, operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let output = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            read_icc_profile(&output, ImageFormat::Jpeg).as_deref(),
+            Some(&profile[..])
+        );
+
+        // An RGB profile no longer describes a grayscale output, so it is dropped.
+        let operations = r#"[
+            {"operation": "grayscale"},
+            {"operation": "convert", "params": {"format": "png"}}
+        ]"#;
+        let response = app
+            .oneshot(multipart_request(&input, operations))
+            .await
+            .unwrap();
+        let output = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(read_icc_profile(&output, ImageFormat::Png), None);
+    }
+
+    #[tokio::test]
+    async fn test_exif_with_gps_is_stripped_after_orientation() {
+        // Big-endian TIFF: IFD0 with Orientation = 6 and a GPS IFD holding GPSLatitudeRef = "N"
+        let mut exif = b"Exif\0\0MM\0\x2A\0\0\0\x08\0\x02".to_vec();
+        exif.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        exif.extend_from_slice(&[0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 38]);
+        exif.extend_from_slice(&[0, 0, 0, 0, 0, 1]);
+        exif.extend_from_slice(&[0, 1, 0, 2, 0, 0, 0, 2, b'N', 0, 0, 0, 0, 0, 0, 0]);
+        let mut jpeg = Vec::new();
+        image::DynamicImage::new_rgb8(30, 20)
+            .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+        let mut input = jpeg[..2].to_vec();
+        input.extend_from_slice(&[0xFF, 0xE1]);
+        input.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        input.extend_from_slice(&exif);
+        input.extend_from_slice(&jpeg[2..]);
+        assert_eq!(read_orientation(&input, ImageFormat::Jpeg), Some(6));
+
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let response = app
+            .oneshot(multipart_request(&input, r#"[{"operation": "grayscale"}]"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let output = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert!(!output.windows(6).any(|window| window == b"Exif\0\0"));
+        assert_eq!(read_orientation(&output, ImageFormat::Jpeg), None);
+        let decoded = image::load_from_memory_with_format(&output, ImageFormat::Jpeg).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (20, 30));
+    }
+
+    fn json_request(body: serde_json::Value) -> axum::http::Request<Body> {
+        axum::http::Request::post("/pipeline")
+            .header("Content-Type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_body_with_base64_image() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let request = json_request(json!({
+            "image_base64": STANDARD.encode(png_bytes(30, 20)),
+            "operations": [{"operation": "resize", "params": {"width": 15, "height": 10}}]
+        }));
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], "image/png");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let image = image::load_from_memory(&body).unwrap();
+        assert_eq!((image.width(), image.height()), (15, 10));
+    }
+
+    #[tokio::test]
+    async fn test_json_body_base64_image_size_is_capped() {
+        let config = Arc::new(Config {
+            server: ServerConfig {
+                max_body_size: 64,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(config);
+        let request = json_request(json!({
+            "image_base64": STANDARD.encode(png_bytes(30, 20)),
+            "operations": [{"operation": "grayscale"}]
+        }));
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_json_body_with_url_fetches_through_ssrf_checks() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let request = json_request(json!({
+            "url": "http://127.0.0.1:9/image.png",
+            "operations": [{"operation": "grayscale"}]
+        }));
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error_code"], "ssrf_blocked");
+    }
+
+    #[tokio::test]
+    async fn test_json_body_requires_exactly_one_image_source() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        for body in [
+            json!({"operations": [{"operation": "grayscale"}]}),
+            json!({
+                "url": "https://example.com/a.png",
+                "image_base64": STANDARD.encode(png_bytes(4, 4)),
+                "operations": [{"operation": "grayscale"}]
+            }),
+        ] {
+            let response = app.clone().oneshot(json_request(body)).await.unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upscaling_over_output_limit_is_rejected() {
+        let config = Arc::new(Config {
+            server: ServerConfig {
+                max_body_size: 1024 * 1024,
+                max_output_pixels: 1_000_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(config);
+        let operations =
+            r#"[{"operation": "resize", "params": {"width": 30000, "height": 30000}}]"#;
+
+        let response = app
+            .oneshot(multipart_request(&png_bytes(10, 10), operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_input_over_declared_pixel_limit_is_rejected() {
+        let config = Arc::new(Config {
+            server: ServerConfig {
+                max_body_size: 1024 * 1024,
+                max_input_pixels: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(config);
+
+        let response = app
+            .oneshot(multipart_request(
+                &png_bytes(20, 20),
+                r#"[{"operation": "grayscale"}]"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_header_declaring_huge_dimensions_is_rejected_before_decoding() {
+        // A 54-byte BMP header claiming 50000x50000 pixels (~7.5 GB decoded) and no pixel data
+        let mut bomb = Vec::new();
+        bomb.extend_from_slice(b"BM");
+        bomb.extend_from_slice(&54u32.to_le_bytes()); // file size
+        bomb.extend_from_slice(&0u32.to_le_bytes()); // reserved
+        bomb.extend_from_slice(&54u32.to_le_bytes()); // pixel data offset
+        bomb.extend_from_slice(&40u32.to_le_bytes()); // BITMAPINFOHEADER size
+        bomb.extend_from_slice(&50_000i32.to_le_bytes());
+        bomb.extend_from_slice(&50_000i32.to_le_bytes());
+        bomb.extend_from_slice(&1u16.to_le_bytes()); // planes
+        bomb.extend_from_slice(&24u16.to_le_bytes()); // bits per pixel
+        bomb.extend_from_slice(&[0; 24]); // uncompressed, no palette
+        assert_eq!(
+            probe_dimensions(&bomb, ImageFormat::Bmp).unwrap(),
+            (50_000, 50_000)
+        );
+
+        let config = Arc::new(Config {
+            server: ServerConfig {
+                max_body_size: 1024 * 1024,
+                max_input_pixels: 100_000_000,
+                allowed_input_formats: vec!["bmp".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(config);
+
+        let response = app
+            .oneshot(multipart_request(&bomb, r#"[{"operation": "grayscale"}]"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("exceeds the limit of 100000000 pixels"));
+    }
+
+    #[tokio::test]
+    async fn test_input_format_outside_allowlist_is_rejected() {
+        let app = |allowed: &[&str]| {
+            Router::new()
+                .route("/pipeline", post(process_pipeline))
+                .with_state(Arc::new(Config {
+                    server: ServerConfig {
+                        max_body_size: 1024 * 1024,
+                        allowed_input_formats: allowed.iter().map(|s| s.to_string()).collect(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }))
+        };
+        let operations = r#"[{"operation": "grayscale"}]"#;
+
+        let response = app(&["jpg", "png"])
+            .oneshot(multipart_request(&png_bytes(4, 4), operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let response = app(&["jpg", "webp"])
+            .oneshot(multipart_request(&png_bytes(4, 4), operations))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        // HEIF has to be listed explicitly
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        let response = app(&["jpg", "png"])
+            .oneshot(multipart_request(heic, operations))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+
+    #[cfg(not(feature = "heif"))]
+    #[tokio::test]
+    async fn test_heif_input_needs_heif_feature() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(Arc::new(Config {
+                server: ServerConfig {
+                    max_body_size: 1024 * 1024,
+                    allowed_input_formats: vec!["heic".to_string()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }));
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        let response = app
+            .oneshot(multipart_request(heic, r#"[{"operation": "grayscale"}]"#))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_operations_point_at_the_offending_field() {
+        let error_body = |operations: &'static str| async move {
+            let app = Router::new()
+                .route("/pipeline", post(process_pipeline))
+                .with_state(create_test_config());
+            let response = app
+                .oneshot(multipart_request(&png_bytes(4, 4), operations))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let body = error_body(r#"[{"operation": "grayscale"}, {"operation": "sparkle"}]"#).await;
+        assert_eq!(body["error_code"], "invalid_operations_json");
+        assert_eq!(body["pointer"], "/1/operation");
+        let message = body["error"].as_str().unwrap();
+        assert!(
+            message.contains("unknown operation `sparkle`"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("expected one of: crop, smartCrop"),
+            "{}",
+            message
+        );
+
+        let body = error_body(
+            r#"[{"operation": "grayscale"}, {"operation": "resize", "params": {"width": "wide", "height": 10}}]"#,
+        )
+        .await;
+        assert_eq!(body["error_code"], "invalid_operations_json");
+        assert_eq!(body["pointer"], "/1/params/width");
+        let message = body["error"].as_str().unwrap();
+        assert!(message.contains("operation 1 (resize)"), "{}", message);
+        assert!(
+            message.contains("invalid type: string \"wide\""),
+            "{}",
+            message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_image_responses_are_nosniff() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        for operations in [
+            r#"[{"operation": "grayscale"}]"#,
+            r#"[{"operation": "convert", "params": {"format": "jpeg"}}]"#,
+        ] {
+            let response = app
+                .clone()
+                .oneshot(multipart_request(&png_bytes(4, 4), operations))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+            assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_etag_is_stable_and_answers_if_none_match_with_304() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let image = png_bytes(8, 8);
+        let operations = r#"[{"operation": "flop"}]"#;
+        let etag = |response: &Response| response.headers()[ETAG].to_str().unwrap().to_string();
+
+        let first = app
+            .clone()
+            .oneshot(multipart_request(&image, operations))
+            .await
+            .unwrap();
+        let second = app
+            .clone()
+            .oneshot(multipart_request(&image, operations))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), axum::http::StatusCode::OK);
+        assert_eq!(etag(&first), etag(&second));
+        assert!(etag(&first).starts_with('"') && etag(&first).ends_with('"'));
+
+        let other = app
+            .clone()
+            .oneshot(multipart_request(&image, r#"[{"operation": "flip"}]"#))
+            .await
+            .unwrap();
+        assert_ne!(etag(&other), etag(&first));
+
+        for if_none_match in [
+            etag(&first),
+            format!("W/{}", etag(&first)),
+            format!("\"stale\", {}", etag(&first)),
+        ] {
+            let mut request = multipart_request(&image, operations);
+            request
+                .headers_mut()
+                .insert(IF_NONE_MATCH, if_none_match.parse().unwrap());
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::NOT_MODIFIED);
+            assert_eq!(etag(&response), etag(&first));
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(body.is_empty());
+        }
+
+        let mut request = multipart_request(&image, operations);
+        request
+            .headers_mut()
+            .insert(IF_NONE_MATCH, "\"stale\"".parse().unwrap());
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_strict_content_type_rejects_mismatched_uploads() {
+        let mut jpeg = Vec::new();
+        image::DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+        let operations = r#"[{"operation": "grayscale"}]"#;
+        let app = |strict_content_type| {
+            Router::new()
+                .route("/pipeline", post(process_pipeline))
+                .with_state(Arc::new(Config {
+                    server: ServerConfig {
+                        max_body_size: 1024 * 1024,
+                        strict_content_type,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }))
+        };
+
+        // The test upload is labelled image/png
+        let response = app(true)
+            .oneshot(multipart_request(&jpeg, operations))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let response = app(true)
+            .oneshot(multipart_request(&png_bytes(4, 4), operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let response = app(false)
+            .oneshot(multipart_request(&jpeg, operations))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "image/jpeg");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_operation_count_is_limited() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(Arc::new(Config {
+                server: ServerConfig {
+                    max_body_size: 1024 * 1024,
+                    max_pipeline_operations: 3,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }));
+        let operations = |count: usize| {
+            serde_json::to_string(&vec![json!({"operation": "grayscale"}); count]).unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(multipart_request(&png_bytes(4, 4), &operations(3)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let response = app
+            .oneshot(multipart_request(&png_bytes(4, 4), &operations(4)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let message = body["error"].as_str().unwrap();
+        assert!(
+            message.contains("Pipeline has 4 operations, exceeding the limit of 3"),
+            "{}",
+            message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_input_reports_detected_format() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let error_message = |response: axum::response::Response| async move {
+            assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            body["error"].as_str().unwrap().to_string()
+        };
+        let operations = r#"[{"operation": "grayscale"}]"#;
+
+        // Header and IHDR intact, image data cut off halfway
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, (x ^ y) as u8])
+        }))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+        let truncated = &png[..png.len() / 2];
+        let response = app
+            .clone()
+            .oneshot(multipart_request(truncated, operations))
+            .await
+            .unwrap();
+        let message = error_message(response).await;
+        assert!(message.contains("png image is truncated"), "{}", message);
+
+        // PNG signature followed by garbage
+        let mut garbage = png[..8].to_vec();
+        garbage.extend_from_slice(b"\0\0\0\0gArb");
+        garbage.extend_from_slice(&[0xAB; 64]);
+        let response = app
+            .oneshot(multipart_request(&garbage, operations))
+            .await
+            .unwrap();
+        let message = error_message(response).await;
+        assert!(message.contains("png image is corrupt"), "{}", message);
+    }
+
+    #[test]
+    fn test_coalescing_key_distinguishes_requests() {
+        let image = png_bytes(8, 8);
+        let grayscale: Vec<PipelineOperationSpec> =
+            serde_json::from_str(r#"[{"operation": "grayscale"}]"#).unwrap();
+        let blur: Vec<PipelineOperationSpec> =
+            serde_json::from_str(r#"[{"operation": "blur", "params": {"sigma": 1.0}}]"#).unwrap();
+
+        let metadata = MetadataOptions::default();
+        let no_assets = BTreeMap::new();
+
+        let key = coalescing_key(&image, &grayscale, ImageFormat::Png, metadata, &no_assets);
+        assert_eq!(
+            key,
+            coalescing_key(&image, &grayscale, ImageFormat::Png, metadata, &no_assets)
+        );
+        assert_ne!(
+            key,
+            coalescing_key(&image, &blur, ImageFormat::Png, metadata, &no_assets)
+        );
+        assert_ne!(
+            key,
+            coalescing_key(&image, &grayscale, ImageFormat::Jpeg, metadata, &no_assets)
+        );
+        assert_ne!(
+            key,
+            coalescing_key(
+                &png_bytes(9, 8),
+                &grayscale,
+                ImageFormat::Png,
+                metadata,
+                &no_assets
+            )
+        );
+        let keep_metadata = MetadataOptions {
+            strip: false,
+            ..metadata
+        };
+        assert_ne!(
+            key,
+            coalescing_key(
+                &image,
+                &grayscale,
+                ImageFormat::Png,
+                keep_metadata,
+                &no_assets
+            )
+        );
+        let watermark = BTreeMap::from([(WATERMARK_ASSET.to_string(), png_bytes(2, 2))]);
+        assert_ne!(
+            key,
+            coalescing_key(&image, &grayscale, ImageFormat::Png, metadata, &watermark)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_identical_requests_share_output() {
+        let app = Router::new()
+            .route("/pipeline", post(process_pipeline))
+            .with_state(create_test_config());
+        let image = png_bytes(37, 23);
+        let operations = r#"[{"operation": "resize", "params": {"width": 12, "height": 12}}]"#;
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| tokio::spawn(app.clone().oneshot(multipart_request(&image, operations))))
+            .collect();
+
+        let mut bodies = Vec::new();
+        for task in tasks {
+            let response = task.await.unwrap().unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+            bodies.push(
+                axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert!(bodies.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    fn signed_security_config() -> SecurityConfig {
+        let mut security = SecurityConfig::default();
+        security.set_key(ApiKey::from(
+            "signing-key-0123456789abcdef012345".to_string(),
+        ));
+        security
+    }
+
+    const SIGNED_PATH: &str =
+        "/pipeline?url=https://example.com/a.png&operations=%5B%7B%22operation%22%3A%22grayscale%22%7D%5D";
+
+    #[test]
+    fn test_verify_url_signature_accepts_valid_signature() {
+        let security = signed_security_config();
+        let signature = security.generate_signature(SIGNED_PATH.as_bytes()).unwrap();
+        let uri: Uri = format!("{}&sign={}", SIGNED_PATH, signature)
+            .parse()
+            .unwrap();
+        assert!(verify_url_signature(&uri, &security).is_ok());
+    }
+
+    #[test]
+    fn test_verify_url_signature_rejects_tampered_request() {
+        let security = signed_security_config();
+        let signature = security.generate_signature(SIGNED_PATH.as_bytes()).unwrap();
+
+        let tampered: Uri = format!(
+            "{}&sign={}",
+            SIGNED_PATH.replace("a.png", "b.png"),
+            signature
+        )
+        .parse()
+        .unwrap();
+        assert!(matches!(
+            verify_url_signature(&tampered, &security),
+            Err(AppError::Unauthorized(_))
+        ));
+
+        let unsigned: Uri = SIGNED_PATH.parse().unwrap();
+        assert!(matches!(
+            verify_url_signature(&unsigned, &security),
+            Err(AppError::Unauthorized(_))
+        ));
+
+        let garbage: Uri = format!("{}&sign=not-hex", SIGNED_PATH).parse().unwrap();
+        assert!(verify_url_signature(&garbage, &security).is_err());
+    }
+
+    #[test]
+    fn test_verify_url_signature_skipped_without_key() {
+        let uri: Uri = SIGNED_PATH.parse().unwrap();
+        assert!(verify_url_signature(&uri, &SecurityConfig::default()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_with_tampered_signature_is_unauthorized() {
+        let config = Config {
+            security: signed_security_config(),
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route("/pipeline", axum::routing::get(process_pipeline))
+            .with_state(Arc::new(config));
+        let request = axum::http::Request::get(format!("{}&sign={}", SIGNED_PATH, "00".repeat(32)))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::UNAUTHORIZED);
+    }
+
+    /// Serves `body` once on a local port without a `Content-Length`, closing the connection
+    /// to end the body.
+    async fn serve_unsized_body(body: Vec<u8>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            for chunk in body.chunks(256) {
+                if socket.write_all(chunk).await.is_err() {
+                    return;
+                }
+            }
+        });
+        format!("http://{}/image.png", addr)
+    }
+
+    async fn get_without_proxy(url: &str) -> reqwest::Response {
+        reqwest::Client::builder()
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(url)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_body_without_content_length_is_capped_while_streaming() {
+        let url = serve_unsized_body(vec![7u8; 4096]).await;
+        let response = get_without_proxy(&url).await;
+        assert_eq!(response.content_length(), None);
+        match read_body_capped(response, 1000).await {
+            Err(AppError::PayloadTooLarge(msg)) => {
+                assert!(msg.ends_with("exceeds limit of 1000 bytes"), "{}", msg)
+            }
+            other => panic!("Expected PayloadTooLarge, got {:?}", other),
+        }
+
+        let url = serve_unsized_body(vec![7u8; 4096]).await;
+        let response = get_without_proxy(&url).await;
+        assert_eq!(read_body_capped(response, 4096).await.unwrap().len(), 4096);
+    }
+
+    #[tokio::test]
+    async fn test_dns_rebinding_between_check_and_connect_is_blocked() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // First answer is public (passes the early check), later answers point at loopback
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = lookups.clone();
+        let resolver = SafeResolver {
+            lookup: Arc::new(move |_host: String| -> LookupFuture {
+                let ip = if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))
+                } else {
+                    IpAddr::V4(Ipv4Addr::LOCALHOST)
+                };
+                Box::pin(async move { Ok(vec![ip]) })
+            }),
+            allow: is_safe_ip,
+        };
+        let client = fetch_client(resolver.clone());
+
+        let result = fetch_image(
+            "http://rebind.example/image.png",
+            &create_test_config(),
+            &client,
+            &resolver,
+        )
+        .await;
+        match result {
+            Err(AppError::SsrfBlocked(msg)) => assert!(msg.contains("rebind.example"), "{}", msg),
+            other => panic!("Expected SsrfBlocked, got {:?}", other),
+        }
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resolver_rejects_unspecified_and_ipv4_embedding_addresses() {
+        let unsafe_answers = [
+            "0.0.0.0",
+            "0.1.2.3",
+            "::",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "::ffff:169.254.169.254",
+            "::127.0.0.1",
+        ];
+        for answer in unsafe_answers {
+            let ip: IpAddr = answer.parse().unwrap();
+            let resolver = SafeResolver {
+                lookup: Arc::new(move |_host: String| -> LookupFuture {
+                    Box::pin(async move { Ok(vec![ip]) })
+                }),
+                allow: is_safe_ip,
+            };
+            match resolver.resolve_safe("rebind.example").await {
+                Err(AppError::SsrfBlocked(_)) => {}
+                other => panic!("Expected SsrfBlocked for {}, got {:?}", answer, other),
+            }
+        }
+
+        let ip: IpAddr = "::ffff:93.184.216.34".parse().unwrap();
+        let resolver = SafeResolver {
+            lookup: Arc::new(move |_host: String| -> LookupFuture {
+                Box::pin(async move { Ok(vec![ip]) })
+            }),
+            allow: is_safe_ip,
+        };
+        assert_eq!(
+            resolver.resolve_safe("public.example").await.unwrap(),
+            vec![ip]
+        );
+    }
+
+    /// Answers every request on a local port with a `302` to `location`.
+    async fn serve_redirects(location: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    location
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}/image.png", addr)
+    }
+
+    /// Resolver for redirect tests: the mock server's loopback address counts as public.
+    fn loopback_allowed_resolver() -> SafeResolver {
+        SafeResolver {
+            allow: |ip| ip == IpAddr::V4(Ipv4Addr::LOCALHOST) || is_safe_ip(ip),
+            ..SafeResolver::system()
+        }
+    }
+
+    fn config_with_max_redirects(max_redirects: usize) -> Config {
+        Config {
+            server: ServerConfig {
+                max_body_size: 1024 * 1024,
+                max_redirects,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Answers the `n`th request on a local port with `responses[n]`'s status line and `body`,
+    /// repeating the last one; returns the URL and a count of requests served.
+    async fn serve_statuses(
+        responses: &'static [&'static str],
+        body: Vec<u8>,
+    ) -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let served = Arc::new(AtomicUsize::new(0));
+        let counter = served.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                let status = responses[n.min(responses.len() - 1)];
+                let body: &[u8] = if status.starts_with("200") {
+                    &body
+                } else {
+                    b""
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(body).await;
+            }
+        });
+        (format!("http://{}/image.png", addr), served)
+    }
+
+    fn config_with_retries(fetch_retries: u32) -> Config {
+        let mut config = config_with_max_redirects(0);
+        config.server.fetch_retries = fetch_retries;
+        config.server.fetch_retry_backoff_ms = 1;
+        config
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_fetches_beyond_the_limit_are_rejected() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Answers each request after a delay longer than FETCH_SLOT_WAIT
+        let image = png_bytes(4, 4);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/slow.png", listener.local_addr().unwrap());
+        let body = image.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let body = body.clone();
+                tokio::spawn(async move {
+                    let mut request = [0u8; 1024];
+                    let _ = socket.read(&mut request).await;
+                    tokio::time::sleep(FETCH_SLOT_WAIT * 3).await;
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    );
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    let _ = socket.write_all(&body).await;
+                });
+            }
+        });
+        let resolver = loopback_allowed_resolver();
+        let client = fetch_client(resolver.clone());
+        let mut config = config_with_max_redirects(0);
+        config.server.max_concurrent_fetches = 1;
+        let config = Arc::new(config);
+
+        let first = tokio::spawn({
+            let (url, config, client, resolver) = (
+                url.clone(),
+                config.clone(),
+                client.clone(),
+                resolver.clone(),
+            );
+            async move { fetch_image(&url, &config, &client, &resolver).await }
+        });
+        // Wait until the first fetch holds the only slot
+        while config.server.fetch_slots().unwrap().available_permits() > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let shed = fetch_image(&url, &config, &client, &resolver).await;
+        match &shed {
+            Err(AppError::ServiceUnavailable(msg)) => assert!(msg.contains("limit 1"), "{}", msg),
+            other => panic!("Expected ServiceUnavailable, got {:?}", other),
+        }
+        assert_eq!(
+            shed.unwrap_err().into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(first.await.unwrap().unwrap(), image);
+        // The slot is free again
+        assert_eq!(config.server.fetch_slots().unwrap().available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_retried_until_the_fetch_succeeds() {
+        use std::sync::atomic::Ordering;
+
+        let image = png_bytes(4, 4);
+        let (url, served) = serve_statuses(
+            &["503 Service Unavailable", "502 Bad Gateway", "200 OK"],
+            image.clone(),
+        )
+        .await;
+        let resolver = loopback_allowed_resolver();
+        let client = fetch_client(resolver.clone());
+
+        let fetched = fetch_image(&url, &config_with_retries(3), &client, &resolver)
+            .await
+            .unwrap();
+        assert_eq!(fetched, image);
+        assert_eq!(served.load(Ordering::SeqCst), 3);
+
+        // Out of retries, the last 5xx is reported
+        let (url, served) = serve_statuses(&["503 Service Unavailable"], Vec::new()).await;
+        match fetch_image(&url, &config_with_retries(1), &client, &resolver).await {
+            Err(AppError::BadRequest(msg)) => assert!(msg.contains("503"), "{}", msg),
+            other => panic!("Expected BadRequest, got {:?}", other),
+        }
+        assert_eq!(served.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        use std::sync::atomic::Ordering;
+
+        let (url, served) = serve_statuses(&["404 Not Found", "200 OK"], png_bytes(4, 4)).await;
+        let resolver = loopback_allowed_resolver();
+        let client = fetch_client(resolver.clone());
+
+        match fetch_image(&url, &config_with_retries(3), &client, &resolver).await {
+            Err(AppError::BadRequest(msg)) => assert!(msg.contains("404"), "{}", msg),
+            other => panic!("Expected BadRequest, got {:?}", other),
+        }
+        assert_eq!(served.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_private_address_is_blocked() {
+        let url = serve_redirects("http://169.254.169.254/latest/meta-data/").await;
+        let resolver = loopback_allowed_resolver();
+        let client = fetch_client(resolver.clone());
+
+        let result = fetch_image(&url, &config_with_max_redirects(5), &client, &resolver).await;
+        match result {
+            Err(AppError::SsrfBlocked(msg)) => assert!(msg.contains("169.254.169.254"), "{}", msg),
+            other => panic!("Expected SsrfBlocked, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_limit_is_enforced() {
+        let url = serve_redirects("/again").await;
+        let resolver = loopback_allowed_resolver();
+        let client = fetch_client(resolver.clone());
+
+        let result = fetch_image(&url, &config_with_max_redirects(2), &client, &resolver).await;
+        match result {
+            Err(AppError::BadRequest(msg)) => {
+                assert_eq!(msg, "Too many redirects when fetching image (limit 2)")
+            }
+            other => panic!("Expected BadRequest, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_host_allowlist_is_enforced() {
+        // Every name resolves to the local mock server
+        let resolver = SafeResolver {
+            lookup: Arc::new(|_host: String| -> LookupFuture {
+                Box::pin(async { Ok(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]) })
+            }),
+            allow: |_| true,
+        };
+        let client = fetch_client(resolver.clone());
+        let config_allowing = |hosts: &[&str]| {
+            let mut config = config_with_max_redirects(0);
+            config
+                .security
+                .set_allowed_fetch_hosts(hosts.iter().map(|host| host.to_string()).collect());
+            config
+        };
+        let image = png_bytes(4, 4);
+
+        for allowed in ["img.example.com", "*.example.com"] {
+            let url = serve_unsized_body(image.clone())
+                .await
+                .replace("127.0.0.1", "img.example.com");
+            let fetched = fetch_image(&url, &config_allowing(&[allowed]), &client, &resolver)
+                .await
+                .unwrap();
+            assert_eq!(fetched, image, "{}", allowed);
+        }
+
+        let result = fetch_image(
+            "http://img.example.com/image.png",
+            &config_allowing(&["other.example"]),
+            &client,
+            &resolver,
+        )
+        .await;
+        match result {
+            Err(AppError::Unauthorized(msg)) => assert!(msg.contains("img.example.com"), "{}", msg),
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+    }
 }
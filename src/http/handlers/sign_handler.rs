@@ -0,0 +1,142 @@
+//! HTTP handler for the /sign endpoint.
+//!
+//! Signs a client-provided payload with the server's HMAC-SHA256 key, or verifies a signature
+//! the client already holds. Lets clients produce signed URLs or tokens without sharing the key.
+//!
+//! Example usage:
+//!   POST /sign
+//!   {"payload": "/pipeline?url=https://example.com/a.png&operations=..."}
+//!   {"payload": "...", "signature": "<hex>"}
+
+use crate::config::{current_config, Config, LiveConfig};
+use crate::http::errors::AppError;
+use axum::extract::State;
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct SignRequest {
+    pub payload: String,
+    /// Hex signature to verify against `payload` instead of only signing it.
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignResponse {
+    /// Hex-encoded HMAC-SHA256 of the payload.
+    pub signature: String,
+    /// Whether the provided signature matches; omitted when none was provided.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valid: Option<bool>,
+}
+
+/// Handles POST /sign requests.
+///
+/// Refuses to sign without a configured key, since signatures made with the placeholder key
+/// would be forgeable by anyone. Uses the key of the live configuration, so a reload rotates it.
+pub async fn sign_payload(
+    State(config): State<Arc<Config>>,
+    live: Option<Extension<LiveConfig>>,
+    Json(request): Json<SignRequest>,
+) -> Result<Json<SignResponse>, AppError> {
+    let config = current_config(&config, live.as_ref().map(|Extension(live)| live));
+    if config.security.key().is_none_or(|key| key.is_empty()) {
+        return Err(AppError::Unauthorized(
+            "Signing requires a configured API key".to_string(),
+        ));
+    }
+
+    let payload = request.payload.as_bytes();
+    let signature = config.security.generate_signature(payload).map_err(|e| {
+        AppError::InternalServerError(format!("Failed to generate signature: {}", e))
+    })?;
+    let valid = match request.signature {
+        Some(provided) => Some(
+            config
+                .security
+                .validate_signature(payload, &provided)
+                .map_err(|e| AppError::BadRequest(format!("Invalid signature: {}", e)))?,
+        ),
+        None => None,
+    };
+
+    Ok(Json(SignResponse { signature, valid }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::ApiKey;
+    use axum::{body::Body, http::Request, http::StatusCode, routing::post, Router};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    fn app(key: Option<&str>) -> Router {
+        let mut config = Config::default();
+        if let Some(key) = key {
+            config.security.set_key(ApiKey::from(key.to_string()));
+        }
+        Router::new()
+            .route("/sign", post(sign_payload))
+            .with_state(Arc::new(config))
+    }
+
+    fn request(body: Value) -> Request<Body> {
+        Request::post("/sign")
+            .header("Content-Type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn body_json(response: axum::response::Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sign_then_verify_round_trip() {
+        let key = "sign-test-key-0123456789abcdef0123";
+        let response = app(Some(key))
+            .oneshot(request(json!({"payload": "hello"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        let signature = body["signature"].as_str().unwrap().to_string();
+        assert_eq!(signature.len(), 64);
+        assert!(body.get("valid").is_none());
+
+        let response = app(Some(key))
+            .oneshot(request(json!({"payload": "hello", "signature": signature})))
+            .await
+            .unwrap();
+        assert_eq!(body_json(response).await["valid"], true);
+
+        let response = app(Some(key))
+            .oneshot(request(
+                json!({"payload": "tampered", "signature": signature}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(body_json(response).await["valid"], false);
+    }
+
+    #[tokio::test]
+    async fn test_sign_rejects_missing_key_and_malformed_signature() {
+        let response = app(None)
+            .oneshot(request(json!({"payload": "hello"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app(Some("sign-test-key-0123456789abcdef0123"))
+            .oneshot(request(json!({"payload": "hello", "signature": "not hex"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,429 @@
+//! HTTP handler for the /srcset endpoint.
+//!
+//! Accepts multipart/form-data with an image and a list of target widths, resizes the image to
+//! every width keeping its aspect ratio and returns all renditions at once as JSON, ready to
+//! build an HTML `srcset`.
+//!
+//! Example usage:
+//!   POST /srcset
+//!   - image: file
+//!   - widths: '[320, 640, 1280]'
+//!   - format: 'webp' (optional, defaults to the input format)
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Multipart, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{DynamicImage, ImageFormat};
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    http::{
+        errors::AppError,
+        handlers::pipeline_handler::{
+            check_animated_input, first_frame_only_header, format_from_name,
+        },
+    },
+    image::{
+        format_support::{decode_error, detect_input_format, format_support},
+        icc::{embed_icc_profile, read_icc_profile},
+        limits::ImageLimits,
+        metadata::{apply_orientation, read_orientation},
+        operations::{format::encode_image, transform::resize},
+        params::{FormatConversionParams, ResizeParams},
+    },
+    server::cpu_pool::run_cpu,
+    utils::image_utils::probe_dimensions,
+};
+
+/// Most widths one request may ask for.
+pub const MAX_SRCSET_WIDTHS: usize = 10;
+
+/// One rendition of the uploaded image.
+#[derive(Debug, Serialize)]
+pub struct SrcsetItem {
+    pub width: u32,
+    pub height: u32,
+    pub content_type: String,
+    /// Base64-encoded resized image.
+    pub data: String,
+}
+
+/// Handles POST /srcset requests.
+///
+/// The widths are bounded by `MAX_SRCSET_WIDTHS`, every rendition by the usual output limits
+/// and their combined pixels by `max_output_pixels`, so one call can't do more work than a
+/// handful of `/pipeline` calls.
+pub async fn create_srcset(
+    State(config): State<Arc<Config>>,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    let mut image_bytes = None;
+    let mut widths = None;
+    let mut format = None;
+    while let Some(field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+        match name.as_str() {
+            "image" => {
+                let data = field.bytes().await?;
+                if data.len() > config.server.max_body_size {
+                    return Err(AppError::PayloadTooLarge(format!(
+                        "Image size {} exceeds limit of {} bytes",
+                        data.len(),
+                        config.server.max_body_size
+                    )));
+                }
+                image_bytes = Some(data);
+            }
+            "widths" => widths = Some(parse_widths(&field.text().await?)?),
+            "format" => format = Some(field.text().await?),
+            _ => tracing::debug!("Ignoring unknown multipart field: {}", name),
+        }
+    }
+    let image_bytes = image_bytes
+        .ok_or_else(|| AppError::BadRequest("Missing image in multipart request".to_string()))?;
+    let widths = widths
+        .ok_or_else(|| AppError::BadRequest("Missing widths in multipart request".to_string()))?;
+
+    let original_format = detect_input_format(&image_bytes, &config.server.allowed_input_formats)?;
+    let output_format = match &format {
+        Some(name) => format_from_name(name)
+            .ok_or_else(|| AppError::BadRequest(format!("Unsupported output format '{}'", name)))?,
+        None => original_format,
+    };
+    format_support().ensure_supported(original_format, output_format)?;
+
+    let limits = ImageLimits::from_config(&config.server);
+    let dimensions = probe_dimensions(&image_bytes, original_format)?;
+    limits.check_input(dimensions)?;
+    let first_frame_only = check_animated_input(
+        &image_bytes,
+        original_format,
+        config.server.animated_input_policy,
+    )?;
+    let orientation = read_orientation(&image_bytes, original_format).filter(|&o| o != 1);
+    // Orientations 5-8 turn the image by a quarter
+    let oriented = match orientation {
+        Some(5..=8) => (dimensions.1, dimensions.0),
+        _ => dimensions,
+    };
+    let sizes = rendition_sizes(oriented, &widths, &limits)?;
+    let encoding = config.encoding.encoder_params(None, output_format);
+
+    let items = run_cpu("Srcset", move || {
+        let icc_profile = read_icc_profile(&image_bytes, original_format);
+        let mut image = image::load_from_memory_with_format(&image_bytes, original_format)
+            .map_err(|e| decode_error(original_format, e))?;
+        if let Some(orientation) = orientation {
+            image = apply_orientation(image, orientation);
+        }
+        sizes
+            .into_iter()
+            .map(|(width, height)| {
+                render(
+                    &image,
+                    width,
+                    height,
+                    output_format,
+                    encoding.as_ref(),
+                    icc_profile.as_deref(),
+                )
+            })
+            .collect::<Result<Vec<_>, AppError>>()
+    })
+    .await?;
+    Ok((first_frame_only_header(first_frame_only), Json(items)).into_response())
+}
+
+/// Widths from a JSON array such as `[320, 640]` or a comma-separated list such as `320,640`.
+fn parse_widths(value: &str) -> Result<Vec<u32>, AppError> {
+    let value = value.trim();
+    let widths = if value.starts_with('[') {
+        serde_json::from_str::<Vec<u32>>(value)
+            .map_err(|e| AppError::BadRequest(format!("Invalid widths: {}", e)))?
+    } else {
+        value
+            .split(',')
+            .map(|width| {
+                width
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| AppError::BadRequest(format!("Invalid width '{}'", width.trim())))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    if widths.is_empty() {
+        return Err(AppError::BadRequest(
+            "At least one width is required".to_string(),
+        ));
+    }
+    if widths.len() > MAX_SRCSET_WIDTHS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} widths are allowed, got {}",
+            MAX_SRCSET_WIDTHS,
+            widths.len()
+        )));
+    }
+    if widths.contains(&0) {
+        return Err(AppError::BadRequest(
+            "Widths must be greater than zero".to_string(),
+        ));
+    }
+    Ok(widths)
+}
+
+/// Output size for every width, keeping the aspect ratio of `(width, height)`.
+///
+/// Rejects renditions over the output limits, and requests whose renditions add up to more
+/// than `max_output_pixels`.
+fn rendition_sizes(
+    (width, height): (u32, u32),
+    widths: &[u32],
+    limits: &ImageLimits,
+) -> Result<Vec<(u32, u32)>, AppError> {
+    let sizes: Vec<(u32, u32)> = widths
+        .iter()
+        .map(|&target| {
+            let scaled = (height as f64 * target as f64 / width.max(1) as f64).round();
+            (target, (scaled as u32).max(1))
+        })
+        .collect();
+    for &(w, h) in &sizes {
+        if limits.max_output_dimension > 0 && w.max(h) > limits.max_output_dimension {
+            return Err(AppError::BadRequest(format!(
+                "Rendition of {}x{} exceeds the maximum dimension of {}",
+                w, h, limits.max_output_dimension
+            )));
+        }
+    }
+    let total: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+    if limits.max_output_pixels > 0 && total > limits.max_output_pixels {
+        return Err(AppError::BadRequest(format!(
+            "Renditions total {} pixels, exceeding the limit of {} pixels",
+            total, limits.max_output_pixels
+        )));
+    }
+    Ok(sizes)
+}
+
+fn render(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    format: ImageFormat,
+    encoding: Option<&FormatConversionParams>,
+    icc_profile: Option<&[u8]>,
+) -> Result<SrcsetItem, AppError> {
+    let resized = resize(image.clone(), &ResizeParams { width, height });
+    let mut output = encode_image(&resized, format, encoding)?;
+    if let Some(profile) = icc_profile {
+        output = embed_icc_profile(output, format, profile);
+    }
+    Ok(SrcsetItem {
+        width: resized.width(),
+        height: resized.height(),
+        content_type: format.to_mime_type().to_string(),
+        data: STANDARD.encode(output),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{AnimatedInputPolicy, ServerConfig};
+    use axum::{body::Body, http::StatusCode, routing::post, Router};
+    use image::GenericImageView;
+    use serde_json::Value;
+    use std::io::Cursor;
+    use tower::ServiceExt;
+
+    const BOUNDARY: &str = "imaginary-srcset-boundary";
+
+    fn app() -> Router {
+        app_with(AnimatedInputPolicy::default())
+    }
+
+    fn app_with(animated_input_policy: AnimatedInputPolicy) -> Router {
+        let config = Config {
+            server: ServerConfig {
+                max_body_size: 1024 * 1024,
+                animated_input_policy,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Router::new()
+            .route("/srcset", post(create_srcset))
+            .with_state(Arc::new(config))
+    }
+
+    fn srcset_request(
+        image: &[u8],
+        widths: &str,
+        format: Option<&str>,
+    ) -> axum::http::Request<Body> {
+        let mut body = Vec::new();
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"image.png\"\r\nContent-Type: image/png\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(image);
+        body.extend_from_slice(b"\r\n");
+        let mut fields = vec![("widths", widths)];
+        fields.extend(format.map(|format| ("format", format)));
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+        axum::http::Request::post("/srcset")
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    /// A two-frame 8x4 GIF, red then green.
+    fn animated_gif() -> Vec<u8> {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, Rgba, RgbaImage};
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            let frames = [Rgba([255u8, 0, 0, 255]), Rgba([0u8, 255, 0, 255])]
+                .into_iter()
+                .map(|color| {
+                    Frame::from_parts(
+                        RgbaImage::from_pixel(8, 4, color),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(50, 1),
+                    )
+                });
+            encoder.encode_frames(frames).unwrap();
+        }
+        bytes
+    }
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_srcset_scales_to_every_width() {
+        let input = png_bytes(2000, 1000);
+        let response = app()
+            .oneshot(srcset_request(&input, "[320, 640, 1280]", Some("jpeg")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let items: Vec<Value> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(items.len(), 3);
+        for (item, (width, height)) in items.iter().zip([(320, 160), (640, 320), (1280, 640)]) {
+            assert_eq!(item["width"], width);
+            assert_eq!(item["height"], height);
+            assert_eq!(item["content_type"], "image/jpeg");
+            let data = STANDARD.decode(item["data"].as_str().unwrap()).unwrap();
+            let decoded = image::load_from_memory_with_format(&data, ImageFormat::Jpeg).unwrap();
+            assert_eq!(decoded.dimensions(), (width, height));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_srcset_accepts_comma_separated_widths() {
+        let input = png_bytes(40, 30);
+        let response = app()
+            .oneshot(srcset_request(&input, "20, 10", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let items: Vec<Value> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(items[0]["height"], 15);
+        assert_eq!(items[1]["height"], 8);
+        assert_eq!(items[1]["content_type"], "image/png");
+    }
+
+    #[tokio::test]
+    async fn test_srcset_rejects_too_many_or_invalid_widths() {
+        let input = png_bytes(40, 30);
+        let too_many = (1..=MAX_SRCSET_WIDTHS as u32 + 1)
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        for widths in [too_many.as_str(), "[]", "[0]", "abc"] {
+            let response = app()
+                .oneshot(srcset_request(&input, widths, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", widths);
+        }
+    }
+
+    #[test]
+    fn test_rendition_sizes_respect_total_pixel_limit() {
+        // 50x5 and 100x10 renditions total exactly 1250 pixels
+        let limits = |max_output_pixels| ImageLimits {
+            max_output_pixels,
+            ..Default::default()
+        };
+        assert!(rendition_sizes((100, 10), &[50, 100], &limits(1250)).is_ok());
+        assert!(matches!(
+            rendition_sizes((100, 10), &[50, 100], &limits(1249)),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_srcset_applies_the_animated_input_policy() {
+        let response = app_with(AnimatedInputPolicy::FirstFrame)
+            .oneshot(srcset_request(&animated_gif(), "[4]", Some("png")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-first-frame-only"], "true");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let items: Vec<Value> = serde_json::from_slice(&bytes).unwrap();
+        let data = STANDARD.decode(items[0]["data"].as_str().unwrap()).unwrap();
+        let decoded = image::load_from_memory_with_format(&data, ImageFormat::Png).unwrap();
+        assert_eq!(decoded.to_rgba8().get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+        let response = app_with(AnimatedInputPolicy::Reject)
+            .oneshot(srcset_request(&animated_gif(), "[4]", Some("png")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app_with(AnimatedInputPolicy::Reject)
+            .oneshot(srcset_request(&png_bytes(8, 4), "[4]", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("x-first-frame-only").is_none());
+    }
+}
@@ -0,0 +1,171 @@
+//! HTTP handler for the /pipeline/validate endpoint.
+//!
+//! Checks an operations array before any image is uploaded: each entry is parsed into a
+//! `PipelineOperationSpec` and its params are validated exactly as the executor would, but no
+//! image is decoded. Errors that depend on the image (e.g. a crop outside its bounds) can only
+//! surface when the pipeline actually runs.
+//!
+//! Example usage:
+//!   POST /pipeline/validate
+//!   [{"operation": "blur", "params": {"sigma": 2.0}}, {"operation": "grayscale"}]
+
+use axum::Json;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    http::errors::{AppError, ErrorCode},
+    image::{pipeline_executor::validate_operation, pipeline_types::PipelineOperationSpec},
+};
+
+/// Outcome of validating a whole operations array.
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    /// True when every operation is valid.
+    pub valid: bool,
+    pub operations: Vec<OperationValidation>,
+}
+
+/// Per-operation outcome returned by `/pipeline/validate`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status")]
+pub enum OperationValidation {
+    #[serde(rename = "ok")]
+    Valid { index: usize, operation: String },
+    #[serde(rename = "error")]
+    Invalid {
+        index: usize,
+        /// Operation name as sent, when one could be read.
+        operation: Option<String>,
+        error: String,
+        error_code: ErrorCode,
+    },
+}
+
+/// Handles POST /pipeline/validate requests.
+///
+/// Always answers `200` with a report once the body is a non-empty JSON array; invalid
+/// operations are reported per entry rather than failing the request.
+pub async fn validate_pipeline(
+    Json(operations): Json<Value>,
+) -> Result<Json<ValidationReport>, AppError> {
+    let Value::Array(operations) = operations else {
+        return Err(AppError::BadRequest(
+            "'operations' must be a JSON array".to_string(),
+        ));
+    };
+    if operations.is_empty() {
+        return Err(AppError::BadRequest(
+            "'operations' array cannot be empty".to_string(),
+        ));
+    }
+
+    let operations: Vec<_> = operations
+        .into_iter()
+        .enumerate()
+        .map(|(index, operation)| validate_entry(index, operation))
+        .collect();
+    let valid = operations
+        .iter()
+        .all(|result| matches!(result, OperationValidation::Valid { .. }));
+    Ok(Json(ValidationReport { valid, operations }))
+}
+
+fn validate_entry(index: usize, entry: Value) -> OperationValidation {
+    let requested_name = entry
+        .get("operation")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let outcome = serde_json::from_value::<PipelineOperationSpec>(entry)
+        .map_err(|e| AppError::InvalidOperation(format!("Invalid operation: {}", e)))
+        .and_then(|spec| validate_operation(&spec).map(|()| spec));
+    match outcome {
+        Ok(spec) => OperationValidation::Valid {
+            index,
+            operation: spec.operation.to_string(),
+        },
+        Err(e) => OperationValidation::Invalid {
+            index,
+            operation: requested_name,
+            error_code: e.error_code(),
+            error: e.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, http::StatusCode, routing::post, Router};
+    use tower::ServiceExt;
+
+    async fn validate(body: &str) -> (StatusCode, Value) {
+        let response = Router::new()
+            .route("/pipeline/validate", post(validate_pipeline))
+            .oneshot(
+                Request::post("/pipeline/validate")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_valid_pipeline() {
+        let (status, body) = validate(
+            r#"[
+                {"operation": "resize", "params": {"width": 100, "height": 80}},
+                {"operation": "blur", "params": {"sigma": 1.5}},
+                {"operation": "smart_crop", "params": {"width": 50, "height": 50}},
+                {"operation": "grayscale"}
+            ]"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], true);
+        let operations = body["operations"].as_array().unwrap();
+        assert_eq!(operations.len(), 4);
+        assert!(operations.iter().all(|op| op["status"] == "ok"));
+        assert_eq!(operations[2]["operation"], "smartCrop");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_blur_sigma_is_reported_per_operation() {
+        let (status, body) = validate(
+            r#"[
+                {"operation": "grayscale"},
+                {"operation": "blur", "params": {"sigma": -1.0}},
+                {"operation": "sparkle"}
+            ]"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], false);
+        let operations = body["operations"].as_array().unwrap();
+        assert_eq!(operations[0]["status"], "ok");
+        assert_eq!(operations[1]["status"], "error");
+        assert_eq!(operations[1]["operation"], "blur");
+        assert_eq!(operations[1]["error_code"], "invalid_parameters");
+        assert_eq!(operations[2]["status"], "error");
+        assert_eq!(operations[2]["operation"], "sparkle");
+        assert_eq!(operations[2]["error_code"], "invalid_operation");
+    }
+
+    #[tokio::test]
+    async fn test_non_array_or_empty_body_is_rejected() {
+        let (status, _) = validate(r#"{"operation": "grayscale"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = validate("[]").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,65 @@
+//! Build information for deployment verification.
+
+use axum::response::IntoResponse;
+use axum::Json;
+use serde_json::json;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short git SHA the binary was built from, captured by `build.rs`.
+const GIT_COMMIT: &str = env!("IMAGINARY_GIT_COMMIT");
+/// Unix time of the build in seconds, captured by `build.rs`.
+const BUILD_TIMESTAMP: &str = env!("IMAGINARY_BUILD_TIMESTAMP");
+
+/// Cargo features of this crate that were enabled at compile time.
+fn enabled_features() -> Vec<&'static str> {
+    [
+        ("jpeg", cfg!(feature = "jpeg")),
+        ("png", cfg!(feature = "png")),
+        ("webp", cfg!(feature = "webp")),
+        ("heif", cfg!(feature = "heif")),
+        ("gif", cfg!(feature = "gif")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+/// Build time as RFC 3339, or the raw value if it is not a Unix timestamp.
+fn build_timestamp() -> String {
+    BUILD_TIMESTAMP
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_else(|| BUILD_TIMESTAMP.to_string())
+}
+
+/// Reports the crate version, git commit, build time and enabled features.
+pub async fn version() -> impl IntoResponse {
+    Json(json!({
+        "version": VERSION,
+        "commit": GIT_COMMIT,
+        "build_timestamp": build_timestamp(),
+        "features": enabled_features(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_version_reports_build_info() {
+        let response = version().await.into_response();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(!body["version"].as_str().unwrap().is_empty());
+        assert!(!body["commit"].as_str().unwrap().is_empty());
+        assert!(body["build_timestamp"].as_str().unwrap().contains('T'));
+        assert!(body["features"].is_array());
+    }
+}
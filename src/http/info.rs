@@ -17,16 +17,13 @@ pub enum AppInfo {
     OriginAllowed(String),
     #[allow(dead_code)]
     OriginNotAllowed(String),
-    GeneratedSignature(String),
-    ValidatedSignature(bool),
     ExpectedApiKey(String),
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub enum ImageInfo {
-    #[allow(dead_code)]
     ImageProcessedSuccessfully(String),
-    #[allow(dead_code)]
     ImageConvertedSuccessfully(String),
 }
 
@@ -37,8 +34,6 @@ impl fmt::Display for AppInfo {
             AppInfo::SecurityConfigNotSecure => "Security configuration is not secure.".to_string(),
             AppInfo::OriginAllowed(origin) => format!("Origin {} is allowed.", origin),
             AppInfo::OriginNotAllowed(origin) => format!("Origin {} is not allowed.", origin),
-            AppInfo::GeneratedSignature(signature) => format!("Generated signature: {}", signature),
-            AppInfo::ValidatedSignature(is_valid) => format!("Signature is valid: {}", is_valid),
             AppInfo::ExpectedApiKey(api_key) => format!("Expected API key: '{}'", api_key),
         };
         write!(f, "{}", info_message)
@@ -76,13 +71,6 @@ impl IntoResponse for AppInfo {
             AppInfo::OriginNotAllowed(origin) => {
                 (StatusCode::OK, format!("Origin {} is not allowed.", origin))
             }
-            AppInfo::GeneratedSignature(signature) => (
-                StatusCode::OK,
-                format!("Generated signature: {}", signature),
-            ),
-            AppInfo::ValidatedSignature(is_valid) => {
-                (StatusCode::OK, format!("Signature is valid: {}", is_valid))
-            }
             AppInfo::ExpectedApiKey(api_key) => {
                 (StatusCode::OK, format!("Expected API key: '{}'", api_key))
             }
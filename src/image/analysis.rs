@@ -0,0 +1,156 @@
+//! Image statistics for automated quality checks.
+//!
+//! Computes per-channel min/max/mean/standard deviation and 256-bucket histograms for red,
+//! green, blue and luma. The pixel buffer is walked once, only filling the histograms; every
+//! statistic is then derived from the histograms.
+
+use image::DynamicImage;
+use serde::Serialize;
+
+/// Luma weights (Rec. 709, as used by `image`'s grayscale conversion) scaled by 10 000.
+const LUMA_WEIGHTS: [u32; 3] = [2126, 7152, 722];
+
+/// Statistics of a single 8-bit channel.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelStats {
+    pub min: u8,
+    pub max: u8,
+    pub mean: f64,
+    pub stddev: f64,
+    /// Pixel count per value, indexed by value (256 buckets).
+    pub histogram: Vec<u64>,
+}
+
+/// Channel statistics of a whole image.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageAnalysis {
+    pub width: u32,
+    pub height: u32,
+    pub red: ChannelStats,
+    pub green: ChannelStats,
+    pub blue: ChannelStats,
+    pub luma: ChannelStats,
+}
+
+/// Analyzes `image` as 8-bit RGB; alpha is ignored.
+pub fn analyze(image: &DynamicImage) -> ImageAnalysis {
+    let converted;
+    let rgb = match image.as_rgb8() {
+        Some(rgb) => rgb,
+        None => {
+            converted = image.to_rgb8();
+            &converted
+        }
+    };
+
+    let mut histograms = [[0u64; 256]; 4];
+    for pixel in rgb.as_raw().chunks_exact(3) {
+        let (r, g, b) = (pixel[0], pixel[1], pixel[2]);
+        histograms[0][r as usize] += 1;
+        histograms[1][g as usize] += 1;
+        histograms[2][b as usize] += 1;
+        let luma = (LUMA_WEIGHTS[0] * r as u32
+            + LUMA_WEIGHTS[1] * g as u32
+            + LUMA_WEIGHTS[2] * b as u32
+            + 5_000)
+            / 10_000;
+        histograms[3][luma as usize] += 1;
+    }
+
+    let [red, green, blue, luma] = histograms.map(|histogram| channel_stats(&histogram));
+    ImageAnalysis {
+        width: rgb.width(),
+        height: rgb.height(),
+        red,
+        green,
+        blue,
+        luma,
+    }
+}
+
+fn channel_stats(histogram: &[u64; 256]) -> ChannelStats {
+    let count: u64 = histogram.iter().sum();
+    let min = histogram.iter().position(|&n| n > 0).unwrap_or(0) as u8;
+    let max = histogram.iter().rposition(|&n| n > 0).unwrap_or(0) as u8;
+    let (mean, stddev) = if count == 0 {
+        (0.0, 0.0)
+    } else {
+        let total = count as f64;
+        let mean = weighted_sum(histogram, |value| value) / total;
+        let variance = weighted_sum(histogram, |value| (value - mean).powi(2)) / total;
+        (mean, variance.sqrt())
+    };
+    ChannelStats {
+        min,
+        max,
+        mean,
+        stddev,
+        histogram: histogram.to_vec(),
+    }
+}
+
+/// Sum of `f(value)` over every pixel, computed per bucket.
+fn weighted_sum(histogram: &[u64; 256], f: impl Fn(f64) -> f64) -> f64 {
+    histogram
+        .iter()
+        .enumerate()
+        .map(|(value, &n)| n as f64 * f(value as f64))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_solid_color_has_no_spread() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(20, 10, Rgb([200, 100, 50])));
+        let analysis = analyze(&image);
+
+        assert_eq!((analysis.width, analysis.height), (20, 10));
+        for (stats, value) in [
+            (&analysis.red, 200u8),
+            (&analysis.green, 100),
+            (&analysis.blue, 50),
+        ] {
+            assert_eq!((stats.min, stats.max), (value, value));
+            assert!((stats.mean - value as f64).abs() < 1e-9);
+            assert!(stats.stddev.abs() < 1e-9);
+            assert_eq!(stats.histogram.len(), 256);
+            assert_eq!(stats.histogram[value as usize], 200);
+        }
+        // 0.2126 * 200 + 0.7152 * 100 + 0.0722 * 50 = 117.64
+        assert_eq!(analysis.luma.min, 118);
+        assert!(analysis.luma.stddev.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_gradient_spreads_the_histogram() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 4, |x, _| {
+            Rgb([x as u8, x as u8, x as u8])
+        }));
+        let analysis = analyze(&image);
+
+        for stats in [
+            &analysis.red,
+            &analysis.green,
+            &analysis.blue,
+            &analysis.luma,
+        ] {
+            assert_eq!((stats.min, stats.max), (0, 255));
+            assert!(stats.histogram.iter().all(|&n| n == 4));
+            assert!((stats.mean - 127.5).abs() < 1e-9);
+            // Uniform distribution over 0..=255: sqrt((256^2 - 1) / 12)
+            assert!((stats.stddev - 73.9003).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_non_rgb_images_are_converted() {
+        let image = DynamicImage::new_luma8(3, 3);
+        let analysis = analyze(&image);
+        assert_eq!(analysis.red.histogram[0], 9);
+        assert_eq!(analysis.luma.max, 0);
+    }
+}
@@ -0,0 +1,171 @@
+//! Image comparison metrics for regression testing.
+//!
+//! Compares two images of the same size and reports their structural similarity (SSIM), the
+//! mean absolute error of every RGBA channel and the share of pixels that differ at all.
+
+use image::{DynamicImage, GenericImageView, GrayImage};
+use serde::Serialize;
+
+use crate::http::errors::AppError;
+
+/// Side of the square windows SSIM is computed over; edge windows may be smaller.
+const SSIM_WINDOW: u32 = 8;
+/// SSIM stabilizers for 8-bit data: `(0.01 * 255)^2` and `(0.03 * 255)^2`.
+const SSIM_C1: f64 = 6.5025;
+const SSIM_C2: f64 = 58.5225;
+
+/// Mean absolute difference per channel, in 0..=255.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelErrors {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+/// How different two images are.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageComparison {
+    pub width: u32,
+    pub height: u32,
+    /// Mean SSIM of the luma over 8x8 windows: 1.0 for identical images, lower is more different.
+    pub ssim: f64,
+    pub mean_absolute_error: ChannelErrors,
+    /// Share of pixels with any channel differing, in percent.
+    pub different_pixels_percent: f64,
+}
+
+/// Compares `a` with `b`, which must have the same dimensions.
+pub fn compare(a: &DynamicImage, b: &DynamicImage) -> Result<ImageComparison, AppError> {
+    if a.dimensions() != b.dimensions() {
+        let ((aw, ah), (bw, bh)) = (a.dimensions(), b.dimensions());
+        return Err(AppError::BadRequest(format!(
+            "Images must have the same dimensions to be compared, got {}x{} and {}x{}",
+            aw, ah, bw, bh
+        )));
+    }
+    let (width, height) = a.dimensions();
+    let (rgba_a, rgba_b) = (a.to_rgba8(), b.to_rgba8());
+
+    let mut totals = [0u64; 4];
+    let mut different = 0u64;
+    for (pa, pb) in rgba_a.pixels().zip(rgba_b.pixels()) {
+        let mut differs = false;
+        for (total, (&ca, &cb)) in totals.iter_mut().zip(pa.0.iter().zip(pb.0.iter())) {
+            let diff = ca.abs_diff(cb);
+            *total += diff as u64;
+            differs |= diff != 0;
+        }
+        different += differs as u64;
+    }
+    let pixels = width as u64 * height as u64;
+    let per_pixel = |total: u64| {
+        if pixels == 0 {
+            0.0
+        } else {
+            total as f64 / pixels as f64
+        }
+    };
+
+    Ok(ImageComparison {
+        width,
+        height,
+        ssim: mean_ssim(&a.to_luma8(), &b.to_luma8()),
+        mean_absolute_error: ChannelErrors {
+            red: per_pixel(totals[0]),
+            green: per_pixel(totals[1]),
+            blue: per_pixel(totals[2]),
+            alpha: per_pixel(totals[3]),
+        },
+        different_pixels_percent: per_pixel(different) * 100.0,
+    })
+}
+
+/// Mean SSIM over non-overlapping windows of two same-sized luma images.
+fn mean_ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    let (width, height) = a.dimensions();
+    let mut sum = 0.0;
+    let mut windows = 0u32;
+    for y in (0..height).step_by(SSIM_WINDOW as usize) {
+        for x in (0..width).step_by(SSIM_WINDOW as usize) {
+            let w = SSIM_WINDOW.min(width - x);
+            let h = SSIM_WINDOW.min(height - y);
+            sum += window_ssim(a, b, x, y, w, h);
+            windows += 1;
+        }
+    }
+    if windows == 0 {
+        1.0
+    } else {
+        sum / windows as f64
+    }
+}
+
+fn window_ssim(a: &GrayImage, b: &GrayImage, x: u32, y: u32, w: u32, h: u32) -> f64 {
+    let values = || {
+        (y..y + h).flat_map(move |py| {
+            (x..x + w).map(move |px| (a.get_pixel(px, py)[0] as f64, b.get_pixel(px, py)[0] as f64))
+        })
+    };
+    let n = (w * h) as f64;
+    let (sum_a, sum_b) = values().fold((0.0, 0.0), |(sa, sb), (va, vb)| (sa + va, sb + vb));
+    let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+    let (var_a, var_b, covariance) =
+        values().fold((0.0, 0.0, 0.0), |(var_a, var_b, cov), (va, vb)| {
+            let (da, db) = (va - mean_a, vb - mean_b);
+            (var_a + da * da, var_b + db * db, cov + da * db)
+        });
+    let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+
+    ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+        / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (var_a + var_b + SSIM_C2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            Rgb([(x * 8) as u8, (y * 8) as u8, ((x + y) * 4) as u8])
+        }))
+    }
+
+    #[test]
+    fn test_image_compared_with_itself_has_no_difference() {
+        let image = gradient(20, 12);
+        let comparison = compare(&image, &image).unwrap();
+        assert_eq!(comparison.ssim, 1.0);
+        assert_eq!(
+            comparison.mean_absolute_error,
+            ChannelErrors {
+                red: 0.0,
+                green: 0.0,
+                blue: 0.0,
+                alpha: 0.0,
+            }
+        );
+        assert_eq!(comparison.different_pixels_percent, 0.0);
+    }
+
+    #[test]
+    fn test_brightened_image_has_nonzero_error() {
+        let image = gradient(20, 12);
+        let brightened = image.brighten(10);
+        let comparison = compare(&image, &brightened).unwrap();
+        assert!(comparison.mean_absolute_error.red > 0.0);
+        assert!(comparison.mean_absolute_error.green > 0.0);
+        assert_eq!(comparison.mean_absolute_error.alpha, 0.0);
+        assert!(comparison.different_pixels_percent > 0.0);
+        assert!(comparison.ssim < 1.0);
+    }
+
+    #[test]
+    fn test_mismatched_dimensions_are_rejected() {
+        assert!(matches!(
+            compare(&gradient(20, 12), &gradient(12, 20)),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,356 @@
+//! Image format support matrix.
+//!
+//! Which formats can be decoded or encoded depends on the `image` crate features the binary
+//! was built with (e.g. AVIF needs `avif`). The matrix is probed once, at startup, so that
+//! unsupported formats are rejected with a clear message before any processing starts and
+//! so that `/capabilities` can report what this build handles.
+
+use crate::http::errors::AppError;
+use crate::image::heif;
+use image::error::UnsupportedErrorKind;
+use image::{DynamicImage, ImageFormat};
+use serde::Serialize;
+use std::io::Cursor;
+use std::sync::OnceLock;
+
+/// Formats probed for support.
+const CANDIDATE_FORMATS: [ImageFormat; 15] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+    ImageFormat::Pnm,
+    ImageFormat::Tiff,
+    ImageFormat::Tga,
+    ImageFormat::Dds,
+    ImageFormat::Bmp,
+    ImageFormat::Ico,
+    ImageFormat::Hdr,
+    ImageFormat::OpenExr,
+    ImageFormat::Farbfeld,
+    ImageFormat::Avif,
+    ImageFormat::Qoi,
+];
+
+static FORMAT_SUPPORT: OnceLock<FormatSupport> = OnceLock::new();
+
+/// Decode/encode support of a single format.
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatCapability {
+    #[serde(serialize_with = "serialize_format")]
+    pub format: ImageFormat,
+    pub mime_type: &'static str,
+    pub decode: bool,
+    pub encode: bool,
+}
+
+/// Decode/encode support for every probed format.
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatSupport {
+    formats: Vec<FormatCapability>,
+}
+
+impl FormatSupport {
+    /// Probes the formats compiled into this binary.
+    ///
+    /// Encoding is verified by actually encoding a 1x1 image, since some encoders only accept
+    /// certain color types. Decoding is verified with [`probe_decoder`]: `ImageFormat::can_read`
+    /// is not feature-aware and claims AVIF even without the `avif-decoder` feature.
+    pub fn probe() -> Self {
+        let formats = CANDIDATE_FORMATS
+            .iter()
+            .map(|&format| FormatCapability {
+                format,
+                mime_type: format.to_mime_type(),
+                decode: probe_decoder(format),
+                encode: format.can_write() && probe_encode(format),
+            })
+            .collect();
+        Self { formats }
+    }
+
+    pub fn formats(&self) -> &[FormatCapability] {
+        &self.formats
+    }
+
+    pub fn can_decode(&self, format: ImageFormat) -> bool {
+        self.find(format).is_some_and(|c| c.decode)
+    }
+
+    pub fn can_encode(&self, format: ImageFormat) -> bool {
+        self.find(format).is_some_and(|c| c.encode)
+    }
+
+    /// Fails with `UnsupportedMediaType` if `input` cannot be decoded or `output` cannot be
+    /// encoded by this build.
+    pub fn ensure_supported(
+        &self,
+        input: ImageFormat,
+        output: ImageFormat,
+    ) -> Result<(), AppError> {
+        if !self.can_decode(input) {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "Decoding {} is not supported by this build",
+                format_name(input)
+            )));
+        }
+        if !self.can_encode(output) {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "Encoding {} is not supported by this build",
+                format_name(output)
+            )));
+        }
+        Ok(())
+    }
+
+    fn find(&self, format: ImageFormat) -> Option<&FormatCapability> {
+        self.formats.iter().find(|c| c.format == format)
+    }
+}
+
+/// Returns the process-wide support matrix, probing it on first use.
+pub fn format_support() -> &'static FormatSupport {
+    FORMAT_SUPPORT.get_or_init(FormatSupport::probe)
+}
+
+/// Lowercase format name, e.g. `png`, `openexr`.
+pub fn format_name(format: ImageFormat) -> String {
+    format!("{:?}", format).to_lowercase()
+}
+
+/// Detects the format of `bytes` and checks it against `allowed` before anything is decoded.
+///
+/// `allowed` holds file extensions (`png`, `jpg`, `tif`, ...); an empty list accepts any
+/// format.
+pub fn detect_input_format(bytes: &[u8], allowed: &[String]) -> Result<ImageFormat, AppError> {
+    let format = image::guess_format(bytes).map_err(|_| {
+        AppError::UnsupportedMediaType("Could not determine image format".to_string())
+    })?;
+    if !allowed.is_empty()
+        && !allowed
+            .iter()
+            .any(|name| ImageFormat::from_extension(name) == Some(format))
+    {
+        return Err(AppError::UnsupportedMediaType(format!(
+            "Input format {} is not allowed",
+            format_name(format)
+        )));
+    }
+    Ok(format)
+}
+
+/// Checks the `Content-Type` an upload declared against the format of its bytes.
+///
+/// Uploads without a type or declared as `application/octet-stream` pass; any other type must
+/// name the detected format, so e.g. a PNG/HTML polyglot uploaded as `text/html`, or a JPEG
+/// labelled `image/png`, is rejected.
+pub fn check_declared_type(declared: Option<&str>, bytes: &[u8]) -> Result<(), AppError> {
+    let Some(declared) = declared else {
+        return Ok(());
+    };
+    let mime = declared
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let matches = match mime.as_str() {
+        "" | "application/octet-stream" => true,
+        "image/heic" | "image/heif" => heif::is_heif(bytes),
+        _ => ImageFormat::from_mime_type(&mime)
+            .is_some_and(|format| image::guess_format(bytes).ok() == Some(format)),
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(AppError::UnsupportedMediaType(format!(
+            "Declared Content-Type {} does not match the uploaded image data",
+            mime
+        )))
+    }
+}
+
+/// Maps a failure to read or decode an input of `format` (as detected from its header) to an
+/// `AppError` saying what went wrong.
+///
+/// Features this build cannot decode are `UnsupportedMediaType`, decoder limits are
+/// `PayloadTooLarge`, and everything else is an `ImageProcessingError` that tells a truncated
+/// file (the data ends early) apart from a corrupt one.
+pub fn decode_error(format: ImageFormat, error: image::ImageError) -> AppError {
+    let name = format_name(format);
+    match error {
+        image::ImageError::Unsupported(e) => AppError::UnsupportedMediaType(format!(
+            "Detected {} image, but it cannot be decoded: {}",
+            name, e
+        )),
+        image::ImageError::Limits(e) => AppError::PayloadTooLarge(format!(
+            "Detected {} image exceeds decoder limits: {}",
+            name, e
+        )),
+        e if is_truncation(&e) => {
+            AppError::ImageProcessingError(format!("Detected {} image is truncated: {}", name, e))
+        }
+        e => AppError::ImageProcessingError(format!("Detected {} image is corrupt: {}", name, e)),
+    }
+}
+
+/// Whether the decoder ran out of data, as opposed to finding invalid data.
+///
+/// Decoders report this either as an I/O `UnexpectedEof` or as a format error whose message
+/// mentions the end of the data, depending on the codec.
+fn is_truncation(error: &image::ImageError) -> bool {
+    match error {
+        image::ImageError::IoError(e) => e.kind() == std::io::ErrorKind::UnexpectedEof,
+        image::ImageError::Decoding(e) => {
+            let message = e.to_string().to_lowercase();
+            ["end of", "eof", "truncated", "not enough data"]
+                .iter()
+                .any(|hint| message.contains(hint))
+        }
+        _ => false,
+    }
+}
+
+fn serialize_format<S: serde::Serializer>(
+    format: &ImageFormat,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_name(*format))
+}
+
+fn probe_encode(format: ImageFormat) -> bool {
+    let samples = [
+        DynamicImage::new_rgb8(1, 1),
+        DynamicImage::new_rgba8(1, 1),
+        DynamicImage::new_rgb32f(1, 1),
+    ];
+    samples.iter().any(|sample| {
+        sample
+            .write_to(&mut Cursor::new(Vec::new()), format)
+            .is_ok()
+    })
+}
+
+/// Whether a decoder for `format` is compiled in.
+///
+/// Decoding empty data fails either way, but only a missing decoder reports the format itself
+/// as unsupported.
+fn probe_decoder(format: ImageFormat) -> bool {
+    !matches!(
+        image::load_from_memory_with_format(&[], format),
+        Err(image::ImageError::Unsupported(e))
+            if matches!(e.kind(), UnsupportedErrorKind::Format(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_reflects_compiled_features() {
+        let support = FormatSupport::probe();
+        // Enabled by the image crate's default features
+        assert!(support.can_decode(ImageFormat::Png));
+        assert!(support.can_encode(ImageFormat::Png));
+        assert!(support.can_decode(ImageFormat::Jpeg));
+        assert!(support.can_encode(ImageFormat::Jpeg));
+        // AVIF requires the non-default `avif` features
+        assert!(!support.can_decode(ImageFormat::Avif));
+        assert!(!support.can_encode(ImageFormat::Avif));
+    }
+
+    #[test]
+    fn test_ensure_supported_rejects_unavailable_formats() {
+        let support = FormatSupport::probe();
+        assert!(support
+            .ensure_supported(ImageFormat::Png, ImageFormat::Jpeg)
+            .is_ok());
+        match support.ensure_supported(ImageFormat::Png, ImageFormat::Avif) {
+            Err(AppError::UnsupportedMediaType(msg)) => {
+                assert_eq!(msg, "Encoding avif is not supported by this build")
+            }
+            other => panic!("Expected UnsupportedMediaType, got {:?}", other),
+        }
+        assert!(support
+            .ensure_supported(ImageFormat::Avif, ImageFormat::Png)
+            .is_err());
+    }
+
+    #[test]
+    fn test_detect_input_format_applies_allowlist() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(2, 2)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let mut ppm = b"P6\n2 2\n255\n".to_vec();
+        ppm.extend_from_slice(&[0; 12]);
+        let allowed = vec!["png".to_string(), "jpg".to_string()];
+
+        assert_eq!(
+            detect_input_format(&png, &allowed).unwrap(),
+            ImageFormat::Png
+        );
+        match detect_input_format(&ppm, &allowed) {
+            Err(AppError::UnsupportedMediaType(msg)) => {
+                assert_eq!(msg, "Input format pnm is not allowed")
+            }
+            other => panic!("Expected UnsupportedMediaType, got {:?}", other),
+        }
+        assert!(detect_input_format(&png, &[]).is_ok());
+    }
+
+    #[test]
+    fn test_decode_error_classifies_failures() {
+        let unsupported =
+            image::ImageError::Unsupported(image::error::UnsupportedError::from_format_and_kind(
+                image::error::ImageFormatHint::Exact(ImageFormat::Tiff),
+                image::error::UnsupportedErrorKind::GenericFeature("JPEG compression".into()),
+            ));
+        assert!(matches!(
+            decode_error(ImageFormat::Tiff, unsupported),
+            AppError::UnsupportedMediaType(msg) if msg.starts_with("Detected tiff image")
+        ));
+
+        let eof = image::ImageError::IoError(std::io::ErrorKind::UnexpectedEof.into());
+        assert!(matches!(
+            decode_error(ImageFormat::Png, eof),
+            AppError::ImageProcessingError(msg) if msg.starts_with("Detected png image is truncated")
+        ));
+
+        let invalid = image::ImageError::Decoding(image::error::DecodingError::new(
+            image::error::ImageFormatHint::Exact(ImageFormat::Png),
+            "invalid chunk type",
+        ));
+        assert!(matches!(
+            decode_error(ImageFormat::Png, invalid),
+            AppError::ImageProcessingError(msg) if msg.starts_with("Detected png image is corrupt")
+        ));
+    }
+
+    #[test]
+    fn test_declared_type_must_match_content() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(1, 1)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        for declared in [None, Some("image/png"), Some("IMAGE/PNG; charset=binary")] {
+            assert!(
+                check_declared_type(declared, &png).is_ok(),
+                "{:?}",
+                declared
+            );
+        }
+        assert!(check_declared_type(Some("application/octet-stream"), &png).is_ok());
+        for declared in ["image/jpeg", "text/html", "image/heic", "nonsense"] {
+            assert!(
+                matches!(
+                    check_declared_type(Some(declared), &png),
+                    Err(AppError::UnsupportedMediaType(_))
+                ),
+                "{}",
+                declared
+            );
+        }
+    }
+}
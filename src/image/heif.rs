@@ -0,0 +1,193 @@
+//! HEIC/HEIF input support.
+//!
+//! The `image` crate can't read HEIF (the format iPhones save photos in), so with the `heif`
+//! feature uploads are decoded with libheif and handed to the rest of the pipeline as lossless
+//! PNG. HEIF is never an output format.
+
+use crate::http::errors::AppError;
+use crate::image::limits::ImageLimits;
+
+/// Major brands of HEIF files, found after the `ftyp` box header at offset 4.
+const HEIF_BRANDS: [&[u8; 4]; 6] = [b"heic", b"heix", b"heim", b"heis", b"mif1", b"msf1"];
+
+/// Whether `bytes` start like a HEIF file, judged by the `ftyp` box.
+pub fn is_heif(bytes: &[u8]) -> bool {
+    bytes.len() >= 12
+        && &bytes[4..8] == b"ftyp"
+        && HEIF_BRANDS.iter().any(|brand| &bytes[8..12] == *brand)
+}
+
+/// Whether `allowed_input_formats` lets HEIF through: `heic` or `heif` must be listed, or the
+/// list must be empty.
+pub fn is_allowed(allowed: &[String]) -> bool {
+    allowed.is_empty()
+        || allowed
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case("heic") || name.eq_ignore_ascii_case("heif"))
+}
+
+/// Decodes the primary image of a HEIF file and re-encodes it as PNG, so it can go through the
+/// regular decode path. The declared size is checked against `limits` before decoding.
+#[cfg(feature = "heif")]
+pub fn transcode_to_png(bytes: &[u8], limits: &ImageLimits) -> Result<Vec<u8>, AppError> {
+    use crate::image::operations::format::encode_image;
+    use image::ImageFormat;
+
+    let image = decode(bytes, limits)?;
+    encode_image(&image, ImageFormat::Png, None)
+}
+
+#[cfg(not(feature = "heif"))]
+pub fn transcode_to_png(_bytes: &[u8], _limits: &ImageLimits) -> Result<Vec<u8>, AppError> {
+    Err(AppError::UnsupportedMediaType(
+        "Decoding heif is not supported by this build".to_string(),
+    ))
+}
+
+#[cfg(feature = "heif")]
+fn decode(bytes: &[u8], limits: &ImageLimits) -> Result<image::DynamicImage, AppError> {
+    use image::{DynamicImage, RgbImage, RgbaImage};
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let corrupt = |e: libheif_rs::HeifError| {
+        AppError::ImageProcessingError(format!("Detected heif image is corrupt: {}", e))
+    };
+    let context = HeifContext::read_from_bytes(bytes).map_err(corrupt)?;
+    let handle = context.primary_image_handle().map_err(corrupt)?;
+    let (width, height) = (handle.width(), handle.height());
+    limits.check_input((width, height))?;
+
+    let has_alpha = handle.has_alpha_channel();
+    let chroma = if has_alpha {
+        RgbChroma::Rgba
+    } else {
+        RgbChroma::Rgb
+    };
+    // libheif applies the rotation and mirroring stored in the file while decoding
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .map_err(corrupt)?;
+    let planes = decoded.planes();
+    let plane = planes.interleaved.ok_or_else(|| {
+        AppError::ImageProcessingError("Decoded heif image has no interleaved plane".to_string())
+    })?;
+
+    // Rows may be padded; copy them into a tightly packed buffer
+    let channels = if has_alpha { 4 } else { 3 };
+    let row_len = plane.width as usize * channels;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    let invalid =
+        || AppError::ImageProcessingError("Decoded heif image has an invalid size".to_string());
+    Ok(if has_alpha {
+        DynamicImage::ImageRgba8(
+            RgbaImage::from_raw(plane.width, plane.height, pixels).ok_or_else(invalid)?,
+        )
+    } else {
+        DynamicImage::ImageRgb8(
+            RgbImage::from_raw(plane.width, plane.height, pixels).ok_or_else(invalid)?,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heif_is_detected_by_brand() {
+        let mut header = vec![0, 0, 0, 24];
+        header.extend_from_slice(b"ftypheic");
+        header.extend_from_slice(&[0; 12]);
+        assert!(is_heif(&header));
+
+        header[8..12].copy_from_slice(b"avif");
+        assert!(!is_heif(&header));
+        assert!(!is_heif(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[test]
+    fn test_heif_must_be_allowed_explicitly() {
+        assert!(is_allowed(&[]));
+        assert!(is_allowed(&["png".to_string(), "HEIC".to_string()]));
+        assert!(!is_allowed(&["png".to_string(), "jpeg".to_string()]));
+    }
+
+    #[cfg(not(feature = "heif"))]
+    #[test]
+    fn test_heif_is_unsupported_without_feature() {
+        assert!(matches!(
+            transcode_to_png(b"....ftypheic", &ImageLimits::default()),
+            Err(AppError::UnsupportedMediaType(_))
+        ));
+    }
+
+    /// Encodes a small HEIC fixture with libheif's HEVC encoder.
+    #[cfg(feature = "heif")]
+    fn heic_fixture(width: u32, height: u32) -> Vec<u8> {
+        use libheif_rs::{
+            Channel, ColorSpace, CompressionFormat, EncoderQuality, HeifContext, Image, LibHeif,
+            RgbChroma,
+        };
+
+        let lib_heif = LibHeif::new();
+        let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::Rgb)).unwrap();
+        image
+            .create_plane(Channel::Interleaved, width, height, 8)
+            .unwrap();
+        let plane = image.planes_mut().interleaved.unwrap();
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let offset = y * plane.stride + x * 3;
+                plane.data[offset..offset + 3].copy_from_slice(&[200, 40, 40]);
+            }
+        }
+        let mut context = HeifContext::new().unwrap();
+        let mut encoder = lib_heif
+            .encoder_for_format(CompressionFormat::Hevc)
+            .unwrap();
+        encoder.set_quality(EncoderQuality::LossLess).unwrap();
+        context.encode_image(&image, &mut encoder, None).unwrap();
+        context.write_to_bytes().unwrap()
+    }
+
+    #[cfg(feature = "heif")]
+    #[test]
+    fn test_heic_is_decoded_and_resized() {
+        use crate::image::operations::resize;
+        use crate::image::params::ResizeParams;
+        use image::GenericImageView;
+
+        let heic = heic_fixture(64, 48);
+        assert!(is_heif(&heic));
+
+        let png = transcode_to_png(&heic, &ImageLimits::default()).unwrap();
+        let decoded = image::load_from_memory_with_format(&png, image::ImageFormat::Png).unwrap();
+        assert_eq!(decoded.dimensions(), (64, 48));
+        let resized = resize(
+            decoded,
+            &ResizeParams {
+                width: 32,
+                height: 24,
+            },
+        );
+        assert_eq!(resized.dimensions(), (32, 24));
+        let [r, g, b, _] = resized.to_rgba8().get_pixel(16, 12).0;
+        assert!(
+            r > 150 && g < 90 && b < 90,
+            "unexpected color {:?}",
+            (r, g, b)
+        );
+
+        let limits = ImageLimits {
+            max_input_pixels: 100,
+            ..Default::default()
+        };
+        assert!(matches!(
+            transcode_to_png(&heic, &limits),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,341 @@
+//! ICC color profile preservation.
+//!
+//! Decoding to a `DynamicImage` drops the embedded ICC profile, and the `image` crate's
+//! encoders cannot write one. The profile is therefore read from the original bytes and
+//! spliced into the encoded output: an `iCCP` chunk for PNG, `APP2` segments for JPEG and an
+//! `ICCP` chunk (in an extended `VP8X` container) for WebP.
+//!
+//! A profile is only embedded when its color space matches the output: a pipeline that turns
+//! an RGB image gray (e.g. `grayscale`) drops an RGB profile instead of writing an invalid file.
+
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{ImageDecoder, ImageFormat};
+use std::io::Cursor;
+
+pub(super) const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+/// Profile bytes per JPEG APP2 segment: 65535 minus the length field, marker and sequence bytes.
+const JPEG_ICC_CHUNK_SIZE: usize = 65535 - 2 - JPEG_ICC_MARKER.len() - 2;
+
+/// Color model of image data or of an ICC profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorModel {
+    Gray,
+    Rgb,
+}
+
+/// Reads the ICC profile embedded in an encoded image, if the format supports one.
+pub fn read_icc_profile(bytes: &[u8], format: ImageFormat) -> Option<Vec<u8>> {
+    let cursor = Cursor::new(bytes);
+    let profile = match format {
+        ImageFormat::Png => PngDecoder::new(cursor).ok()?.icc_profile(),
+        ImageFormat::Jpeg => JpegDecoder::new(cursor).ok()?.icc_profile(),
+        ImageFormat::WebP => WebPDecoder::new(cursor).ok()?.icc_profile(),
+        _ => None,
+    };
+    profile.filter(|profile| !profile.is_empty())
+}
+
+/// Bytes [`embed_icc_profile`] adds to a JPEG when embedding `profile`.
+pub fn jpeg_embedding_size(profile: &[u8]) -> usize {
+    let segments = profile.len().div_ceil(JPEG_ICC_CHUNK_SIZE);
+    profile.len() + segments * (2 + 2 + JPEG_ICC_MARKER.len() + 2)
+}
+
+/// Embeds `profile` into an encoded PNG, JPEG or WebP image.
+///
+/// Returns `encoded` unchanged for other formats, for output the splicer does not recognise, or
+/// when the profile's color space does not match the image's color model.
+pub fn embed_icc_profile(encoded: Vec<u8>, format: ImageFormat, profile: &[u8]) -> Vec<u8> {
+    let output_model = match format {
+        ImageFormat::Png => png_color_model(&encoded),
+        ImageFormat::Jpeg => jpeg_color_model(&encoded),
+        // Both VP8 and VP8L store color images
+        ImageFormat::WebP => Some(ColorModel::Rgb),
+        _ => None,
+    };
+    let Some(output_model) = output_model else {
+        return encoded;
+    };
+    if profile_color_model(profile) != Some(output_model) {
+        tracing::debug!(
+            ?output_model,
+            "Dropping ICC profile that no longer matches the image"
+        );
+        return encoded;
+    }
+
+    let embedded = match format {
+        ImageFormat::Png => embed_png(&encoded, profile),
+        ImageFormat::Jpeg => embed_jpeg(&encoded, profile),
+        ImageFormat::WebP => embed_webp(&encoded, profile),
+        _ => None,
+    };
+    embedded.unwrap_or(encoded)
+}
+
+/// Color space from the profile header (`RGB ` or `GRAY` at offset 16).
+fn profile_color_model(profile: &[u8]) -> Option<ColorModel> {
+    match profile.get(16..20)? {
+        b"RGB " => Some(ColorModel::Rgb),
+        b"GRAY" => Some(ColorModel::Gray),
+        _ => None,
+    }
+}
+
+/// Color model from the PNG `IHDR` color type (0 and 4 are grayscale).
+fn png_color_model(png: &[u8]) -> Option<ColorModel> {
+    if !png.starts_with(PNG_SIGNATURE) || png.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    match png.get(25)? {
+        0 | 4 => Some(ColorModel::Gray),
+        2 | 3 | 6 => Some(ColorModel::Rgb),
+        _ => None,
+    }
+}
+
+/// Color model from the component count of the first JPEG start-of-frame segment.
+fn jpeg_color_model(jpeg: &[u8]) -> Option<ColorModel> {
+    let mut segments = jpeg_segments(jpeg)?;
+    let (_, frame) = segments.find(|(marker, _)| (0xC0..=0xC2).contains(marker))?;
+    match frame.get(7)? {
+        1 => Some(ColorModel::Gray),
+        3 => Some(ColorModel::Rgb),
+        _ => None,
+    }
+}
+
+/// Inserts an `iCCP` chunk right after `IHDR`, as the PNG spec requires it before `PLTE`/`IDAT`.
+fn embed_png(png: &[u8], profile: &[u8]) -> Option<Vec<u8>> {
+    // Signature (8) + IHDR length/type/data/crc (4 + 4 + 13 + 4)
+    let ihdr_end = PNG_SIGNATURE.len() + 25;
+    if png.len() < ihdr_end {
+        return None;
+    }
+    let mut data = b"ICC Profile\0\0".to_vec(); // name, separator, compression method 0
+    data.extend_from_slice(&zlib_stored(profile));
+
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(b"iCCP");
+    chunk.extend_from_slice(&data);
+    chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+
+    let mut output = Vec::with_capacity(png.len() + chunk.len());
+    output.extend_from_slice(&png[..ihdr_end]);
+    output.extend_from_slice(&chunk);
+    output.extend_from_slice(&png[ihdr_end..]);
+    Some(output)
+}
+
+/// Inserts `APP2` ICC segments after `SOI` and, if present, the `APP0` JFIF header.
+fn embed_jpeg(jpeg: &[u8], profile: &[u8]) -> Option<Vec<u8>> {
+    let mut insert_at = 2;
+    if let Some((0xE0, app0)) = jpeg_segments(jpeg)?.next() {
+        insert_at += 2 + app0.len();
+    }
+    let chunks: Vec<_> = profile.chunks(JPEG_ICC_CHUNK_SIZE).collect();
+    if chunks.len() > u8::MAX as usize {
+        return None;
+    }
+
+    let mut output = Vec::with_capacity(jpeg.len() + profile.len() + chunks.len() * 18);
+    output.extend_from_slice(&jpeg[..insert_at]);
+    for (index, chunk) in chunks.iter().enumerate() {
+        let length = 2 + JPEG_ICC_MARKER.len() + 2 + chunk.len();
+        output.extend_from_slice(&[0xFF, 0xE2]);
+        output.extend_from_slice(&(length as u16).to_be_bytes());
+        output.extend_from_slice(JPEG_ICC_MARKER);
+        output.extend_from_slice(&[index as u8 + 1, chunks.len() as u8]);
+        output.extend_from_slice(chunk);
+    }
+    output.extend_from_slice(&jpeg[insert_at..]);
+    Some(output)
+}
+
+/// Iterates over JPEG marker segments up to start-of-scan, yielding each marker and its
+/// segment (starting with the length field).
+pub(super) fn jpeg_segments(jpeg: &[u8]) -> Option<impl Iterator<Item = (u8, &[u8])>> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut position = 2;
+    Some(std::iter::from_fn(move || {
+        if jpeg.get(position) != Some(&0xFF) {
+            return None;
+        }
+        let marker = *jpeg.get(position + 1)?;
+        if marker == 0xDA {
+            return None;
+        }
+        let length = u16::from_be_bytes([*jpeg.get(position + 2)?, *jpeg.get(position + 3)?]);
+        let segment = jpeg.get(position + 2..position + 2 + length as usize)?;
+        position += 2 + length as usize;
+        Some((marker, segment))
+    }))
+}
+
+/// Wraps a simple-format WebP (`VP8 ` or `VP8L`) in a `VP8X` container with an `ICCP` chunk.
+fn embed_webp(webp: &[u8], profile: &[u8]) -> Option<Vec<u8>> {
+    if webp.get(0..4)? != b"RIFF" || webp.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let image_chunk = webp.get(12..)?;
+    let (width, height, alpha) = match image_chunk.get(0..4)? {
+        b"VP8L" => {
+            // Signature byte, then 14-bit width - 1, 14-bit height - 1 and the alpha bit
+            let bits = u32::from_le_bytes(image_chunk.get(9..13)?.try_into().ok()?);
+            (
+                (bits & 0x3FFF) + 1,
+                ((bits >> 14) & 0x3FFF) + 1,
+                bits & (1 << 28) != 0,
+            )
+        }
+        b"VP8 " => {
+            // Frame tag (3 bytes) and start code (3 bytes) precede the 14-bit dimensions
+            let dimension = |offset: usize| -> Option<u32> {
+                let raw = image_chunk.get(offset..offset + 2)?;
+                Some(u16::from_le_bytes([raw[0], raw[1]]) as u32 & 0x3FFF)
+            };
+            (dimension(14)?, dimension(16)?, false)
+        }
+        _ => return None,
+    };
+
+    let mut vp8x = vec![if alpha { 0x30 } else { 0x20 }, 0, 0, 0];
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+    let mut body = b"WEBP".to_vec();
+    push_riff_chunk(&mut body, b"VP8X", &vp8x);
+    push_riff_chunk(&mut body, b"ICCP", profile);
+    body.extend_from_slice(image_chunk);
+
+    let mut output = b"RIFF".to_vec();
+    output.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    output.extend_from_slice(&body);
+    Some(output)
+}
+
+fn push_riff_chunk(output: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(fourcc);
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    output.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+/// zlib stream of uncompressed ("stored") deflate blocks.
+///
+/// Profiles are small, so skipping compression avoids a deflate dependency at little cost.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        output.push(blocks.peek().is_none() as u8);
+        let length = block.len() as u16;
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::DynamicImage;
+
+    /// Minimal profile: only the header fields read here and by decoders are meaningful.
+    fn test_profile(color_space: &[u8; 4]) -> Vec<u8> {
+        let mut profile = vec![0u8; 200];
+        profile[0..4].copy_from_slice(&200u32.to_be_bytes());
+        profile[12..16].copy_from_slice(b"mntr");
+        profile[16..20].copy_from_slice(color_space);
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile[36..40].copy_from_slice(b"acsp");
+        for (i, byte) in profile[128..].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        profile
+    }
+
+    fn encode(image: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_profile_round_trips_through_png_and_jpeg() {
+        let profile = test_profile(b"RGB ");
+        let image = DynamicImage::new_rgb8(16, 8);
+        for format in [ImageFormat::Png, ImageFormat::Jpeg] {
+            let embedded = embed_icc_profile(encode(&image, format), format, &profile);
+            assert_eq!(read_icc_profile(&embedded, format), Some(profile.clone()));
+            // The image itself still decodes
+            let decoded = image::load_from_memory_with_format(&embedded, format).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (16, 8));
+        }
+    }
+
+    #[test]
+    fn test_webp_gets_an_extended_container() {
+        let profile = test_profile(b"RGB ");
+        let webp = encode(&DynamicImage::new_rgba8(5, 3), ImageFormat::WebP);
+        let embedded = embed_icc_profile(webp, ImageFormat::WebP, &profile);
+        assert_eq!(&embedded[12..16], b"VP8X");
+        assert_eq!(&embedded[30..34], b"ICCP");
+        // Canvas size is stored minus one, in 24 bits
+        assert_eq!(&embedded[24..30], &[4, 0, 0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn test_mismatched_color_model_drops_the_profile() {
+        let rgb_profile = test_profile(b"RGB ");
+        let gray_png = encode(&DynamicImage::new_luma8(4, 4), ImageFormat::Png);
+        let output = embed_icc_profile(gray_png.clone(), ImageFormat::Png, &rgb_profile);
+        assert_eq!(output, gray_png);
+
+        let gray_profile = test_profile(b"GRAY");
+        let output = embed_icc_profile(gray_png, ImageFormat::Png, &gray_profile);
+        assert_eq!(
+            read_icc_profile(&output, ImageFormat::Png),
+            Some(gray_profile)
+        );
+    }
+}
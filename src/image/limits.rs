@@ -0,0 +1,433 @@
+//! Image size limits.
+//!
+//! Guards against decompression bombs (small files declaring huge dimensions) and against
+//! pipelines that blow a small input up into a huge output (e.g. `resize` to 30000x30000).
+//! Both checks run on declared/projected dimensions, before any pixels are allocated.
+//! The same projection gives dry runs their output size when it doesn't depend on the pixels.
+
+use crate::http::errors::AppError;
+use crate::image::params::{
+    BorderParams, CropParams, ExtractParams, ResizeParams, ResizePercentParams, RotateParams,
+    SmartCropParams, ThumbnailMode, ThumbnailParams, ZoomParams,
+};
+use crate::image::pipeline_executor::validate_operation;
+use crate::image::pipeline_types::{PipelineOperationSpec, SupportedOperation};
+use crate::server::ServerConfig;
+use serde::de::DeserializeOwned;
+
+/// Size limits for one request; a limit of 0 disables that check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImageLimits {
+    pub max_input_pixels: u64,
+    pub max_output_pixels: u64,
+    pub max_output_dimension: u32,
+}
+
+impl ImageLimits {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            max_input_pixels: config.max_input_pixels,
+            max_output_pixels: config.max_output_pixels,
+            max_output_dimension: config.max_output_dimension,
+        }
+    }
+
+    /// Rejects pipelines with more than `max_operations` operations (0 = unlimited).
+    pub fn check_operation_count(
+        operations_spec: &[PipelineOperationSpec],
+        max_operations: usize,
+    ) -> Result<(), AppError> {
+        if max_operations > 0 && operations_spec.len() > max_operations {
+            return Err(AppError::BadRequest(format!(
+                "Pipeline has {} operations, exceeding the limit of {}",
+                operations_spec.len(),
+                max_operations
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rejects inputs whose declared dimensions exceed `max_input_pixels`.
+    pub fn check_input(&self, (width, height): (u32, u32)) -> Result<(), AppError> {
+        let pixels = width as u64 * height as u64;
+        if self.max_input_pixels > 0 && pixels > self.max_input_pixels {
+            return Err(AppError::BadRequest(format!(
+                "Input image of {}x{} exceeds the limit of {} pixels",
+                width, height, self.max_input_pixels
+            )));
+        }
+        Ok(())
+    }
+
+    /// Projects the image size through the pipeline and rejects it as soon as an operation
+    /// would produce an image over `max_output_pixels` or `max_output_dimension`.
+    ///
+    /// Operations with `ignore_failure` are checked too, since they would still allocate their
+    /// output before anything could skip them.
+    pub fn check_pipeline(
+        &self,
+        input: (u32, u32),
+        operations_spec: &[PipelineOperationSpec],
+    ) -> Result<(), AppError> {
+        let mut dimensions = input;
+        for spec in operations_spec {
+            if spec.region.is_some() {
+                // Region operations must preserve the region's size
+                continue;
+            }
+            dimensions = projected_dimensions(dimensions, spec);
+            self.check_output(spec.operation, dimensions)?;
+        }
+        Ok(())
+    }
+
+    fn check_output(
+        &self,
+        operation: SupportedOperation,
+        (width, height): (u32, u32),
+    ) -> Result<(), AppError> {
+        if self.max_output_dimension > 0 && width.max(height) > self.max_output_dimension {
+            return Err(AppError::BadRequest(format!(
+                "Operation {} would produce a {}x{} image, exceeding the maximum dimension of {}",
+                operation, width, height, self.max_output_dimension
+            )));
+        }
+        let pixels = width as u64 * height as u64;
+        if self.max_output_pixels > 0 && pixels > self.max_output_pixels {
+            return Err(AppError::BadRequest(format!(
+                "Operation {} would produce a {}x{} image, exceeding the limit of {} pixels",
+                operation, width, height, self.max_output_pixels
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Dimensions after applying `spec` to an image of `dimensions`.
+///
+/// Mirrors the size behavior of the operations in `operations::transform` and `border`.
+/// Parameters that fail to parse leave the size unchanged; the executor reports them.
+fn projected_dimensions((width, height): (u32, u32), spec: &PipelineOperationSpec) -> (u32, u32) {
+    match spec.operation {
+        SupportedOperation::Resize => parse::<ResizeParams>(spec)
+            .map_or((width, height), |params| (params.width, params.height)),
+        SupportedOperation::Enlarge => {
+            parse::<ResizeParams>(spec).map_or((width, height), |params| {
+                // A smaller target is rejected by the executor
+                if params.width >= width && params.height >= height {
+                    fit_within((width, height), (params.width, params.height))
+                } else {
+                    (width, height)
+                }
+            })
+        }
+        SupportedOperation::Thumbnail => {
+            parse::<ThumbnailParams>(spec).map_or((width, height), |params| match params.mode {
+                ThumbnailMode::Fit => fit_within((width, height), (params.width, params.height)),
+                ThumbnailMode::Cover => (params.width, params.height),
+            })
+        }
+        SupportedOperation::ResizePercent => parse::<ResizePercentParams>(spec)
+            .map_or((width, height), |params| {
+                params.target_dimensions((width, height))
+            }),
+        SupportedOperation::Zoom => parse::<ZoomParams>(spec).map_or((width, height), |params| {
+            let zoomed = |side: u32| (side as f64 * params.factor as f64).round().max(1.0);
+            (
+                zoomed(width).min(u32::MAX as f64) as u32,
+                zoomed(height).min(u32::MAX as f64) as u32,
+            )
+        }),
+        SupportedOperation::Rotate => {
+            parse::<RotateParams>(spec).map_or((width, height), |params| {
+                if params.swaps_dimensions() {
+                    (height, width)
+                } else {
+                    (width, height)
+                }
+            })
+        }
+        SupportedOperation::Crop => parse::<CropParams>(spec).map_or((width, height), |params| {
+            (params.width.min(width), params.height.min(height))
+        }),
+        SupportedOperation::Extract => parse::<ExtractParams>(spec)
+            .map_or((width, height), |params| {
+                (params.width.min(width), params.height.min(height))
+            }),
+        SupportedOperation::SmartCrop => parse::<SmartCropParams>(spec)
+            .map_or((width, height), |params| {
+                (params.width.min(width), params.height.min(height))
+            }),
+        SupportedOperation::Border => {
+            parse::<BorderParams>(spec).map_or((width, height), |params| {
+                let grown = |side: u32| side.saturating_add(params.width.saturating_mul(2));
+                (grown(width), grown(height))
+            })
+        }
+        _ => (width, height),
+    }
+}
+
+/// Output size of a pipeline when it follows from the parameters alone, without any pixels.
+///
+/// Returns `None` as soon as an operation's outcome depends on the image: its size (e.g.
+/// `smartCrop`, `thumbnail` unless in `cover` mode), whether it fails on the image (`watermark` fonts, out-of-bounds
+/// `crop`), invalid parameters or a region. The pipeline has to be run in that case.
+pub fn exact_output_dimensions(
+    input: (u32, u32),
+    operations_spec: &[PipelineOperationSpec],
+) -> Option<(u32, u32)> {
+    operations_spec
+        .iter()
+        .try_fold(input, |(width, height), spec| {
+            if spec.region.is_some() || validate_operation(spec).is_err() {
+                return None;
+            }
+            let in_bounds = |x: u32, y: u32, w: u32, h: u32| {
+                let fits = x.checked_add(w).is_some_and(|right| right <= width)
+                    && y.checked_add(h).is_some_and(|bottom| bottom <= height);
+                fits.then_some((w, h))
+            };
+            match spec.operation {
+                SupportedOperation::Resize
+                | SupportedOperation::ResizePercent
+                | SupportedOperation::Rotate
+                | SupportedOperation::Border => Some(projected_dimensions((width, height), spec)),
+                SupportedOperation::Crop => {
+                    parse::<CropParams>(spec).and_then(|p| in_bounds(p.x, p.y, p.width, p.height))
+                }
+                SupportedOperation::Extract => parse::<ExtractParams>(spec)
+                    .and_then(|p| in_bounds(p.x, p.y, p.width, p.height)),
+                SupportedOperation::Thumbnail => parse::<ThumbnailParams>(spec)
+                    .filter(|p| p.mode == ThumbnailMode::Cover)
+                    .map(|p| (p.width, p.height)),
+                SupportedOperation::Grayscale
+                | SupportedOperation::Blur
+                | SupportedOperation::AdjustBrightness
+                | SupportedOperation::AdjustContrast
+                | SupportedOperation::Sharpen
+                | SupportedOperation::Flatten
+                | SupportedOperation::Background
+                | SupportedOperation::AutoEnhance
+                | SupportedOperation::Normalize
+                | SupportedOperation::Colorize
+                | SupportedOperation::SetOpacity
+                | SupportedOperation::ChannelGain
+                | SupportedOperation::Gamma
+                | SupportedOperation::RoundCorners
+                | SupportedOperation::Flip
+                | SupportedOperation::Flop
+                | SupportedOperation::Mirror
+                | SupportedOperation::Autorotate
+                | SupportedOperation::Convert => Some((width, height)),
+                _ => None,
+            }
+        })
+}
+
+/// Largest size with the aspect ratio of `dimensions` that fits in `bounds`.
+fn fit_within((width, height): (u32, u32), (max_width, max_height): (u32, u32)) -> (u32, u32) {
+    if width == 0 || height == 0 {
+        return (width, height);
+    }
+    let ratio = (max_width as f64 / width as f64).min(max_height as f64 / height as f64);
+    let scaled = |side: u32| ((side as f64 * ratio).round().max(1.0)).min(u32::MAX as f64) as u32;
+    (scaled(width), scaled(height))
+}
+
+fn parse<T: DeserializeOwned>(spec: &PipelineOperationSpec) -> Option<T> {
+    serde_json::from_value(spec.params.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn spec(operation: SupportedOperation, params: serde_json::Value) -> PipelineOperationSpec {
+        PipelineOperationSpec {
+            operation,
+            params,
+            ignore_failure: false,
+            region: None,
+        }
+    }
+
+    fn limits() -> ImageLimits {
+        ImageLimits {
+            max_input_pixels: 50_000_000,
+            max_output_pixels: 40_000_000,
+            max_output_dimension: 16_384,
+        }
+    }
+
+    #[test]
+    fn test_upscaling_resize_over_limit_is_rejected() {
+        let ops = [spec(
+            SupportedOperation::Resize,
+            json!({"width": 30000, "height": 30000}),
+        )];
+        match limits().check_pipeline((10, 10), &ops) {
+            Err(AppError::BadRequest(msg)) => assert!(msg.contains("30000x30000"), "{}", msg),
+            other => panic!("Expected BadRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_repeated_enlarge_and_zoom_are_tracked() {
+        // Each step alone is fine; the accumulated size is not
+        let ops = [
+            spec(
+                SupportedOperation::Enlarge,
+                json!({"width": 4000, "height": 4000}),
+            ),
+            spec(SupportedOperation::Zoom, json!({"factor": 2.0})),
+        ];
+        assert!(limits().check_pipeline((1000, 1000), &ops[..1]).is_ok());
+        assert!(matches!(
+            limits().check_pipeline((1000, 1000), &ops),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_resize_percent_is_projected() {
+        let ops = [spec(
+            SupportedOperation::ResizePercent,
+            json!({"width_pct": 50, "height_pct": 300}),
+        )];
+        assert_eq!(exact_output_dimensions((200, 100), &ops), Some((100, 300)));
+        let limits = ImageLimits {
+            max_output_dimension: 299,
+            ..Default::default()
+        };
+        assert!(matches!(
+            limits.check_pipeline((200, 100), &ops),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_rotate_swapping_sides_checks_max_dimension() {
+        let ops = [spec(SupportedOperation::Rotate, json!({"degrees": 90}))];
+        let limits = ImageLimits {
+            max_output_dimension: 1000,
+            ..Default::default()
+        };
+        assert!(limits.check_pipeline((1000, 20), &ops).is_ok());
+        let keep_canvas = [spec(
+            SupportedOperation::Rotate,
+            json!({"degrees": 45, "keep_canvas": true}),
+        )];
+        assert!(limits.check_pipeline((1000, 20), &keep_canvas).is_ok());
+        let limits = ImageLimits {
+            max_output_dimension: 999,
+            ..limits
+        };
+        assert!(limits.check_pipeline((20, 999), &ops).is_ok());
+        assert!(limits.check_pipeline((1000, 20), &ops).is_err());
+    }
+
+    #[test]
+    fn test_rotate_dimensions_use_normalized_degrees() {
+        let rotated = |degrees: i32| {
+            let ops = [spec(
+                SupportedOperation::Rotate,
+                json!({ "degrees": degrees }),
+            )];
+            exact_output_dimensions((40, 10), &ops)
+        };
+        assert_eq!(rotated(450), Some((10, 40)));
+        assert_eq!(rotated(-90), Some((10, 40)));
+        assert_eq!(rotated(360), Some((40, 10)));
+        assert_eq!(rotated(-180), Some((40, 10)));
+        assert_eq!(rotated(45), None);
+    }
+
+    #[test]
+    fn test_border_growth_is_tracked() {
+        let ops = [spec(SupportedOperation::Border, json!({"width": 10}))];
+        let limits = ImageLimits {
+            max_output_dimension: 1000,
+            ..Default::default()
+        };
+        assert!(limits.check_pipeline((980, 500), &ops).is_ok());
+        assert!(limits.check_pipeline((981, 500), &ops).is_err());
+    }
+
+    #[test]
+    fn test_shrinking_operations_reset_the_projection() {
+        let ops = [
+            spec(
+                SupportedOperation::Crop,
+                json!({"x": 0, "y": 0, "width": 100, "height": 100}),
+            ),
+            spec(SupportedOperation::Zoom, json!({"factor": 10.0})),
+        ];
+        assert!(limits().check_pipeline((8000, 8000), &ops).is_ok());
+    }
+
+    #[test]
+    fn test_input_and_disabled_limits() {
+        assert!(limits().check_input((5000, 5000)).is_ok());
+        assert!(matches!(
+            limits().check_input((10000, 10000)),
+            Err(AppError::BadRequest(_))
+        ));
+
+        let unlimited = ImageLimits::default();
+        let ops = [spec(
+            SupportedOperation::Resize,
+            json!({"width": 30000, "height": 30000}),
+        )];
+        assert!(unlimited.check_input((100_000, 100_000)).is_ok());
+        assert!(unlimited.check_pipeline((10, 10), &ops).is_ok());
+    }
+
+    #[test]
+    fn test_exact_output_dimensions_only_for_predictable_pipelines() {
+        let ops = [
+            spec(
+                SupportedOperation::Resize,
+                json!({"width": 300, "height": 200}),
+            ),
+            spec(SupportedOperation::Rotate, json!({"degrees": 90})),
+            spec(SupportedOperation::Grayscale, json!({})),
+            spec(SupportedOperation::Border, json!({"width": 5})),
+        ];
+        assert_eq!(exact_output_dimensions((640, 480), &ops), Some((210, 310)));
+
+        let crop = |width| {
+            spec(
+                SupportedOperation::Crop,
+                json!({"width": width, "height": 10}),
+            )
+        };
+        assert_eq!(
+            exact_output_dimensions((50, 50), &[crop(20)]),
+            Some((20, 10))
+        );
+        assert_eq!(exact_output_dimensions((50, 50), &[crop(80)]), None);
+        let smart_crop = spec(
+            SupportedOperation::SmartCrop,
+            json!({"width": 20, "height": 20}),
+        );
+        assert_eq!(exact_output_dimensions((50, 50), &[smart_crop]), None);
+        let thumbnail = |mode| {
+            spec(
+                SupportedOperation::Thumbnail,
+                json!({"width": 20, "height": 20, "mode": mode}),
+            )
+        };
+        assert_eq!(exact_output_dimensions((50, 30), &[thumbnail("fit")]), None);
+        assert_eq!(
+            exact_output_dimensions((50, 30), &[thumbnail("cover")]),
+            Some((20, 20))
+        );
+        let invalid = spec(
+            SupportedOperation::Resize,
+            json!({"width": 0, "height": 10}),
+        );
+        assert_eq!(exact_output_dimensions((50, 50), &[invalid]), None);
+    }
+}
@@ -0,0 +1,327 @@
+//! Metadata stripping and EXIF orientation.
+//!
+//! The `image` crate's encoders never write EXIF, XMP or text metadata, but stripping is a
+//! privacy guarantee and should not depend on that. With `strip` on, encoded output is
+//! scrubbed of every metadata block that can identify the camera, owner or location:
+//! - JPEG: `APP1` (EXIF, XMP), `APP13` (IPTC) and `COM` segments,
+//! - PNG: `eXIf`, `tEXt`, `zTXt`, `iTXt` and `tIME` chunks,
+//! - WebP: `EXIF` and `XMP ` chunks.
+//!
+//! ICC profiles describe color, not the photo, and are kept (see `icc`).
+//!
+//! Dropping EXIF also drops the orientation tag, so the orientation is applied to the pixels
+//! first. Clients that want the original pixels can opt into `keep_orientation`, which writes
+//! back an EXIF block holding only the orientation (JPEG output only).
+
+use super::icc::{jpeg_segments, PNG_SIGNATURE};
+use image::{DynamicImage, ImageFormat};
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// How a pipeline request treats metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataOptions {
+    /// Scrub EXIF/XMP/IPTC/text metadata from the output.
+    pub strip: bool,
+    /// Keep the EXIF orientation tag instead of applying it to the pixels.
+    pub keep_orientation: bool,
+}
+
+impl Default for MetadataOptions {
+    fn default() -> Self {
+        Self {
+            strip: true,
+            keep_orientation: false,
+        }
+    }
+}
+
+/// Reads the EXIF orientation (1-8) of a JPEG, PNG or WebP image.
+pub fn read_orientation(bytes: &[u8], format: ImageFormat) -> Option<u8> {
+    let tiff = match format {
+        ImageFormat::Jpeg => jpeg_segments(bytes)?.find_map(|(marker, segment)| match marker {
+            0xE1 => segment.get(2..)?.strip_prefix(EXIF_HEADER),
+            _ => None,
+        })?,
+        ImageFormat::Png => png_chunks(bytes)?
+            .find(|(kind, _, _)| *kind == b"eXIf")
+            .map(|(_, data, _)| data)?,
+        ImageFormat::WebP => {
+            let data = riff_chunks(bytes)?
+                .find(|(fourcc, _, _)| *fourcc == b"EXIF")
+                .map(|(_, data, _)| data)?;
+            // Some writers keep the JPEG-style header in the WebP chunk
+            data.strip_prefix(EXIF_HEADER).unwrap_or(data)
+        }
+        _ => return None,
+    };
+    tiff_orientation(tiff)
+}
+
+/// Finds the orientation tag in IFD0 of a TIFF-structured EXIF block.
+fn tiff_orientation(tiff: &[u8]) -> Option<u8> {
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let raw = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(if big_endian {
+            u16::from_be_bytes(raw)
+        } else {
+            u16::from_le_bytes(raw)
+        })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let raw: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(raw)
+        } else {
+            u32::from_le_bytes(raw)
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+        .map(|orientation| orientation as u8)
+}
+
+/// Rotates and flips the image so it displays upright without its EXIF orientation.
+pub fn apply_orientation(image: DynamicImage, orientation: u8) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Removes identifying metadata from an encoded JPEG, PNG or WebP image.
+///
+/// Returns `encoded` unchanged for other formats and for data the scrubber does not recognise.
+pub fn strip_metadata(encoded: Vec<u8>, format: ImageFormat) -> Vec<u8> {
+    let stripped = match format {
+        ImageFormat::Jpeg => strip_jpeg(&encoded),
+        ImageFormat::Png => strip_png(&encoded),
+        ImageFormat::WebP => strip_webp(&encoded),
+        _ => None,
+    };
+    stripped.unwrap_or(encoded)
+}
+
+/// Adds an EXIF block holding only `orientation` to an encoded JPEG, after the `APP0` header.
+pub fn embed_orientation(jpeg: Vec<u8>, orientation: u8) -> Vec<u8> {
+    let mut insert_at = 2;
+    match jpeg_segments(&jpeg).map(|mut segments| segments.next()) {
+        None => return jpeg,
+        Some(Some((0xE0, app0))) => insert_at += 2 + app0.len(),
+        Some(_) => {}
+    }
+
+    // Big-endian TIFF header, then IFD0 with a single SHORT entry and no next IFD
+    let mut tiff = b"MM\0\x2A\0\0\0\x08\0\x01".to_vec();
+    tiff.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    tiff.extend_from_slice(&[0, 3, 0, 0, 0, 1, 0, orientation, 0, 0]);
+    tiff.extend_from_slice(&[0; 4]);
+
+    let length = 2 + EXIF_HEADER.len() + tiff.len();
+    let mut output = Vec::with_capacity(jpeg.len() + 2 + length);
+    output.extend_from_slice(&jpeg[..insert_at]);
+    output.extend_from_slice(&[0xFF, 0xE1]);
+    output.extend_from_slice(&(length as u16).to_be_bytes());
+    output.extend_from_slice(EXIF_HEADER);
+    output.extend_from_slice(&tiff);
+    output.extend_from_slice(&jpeg[insert_at..]);
+    output
+}
+
+/// Drops `APP1`, `APP13` and `COM` segments before start-of-scan.
+fn strip_jpeg(jpeg: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(jpeg.len());
+    output.extend_from_slice(jpeg.get(0..2)?);
+    let mut position = 2;
+    for (marker, segment) in jpeg_segments(jpeg)? {
+        if !matches!(marker, 0xE1 | 0xED | 0xFE) {
+            output.extend_from_slice(&[0xFF, marker]);
+            output.extend_from_slice(segment);
+        }
+        position += 2 + segment.len();
+    }
+    output.extend_from_slice(&jpeg[position..]);
+    Some(output)
+}
+
+fn strip_png(png: &[u8]) -> Option<Vec<u8>> {
+    let mut output = PNG_SIGNATURE.to_vec();
+    for (kind, _, chunk) in png_chunks(png)? {
+        if !matches!(kind, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            output.extend_from_slice(chunk);
+        }
+    }
+    Some(output)
+}
+
+/// Drops `EXIF` and `XMP ` chunks and clears their flags in the `VP8X` header.
+fn strip_webp(webp: &[u8]) -> Option<Vec<u8>> {
+    let mut body = b"WEBP".to_vec();
+    for (fourcc, _, chunk) in riff_chunks(webp)? {
+        match fourcc {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let flags_at = body.len() + 8;
+                body.extend_from_slice(chunk);
+                // Bit 3: EXIF present, bit 2: XMP present
+                if let Some(flags) = body.get_mut(flags_at) {
+                    *flags &= !0x0C;
+                }
+            }
+            _ => body.extend_from_slice(chunk),
+        }
+    }
+    let mut output = b"RIFF".to_vec();
+    output.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    output.extend_from_slice(&body);
+    Some(output)
+}
+
+/// Iterates over PNG chunks, yielding the chunk type, its data and the whole chunk including
+/// length and CRC. Stops at the first truncated chunk.
+fn png_chunks(png: &[u8]) -> Option<impl Iterator<Item = (&[u8; 4], &[u8], &[u8])>> {
+    if !png.starts_with(PNG_SIGNATURE) {
+        return None;
+    }
+    let mut position = PNG_SIGNATURE.len();
+    Some(std::iter::from_fn(move || {
+        let length = u32::from_be_bytes(png.get(position..position + 4)?.try_into().ok()?);
+        let end = position.checked_add(12 + length as usize)?;
+        let chunk = png.get(position..end)?;
+        position = end;
+        let kind = chunk[4..8].try_into().ok()?;
+        Some((kind, &chunk[8..chunk.len() - 4], chunk))
+    }))
+}
+
+/// Iterates over the chunks of a WebP RIFF container, yielding the FourCC, the chunk data and
+/// the whole chunk including header and padding.
+fn riff_chunks(webp: &[u8]) -> Option<impl Iterator<Item = (&[u8; 4], &[u8], &[u8])>> {
+    if webp.get(0..4)? != b"RIFF" || webp.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut position = 12;
+    Some(std::iter::from_fn(move || {
+        let size = u32::from_le_bytes(webp.get(position + 4..position + 8)?.try_into().ok()?);
+        let data_end = position.checked_add(8 + size as usize)?;
+        let data = webp.get(position + 8..data_end)?;
+        let end = (data_end + size as usize % 2).min(webp.len());
+        let chunk = &webp[position..end];
+        position = end;
+        let fourcc = chunk[0..4].try_into().ok()?;
+        Some((fourcc, data, chunk))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    fn jpeg_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([90, 120, 150])))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg)
+            .unwrap();
+        bytes
+    }
+
+    /// Inserts raw segments right after SOI.
+    fn with_segments(jpeg: &[u8], segments: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut output = jpeg[..2].to_vec();
+        for (marker, data) in segments {
+            output.extend_from_slice(&[0xFF, *marker]);
+            output.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+            output.extend_from_slice(data);
+        }
+        output.extend_from_slice(&jpeg[2..]);
+        output
+    }
+
+    #[test]
+    fn test_orientation_round_trips_through_jpeg() {
+        let jpeg = embed_orientation(jpeg_bytes(8, 4), 6);
+        assert_eq!(read_orientation(&jpeg, ImageFormat::Jpeg), Some(6));
+        assert_eq!(image::load_from_memory(&jpeg).unwrap().dimensions(), (8, 4));
+        assert_eq!(read_orientation(&jpeg_bytes(8, 4), ImageFormat::Jpeg), None);
+    }
+
+    #[test]
+    fn test_little_endian_orientation_is_read() {
+        let mut exif = EXIF_HEADER.to_vec();
+        exif.extend_from_slice(b"II\x2A\0\x08\0\0\0\x01\0");
+        exif.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0]);
+        let jpeg = with_segments(&jpeg_bytes(4, 4), &[(0xE1, &exif)]);
+        assert_eq!(read_orientation(&jpeg, ImageFormat::Jpeg), Some(8));
+    }
+
+    #[test]
+    fn test_apply_orientation_swaps_sides() {
+        let image = DynamicImage::new_rgb8(20, 10);
+        assert_eq!(apply_orientation(image.clone(), 6).dimensions(), (10, 20));
+        assert_eq!(apply_orientation(image.clone(), 3).dimensions(), (20, 10));
+        assert_eq!(apply_orientation(image, 1).dimensions(), (20, 10));
+    }
+
+    #[test]
+    fn test_strip_jpeg_removes_exif_xmp_and_comments() {
+        let xmp = b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta/>";
+        let jpeg = with_segments(
+            &embed_orientation(jpeg_bytes(8, 8), 3),
+            &[
+                (0xE1, xmp),
+                (0xFE, b"shot on a phone"),
+                (0xE2, b"ICC_PROFILE\0"),
+            ],
+        );
+        let stripped = strip_metadata(jpeg, ImageFormat::Jpeg);
+
+        let markers: Vec<u8> = jpeg_segments(&stripped)
+            .unwrap()
+            .map(|(marker, _)| marker)
+            .collect();
+        assert!(!markers.iter().any(|m| matches!(m, 0xE1 | 0xED | 0xFE)));
+        assert!(markers.contains(&0xE2), "ICC segment must be kept");
+        assert_eq!(
+            image::load_from_memory(&stripped).unwrap().dimensions(),
+            (8, 8)
+        );
+    }
+
+    #[test]
+    fn test_strip_png_removes_text_chunks() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        // tEXt chunk with a made-up CRC; the scrubber copies chunks without checking it
+        let mut text = 12u32.to_be_bytes().to_vec();
+        text.extend_from_slice(b"tEXtAuthor\0Alice");
+        text.extend_from_slice(&[0; 4]);
+        let ihdr_end = PNG_SIGNATURE.len() + 25;
+        let mut tagged = png[..ihdr_end].to_vec();
+        tagged.extend_from_slice(&text);
+        tagged.extend_from_slice(&png[ihdr_end..]);
+
+        assert_eq!(strip_metadata(tagged, ImageFormat::Png), png);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod analysis;
+pub mod compare;
+pub mod format_support;
+pub mod heif;
+pub mod icc;
+pub mod limits;
+pub mod metadata;
 pub mod operations;
 pub mod params;
 pub mod pipeline;
@@ -0,0 +1,122 @@
+//! Background operations for images.
+//!
+//! This module fills the area behind an image with a solid color or a linear gradient, e.g. a
+//! backdrop for a transparent logo on a social card.
+
+use crate::image::params::{BackgroundParams, GradientDirection};
+use image::{DynamicImage, Rgb, RgbImage};
+
+/// Composite the image over a solid or linear-gradient background, dropping the alpha channel.
+///
+/// Without `color_end` the background is `color_start` throughout. Otherwise it runs from
+/// `color_start` at the top (`vertical`), left (`horizontal`) or top-left corner (`diagonal`)
+/// to `color_end` at the opposite edge or corner.
+///
+/// # Arguments
+/// * `image` - The input image, typically with transparent areas.
+/// * `params` - The background colors and gradient direction.
+///
+/// # Returns
+/// A new RGB `DynamicImage`; opaque pixels are unchanged.
+pub fn background(image: DynamicImage, params: &BackgroundParams) -> DynamicImage {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let start = params.color_start;
+    let end = params.color_end.unwrap_or(start);
+    // Position along the gradient, 0.0 at `color_start` and 1.0 at `color_end`
+    let fraction = |position: u32, length: u32| {
+        if length > 1 {
+            position as f32 / (length - 1) as f32
+        } else {
+            0.0
+        }
+    };
+    let composited = RgbImage::from_fn(width, height, |x, y| {
+        let t = match params.direction {
+            GradientDirection::Vertical => fraction(y, height),
+            GradientDirection::Horizontal => fraction(x, width),
+            GradientDirection::Diagonal => (fraction(x, width) + fraction(y, height)) / 2.0,
+        };
+        let px = rgba.get_pixel(x, y);
+        let alpha = px[3] as f32 / 255.0;
+        let mut out = [0u8; 3];
+        for c in 0..3 {
+            let backdrop = start[c] as f32 + (end[c] as f32 - start[c] as f32) * t;
+            out[c] = (alpha * px[c] as f32 + (1.0 - alpha) * backdrop).round() as u8;
+        }
+        Rgb(out)
+    });
+    DynamicImage::ImageRgb8(composited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgba};
+
+    /// A transparent image with an opaque red square in the middle.
+    fn create_logo_image(size: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(size, size, |x, y| {
+            let inside =
+                (size / 4..size * 3 / 4).contains(&x) && (size / 4..size * 3 / 4).contains(&y);
+            if inside {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_vertical_gradient_behind_transparency() {
+        let params = BackgroundParams {
+            color_start: [0, 0, 255],
+            color_end: Some([255, 255, 0]),
+            direction: GradientDirection::Vertical,
+        };
+        let result = background(create_logo_image(40), &params).to_rgb8();
+
+        assert_eq!(result.get_pixel(0, 0), &Rgb([0, 0, 255]));
+        assert_eq!(result.get_pixel(0, 39), &Rgb([255, 255, 0]));
+        assert_ne!(result.get_pixel(5, 0), result.get_pixel(5, 39));
+        // Rows are uniform in a vertical gradient
+        assert_eq!(result.get_pixel(0, 5), result.get_pixel(39, 5));
+        // The opaque foreground survives
+        assert_eq!(result.get_pixel(20, 20), &Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    fn test_horizontal_and_diagonal_gradients() {
+        let params = |direction| BackgroundParams {
+            color_start: [0, 0, 0],
+            color_end: Some([200, 200, 200]),
+            direction,
+        };
+        let horizontal = background(
+            create_logo_image(40),
+            &params(GradientDirection::Horizontal),
+        )
+        .to_rgb8();
+        assert_eq!(horizontal.get_pixel(0, 5), horizontal.get_pixel(0, 30));
+        assert_eq!(horizontal.get_pixel(39, 5), &Rgb([200, 200, 200]));
+
+        let diagonal =
+            background(create_logo_image(40), &params(GradientDirection::Diagonal)).to_rgb8();
+        assert_eq!(diagonal.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_eq!(diagonal.get_pixel(39, 39), &Rgb([200, 200, 200]));
+        assert_eq!(diagonal.get_pixel(39, 0), diagonal.get_pixel(0, 39));
+    }
+
+    #[test]
+    fn test_solid_background_without_end_color() {
+        let params = BackgroundParams {
+            color_start: [10, 20, 30],
+            color_end: None,
+            direction: GradientDirection::default(),
+        };
+        let result = background(create_logo_image(8), &params).to_rgb8();
+        assert_eq!(result.get_pixel(0, 0), &Rgb([10, 20, 30]));
+        assert_eq!(result.get_pixel(7, 7), &Rgb([10, 20, 30]));
+        assert_eq!(result.get_pixel(4, 4), &Rgb([255, 0, 0]));
+    }
+}
@@ -1,9 +1,33 @@
 //! Color and filter operations for images.
 //!
-//! This module provides functions for grayscale conversion, brightness/contrast adjustment, sharpening, and blurring.
+//! This module provides functions for grayscale conversion, brightness/contrast adjustment, sharpening, blurring,
+//! flattening transparency onto a background color, one-click auto-enhancement, stylistic color filters
+//! (sepia, invert, tint), per-channel gains, gamma correction, auto-levels and global opacity.
 
-use crate::image::params::BlurParams;
-use image::DynamicImage;
+use crate::image::operations::format::is_high_depth;
+use crate::image::params::{
+    BlurParams, ChannelGainParams, ColorizeMode, ColorizeParams, FlattenParams, GammaParams,
+    NormalizeParams, SetOpacityParams,
+};
+use image::{DynamicImage, Rgb, RgbImage};
+
+/// Standard sepia transform; each row maps the source RGB to one output channel.
+const SEPIA_MATRIX: [[f32; 3]; 3] = [
+    [0.393, 0.769, 0.189],
+    [0.349, 0.686, 0.168],
+    [0.272, 0.534, 0.131],
+];
+
+/// Fraction of the full histogram stretch applied by `auto_enhance`.
+const ENHANCE_CONTRAST_STRENGTH: f32 = 0.75;
+/// Fraction of the distance to mid-gray that `auto_enhance` moves the mean luminance.
+const ENHANCE_BRIGHTNESS_STRENGTH: f32 = 0.3;
+/// Largest brightness shift `auto_enhance` will apply.
+const ENHANCE_MAX_BRIGHTNESS_SHIFT: f32 = 20.0;
+/// Mean saturation below which `auto_enhance` boosts colors.
+const ENHANCE_SATURATION_TARGET: f32 = 0.25;
+/// Largest saturation multiplier `auto_enhance` will apply.
+const ENHANCE_MAX_SATURATION_BOOST: f32 = 1.15;
 
 /// Convert an image to grayscale.
 ///
@@ -11,14 +35,19 @@ use image::DynamicImage;
 /// * `image` - The input image to convert.
 ///
 /// # Returns
-/// A new `DynamicImage` in grayscale.
+/// A new `DynamicImage` in grayscale: 16-bit for inputs with more than 8 bits per channel,
+/// 8-bit otherwise.
 ///
 /// # Examples
 /// # use image::DynamicImage;
 /// # let img = DynamicImage::new_rgb8(100, 100);
 /// let gray = grayscale(img);
 pub fn grayscale(image: DynamicImage) -> DynamicImage {
-    image.to_luma8().into()
+    if is_high_depth(image.color()) {
+        image.to_luma16().into()
+    } else {
+        image.to_luma8().into()
+    }
 }
 
 /// Adjust the brightness of an image by the given value.
@@ -72,10 +101,362 @@ pub fn blur(image: DynamicImage, params: &BlurParams) -> DynamicImage {
     image.blur(params.sigma)
 }
 
+/// Composite the image over a solid background color, dropping the alpha channel.
+///
+/// # Arguments
+/// * `image` - The input image to flatten.
+/// * `params` - The flatten parameters (background color).
+///
+/// # Returns
+/// A new RGB `DynamicImage` with transparent areas replaced by the background.
+pub fn flatten(image: DynamicImage, params: &FlattenParams) -> DynamicImage {
+    let rgba = image.to_rgba8();
+    let background = params.background;
+    let flattened = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let px = rgba.get_pixel(x, y);
+        let alpha = px[3] as f32 / 255.0;
+        let mut out = [0u8; 3];
+        for c in 0..3 {
+            out[c] = (alpha * px[c] as f32 + (1.0 - alpha) * background[c] as f32).round() as u8;
+        }
+        Rgb(out)
+    });
+    DynamicImage::ImageRgb8(flattened)
+}
+
+/// Automatically enhance the image using histogram-based heuristics.
+///
+/// Applies, in one pass:
+/// - a moderate auto-contrast that stretches the 1st..99th luminance percentiles towards the full range,
+/// - a brightness shift nudging the mean luminance towards mid-gray,
+/// - a slight saturation boost for dull, low-saturation images.
+///
+/// Each adjustment scales with how far the image is from balanced, so an already
+/// well-exposed image is left nearly unchanged. Alpha is preserved.
+///
+/// # Arguments
+/// * `image` - The input image to enhance.
+///
+/// # Returns
+/// A new RGBA `DynamicImage` with the enhancement applied.
+pub fn auto_enhance(image: DynamicImage) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let pixel_count = (rgba.width() as usize) * (rgba.height() as usize);
+    if pixel_count == 0 {
+        return DynamicImage::ImageRgba8(rgba);
+    }
+
+    let luma =
+        |px: &image::Rgba<u8>| 0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32;
+
+    // Luminance histogram and mean saturation of the source image
+    let mut histogram = [0usize; 256];
+    let mut saturation_sum = 0.0f32;
+    for px in rgba.pixels() {
+        histogram[luma(px).round().clamp(0.0, 255.0) as usize] += 1;
+        let max = px[0].max(px[1]).max(px[2]) as f32;
+        let min = px[0].min(px[1]).min(px[2]) as f32;
+        if max > 0.0 {
+            saturation_sum += (max - min) / max;
+        }
+    }
+    let mean_saturation = saturation_sum / pixel_count as f32;
+
+    let percentile = |fraction: f32| -> f32 {
+        let target = (pixel_count as f32 * fraction).ceil() as usize;
+        let mut seen = 0;
+        for (value, count) in histogram.iter().enumerate() {
+            seen += count;
+            if seen >= target.max(1) {
+                return value as f32;
+            }
+        }
+        255.0
+    };
+    let low = percentile(0.01);
+    let high = percentile(0.99);
+
+    // Contrast: blend between identity and a full stretch of [low, high] to [0, 255]
+    let mut lut = [0f32; 256];
+    for (value, entry) in lut.iter_mut().enumerate() {
+        let v = value as f32;
+        let stretched = if high > low {
+            (v - low) * 255.0 / (high - low)
+        } else {
+            v
+        };
+        *entry = v + ENHANCE_CONTRAST_STRENGTH * (stretched - v);
+    }
+
+    // Brightness: move the post-contrast mean part of the way to mid-gray
+    let mean_after_contrast = histogram
+        .iter()
+        .enumerate()
+        .map(|(value, count)| lut[value].clamp(0.0, 255.0) * *count as f32)
+        .sum::<f32>()
+        / pixel_count as f32;
+    let shift = ((128.0 - mean_after_contrast) * ENHANCE_BRIGHTNESS_STRENGTH)
+        .clamp(-ENHANCE_MAX_BRIGHTNESS_SHIFT, ENHANCE_MAX_BRIGHTNESS_SHIFT);
+    for entry in lut.iter_mut() {
+        *entry += shift;
+    }
+
+    // Saturation: boost only images that are noticeably dull
+    let saturation_factor = if mean_saturation < ENHANCE_SATURATION_TARGET {
+        (1.0 + (ENHANCE_SATURATION_TARGET - mean_saturation)).min(ENHANCE_MAX_SATURATION_BOOST)
+    } else {
+        1.0
+    };
+
+    for px in rgba.pixels_mut() {
+        let mut channels = [
+            lut[px[0] as usize],
+            lut[px[1] as usize],
+            lut[px[2] as usize],
+        ];
+        let gray = 0.299 * channels[0] + 0.587 * channels[1] + 0.114 * channels[2];
+        for channel in channels.iter_mut() {
+            *channel = gray + (*channel - gray) * saturation_factor;
+        }
+        for c in 0..3 {
+            px[c] = channels[c].round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Apply a stylistic color filter.
+///
+/// `sepia` runs the standard sepia matrix, `invert` replaces each color channel with its
+/// complement and `tint` leaves colors as they are; the optional `tint` multipliers are then
+/// applied per channel. Alpha is preserved.
+///
+/// # Arguments
+/// * `image` - The input image to colorize.
+/// * `params` - The colorize parameters (mode, optional tint).
+///
+/// # Returns
+/// A new `DynamicImage` with the filter applied.
+pub fn colorize(image: DynamicImage, params: &ColorizeParams) -> DynamicImage {
+    let mut image = image;
+    match params.mode {
+        ColorizeMode::Sepia => image = sepia(image),
+        ColorizeMode::Invert => image.invert(),
+        ColorizeMode::Tint => {}
+    }
+    match params.tint {
+        Some(tint) => apply_tint(image, tint),
+        None => image,
+    }
+}
+
+/// Multiply the red, green and blue channels by separate gains.
+///
+/// # Arguments
+/// * `image` - The input image to adjust.
+/// * `params` - The per-channel gains.
+///
+/// # Returns
+/// A new RGBA `DynamicImage` with each channel scaled and clamped to 0-255.
+pub fn channel_gain(image: DynamicImage, params: &ChannelGainParams) -> DynamicImage {
+    apply_tint(image, [params.r, params.g, params.b])
+}
+
+/// Apply gamma correction, mapping each color value `v` to `max * (v / max)^(1 / gamma)`, where
+/// `max` is the largest channel value (255, or 65535 for 16-bit images).
+///
+/// The curve is evaluated once into a lookup table, so the per-pixel cost is a table read.
+/// 8- and 16-bit grayscale and RGB(A) images keep their color type; floating point images are
+/// converted to 8-bit RGBA. Alpha is left unchanged.
+///
+/// # Arguments
+/// * `image` - The input image to correct.
+/// * `params` - The gamma (> 0).
+///
+/// # Returns
+/// A new `DynamicImage` with the corrected colors.
+pub fn gamma(image: DynamicImage, params: &GammaParams) -> DynamicImage {
+    let exponent = 1.0 / params.gamma as f64;
+    let curve = |value: usize, max: f64| {
+        (max * (value as f64 / max).powf(exponent))
+            .round()
+            .clamp(0.0, max)
+    };
+    let lut8: Vec<u8> = (0..=u8::MAX as usize)
+        .map(|value| curve(value, u8::MAX as f64) as u8)
+        .collect();
+    let lut16 = || -> Vec<u16> {
+        (0..=u16::MAX as usize)
+            .map(|value| curve(value, u16::MAX as f64) as u16)
+            .collect()
+    };
+    match image {
+        DynamicImage::ImageLuma8(mut buffer) => {
+            apply_table(&mut buffer, 1, 1, &lut8);
+            DynamicImage::ImageLuma8(buffer)
+        }
+        DynamicImage::ImageLumaA8(mut buffer) => {
+            apply_table(&mut buffer, 2, 1, &lut8);
+            DynamicImage::ImageLumaA8(buffer)
+        }
+        DynamicImage::ImageRgb8(mut buffer) => {
+            apply_table(&mut buffer, 3, 3, &lut8);
+            DynamicImage::ImageRgb8(buffer)
+        }
+        DynamicImage::ImageLuma16(mut buffer) => {
+            apply_table(&mut buffer, 1, 1, &lut16());
+            DynamicImage::ImageLuma16(buffer)
+        }
+        DynamicImage::ImageLumaA16(mut buffer) => {
+            apply_table(&mut buffer, 2, 1, &lut16());
+            DynamicImage::ImageLumaA16(buffer)
+        }
+        DynamicImage::ImageRgb16(mut buffer) => {
+            apply_table(&mut buffer, 3, 3, &lut16());
+            DynamicImage::ImageRgb16(buffer)
+        }
+        DynamicImage::ImageRgba16(mut buffer) => {
+            apply_table(&mut buffer, 4, 3, &lut16());
+            DynamicImage::ImageRgba16(buffer)
+        }
+        other => {
+            let mut buffer = other.into_rgba8();
+            apply_table(&mut buffer, 4, 3, &lut8);
+            DynamicImage::ImageRgba8(buffer)
+        }
+    }
+}
+
+/// Stretch each color channel's histogram so its darkest value maps to 0 and its brightest to
+/// 255 (auto-levels), e.g. for faded scans.
+///
+/// With `clip_percent`, that share of the darkest and of the brightest values in each channel
+/// is treated as outliers and clipped. A channel with a single value is left unchanged, as is
+/// alpha.
+///
+/// # Arguments
+/// * `image` - The input image; 8-bit gray and RGB images keep their color type, others become RGBA.
+/// * `params` - The normalize parameters (outlier clipping).
+///
+/// # Returns
+/// A new `DynamicImage` with each channel spanning the full range.
+pub fn normalize(image: DynamicImage, params: &NormalizeParams) -> DynamicImage {
+    let clip = params.clip_percent as f64 / 100.0;
+    match image {
+        DynamicImage::ImageLuma8(mut buffer) => {
+            stretch_channels(&mut buffer, 1, 1, clip);
+            DynamicImage::ImageLuma8(buffer)
+        }
+        DynamicImage::ImageLumaA8(mut buffer) => {
+            stretch_channels(&mut buffer, 2, 1, clip);
+            DynamicImage::ImageLumaA8(buffer)
+        }
+        DynamicImage::ImageRgb8(mut buffer) => {
+            stretch_channels(&mut buffer, 3, 3, clip);
+            DynamicImage::ImageRgb8(buffer)
+        }
+        other => {
+            let mut buffer = other.into_rgba8();
+            stretch_channels(&mut buffer, 4, 3, clip);
+            DynamicImage::ImageRgba8(buffer)
+        }
+    }
+}
+
+/// Linearly stretches each of the first `color` of every `channels` samples to 0..=255, after
+/// ignoring the `clip` fraction of lowest and of highest values of that channel.
+fn stretch_channels(samples: &mut [u8], channels: usize, color: usize, clip: f64) {
+    let pixel_count = samples.len() / channels;
+    let clipped = (pixel_count as f64 * clip) as usize;
+    for channel in 0..color {
+        let mut histogram = [0usize; 256];
+        for pixel in samples.chunks_exact(channels) {
+            histogram[pixel[channel] as usize] += 1;
+        }
+        // Lowest and highest values with more than `clipped` samples at or beyond them
+        let (mut below, mut above) = (0, 0);
+        let low = (0..256).find(|&value| {
+            below += histogram[value];
+            below > clipped
+        });
+        let high = (0..256).rev().find(|&value| {
+            above += histogram[value];
+            above > clipped
+        });
+        let (Some(low), Some(high)) = (low, high) else {
+            continue;
+        };
+        if high <= low {
+            continue;
+        }
+        let scale = 255.0 / (high - low) as f32;
+        let lut: Vec<u8> = (0..256)
+            .map(|value| {
+                ((value as f32 - low as f32) * scale)
+                    .round()
+                    .clamp(0.0, 255.0) as u8
+            })
+            .collect();
+        for pixel in samples.chunks_exact_mut(channels) {
+            pixel[channel] = lut[pixel[channel] as usize];
+        }
+    }
+}
+
+/// Replaces the first `color` of every `channels` samples by its entry in `lut`.
+fn apply_table<T: Copy + Into<usize>>(samples: &mut [T], channels: usize, color: usize, lut: &[T]) {
+    for pixel in samples.chunks_exact_mut(channels) {
+        for sample in &mut pixel[..color] {
+            *sample = lut[(*sample).into()];
+        }
+    }
+}
+
+/// Multiply the alpha channel by `params.opacity`, e.g. to fade an image before compositing.
+///
+/// # Arguments
+/// * `image` - The input image; converted to RGBA first, so opaque images become translucent.
+/// * `params` - The opacity (0.0-1.0).
+///
+/// # Returns
+/// A new RGBA `DynamicImage` with scaled alpha.
+pub fn set_opacity(image: DynamicImage, params: &SetOpacityParams) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    for px in rgba.pixels_mut() {
+        px[3] = (px[3] as f32 * params.opacity).round().clamp(0.0, 255.0) as u8;
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+fn sepia(image: DynamicImage) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    for px in rgba.pixels_mut() {
+        let source = [px[0] as f32, px[1] as f32, px[2] as f32];
+        for (c, row) in SEPIA_MATRIX.iter().enumerate() {
+            let value: f32 = row.iter().zip(source).map(|(w, v)| w * v).sum();
+            px[c] = value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+fn apply_tint(image: DynamicImage, tint: [f32; 3]) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    for px in rgba.pixels_mut() {
+        for c in 0..3 {
+            px[c] = (px[c] as f32 * tint[c]).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::image::params::BlurParams;
+    use crate::image::params::{
+        BlurParams, ChannelGainParams, ColorizeMode, ColorizeParams, FlattenParams, GammaParams,
+        NormalizeParams, SetOpacityParams, Validate,
+    };
     use image::GenericImageView;
     use image::{DynamicImage, ImageBuffer, Rgba};
 
@@ -94,6 +475,18 @@ mod tests {
         assert_eq!(gray.dimensions(), (100, 100));
     }
 
+    #[test]
+    fn test_grayscale_keeps_16_bit_precision() {
+        let gray = ImageBuffer::from_fn(64, 4, |x, y| image::Luma([(x * 1000 + y * 3 + 1) as u16]));
+        let rgb = DynamicImage::ImageLuma16(gray.clone()).to_rgb16();
+        let result = grayscale(DynamicImage::ImageRgb16(rgb));
+        let result = result.as_luma16().expect("16-bit input stays 16-bit");
+        // Off by at most one from float rounding of the luma weights, where 8 bits would be off by up to 128
+        for (converted, original) in result.pixels().zip(gray.pixels()) {
+            assert!(converted[0].abs_diff(original[0]) <= 1);
+        }
+    }
+
     #[test]
     fn test_adjust_brightness() {
         let img = create_test_image(100, 100);
@@ -125,4 +518,255 @@ mod tests {
         let blurred = blur(img, &params);
         assert_eq!(blurred.dimensions(), (100, 100));
     }
+
+    #[test]
+    fn test_flatten_transparent_pixels() {
+        // Left half fully transparent, right half half-transparent red
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(10, 10, |x, _| {
+            if x < 5 {
+                Rgba([0u8, 0u8, 0u8, 0u8])
+            } else {
+                Rgba([255u8, 0u8, 0u8, 128u8])
+            }
+        }));
+        let params = FlattenParams {
+            background: [0, 0, 255],
+        };
+        let flat = flatten(img, &params);
+        assert_eq!(flat.color(), image::ColorType::Rgb8);
+        let rgb = flat.to_rgb8();
+        assert_eq!(rgb.get_pixel(2, 2).0, [0, 0, 255]);
+        let blended = rgb.get_pixel(7, 7).0;
+        assert!(blended[0] > 120 && blended[0] < 135);
+        assert!(blended[2] > 120 && blended[2] < 135);
+    }
+
+    #[test]
+    fn test_flatten_default_background_is_white() {
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([0u8, 0, 0, 0])));
+        let flat = flatten(img, &FlattenParams::default());
+        assert_eq!(flat.to_rgb8().get_pixel(0, 0).0, [255, 255, 255]);
+    }
+
+    fn luma_spread(image: &DynamicImage) -> u8 {
+        let gray = image.to_luma8();
+        let max = gray.pixels().map(|p| p[0]).max().unwrap_or(0);
+        let min = gray.pixels().map(|p| p[0]).min().unwrap_or(0);
+        max - min
+    }
+
+    #[test]
+    fn test_auto_enhance_increases_contrast_of_dull_image() {
+        // Low-contrast gradient confined to 100..=140
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(41, 10, |x, _| {
+            let v = 100 + x as u8;
+            Rgba([v, v, v, 255u8])
+        }));
+        let before = luma_spread(&img);
+        let enhanced = auto_enhance(img);
+        assert_eq!(enhanced.dimensions(), (41, 10));
+        assert!(
+            luma_spread(&enhanced) > before * 3,
+            "expected contrast to increase"
+        );
+    }
+
+    #[test]
+    fn test_auto_enhance_balanced_image_is_near_identity() {
+        // Full-range gradient with mid-gray mean and vivid colors
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(256, 4, |x, y| {
+            let v = x as u8;
+            match y {
+                0 => Rgba([v, v, v, 255u8]),
+                1 => Rgba([v, 255 - v, 128, 255u8]),
+                2 => Rgba([255 - v, 128, v, 255u8]),
+                _ => Rgba([128, v, 255 - v, 255u8]),
+            }
+        }));
+        let enhanced = auto_enhance(img.clone());
+        let original = img.to_rgba8();
+        let result = enhanced.to_rgba8();
+        let total_diff: u64 = original
+            .pixels()
+            .zip(result.pixels())
+            .map(|(a, b)| {
+                (0..3)
+                    .map(|c| (a[c] as i16 - b[c] as i16).unsigned_abs() as u64)
+                    .sum::<u64>()
+            })
+            .sum();
+        let mean_diff = total_diff as f64 / (256.0 * 4.0 * 3.0);
+        assert!(
+            mean_diff < 4.0,
+            "mean channel change {} too large",
+            mean_diff
+        );
+    }
+
+    #[test]
+    fn test_colorize_invert_yields_complement() {
+        let img =
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([10u8, 200, 55, 180])));
+        let params = ColorizeParams {
+            mode: ColorizeMode::Invert,
+            tint: None,
+        };
+        let inverted = colorize(img, &params);
+        assert_eq!(inverted.get_pixel(1, 1), Rgba([245, 55, 200, 180]));
+    }
+
+    #[test]
+    fn test_colorize_sepia_shifts_gray_toward_brown() {
+        let img = create_test_image(4, 4);
+        let params = ColorizeParams {
+            mode: ColorizeMode::Sepia,
+            tint: None,
+        };
+        let px = colorize(img, &params).get_pixel(0, 0);
+        assert!(
+            px[0] > px[1] && px[1] > px[2],
+            "expected r > g > b, got {:?}",
+            px
+        );
+        assert!(px[2] < 128, "blue should drop below the original gray");
+        assert_eq!(px[3], 255);
+    }
+
+    #[test]
+    fn test_colorize_tint_multiplies_channels() {
+        let img = create_test_image(4, 4);
+        let params = ColorizeParams {
+            mode: ColorizeMode::Tint,
+            tint: Some([1.0, 0.5, 0.0]),
+        };
+        let px = colorize(img, &params).get_pixel(0, 0);
+        assert_eq!(px, Rgba([128, 64, 0, 255]));
+    }
+
+    #[test]
+    fn test_set_opacity_halves_alpha() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([10, 20, 30])));
+        let result = set_opacity(img, &SetOpacityParams { opacity: 0.5 });
+        assert_eq!(result.get_pixel(0, 0), Rgba([10, 20, 30, 128]));
+
+        let translucent =
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(2, 2, Rgba([0u8, 0, 0, 100])));
+        let result = set_opacity(translucent, &SetOpacityParams { opacity: 0.5 });
+        assert_eq!(result.get_pixel(1, 1)[3], 50);
+    }
+
+    #[test]
+    fn test_channel_gain_zero_red_clears_red() {
+        let img =
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([200u8, 100, 50, 255])));
+        let params = ChannelGainParams {
+            r: 0.0,
+            g: 1.0,
+            b: 2.0,
+        };
+        let px = channel_gain(img, &params).get_pixel(0, 0);
+        assert_eq!(px, Rgba([0, 100, 100, 255]));
+
+        let params = ChannelGainParams {
+            r: 2.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        let px = channel_gain(create_test_image(1, 1), &params).get_pixel(0, 0);
+        assert_eq!(px[0], 255, "gain is clamped to 255");
+    }
+
+    #[test]
+    fn test_gamma_one_is_identity() {
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(16, 16, |x, y| {
+            Rgba([(x * 16) as u8, (y * 16) as u8, (x * y) as u8, 128])
+        }));
+        let result = gamma(img.clone(), &GammaParams { gamma: 1.0 });
+        assert_eq!(result, img);
+    }
+
+    #[test]
+    fn test_gamma_brightens_midtones_and_keeps_alpha() {
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(2, 2, Rgba([128, 0, 255, 77])));
+        let px = gamma(img, &GammaParams { gamma: 2.2 }).get_pixel(0, 0);
+        // 255 * (128 / 255)^(1 / 2.2) ~= 186; black and white are fixed points
+        assert_eq!(px, Rgba([186, 0, 255, 77]));
+
+        let rgb = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([64, 64, 64])));
+        let result = gamma(rgb, &GammaParams { gamma: 2.2 });
+        assert!(result.as_rgb8().is_some(), "RGB input stays RGB");
+        assert!(result.get_pixel(0, 0)[0] > 64 + 40);
+    }
+
+    #[test]
+    fn test_gamma_keeps_16_bit_images() {
+        let img = DynamicImage::ImageLuma16(ImageBuffer::from_pixel(2, 2, image::Luma([1000u16])));
+        let result = gamma(img, &GammaParams { gamma: 2.2 });
+        // 65535 * (1000 / 65535)^(1 / 2.2) ~= 9790, far finer than any 8-bit value allows
+        assert_eq!(result.as_luma16().unwrap().get_pixel(0, 0).0, [9790]);
+    }
+
+    /// A gray ramp squeezed into 100..=150, like a faded scan.
+    fn low_contrast_gray() -> DynamicImage {
+        DynamicImage::ImageLuma8(ImageBuffer::from_fn(51, 4, |x, _| {
+            image::Luma([100 + x as u8])
+        }))
+    }
+
+    fn value_range(image: &DynamicImage) -> (u8, u8) {
+        let luma = image.to_luma8();
+        let values: Vec<u8> = luma.pixels().map(|px| px[0]).collect();
+        (*values.iter().min().unwrap(), *values.iter().max().unwrap())
+    }
+
+    #[test]
+    fn test_normalize_stretches_to_the_full_range() {
+        let normalized = normalize(low_contrast_gray(), &NormalizeParams::default());
+        assert_eq!(normalized.color(), image::ColorType::L8);
+        assert_eq!(value_range(&normalized), (0, 255));
+        // The midpoint stays in the middle
+        assert_eq!(normalized.to_luma8().get_pixel(25, 0)[0], 128);
+    }
+
+    #[test]
+    fn test_normalize_clips_outliers() {
+        let mut gray = low_contrast_gray().into_luma8();
+        gray.put_pixel(0, 0, image::Luma([0]));
+        gray.put_pixel(50, 3, image::Luma([255]));
+        let with_outliers = DynamicImage::ImageLuma8(gray);
+
+        // Unclipped, the outliers already span the full range, so nothing changes
+        let unclipped = normalize(with_outliers.clone(), &NormalizeParams::default());
+        assert_eq!(unclipped.to_luma8().get_pixel(25, 1)[0], 125);
+
+        let clipped = normalize(with_outliers, &NormalizeParams { clip_percent: 1.0 });
+        let luma = clipped.to_luma8();
+        // The range is now 100..=150 again, ignoring the two outliers
+        assert_eq!(luma.get_pixel(0, 1)[0], 0);
+        assert_eq!(luma.get_pixel(50, 1)[0], 255);
+        assert_eq!(luma.get_pixel(25, 1)[0], 128);
+    }
+
+    #[test]
+    fn test_normalize_stretches_channels_independently_and_keeps_alpha() {
+        let image = DynamicImage::ImageRgba8(ImageBuffer::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([10, 100, 50, 128])
+            } else {
+                Rgba([20, 200, 50, 128])
+            }
+        }));
+        let normalized = normalize(image, &NormalizeParams::default()).to_rgba8();
+        // Blue has a single value and is left alone
+        assert_eq!(normalized.get_pixel(0, 0), &Rgba([0, 0, 50, 128]));
+        assert_eq!(normalized.get_pixel(1, 0), &Rgba([255, 255, 50, 128]));
+    }
+
+    #[test]
+    fn test_normalize_rejects_clip_percent_out_of_range() {
+        for clip_percent in [-1.0, 50.0, f32::NAN] {
+            assert!(NormalizeParams { clip_percent }.validate().is_err());
+        }
+        assert!(NormalizeParams { clip_percent: 49.9 }.validate().is_ok());
+    }
 }
@@ -1,14 +1,73 @@
 //! Format operations for images.
 //!
-//! This module provides functions for format conversion and autorotation.
+//! This module provides functions for format conversion, encoding with encoder-specific
+//! options, and autorotation.
 
 use crate::http::errors::AppError;
-use crate::image::params::FormatConversionParams;
-use image::{DynamicImage, ImageFormat};
+use crate::image::format_support::format_name;
+use crate::image::operations::color::flatten;
+use crate::image::params::{FlattenParams, FormatConversionParams};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder};
+use image::{ColorType, DynamicImage, ImageEncoder, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io::Cursor;
 
+/// JPEG quality used when none is requested; matches the `image` crate's default.
+const DEFAULT_JPEG_QUALITY: u8 = 75;
+
+/// Encoder defaults for responses that don't ask for a quality (`[encoding]`).
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct EncodingConfig {
+    #[serde(default = "default_quality")]
+    pub default_jpeg_quality: u8,
+}
+
+impl Default for EncodingConfig {
+    fn default() -> Self {
+        Self {
+            default_jpeg_quality: default_quality(),
+        }
+    }
+}
+
+fn default_quality() -> u8 {
+    DEFAULT_JPEG_QUALITY
+}
+
+impl EncodingConfig {
+    /// Encoder options for a response in `output_format`, given those of the last `convert`.
+    ///
+    /// JPEG output without a `quality` gets `default_jpeg_quality`, whether a `convert` or
+    /// the `format` parameter picked JPEG. A `max_bytes` budget is left alone so its search
+    /// isn't capped by the default.
+    pub fn encoder_params(
+        &self,
+        params: Option<FormatConversionParams>,
+        output_format: ImageFormat,
+    ) -> Option<FormatConversionParams> {
+        if output_format != ImageFormat::Jpeg {
+            return params;
+        }
+        let mut params = params.unwrap_or_default();
+        if params.quality.is_none() && params.max_bytes.is_none() {
+            params.quality = Some(self.default_jpeg_quality.clamp(1, 100));
+        }
+        Some(params)
+    }
+}
+
+/// Lowest and highest JPEG quality tried when encoding for a `max_bytes` budget.
+const BUDGET_QUALITY_RANGE: (u8, u8) = (20, 95);
+
 /// Convert the image to a different format with optional quality parameter.
 ///
+/// Transparent images converted to a format without alpha (JPEG, BMP) are first composited
+/// over `params.background` (white by default); encoders would otherwise drop the alpha and
+/// leave whatever color the transparent pixels happen to hold, usually black.
+///
 /// # Arguments
 /// * `image` - The input image to convert.
 /// * `params` - The format conversion parameters (format, quality).
@@ -19,14 +78,11 @@ use std::io::Cursor;
 /// # Examples
 /// # use image::DynamicImage;
 /// # let img = DynamicImage::new_rgb8(100, 100);
-/// let converted = convert_format(img, &FormatConversionParams { format: "jpeg".to_string(), quality: Some(85) });
+/// let converted = convert_format(img, &FormatConversionParams { format: "jpeg".to_string(), quality: Some(85), ..Default::default() });
 pub fn convert_format(
     image: DynamicImage,
     params: &FormatConversionParams,
 ) -> Result<DynamicImage, AppError> {
-    let mut buffer = Vec::new();
-    let mut cursor = Cursor::new(&mut buffer);
-
     // Safely determine the image format without panicking
     let format = match params.format.to_lowercase().as_str() {
         "png" => ImageFormat::Png,
@@ -44,12 +100,425 @@ pub fn convert_format(
         }
     };
 
-    image
-        .write_to(&mut cursor, format)
-        .map_err(|e| AppError::ImageProcessingError(e.to_string()))?;
+    let image = if !supports_alpha(format) && image.color().has_alpha() {
+        let background = params
+            .background
+            .unwrap_or(FlattenParams::default().background);
+        flatten(image, &FlattenParams { background })
+    } else {
+        image
+    };
+    let buffer = encode_image(&image, format, Some(params))?;
     image::load_from_memory(&buffer).map_err(|e| AppError::ImageProcessingError(e.to_string()))
 }
 
+/// Whether `convert` keeps the alpha channel when writing `format`.
+fn supports_alpha(format: ImageFormat) -> bool {
+    !matches!(format, ImageFormat::Jpeg | ImageFormat::Bmp)
+}
+
+/// Encode the image in the given format, applying the encoder options from `params`.
+///
+/// `quality`, `progressive` and `max_bytes` apply to JPEG, `compression` (0-9) and `palette`
+/// to PNG.
+/// Options that don't apply to `format` are ignored, or rejected with `AppError::BadRequest`
+/// when `params.strict` is set. The WebP encoder is lossless only, so it has no quality to
+/// trade for size.
+///
+/// # Arguments
+/// * `image` - The image to encode.
+/// * `format` - The output format.
+/// * `params` - Optional encoder options, typically from the last `convert` operation.
+///
+/// # Returns
+/// The encoded bytes, or an error if the options are rejected or encoding fails.
+///
+/// PNG and TIFF keep 16 bits per channel; other formats get the image narrowed to 8 bits.
+pub fn encode_image(
+    image: &DynamicImage,
+    format: ImageFormat,
+    params: Option<&FormatConversionParams>,
+) -> Result<Vec<u8>, AppError> {
+    encode_image_with_quality(image, format, params).map(|(bytes, _)| bytes)
+}
+
+/// Like [`encode_image`], but also returns the JPEG quality picked for `params.max_bytes`.
+///
+/// The quality is `None` unless a byte budget was searched.
+pub fn encode_image_with_quality(
+    image: &DynamicImage,
+    format: ImageFormat,
+    params: Option<&FormatConversionParams>,
+) -> Result<(Vec<u8>, Option<u8>), AppError> {
+    let mut buffer = Vec::new();
+    let write_error = |e: image::ImageError| {
+        AppError::ImageProcessingError(format!("Failed to encode image: {}", e))
+    };
+    let encodable = encodable(image, format);
+    let image = encodable.as_ref();
+
+    let Some(params) = params else {
+        image
+            .write_to(&mut Cursor::new(&mut buffer), format)
+            .map_err(write_error)?;
+        return Ok((buffer, None));
+    };
+    reject_inapplicable_options(params, format)?;
+
+    match format {
+        ImageFormat::Jpeg => {
+            let progressive = params.progressive == Some(true);
+            if let Some(max_bytes) = params.max_bytes {
+                let (bytes, quality) =
+                    encode_jpeg_within(image, max_bytes, params.quality, progressive)?;
+                return Ok((bytes, Some(quality)));
+            }
+            buffer = encode_jpeg(
+                image,
+                params.quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+                progressive,
+            )?;
+        }
+        ImageFormat::Png => {
+            let compression = match params.compression {
+                None => CompressionType::Default,
+                Some(0..=3) => CompressionType::Fast,
+                Some(4..=6) => CompressionType::Default,
+                Some(_) => CompressionType::Best,
+            };
+            if let Some(colors) = params.palette {
+                return Ok((encode_indexed_png(image, colors, compression)?, None));
+            }
+            PngEncoder::new_with_quality(&mut buffer, compression, PngFilterType::Adaptive)
+                .write_image(
+                    image.as_bytes(),
+                    image.width(),
+                    image.height(),
+                    image.color(),
+                )
+                .map_err(write_error)?;
+        }
+        _ => image
+            .write_to(&mut Cursor::new(&mut buffer), format)
+            .map_err(write_error)?,
+    }
+    Ok((buffer, None))
+}
+
+/// Encodes an indexed-color PNG with a palette of at most `colors` colors (2-256).
+///
+/// Images that already have that few colors keep them exactly; others are reduced by
+/// [`median_cut`]. Transparency goes into the palette's `tRNS` chunk, and indices are packed
+/// into 1, 2 or 4 bits per pixel when the palette is small enough.
+fn encode_indexed_png(
+    image: &DynamicImage,
+    colors: u16,
+    compression: CompressionType,
+) -> Result<Vec<u8>, AppError> {
+    let encode_error = |e: png::EncodingError| {
+        AppError::ImageProcessingError(format!("Failed to encode image: {}", e))
+    };
+    let rgba = image.to_rgba8();
+    let max_colors = colors.clamp(2, 256) as usize;
+    let mut histogram: HashMap<[u8; 4], u32> = HashMap::new();
+    for pixel in rgba.pixels() {
+        *histogram.entry(pixel.0).or_default() += 1;
+    }
+    let mut distinct: Vec<([u8; 4], u32)> = histogram.into_iter().collect();
+    distinct.sort_unstable();
+    let palette = if distinct.len() <= max_colors {
+        distinct.iter().map(|&(color, _)| color).collect()
+    } else {
+        median_cut(&mut distinct, max_colors)
+    };
+    let index_of: HashMap<[u8; 4], u8> = distinct
+        .iter()
+        .map(|&(color, _)| (color, nearest_color(&palette, color)))
+        .collect();
+
+    let depth = match palette.len() {
+        0..=2 => png::BitDepth::One,
+        3..=4 => png::BitDepth::Two,
+        5..=16 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+    let bits = depth as usize;
+
+    let width = rgba.width() as usize;
+    let row_len = (width * bits).div_ceil(8);
+    let mut indices = vec![0u8; row_len * rgba.height() as usize];
+    for (y, row) in rgba.rows().enumerate() {
+        let packed = &mut indices[y * row_len..(y + 1) * row_len];
+        for (x, pixel) in row.enumerate() {
+            let index = index_of[&pixel.0];
+            let bit = x * bits;
+            // Pixels fill each byte from the most significant bit down
+            packed[bit / 8] |= index << (8 - bits - bit % 8);
+        }
+    }
+
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, rgba.width(), rgba.height());
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(depth);
+        encoder.set_compression(match compression {
+            CompressionType::Fast => png::Compression::Fast,
+            CompressionType::Best => png::Compression::Best,
+            _ => png::Compression::Default,
+        });
+        encoder.set_palette(
+            palette
+                .iter()
+                .flat_map(|color| [color[0], color[1], color[2]])
+                .collect::<Vec<_>>(),
+        );
+        let alphas: Vec<u8> = palette.iter().map(|color| color[3]).collect();
+        if alphas.iter().any(|&alpha| alpha < 255) {
+            encoder.set_trns(alphas);
+        }
+        let mut writer = encoder.write_header().map_err(encode_error)?;
+        writer.write_image_data(&indices).map_err(encode_error)?;
+    }
+    Ok(buffer)
+}
+
+/// Reduces `colors` (each with its pixel count) to at most `max_colors` by median cut.
+///
+/// The box with the widest channel range is split at the pixel-weighted median of that channel
+/// until there are `max_colors` boxes or none can be split; each box contributes its
+/// pixel-weighted average color.
+fn median_cut(colors: &mut [([u8; 4], u32)], max_colors: usize) -> Vec<[u8; 4]> {
+    /// Channel with the widest range in `colors`, and that range.
+    fn widest_channel(colors: &[([u8; 4], u32)]) -> (usize, u8) {
+        (0..4)
+            .map(|channel| {
+                let (min, max) = colors.iter().fold((u8::MAX, u8::MIN), |(min, max), c| {
+                    (min.min(c.0[channel]), max.max(c.0[channel]))
+                });
+                (channel, max.saturating_sub(min))
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap_or((0, 0))
+    }
+
+    let mut boxes = vec![colors];
+    while boxes.len() < max_colors {
+        let Some((widest, channel)) = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, colors)| (i, widest_channel(colors)))
+            .filter(|&(_, (_, range))| range > 0)
+            .max_by_key(|&(_, (_, range))| range)
+            .map(|(i, (channel, _))| (i, channel))
+        else {
+            break;
+        };
+        let colors = boxes.swap_remove(widest);
+        colors.sort_unstable_by_key(|c| c.0[channel]);
+        let total: u64 = colors.iter().map(|c| c.1 as u64).sum();
+        let mut seen = 0u64;
+        let median = colors
+            .iter()
+            .position(|c| {
+                seen += c.1 as u64;
+                seen * 2 >= total
+            })
+            .unwrap_or(0);
+        // Both halves keep at least one color
+        let (low, high) = colors.split_at_mut((median + 1).min(colors.len() - 1));
+        boxes.push(low);
+        boxes.push(high);
+    }
+
+    boxes
+        .iter()
+        .map(|colors| {
+            let total: u64 = colors.iter().map(|c| c.1 as u64).sum();
+            let mut average = [0u8; 4];
+            for (channel, value) in average.iter_mut().enumerate() {
+                let sum: u64 = colors
+                    .iter()
+                    .map(|c| c.0[channel] as u64 * c.1 as u64)
+                    .sum();
+                *value = ((sum + total / 2) / total) as u8;
+            }
+            average
+        })
+        .collect()
+}
+
+/// Index of the palette color closest to `color`.
+fn nearest_color(palette: &[[u8; 4]], color: [u8; 4]) -> u8 {
+    let distance = |entry: &[u8; 4]| -> u32 {
+        entry
+            .iter()
+            .zip(color)
+            .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+            .sum()
+    };
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map_or(0, |(index, _)| index as u8)
+}
+
+/// Returns true if `color` has more than 8 bits per channel.
+pub fn is_high_depth(color: ColorType) -> bool {
+    color.bytes_per_pixel() > color.channel_count()
+}
+
+/// Converts `image` to a color type the `format` encoder accepts.
+///
+/// PNG and TIFF keep 16 bits per channel (floating point becomes 16-bit); every other format
+/// gets deeper images narrowed to 8 bits.
+fn encodable(image: &DynamicImage, format: ImageFormat) -> Cow<'_, DynamicImage> {
+    let keeps_16_bit = matches!(format, ImageFormat::Png | ImageFormat::Tiff);
+    let converted = match image {
+        // Neither the PNG nor the TIFF encoder takes floating point samples
+        DynamicImage::ImageRgb32F(_) if keeps_16_bit => DynamicImage::ImageRgb16(image.to_rgb16()),
+        DynamicImage::ImageRgba32F(_) if keeps_16_bit => {
+            DynamicImage::ImageRgba16(image.to_rgba16())
+        }
+        // The TIFF encoder has no gray + alpha color types
+        DynamicImage::ImageLumaA8(_) if format == ImageFormat::Tiff => {
+            DynamicImage::ImageRgba8(image.to_rgba8())
+        }
+        DynamicImage::ImageLumaA16(_) if format == ImageFormat::Tiff => {
+            DynamicImage::ImageRgba16(image.to_rgba16())
+        }
+        _ if keeps_16_bit || !is_high_depth(image.color()) => return Cow::Borrowed(image),
+        DynamicImage::ImageLuma16(_) => DynamicImage::ImageLuma8(image.to_luma8()),
+        DynamicImage::ImageLumaA16(_) => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgb32F(_) => {
+            DynamicImage::ImageRgb8(image.to_rgb8())
+        }
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    };
+    Cow::Owned(converted)
+}
+
+/// Encodes a baseline JPEG, or a progressive one with `progressive`.
+///
+/// The `image` crate only writes baseline JPEG, so progressive images go through
+/// `jpeg-encoder`.
+fn encode_jpeg(image: &DynamicImage, quality: u8, progressive: bool) -> Result<Vec<u8>, AppError> {
+    let encode_error = |e: &dyn std::fmt::Display| {
+        AppError::ImageProcessingError(format!("Failed to encode image: {}", e))
+    };
+    let mut buffer = Vec::new();
+    if progressive {
+        let (width, height) = match (u16::try_from(image.width()), u16::try_from(image.height())) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(encode_error(&"JPEG dimensions are limited to 65535 pixels")),
+        };
+        let mut encoder = jpeg_encoder::Encoder::new(&mut buffer, quality.clamp(1, 100));
+        encoder.set_progressive(true);
+        match image {
+            DynamicImage::ImageLuma8(gray) => {
+                encoder.encode(gray.as_raw(), width, height, jpeg_encoder::ColorType::Luma)
+            }
+            _ => encoder.encode(
+                image.to_rgb8().as_raw(),
+                width,
+                height,
+                jpeg_encoder::ColorType::Rgb,
+            ),
+        }
+        .map_err(|e| encode_error(&e))?;
+        return Ok(buffer);
+    }
+    let mut encoder = JpegEncoder::new_with_quality(&mut buffer, quality.max(1));
+    match image {
+        DynamicImage::ImageLuma8(gray) => encoder.encode_image(gray),
+        _ => encoder.encode_image(&image.to_rgb8()),
+    }
+    .map_err(|e| encode_error(&e))?;
+    Ok(buffer)
+}
+
+/// Encodes a JPEG at the highest quality in `BUDGET_QUALITY_RANGE` (capped by `max_quality`)
+/// that is at most `max_bytes` long, found by binary search. Returns the bytes and quality.
+///
+/// When even the lowest quality is over budget, that attempt is returned and a warning logged.
+fn encode_jpeg_within(
+    image: &DynamicImage,
+    max_bytes: usize,
+    max_quality: Option<u8>,
+    progressive: bool,
+) -> Result<(Vec<u8>, u8), AppError> {
+    let (lowest, highest) = BUDGET_QUALITY_RANGE;
+    let highest = max_quality.map_or(highest, |quality| quality.clamp(lowest, highest));
+    let mut best = (encode_jpeg(image, lowest, progressive)?, lowest);
+    if best.0.len() > max_bytes {
+        tracing::warn!(
+            max_bytes,
+            size = best.0.len(),
+            quality = lowest,
+            "JPEG cannot meet the byte budget; using the lowest quality"
+        );
+        return Ok(best);
+    }
+
+    let (mut low, mut high) = (lowest + 1, highest);
+    while low <= high {
+        let quality = low + (high - low) / 2;
+        let bytes = encode_jpeg(image, quality, progressive)?;
+        if bytes.len() <= max_bytes {
+            best = (bytes, quality);
+            low = quality + 1;
+        } else {
+            high = quality - 1;
+        }
+    }
+    Ok(best)
+}
+
+/// In strict mode, fails on encoder options that the output format doesn't use.
+fn reject_inapplicable_options(
+    params: &FormatConversionParams,
+    format: ImageFormat,
+) -> Result<(), AppError> {
+    if !params.strict {
+        return Ok(());
+    }
+    let inapplicable = [
+        (
+            "quality",
+            params.quality.is_some() && format != ImageFormat::Jpeg,
+        ),
+        (
+            "progressive",
+            params.progressive.is_some() && format != ImageFormat::Jpeg,
+        ),
+        (
+            "compression",
+            params.compression.is_some() && format != ImageFormat::Png,
+        ),
+        (
+            "max_bytes",
+            params.max_bytes.is_some() && format != ImageFormat::Jpeg,
+        ),
+        (
+            "background",
+            params.background.is_some() && supports_alpha(format),
+        ),
+        (
+            "palette",
+            params.palette.is_some() && format != ImageFormat::Png,
+        ),
+    ];
+    match inapplicable.iter().find(|(_, set)| *set) {
+        Some((name, _)) => Err(AppError::BadRequest(format!(
+            "'{}' does not apply to {} output",
+            name,
+            format_name(format)
+        ))),
+        None => Ok(()),
+    }
+}
+
 /// Autorotate the image based on its EXIF orientation.
 ///
 /// # Arguments
@@ -64,8 +533,9 @@ pub fn autorotate(image: DynamicImage) -> DynamicImage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::image::params::FormatConversionParams;
+    use crate::image::params::{FormatConversionParams, Validate};
     use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, Rgba};
+    use serde_json::{json, Value};
 
     fn create_test_image(width: u32, height: u32) -> DynamicImage {
         DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
@@ -81,11 +551,356 @@ mod tests {
         let params = FormatConversionParams {
             format: "png".to_string(),
             quality: Some(90),
+            ..Default::default()
         };
         let converted_img = convert_format(img, &params).unwrap();
         assert_eq!(converted_img.color(), ColorType::Rgba8);
     }
 
+    #[test]
+    fn test_transparent_areas_become_the_background_in_jpeg() {
+        // Left half opaque red, right half fully transparent (and black underneath)
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(16, 16, |x, _| {
+            if x < 8 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        }));
+        let mut png = Vec::new();
+        img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let transparent_png = image::load_from_memory(&png).unwrap();
+        let params = FormatConversionParams {
+            format: "jpeg".to_string(),
+            quality: Some(95),
+            ..Default::default()
+        };
+
+        let jpeg = convert_format(transparent_png.clone(), &params).unwrap();
+        let transparent = jpeg.get_pixel(13, 8);
+        assert!(
+            transparent.0[..3].iter().all(|&c| c > 245),
+            "{:?}",
+            transparent
+        );
+        let opaque = jpeg.get_pixel(2, 8);
+        assert!(opaque[0] > 200 && opaque[1] < 50, "{:?}", opaque);
+
+        let custom = FormatConversionParams {
+            background: Some([0, 0, 255]),
+            ..params
+        };
+        let jpeg = convert_format(transparent_png, &custom).unwrap();
+        let transparent = jpeg.get_pixel(13, 8);
+        assert!(
+            transparent[0] < 20 && transparent[2] > 235,
+            "{:?}",
+            transparent
+        );
+    }
+
+    #[test]
+    fn test_background_rejected_for_alpha_formats_in_strict_mode() {
+        let params = FormatConversionParams {
+            format: "png".to_string(),
+            background: Some([0, 0, 0]),
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            convert_format(create_test_image(8, 8), &params),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    /// A 16-bit gray gradient whose values are not multiples of 257, so any trip through 8 bits
+    /// changes them.
+    fn gradient_luma16(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma16(ImageBuffer::from_fn(width, height, |x, y| {
+            image::Luma([(x * 1031 + y * 17 + 1) as u16])
+        }))
+    }
+
+    #[test]
+    fn test_16_bit_gray_survives_png_and_tiff_conversion() {
+        let original = gradient_luma16(40, 30);
+        for format in ["tiff", "png"] {
+            let params = FormatConversionParams {
+                format: format.to_string(),
+                ..Default::default()
+            };
+            let converted = convert_format(original.clone(), &params).unwrap();
+            assert_eq!(converted.color(), ColorType::L16, "{}", format);
+            assert_eq!(
+                converted.as_luma16().unwrap(),
+                original.as_luma16().unwrap(),
+                "{}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn test_16_bit_images_are_narrowed_for_8_bit_formats() {
+        let rgb16 = DynamicImage::ImageRgb16(ImageBuffer::from_pixel(
+            8,
+            8,
+            image::Rgb([65535u16, 32896, 0]),
+        ));
+        for format in [ImageFormat::Jpeg, ImageFormat::Bmp] {
+            let bytes = encode_image(&rgb16, format, None).unwrap();
+            let decoded = image::load_from_memory_with_format(&bytes, format).unwrap();
+            assert_eq!(decoded.color(), ColorType::Rgb8, "{:?}", format);
+        }
+        let float = DynamicImage::ImageRgba32F(rgb16.to_rgba32f());
+        let bytes = encode_image(&float, ImageFormat::Png, None).unwrap();
+        assert_eq!(
+            image::load_from_memory(&bytes).unwrap().color(),
+            ColorType::Rgba16
+        );
+    }
+
+    /// Structured but non-trivial content so compression levels make a difference.
+    fn create_pattern_image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(width, height, |x, y| {
+            image::Rgb([
+                (x * 7 + y * 3) as u8,
+                ((x ^ y) * 5) as u8,
+                ((x * y) % 251) as u8,
+            ])
+        }))
+    }
+
+    #[test]
+    fn test_progressive_jpeg_decodes_back() {
+        let img = create_pattern_image(64, 48);
+        let encode = |progressive| {
+            let params = FormatConversionParams {
+                format: "jpeg".to_string(),
+                quality: Some(90),
+                progressive: Some(progressive),
+                ..Default::default()
+            };
+            encode_image(&img, ImageFormat::Jpeg, Some(&params)).unwrap()
+        };
+        // Progressive images carry an SOF2 frame header, baseline ones SOF0
+        let has_marker = |bytes: &[u8], marker: u8| bytes.windows(2).any(|m| m == [0xFF, marker]);
+
+        let progressive = encode(true);
+        assert!(has_marker(&progressive, 0xC2));
+        assert!(!has_marker(&progressive, 0xC0));
+        let decoded = image::load_from_memory(&progressive).unwrap();
+        assert_eq!(decoded.dimensions(), (64, 48));
+
+        let baseline = encode(false);
+        assert!(has_marker(&baseline, 0xC0));
+        assert!(!has_marker(&baseline, 0xC2));
+    }
+
+    #[test]
+    fn test_png_compression_levels_change_size() {
+        let img = create_pattern_image(256, 256);
+        let encode = |level| {
+            let params = FormatConversionParams {
+                format: "png".to_string(),
+                compression: Some(level),
+                ..Default::default()
+            };
+            encode_image(&img, ImageFormat::Png, Some(&params)).unwrap()
+        };
+        let fast = encode(0);
+        let best = encode(9);
+        assert_ne!(fast.len(), best.len());
+        assert!(best.len() < fast.len());
+        for bytes in [fast, best] {
+            let decoded = image::load_from_memory(&bytes).unwrap();
+            assert_eq!(decoded.to_rgb8(), img.to_rgb8());
+        }
+    }
+
+    #[test]
+    fn test_palette_writes_a_smaller_indexed_png() {
+        // A gradient with a little noise, like a photographed sky
+        let img = DynamicImage::ImageRgb8(ImageBuffer::from_fn(128, 128, |x, y| {
+            let noise = (x * 7 + y * 13) % 5;
+            image::Rgb([(x * 2 + noise) as u8, (y * 2) as u8, (128 + noise) as u8])
+        }));
+        let png = |palette| {
+            let params = FormatConversionParams {
+                format: "png".to_string(),
+                palette,
+                ..Default::default()
+            };
+            encode_image(&img, ImageFormat::Png, Some(&params)).unwrap()
+        };
+        let truecolor = png(None);
+        let indexed = png(Some(16));
+        assert!(
+            indexed.len() < truecolor.len(),
+            "indexed {} bytes, truecolor {} bytes",
+            indexed.len(),
+            truecolor.len()
+        );
+
+        let decoder = png::Decoder::new(Cursor::new(&indexed));
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+        assert!(reader.info().palette.as_ref().unwrap().len() / 3 <= 16);
+
+        let decoded = image::load_from_memory(&indexed).unwrap();
+        assert_eq!(decoded.dimensions(), (128, 128));
+        let distinct: std::collections::HashSet<_> =
+            decoded.to_rgba8().pixels().map(|pixel| pixel.0).collect();
+        assert!(distinct.len() <= 16, "{} colors", distinct.len());
+    }
+
+    #[test]
+    fn test_palette_keeps_transparency() {
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(16, 16, |x, _| {
+            if x < 8 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 0])
+            }
+        }));
+        let params = FormatConversionParams {
+            format: "png".to_string(),
+            palette: Some(4),
+            ..Default::default()
+        };
+        let bytes = encode_image(&img, ImageFormat::Png, Some(&params)).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(2, 2)[3], 255);
+        assert_eq!(decoded.get_pixel(12, 2)[3], 0);
+    }
+
+    #[test]
+    fn test_palette_size_is_validated() {
+        for (colors, valid) in [(1, false), (2, true), (256, true), (257, false)] {
+            let params = FormatConversionParams {
+                format: "png".to_string(),
+                palette: Some(colors),
+                ..Default::default()
+            };
+            assert_eq!(params.validate().is_ok(), valid, "{}", colors);
+        }
+    }
+
+    #[test]
+    fn test_inapplicable_options_ignored_unless_strict() {
+        let lenient = FormatConversionParams {
+            format: "jpeg".to_string(),
+            compression: Some(9),
+            ..Default::default()
+        };
+        assert!(convert_format(create_test_image(8, 8), &lenient).is_ok());
+
+        let strict = FormatConversionParams {
+            strict: true,
+            ..lenient
+        };
+        match convert_format(create_test_image(8, 8), &strict) {
+            Err(AppError::BadRequest(msg)) => {
+                assert_eq!(msg, "'compression' does not apply to jpeg output")
+            }
+            other => panic!("Expected BadRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_max_bytes_picks_highest_quality_within_budget() {
+        let img = create_pattern_image(128, 128);
+        let params = |max_bytes| FormatConversionParams {
+            format: "jpeg".to_string(),
+            max_bytes: Some(max_bytes),
+            ..Default::default()
+        };
+        let size_at = |quality| encode_jpeg(&img, quality, false).unwrap().len();
+        let budget = (size_at(40) + size_at(70)) / 2;
+
+        let (bytes, quality) =
+            encode_image_with_quality(&img, ImageFormat::Jpeg, Some(&params(budget))).unwrap();
+        let quality = quality.unwrap();
+        assert!(bytes.len() <= budget);
+        assert!((40..70).contains(&quality), "quality {}", quality);
+
+        let capped = FormatConversionParams {
+            quality: Some(30),
+            ..params(usize::MAX)
+        };
+        let (_, quality) =
+            encode_image_with_quality(&img, ImageFormat::Jpeg, Some(&capped)).unwrap();
+        assert_eq!(quality, Some(30));
+    }
+
+    #[test]
+    fn test_impossible_max_bytes_returns_lowest_quality() {
+        let img = create_pattern_image(128, 128);
+        let params = FormatConversionParams {
+            format: "jpeg".to_string(),
+            max_bytes: Some(100),
+            ..Default::default()
+        };
+        let (bytes, quality) =
+            encode_image_with_quality(&img, ImageFormat::Jpeg, Some(&params)).unwrap();
+        assert_eq!(quality, Some(BUDGET_QUALITY_RANGE.0));
+        assert!(bytes.len() > 100);
+        assert_eq!(
+            bytes,
+            encode_jpeg(&img, BUDGET_QUALITY_RANGE.0, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_configured_default_quality_applies_to_jpeg_without_quality() {
+        let img = create_pattern_image(128, 128);
+        let encoding = EncodingConfig {
+            default_jpeg_quality: 60,
+        };
+        let convert =
+            |params: Value| Some(serde_json::from_value::<FormatConversionParams>(params).unwrap());
+        let quality = |params: Option<FormatConversionParams>, format| {
+            encoding
+                .encoder_params(params, format)
+                .and_then(|params| params.quality)
+        };
+        assert_eq!(
+            quality(convert(json!({"format": "jpeg"})), ImageFormat::Jpeg),
+            Some(60)
+        );
+        assert_eq!(
+            quality(
+                convert(json!({"format": "jpeg", "quality": 90})),
+                ImageFormat::Jpeg
+            ),
+            Some(90)
+        );
+        // JPEG picked by the `format` parameter, with or without a convert to another format
+        assert_eq!(quality(None, ImageFormat::Jpeg), Some(60));
+        assert_eq!(
+            quality(convert(json!({"format": "png"})), ImageFormat::Jpeg),
+            Some(60)
+        );
+        assert_eq!(
+            quality(convert(json!({"format": "png"})), ImageFormat::Png),
+            None
+        );
+        assert!(encoding.encoder_params(None, ImageFormat::WebP).is_none());
+        assert_eq!(
+            quality(
+                convert(json!({"format": "jpeg", "max_bytes": 5000})),
+                ImageFormat::Jpeg
+            ),
+            None
+        );
+
+        let params = encoding.encoder_params(None, ImageFormat::Jpeg).unwrap();
+        let configured = encode_image(&img, ImageFormat::Jpeg, Some(&params)).unwrap();
+        let crate_default = encode_image(&img, ImageFormat::Jpeg, None).unwrap();
+        assert!(configured.len() < crate_default.len());
+    }
+
     #[test]
     fn test_autorotate() {
         let img = create_test_image(100, 100);
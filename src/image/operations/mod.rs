@@ -2,25 +2,34 @@
 //!
 //! This module organizes all image processing operations into submodules:
 //! - [`transform`]: resizing, rotating, cropping, flipping, enlarging, extracting, zooming, smart cropping, thumbnails
-//! - [`color`]: grayscale, brightness/contrast, sharpen, blur
+//! - [`color`]: grayscale, brightness/contrast, sharpen, blur, flatten, auto-enhance, normalize, colorize
 //! - [`watermark`]: text and image watermarking
 //! - [`format`]: format conversion, autorotate
 //! - [`overlay`]: overlaying images, drawing text
+//! - [`shape`]: rounded corners, borders
+//! - [`background`]: solid and gradient backgrounds behind transparency
 //!
 //! Most common operations are re-exported at this level for ergonomic imports.
 
+pub mod background;
 pub mod color;
 pub mod format;
 pub mod overlay;
+pub mod shape;
 pub mod transform;
 pub mod watermark;
 
 // Re-export most common operations for ergonomic use
-pub use color::{adjust_brightness, adjust_contrast, blur, grayscale, sharpen};
+pub use background::background;
+pub use color::{
+    adjust_brightness, adjust_contrast, auto_enhance, blur, channel_gain, colorize, flatten, gamma,
+    grayscale, normalize, set_opacity, sharpen,
+};
 pub use transform::{
-    crop, enlarge, extract, flip_horizontal, flip_vertical, resize, rotate, smart_crop, thumbnail,
-    zoom,
+    crop, enlarge, extract, flip_horizontal, flip_vertical, mirror, resize, resize_percent, rotate,
+    smart_crop, thumbnail, trim, zoom,
 };
 // pub use watermark::watermark; // Not re-exported at top level unless part of public API
 pub use format::{autorotate, convert_format};
-// Note: overlay and draw_text are not re-exported; use overlay::overlay if needed internally.
+pub use shape::{border, round_corners};
+// Note: overlay and draw_text are not re-exported; the pipeline calls them through `overlay::`.
@@ -2,60 +2,60 @@
 //!
 //! This module provides functions for overlaying images and drawing text.
 
-use crate::http::errors::AppError;
-use image::Rgba;
-use image::{DynamicImage, GenericImage};
-use rusttype::{point, Font, Scale};
+use super::watermark::EMBEDDED_FONT;
+use crate::image::params::{BlendMode, DrawTextParams, OverlayParams};
+use image::DynamicImage;
+use rusttype::{point, Scale};
 
-/// Overlay one image on top of another at the given coordinates.
+/// Composites `overlay_image` onto `image` with its top-left corner at (`params.x`, `params.y`).
 ///
-/// # Arguments
-/// * `image` - The base image to overlay onto.
-/// * `overlay_image` - The image to overlay.
-/// * `x` - The x-coordinate for the overlay position.
-/// * `y` - The y-coordinate for the overlay position.
-///
-/// # Returns
-/// A new `DynamicImage` with the overlay applied, or an error if the operation fails.
-///
-/// # Examples
-/// # use image::DynamicImage;
-/// # let base = DynamicImage::new_rgb8(100, 100);
-/// # let overlay_img = DynamicImage::new_rgb8(50, 50);
-/// let result = overlay(base, overlay_img, 10, 10).unwrap();
-#[allow(dead_code)]
+/// Each overlay pixel is combined with the base pixel below it by `params.blend_mode`, and the
+/// result is mixed in by the overlay's alpha times `params.opacity`. Parts of the overlay
+/// outside the base are clipped, so the output keeps the base size; it has an alpha channel.
 pub(crate) fn overlay(
     image: DynamicImage,
-    overlay_image: DynamicImage,
-    x: u32,
-    y: u32,
-) -> Result<DynamicImage, AppError> {
-    let mut img = image.clone();
-    img.copy_from(&overlay_image, x, y)
-        .map_err(|e| AppError::ImageProcessingError(e.to_string()))?;
-    Ok(img)
+    overlay_image: &DynamicImage,
+    params: &OverlayParams,
+) -> DynamicImage {
+    let mut base = image.to_rgba8();
+    let top = overlay_image.to_rgba8();
+    let width = top.width().min(base.width().saturating_sub(params.x));
+    let height = top.height().min(base.height().saturating_sub(params.y));
+    for y in 0..height {
+        for x in 0..width {
+            let src = top.get_pixel(x, y);
+            let alpha = src[3] as f32 / 255.0 * params.opacity;
+            if alpha <= 0.0 {
+                continue;
+            }
+            let dst = base.get_pixel_mut(params.x + x, params.y + y);
+            for c in 0..3 {
+                let (below, above) = (dst[c] as f32, src[c] as f32);
+                let blended = match params.blend_mode {
+                    BlendMode::Normal => above,
+                    BlendMode::Multiply => below * above / 255.0,
+                    BlendMode::Screen => 255.0 - (255.0 - below) * (255.0 - above) / 255.0,
+                };
+                dst[c] = (below + (blended - below) * alpha).round() as u8;
+            }
+            dst[3] = (dst[3] as f32 + (255.0 - dst[3] as f32) * alpha).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(base)
 }
 
-/// Draws text onto the image at the specified position and font size.
-#[allow(dead_code)]
-pub(crate) fn draw_text(
-    image: DynamicImage,
-    text: &str,
-    x: u32,
-    y: u32,
-    font_size: u32,
-) -> DynamicImage {
-    let font_data = include_bytes!(concat!(
-        env!("CARGO_MANIFEST_DIR"),
-        "/assets/fonts/DejaVuSans.ttf"
-    ));
-    let font = Font::try_from_bytes(font_data as &[u8]).expect("Failed to load font");
-    let scale = Scale::uniform(font_size as f32);
-    let color = Rgba([255, 255, 255, 255]);
+/// Draws text with its top-left corner at (`params.x`, `params.y`) in the embedded DejaVu Sans.
+///
+/// Unlike `watermark`, the position is used as given: text running past the image edges is
+/// clipped rather than moved back inside.
+pub(crate) fn draw_text(image: DynamicImage, params: &DrawTextParams) -> DynamicImage {
+    let font = &*EMBEDDED_FONT;
+    let scale = Scale::uniform(params.font_size as f32);
+    let color = params.color;
     let mut rgba = image.to_rgba8();
     let v_metrics = font.v_metrics(scale);
-    let start = point(x as f32, y as f32 + v_metrics.ascent);
-    for glyph in font.layout(text, scale, start) {
+    let start = point(params.x as f32, params.y as f32 + v_metrics.ascent);
+    for glyph in font.layout(&params.text, scale, start) {
         if let Some(bb) = glyph.pixel_bounding_box() {
             glyph.draw(|gx, gy, gv| {
                 let px = bb.min.x + gx as i32;
@@ -66,7 +66,7 @@ pub(crate) fn draw_text(
                     for c in 0..3 {
                         pixel[c] = ((1.0 - gv) * pixel[c] as f32 + gv * color[c] as f32) as u8;
                     }
-                    pixel[3] = 255;
+                    pixel[3] = pixel[3].max((gv * 255.0) as u8);
                 }
             });
         }
@@ -79,6 +79,16 @@ mod tests {
     use super::*;
     use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
 
+    fn text_params(text: &str, x: u32, y: u32, font_size: u32) -> DrawTextParams {
+        DrawTextParams {
+            text: text.to_string(),
+            x,
+            y,
+            font_size,
+            color: [255, 255, 255],
+        }
+    }
+
     fn create_test_image(width: u32, height: u32) -> DynamicImage {
         DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
             width,
@@ -87,20 +97,86 @@ mod tests {
         ))
     }
 
+    fn overlay_params(x: u32, y: u32, blend_mode: BlendMode) -> OverlayParams {
+        OverlayParams {
+            x,
+            y,
+            opacity: 1.0,
+            blend_mode,
+            url: None,
+            source: None,
+        }
+    }
+
     #[test]
     fn test_overlay() {
         let img1 = create_test_image(100, 100);
         let img2 = create_test_image(50, 50);
-        let result = overlay(img1.clone(), img2, 25, 25);
-        assert!(result.is_ok());
-        let overlaid_img = result.unwrap();
+        let overlaid_img = overlay(img1, &img2, &overlay_params(25, 25, BlendMode::Normal));
         assert_eq!(overlaid_img.dimensions(), (100, 100));
     }
 
+    #[test]
+    fn test_overlay_normal_places_at_coordinates_and_clips() {
+        let base = create_test_image(10, 10);
+        let red = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(3, 3, Rgba([255, 0, 0, 255])));
+        let result = overlay(base, &red, &overlay_params(4, 5, BlendMode::Normal));
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        assert_eq!(result.get_pixel(4, 5), red);
+        assert_eq!(result.get_pixel(6, 7), red);
+        assert_eq!(result.get_pixel(3, 5), blue);
+        assert_eq!(result.get_pixel(7, 5), blue);
+        assert_eq!(result.get_pixel(4, 8), blue);
+
+        // Overhanging the bottom-right corner: the visible part is drawn, the rest dropped
+        let clipped = overlay(
+            result,
+            &create_test_image(5, 5),
+            &overlay_params(8, 8, BlendMode::Normal),
+        );
+        assert_eq!(clipped.dimensions(), (10, 10));
+        assert_eq!(clipped.get_pixel(9, 9), blue);
+    }
+
+    #[test]
+    fn test_overlay_blend_modes_and_opacity() {
+        let gray = |value: u8| {
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
+                4,
+                4,
+                Rgba([value, value, value, 255]),
+            ))
+        };
+        let multiply = overlay(
+            gray(200),
+            &gray(128),
+            &overlay_params(0, 0, BlendMode::Multiply),
+        );
+        // 200 * 128 / 255: multiplying darkens the base
+        assert_eq!(multiply.get_pixel(1, 1), Rgba([100, 100, 100, 255]));
+
+        let screen = overlay(
+            gray(100),
+            &gray(128),
+            &overlay_params(0, 0, BlendMode::Screen),
+        );
+        // 255 - 155 * 127 / 255: screening lightens it
+        assert_eq!(screen.get_pixel(1, 1), Rgba([178, 178, 178, 255]));
+
+        let half = OverlayParams {
+            opacity: 0.5,
+            ..overlay_params(0, 0, BlendMode::Normal)
+        };
+        assert_eq!(
+            overlay(gray(0), &gray(200), &half).get_pixel(1, 1),
+            Rgba([100, 100, 100, 255])
+        );
+    }
+
     #[test]
     fn test_draw_text_center() {
         let img = create_test_image(200, 100);
-        let result = draw_text(img, "Hello", 80, 40, 24);
+        let result = draw_text(img, &text_params("Hello", 80, 40, 24));
         // Scan a 20x20 region around (100, 50) for any non-black, fully opaque pixel
         let mut found = false;
         for dx in 90..110 {
@@ -121,7 +197,7 @@ mod tests {
     #[test]
     fn test_draw_text_top_left() {
         let img = create_test_image(200, 100);
-        let result = draw_text(img, "A", 0, 0, 32);
+        let result = draw_text(img, &text_params("A", 0, 0, 32));
         // Scan a 20x20 region in the top-left for any non-black, fully opaque pixel
         let mut found = false;
         for dx in 0..20 {
@@ -138,4 +214,30 @@ mod tests {
         }
         assert!(found, "No text pixels found in expected region");
     }
+
+    #[test]
+    fn test_draw_text_stays_near_requested_position() {
+        let img =
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(200, 100, Rgba([0u8, 0, 0, 255])));
+        let params = DrawTextParams {
+            color: [255, 0, 0],
+            ..text_params("Hi", 120, 60, 20)
+        };
+        let result = draw_text(img, &params);
+
+        let mut text_pixels = 0;
+        for (x, y, px) in result.pixels() {
+            if px[0] > 0 {
+                text_pixels += 1;
+                assert!(
+                    (120..160).contains(&x) && (60..85).contains(&y),
+                    "Text pixel at ({}, {}) is outside the requested area",
+                    x,
+                    y
+                );
+                assert_eq!((px[1], px[2]), (0, 0), "Text is not drawn in red");
+            }
+        }
+        assert!(text_pixels > 0, "No text pixels were drawn");
+    }
 }
@@ -0,0 +1,133 @@
+//! Shape operations for images.
+//!
+//! This module provides rounded corners and solid borders, e.g. for avatars.
+
+use crate::image::params::{BorderParams, RoundCornersParams};
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+/// Make the corners of the image transparent, leaving a rounded rectangle.
+///
+/// The radius is clamped to half the shorter side, so a large radius on a square image gives a
+/// circle. Edges are anti-aliased by pixel coverage.
+///
+/// # Arguments
+/// * `image` - The input image.
+/// * `params` - The corner radius in pixels.
+///
+/// # Returns
+/// A new RGBA `DynamicImage` with transparent corners.
+pub fn round_corners(image: DynamicImage, params: &RoundCornersParams) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let radius = params.radius.min(width / 2).min(height / 2);
+    if radius == 0 {
+        return DynamicImage::ImageRgba8(rgba);
+    }
+
+    let r = radius as f32;
+    for y in 0..height {
+        for x in 0..width {
+            // Corner circle centers sit `radius` in from each edge
+            let cx = if x < radius {
+                r
+            } else if x >= width - radius {
+                (width - radius) as f32
+            } else {
+                continue;
+            };
+            let cy = if y < radius {
+                r
+            } else if y >= height - radius {
+                (height - radius) as f32
+            } else {
+                continue;
+            };
+            let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy);
+            let coverage = (r - distance + 0.5).clamp(0.0, 1.0);
+            let pixel = rgba.get_pixel_mut(x, y);
+            pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Surround the image with a solid border, growing each side by `params.width` pixels.
+///
+/// # Arguments
+/// * `image` - The input image.
+/// * `params` - The border width and color.
+///
+/// # Returns
+/// A new `DynamicImage`, RGBA if the input has an alpha channel and RGB otherwise.
+pub fn border(image: DynamicImage, params: &BorderParams) -> DynamicImage {
+    let [r, g, b] = params.color;
+    let width = image.width().saturating_add(params.width.saturating_mul(2));
+    let height = image
+        .height()
+        .saturating_add(params.width.saturating_mul(2));
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+    imageops::overlay(
+        &mut canvas,
+        &image.to_rgba8(),
+        params.width as i64,
+        params.width as i64,
+    );
+    let bordered = DynamicImage::ImageRgba8(canvas);
+    if image.color().has_alpha() {
+        bordered
+    } else {
+        DynamicImage::ImageRgb8(bordered.into_rgb8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgb, RgbImage};
+
+    fn create_test_image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([0, 128, 255])))
+    }
+
+    #[test]
+    fn test_round_corners_clears_corners_and_keeps_center() {
+        let result = round_corners(
+            create_test_image(100, 60),
+            &RoundCornersParams { radius: 20 },
+        );
+        assert_eq!(result.dimensions(), (100, 60));
+
+        for (x, y) in [(0, 0), (99, 0), (0, 59), (99, 59), (3, 3), (96, 56)] {
+            assert_eq!(result.get_pixel(x, y)[3], 0, "corner ({}, {})", x, y);
+        }
+        for (x, y) in [(50, 30), (20, 0), (0, 30), (99, 30), (50, 59), (12, 12)] {
+            assert_eq!(result.get_pixel(x, y)[3], 255, "inside ({}, {})", x, y);
+        }
+        assert_eq!(result.get_pixel(50, 30), Rgba([0, 128, 255, 255]));
+    }
+
+    #[test]
+    fn test_round_corners_radius_is_clamped_to_a_circle() {
+        let result = round_corners(
+            create_test_image(40, 40),
+            &RoundCornersParams { radius: 500 },
+        );
+        assert_eq!(result.get_pixel(5, 5)[3], 0);
+        assert_eq!(result.get_pixel(20, 2)[3], 255);
+        assert_eq!(result.get_pixel(20, 20)[3], 255);
+    }
+
+    #[test]
+    fn test_border_grows_image_and_paints_edges() {
+        let params = BorderParams {
+            width: 5,
+            color: [255, 0, 0],
+        };
+        let result = border(create_test_image(20, 10), &params);
+        assert_eq!(result.dimensions(), (30, 20));
+        assert!(!result.color().has_alpha());
+        assert_eq!(result.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(result.get_pixel(29, 19), Rgba([255, 0, 0, 255]));
+        assert_eq!(result.get_pixel(5, 5), Rgba([0, 128, 255, 255]));
+    }
+}
@@ -1,25 +1,60 @@
 //! Transform operations for images.
 //!
-//! This module provides functions for resizing, rotating, cropping, flipping, enlarging, extracting, zooming, smart cropping, and creating thumbnails.
+//! This module provides functions for resizing, rotating, cropping, flipping, enlarging, extracting, zooming, smart cropping, trimming borders, and creating thumbnails.
+//!
+//! Parameters are validated by the pipeline executor before these run; out-of-range values
+//! that reach them anyway are clamped rather than panicking.
 
+use crate::http::errors::AppError;
 use crate::image::params::{
-    CropParams, ExtractParams, ResizeParams, RotateParams, SmartCropParams, ThumbnailParams,
-    Validate, ZoomParams,
+    CropParams, ExtractParams, MirrorAxis, MirrorParams, ResampleFilter, ResizeParams,
+    ResizePercentParams, RotateParams, SmartCropParams, SmartCropStrategy, ThumbnailMode,
+    ThumbnailParams, TrimParams, ZoomParams,
 };
-use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, Rgba};
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+use imageproc::gradients::sobel_gradients;
+
+/// Longest side of the downscaled copy `smart_crop` analyzes; larger images are scaled down.
+const SMART_CROP_ANALYSIS_SIZE: u32 = 256;
+/// Weight of saturation relative to edge energy for the `attention` strategy.
+const ATTENTION_SATURATION_WEIGHT: u64 = 4;
 
 /// Resize the image to the given dimensions.
 pub fn resize(image: DynamicImage, params: &ResizeParams) -> DynamicImage {
     image.resize_exact(params.width, params.height, FilterType::Lanczos3)
 }
 
-/// Rotate the image by the given degrees.
+/// Resize the image to a percentage of its current size; width and height scale independently.
+pub fn resize_percent(image: DynamicImage, params: &ResizePercentParams) -> DynamicImage {
+    let (width, height) = params.target_dimensions(image.dimensions());
+    image.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+/// Rotate the image clockwise by the given degrees, normalized modulo 360.
+///
+/// Without `keep_canvas` only multiples of 90 are lossless turns; `0` and `360` return the
+/// image unchanged. With `keep_canvas`, the content is rotated clockwise about the center and the output keeps
+/// the input dimensions; corners that leave the canvas are clipped and uncovered areas are
+/// transparent.
 pub fn rotate(image: DynamicImage, params: &RotateParams) -> DynamicImage {
-    match params.degrees {
-        90.0 => image.rotate90(),
-        180.0 => image.rotate180(),
-        270.0 => image.rotate270(),
-        _ => image.rotate90(),
+    let degrees = params.normalized_degrees();
+    if degrees == 0.0 {
+        return image;
+    }
+    if params.keep_canvas {
+        return DynamicImage::ImageRgba8(rotate_about_center(
+            &image.to_rgba8(),
+            degrees.to_radians(),
+            Interpolation::Bilinear,
+            Rgba([0, 0, 0, 0]),
+        ));
+    }
+    // Validation only admits multiples of 90 here
+    match degrees as u32 {
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        _ => image.rotate270(),
     }
 }
 
@@ -38,62 +73,219 @@ pub fn flip_vertical(image: DynamicImage) -> DynamicImage {
     image.flipv()
 }
 
-/// Enlarge the image using the given resize parameters.
-pub fn enlarge(image: DynamicImage, params: &ResizeParams) -> DynamicImage {
-    params.validate().expect("Invalid enlarge params");
+/// Mirror the image along `params.axis`; `both` is a 180 degree rotation.
+pub fn mirror(image: DynamicImage, params: &MirrorParams) -> DynamicImage {
+    match params.axis {
+        MirrorAxis::Horizontal => flip_horizontal(image),
+        MirrorAxis::Vertical => flip_vertical(image),
+        MirrorAxis::Both => flip_vertical(flip_horizontal(image)),
+    }
+}
+
+/// Enlarge the image to fit within the given dimensions, preserving its aspect ratio.
+///
+/// Enlarge only upscales: a target smaller than the image in either dimension is rejected
+/// with `AppError::BadRequest` rather than left as a silent no-op; use `resize` to shrink.
+pub fn enlarge(image: DynamicImage, params: &ResizeParams) -> Result<DynamicImage, AppError> {
     let (orig_w, orig_h) = image.dimensions();
-    if params.width > orig_w || params.height > orig_h {
-        image.resize(params.width, params.height, FilterType::Lanczos3)
-    } else {
-        image
+    if params.width < orig_w || params.height < orig_h {
+        return Err(AppError::BadRequest(format!(
+            "Enlarge target {}x{} is smaller than the {}x{} image; enlarge only upscales",
+            params.width, params.height, orig_w, orig_h
+        )));
     }
+    Ok(image.resize(params.width, params.height, FilterType::Lanczos3))
 }
 
 /// Extract a subregion from the image.
 pub fn extract(image: DynamicImage, params: &ExtractParams) -> DynamicImage {
-    params.validate().expect("Invalid extract params");
     let (img_w, img_h) = image.dimensions();
     let x = params.x.min(img_w);
     let y = params.y.min(img_h);
-    let w = params.width.min(img_w.saturating_sub(x));
-    let h = params.height.min(img_h.saturating_sub(y));
+    let w = params.width.max(1).min(img_w.saturating_sub(x));
+    let h = params.height.max(1).min(img_h.saturating_sub(y));
     image.crop_imm(x, y, w, h)
 }
 
 /// Zoom into the image by the given factor.
 pub fn zoom(image: DynamicImage, params: &ZoomParams) -> DynamicImage {
-    params.validate().expect("Invalid zoom params");
     let (orig_w, orig_h) = image.dimensions();
     let new_w = ((orig_w as f32) * params.factor).round().max(1.0) as u32;
     let new_h = ((orig_h as f32) * params.factor).round().max(1.0) as u32;
     image.resize(new_w, new_h, FilterType::Lanczos3)
 }
 
+/// Crop away uniform borders, e.g. the white margins of a scan.
+///
+/// A pixel is background when every RGB channel is within `params.tolerance` of the
+/// background color, which is `params.background` or else the top-left pixel; alpha is not
+/// compared. The image is cropped to the bounding box of the remaining pixels. An image that is
+/// background throughout is returned unchanged rather than cropped to nothing.
+pub fn trim(image: DynamicImage, params: &TrimParams) -> DynamicImage {
+    let rgb = image.to_rgb8();
+    if rgb.width() == 0 || rgb.height() == 0 {
+        return image;
+    }
+    let background = params.background.unwrap_or(rgb.get_pixel(0, 0).0);
+    // (left, top, right, bottom), inclusive
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in rgb.enumerate_pixels() {
+        let is_content = pixel
+            .0
+            .iter()
+            .zip(background)
+            .any(|(&value, background)| value.abs_diff(background) > params.tolerance);
+        if is_content {
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }
+            });
+        }
+    }
+    match bounds {
+        Some((left, top, right, bottom)) => {
+            image.crop_imm(left, top, right - left + 1, bottom - top + 1)
+        }
+        None => image,
+    }
+}
+
 /// Perform a smart crop on the image using the given parameters.
+///
+/// The `entropy` and `attention` strategies pick the window of the requested size that holds
+/// the most Sobel edge energy (`attention` also weighs in saturation), so detailed subjects
+/// are kept; ties resolve towards the center. `center`, or a window as large as the image,
+/// falls back to a plain center crop. Large images are downscaled for the analysis with
+/// `params.filter`.
 pub fn smart_crop(image: DynamicImage, params: &SmartCropParams) -> DynamicImage {
-    params.validate().expect("Invalid smart crop params");
     let (img_w, img_h) = image.dimensions();
-    let crop_w = params.width.min(img_w);
-    let crop_h = params.height.min(img_h);
-    let x = (img_w.saturating_sub(crop_w)) / 2;
-    let y = (img_h.saturating_sub(crop_h)) / 2;
+    let crop_w = params.width.max(1).min(img_w);
+    let crop_h = params.height.max(1).min(img_h);
+    let (x, y) =
+        if params.strategy == SmartCropStrategy::Center || (crop_w, crop_h) == (img_w, img_h) {
+            ((img_w - crop_w) / 2, (img_h - crop_h) / 2)
+        } else {
+            energy_crop_origin(&image, crop_w, crop_h, params.strategy, params.filter)
+        };
     image.crop_imm(x, y, crop_w, crop_h)
 }
 
+/// Top-left corner of the `crop_w` x `crop_h` window with the highest total energy.
+fn energy_crop_origin(
+    image: &DynamicImage,
+    crop_w: u32,
+    crop_h: u32,
+    strategy: SmartCropStrategy,
+    filter: ResampleFilter,
+) -> (u32, u32) {
+    let (img_w, img_h) = image.dimensions();
+    let scale = (img_w.max(img_h) as f32 / SMART_CROP_ANALYSIS_SIZE as f32).max(1.0);
+    let analysis = if scale > 1.0 {
+        image.resize_exact(
+            ((img_w as f32 / scale).round() as u32).max(1),
+            ((img_h as f32 / scale).round() as u32).max(1),
+            filter.into(),
+        )
+    } else {
+        image.clone()
+    };
+    let (width, height) = (analysis.width() as usize, analysis.height() as usize);
+    let win_w = ((crop_w as f32 / scale).round() as usize).clamp(1, width);
+    let win_h = ((crop_h as f32 / scale).round() as usize).clamp(1, height);
+
+    // Summed-area table of the energy map, so every window sum is O(1)
+    let energy = energy_map(&analysis, strategy);
+    let stride = width + 1;
+    let mut table = vec![0u64; stride * (height + 1)];
+    for y in 0..height {
+        let mut row_sum = 0u64;
+        for x in 0..width {
+            row_sum += energy[y * width + x];
+            table[(y + 1) * stride + x + 1] = table[y * stride + x + 1] + row_sum;
+        }
+    }
+    let window_sum = |x: usize, y: usize| {
+        table[(y + win_h) * stride + x + win_w] + table[y * stride + x]
+            - table[y * stride + x + win_w]
+            - table[(y + win_h) * stride + x]
+    };
+
+    let (center_x, center_y) = ((width - win_w) / 2, (height - win_h) / 2);
+    let mut best = (center_x, center_y);
+    let mut best_key = (window_sum(center_x, center_y), 0usize);
+    for y in 0..=height - win_h {
+        for x in 0..=width - win_w {
+            let distance = x.abs_diff(center_x) + y.abs_diff(center_y);
+            let sum = window_sum(x, y);
+            if sum > best_key.0 || (sum == best_key.0 && distance < best_key.1) {
+                best = (x, y);
+                best_key = (sum, distance);
+            }
+        }
+    }
+
+    let x = ((best.0 as f32 * scale).round() as u32).min(img_w - crop_w);
+    let y = ((best.1 as f32 * scale).round() as u32).min(img_h - crop_h);
+    (x, y)
+}
+
+/// Per-pixel energy, row-major: Sobel gradient magnitude, plus weighted saturation for
+/// `attention`.
+fn energy_map(image: &DynamicImage, strategy: SmartCropStrategy) -> Vec<u64> {
+    let gradients = sobel_gradients(&image.to_luma8());
+    let mut energy: Vec<u64> = gradients.pixels().map(|p| p[0] as u64).collect();
+    if strategy == SmartCropStrategy::Attention {
+        for (value, px) in energy.iter_mut().zip(image.to_rgb8().pixels()) {
+            let saturation = px[0].max(px[1]).max(px[2]) - px[0].min(px[1]).min(px[2]);
+            *value += saturation as u64 * ATTENTION_SATURATION_WEIGHT;
+        }
+    }
+    energy
+}
+
 /// Create a thumbnail of the image with the given parameters.
+///
+/// `Fit` keeps the whole image within the box; `Cover` fills the box and center-crops the
+/// overflow, so the output is exactly `width` x `height`. Scaling uses `params.filter`.
 pub fn thumbnail(image: DynamicImage, params: &ThumbnailParams) -> DynamicImage {
-    params.validate().expect("Invalid thumbnail params");
-    image.thumbnail(params.width, params.height)
+    let (box_width, box_height) = (params.width.max(1), params.height.max(1));
+    match params.mode {
+        ThumbnailMode::Fit => image.resize(box_width, box_height, params.filter.into()),
+        ThumbnailMode::Cover => {
+            // Crop the source to the box's aspect ratio first, so no oversized
+            // intermediate image is allocated
+            let (width, height) = image.dimensions();
+            let target_ratio = box_width as f64 / box_height as f64;
+            let (crop_width, crop_height) = if width as f64 / height as f64 > target_ratio {
+                ((height as f64 * target_ratio).round() as u32, height)
+            } else {
+                (width, (width as f64 / target_ratio).round() as u32)
+            };
+            let (crop_width, crop_height) =
+                (crop_width.max(1).min(width), crop_height.max(1).min(height));
+            image
+                .crop_imm(
+                    (width - crop_width) / 2,
+                    (height - crop_height) / 2,
+                    crop_width,
+                    crop_height,
+                )
+                .resize_exact(box_width, box_height, params.filter.into())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::image::params::{
-        CropParams, ExtractParams, ResizeParams, RotateParams, SmartCropParams, ThumbnailParams,
-        ZoomParams,
+        CropParams, ExtractParams, MirrorAxis, MirrorParams, ResampleFilter, ResizeParams,
+        ResizePercentParams, RotateParams, SmartCropParams, SmartCropStrategy, ThumbnailMode,
+        ThumbnailParams, TrimParams, Validate, ZoomParams,
     };
-    use image::{DynamicImage, ImageBuffer, Rgba};
+    use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage};
 
     fn create_test_image(width: u32, height: u32) -> DynamicImage {
         DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
@@ -117,11 +309,74 @@ mod tests {
     #[test]
     fn test_rotate() {
         let img = create_test_image(100, 100);
-        let params = RotateParams { degrees: 90.0 };
+        let params = RotateParams {
+            degrees: 90.0,
+            ..Default::default()
+        };
         let rotated = rotate(img, &params);
         assert_eq!(rotated.dimensions(), (100, 100));
     }
 
+    #[test]
+    fn test_rotate_normalizes_degrees() {
+        let mut img = RgbaImage::new(3, 2);
+        img.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        let img = DynamicImage::ImageRgba8(img);
+        let rotate_by = |degrees: f32| {
+            let params = RotateParams {
+                degrees,
+                ..Default::default()
+            };
+            assert!(params.validate().is_ok(), "{} degrees", degrees);
+            rotate(img.clone(), &params)
+        };
+
+        for degrees in [0.0, 360.0, -360.0] {
+            assert_eq!(rotate_by(degrees), img, "{} degrees", degrees);
+        }
+        // -90 is a 270 degree turn: the top-left pixel moves to the bottom-left
+        let counter_clockwise = rotate_by(-90.0);
+        assert_eq!(counter_clockwise.dimensions(), (2, 3));
+        assert_eq!(counter_clockwise.get_pixel(0, 2), Rgba([255, 0, 0, 255]));
+        // 450 is a 90 degree turn: the top-left pixel moves to the top-right
+        let clockwise = rotate_by(450.0);
+        assert_eq!(clockwise.dimensions(), (2, 3));
+        assert_eq!(clockwise.get_pixel(1, 0), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_rotate_rejects_non_right_angles_without_keep_canvas() {
+        let params = RotateParams {
+            degrees: 45.0,
+            ..Default::default()
+        };
+        assert!(params.validate().is_err());
+        let params = RotateParams {
+            degrees: f32::NAN,
+            keep_canvas: true,
+        };
+        assert!(params.validate().is_err());
+        let params = RotateParams {
+            degrees: -30.0,
+            keep_canvas: true,
+        };
+        assert!(params.validate().is_ok());
+    }
+
+    #[test]
+    fn test_rotate_keep_canvas() {
+        let img = create_test_image(120, 80);
+        let params = RotateParams {
+            degrees: 10.0,
+            keep_canvas: true,
+        };
+        let rotated = rotate(img, &params);
+        assert_eq!(rotated.dimensions(), (120, 80));
+        // The center stays covered while the clipped-in corners become transparent
+        assert_eq!(rotated.get_pixel(60, 40), Rgba([255, 0, 0, 255]));
+        assert_eq!(rotated.get_pixel(0, 0)[3], 0);
+    }
+
     #[test]
     fn test_crop() {
         let img = create_test_image(100, 100);
@@ -149,6 +404,49 @@ mod tests {
         assert_eq!(flipped.dimensions(), (100, 100));
     }
 
+    /// A 3x2 image whose pixels encode their own position as `[x, y, 0, 255]`.
+    fn create_position_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(3, 2, |x, y| {
+            Rgba([x as u8, y as u8, 0, 255])
+        }))
+    }
+
+    #[test]
+    fn test_mirror_each_axis() {
+        // Where each output pixel comes from
+        type Source = fn(u32, u32) -> (u32, u32);
+        let cases: [(MirrorAxis, Source); 3] = [
+            (MirrorAxis::Horizontal, |x, y| (2 - x, y)),
+            (MirrorAxis::Vertical, |x, y| (x, 1 - y)),
+            (MirrorAxis::Both, |x, y| (2 - x, 1 - y)),
+        ];
+        for (axis, source) in cases {
+            let mirrored = mirror(create_position_image(), &MirrorParams { axis });
+            assert_eq!(mirrored.dimensions(), (3, 2));
+            for (x, y, px) in mirrored.pixels() {
+                let (sx, sy) = source(x, y);
+                assert_eq!(
+                    px,
+                    Rgba([sx as u8, sy as u8, 0, 255]),
+                    "{:?} at {},{}",
+                    axis,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_mirror_rejects_unknown_axis() {
+        let parse =
+            |axis| serde_json::from_value::<MirrorParams>(serde_json::json!({ "axis": axis }));
+        assert_eq!(parse("both").unwrap().axis, MirrorAxis::Both);
+        assert!(parse("diagonal").is_err());
+        assert!(parse("Horizontal").is_err());
+        assert!(serde_json::from_value::<MirrorParams>(serde_json::json!({})).is_err());
+    }
+
     #[test]
     fn test_enlarge() {
         let img = create_test_image(50, 50);
@@ -156,8 +454,89 @@ mod tests {
             width: 100,
             height: 100,
         };
-        let enlarged = enlarge(img, &params);
+        let enlarged = enlarge(img, &params).unwrap();
         assert_eq!(enlarged.dimensions(), (100, 100));
+
+        // Upscaling keeps the aspect ratio within the target box
+        let enlarged = enlarge(create_test_image(50, 25), &params).unwrap();
+        assert_eq!(enlarged.dimensions(), (100, 50));
+    }
+
+    #[test]
+    fn test_enlarge_rejects_downscaling() {
+        for (width, height) in [(40, 40), (40, 100), (100, 40)] {
+            let params = ResizeParams { width, height };
+            match enlarge(create_test_image(50, 50), &params) {
+                Err(AppError::BadRequest(msg)) => {
+                    assert!(msg.contains("only upscales"), "{}", msg)
+                }
+                other => panic!(
+                    "expected BadRequest, got {:?}",
+                    other.map(|i| i.dimensions())
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_params_do_not_panic() {
+        // Parameters the executor would reject, sent straight to the operations
+        let sizes = [0, 1, 3, 49, 50, 51, u32::MAX];
+        let factors = [0.0, -1.0, f32::NAN, f32::MIN_POSITIVE];
+        let strategies = [
+            SmartCropStrategy::Entropy,
+            SmartCropStrategy::Attention,
+            SmartCropStrategy::Center,
+        ];
+        for (img_w, img_h) in [(1, 1), (7, 3), (50, 50)] {
+            let img = create_test_image(img_w, img_h);
+            for &width in &sizes {
+                for &height in &sizes {
+                    // Larger targets would only upscale to a huge allocation
+                    if width.max(height) <= 51 {
+                        let params = ResizeParams { width, height };
+                        let upscales = width >= img_w && height >= img_h;
+                        assert_eq!(enlarge(img.clone(), &params).is_ok(), upscales);
+                        for mode in [ThumbnailMode::Fit, ThumbnailMode::Cover] {
+                            let params = ThumbnailParams {
+                                width,
+                                height,
+                                mode,
+                                ..Default::default()
+                            };
+                            let thumb = thumbnail(img.clone(), &params);
+                            assert!(thumb.width() >= 1 && thumb.height() >= 1);
+                        }
+                    }
+                    for strategy in strategies {
+                        let params = SmartCropParams {
+                            width,
+                            height,
+                            strategy,
+                            ..Default::default()
+                        };
+                        let cropped = smart_crop(img.clone(), &params);
+                        assert!(cropped.width() <= img_w && cropped.height() <= img_h);
+                    }
+                    for &offset in &sizes {
+                        let params = ExtractParams {
+                            x: offset,
+                            y: offset,
+                            width,
+                            height,
+                        };
+                        let extracted = extract(img.clone(), &params);
+                        assert!(extracted.width() <= img_w && extracted.height() <= img_h);
+                    }
+                }
+            }
+            for factor in factors {
+                assert_eq!(
+                    zoom(img.clone(), &ZoomParams { factor }).dimensions(),
+                    (1, 1)
+                );
+            }
+        }
     }
 
     #[test]
@@ -188,19 +567,239 @@ mod tests {
             width: 50,
             height: 50,
             quality: None,
+            ..Default::default()
         };
         let cropped = smart_crop(img, &params);
         assert_eq!(cropped.dimensions(), (50, 50));
     }
 
+    /// Flat gray image with a high-detail checkerboard at x 150..180, y 60..90.
+    fn create_off_center_detail_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(image::ImageBuffer::from_fn(200, 100, |x, y| {
+            let in_detail = (150..180).contains(&x) && (60..90).contains(&y);
+            if in_detail && (x / 3 + y / 3) % 2 == 0 {
+                Rgba([255u8, 255u8, 255u8, 255u8])
+            } else if in_detail {
+                Rgba([0u8, 0u8, 0u8, 255u8])
+            } else {
+                Rgba([128u8, 128u8, 128u8, 255u8])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_smart_crop_entropy_keeps_detailed_region() {
+        let img = create_off_center_detail_image();
+        for strategy in [SmartCropStrategy::Entropy, SmartCropStrategy::Attention] {
+            let params = SmartCropParams {
+                width: 60,
+                height: 60,
+                quality: None,
+                strategy,
+                filter: ResampleFilter::default(),
+            };
+            let (x, y) = energy_crop_origin(&img, 60, 60, strategy, params.filter);
+            assert!(
+                x <= 150 && x + 60 >= 180 && y <= 60 && y + 60 >= 90,
+                "{:?} window at ({}, {}) misses the detail",
+                strategy,
+                x,
+                y
+            );
+            assert_eq!(smart_crop(img.clone(), &params).dimensions(), (60, 60));
+        }
+    }
+
+    #[test]
+    fn test_smart_crop_flat_image_and_center_strategy_crop_center() {
+        let flat = create_test_image(100, 80);
+        assert_eq!(
+            energy_crop_origin(
+                &flat,
+                40,
+                40,
+                SmartCropStrategy::Entropy,
+                ResampleFilter::Triangle
+            ),
+            (30, 20)
+        );
+
+        let params = SmartCropParams {
+            width: 60,
+            height: 60,
+            quality: None,
+            strategy: SmartCropStrategy::Center,
+            filter: ResampleFilter::default(),
+        };
+        let cropped = smart_crop(create_off_center_detail_image(), &params);
+        // The center window (70..130, 20..80) doesn't reach the checkerboard
+        assert!(cropped.to_rgba8().pixels().all(|p| p[0] == 128));
+    }
+
+    #[test]
+    fn test_smart_crop_large_image_is_analyzed_downscaled() {
+        // 4x the analysis size; the detail sits in the bottom-right corner
+        let img = DynamicImage::ImageRgba8(image::ImageBuffer::from_fn(1024, 512, |x, y| {
+            if x >= 900 && y >= 400 && (x / 8 + y / 8) % 2 == 0 {
+                Rgba([255u8, 255u8, 255u8, 255u8])
+            } else {
+                Rgba([0u8, 0u8, 0u8, 255u8])
+            }
+        }));
+        for filter in [ResampleFilter::Triangle, ResampleFilter::Lanczos3] {
+            let (x, y) = energy_crop_origin(&img, 200, 200, SmartCropStrategy::Entropy, filter);
+            assert!(x + 200 > 1000 && y + 200 > 480, "window at ({}, {})", x, y);
+            assert!(x + 200 <= 1024 && y + 200 <= 512);
+        }
+    }
+
+    #[test]
+    fn test_smart_crop_analysis_defaults_to_lanczos3() {
+        let params: SmartCropParams =
+            serde_json::from_value(serde_json::json!({"width": 10, "height": 10})).unwrap();
+        assert_eq!(params.filter, ResampleFilter::Lanczos3);
+        assert_eq!(SmartCropParams::default().filter, ResampleFilter::Lanczos3);
+        let params: SmartCropParams =
+            serde_json::from_value(serde_json::json!({"filter": "triangle"})).unwrap();
+        assert_eq!(params.filter, ResampleFilter::Triangle);
+    }
+
     #[test]
     fn test_thumbnail() {
         let img = create_test_image(100, 100);
         let params = ThumbnailParams {
             width: 20,
             height: 20,
+            ..Default::default()
         };
         let thumb = thumbnail(img, &params);
         assert_eq!(thumb.dimensions(), (20, 20));
     }
+
+    #[test]
+    fn test_thumbnail_cover_fills_and_crops_while_fit_fits() {
+        // Left half red, right half blue; a centered square crop keeps both halves
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(200, 100, |x, _| {
+            if x < 100 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 255])
+            }
+        }));
+        let params = |mode| ThumbnailParams {
+            width: 50,
+            height: 50,
+            mode,
+            ..Default::default()
+        };
+
+        let fit = thumbnail(img.clone(), &params(ThumbnailMode::Fit));
+        assert_eq!(fit.dimensions(), (50, 25));
+
+        let cover = thumbnail(img, &params(ThumbnailMode::Cover));
+        assert_eq!(cover.dimensions(), (50, 50));
+        // The middle 100x100 is cropped (50 red and 50 blue columns) and scaled to 50x50
+        assert_eq!(cover.get_pixel(2, 25), Rgba([255, 0, 0, 255]));
+        assert_eq!(cover.get_pixel(47, 25), Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_thumbnail_filter_only_changes_resampling() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 32, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 8) as u8, ((x + y) * 2) as u8])
+        }));
+        let params = |filter| ThumbnailParams {
+            width: 16,
+            height: 16,
+            filter,
+            ..Default::default()
+        };
+        let nearest = thumbnail(img.clone(), &params(ResampleFilter::Nearest));
+        let lanczos = thumbnail(img, &params(ResampleFilter::Lanczos3));
+        assert_eq!(nearest.dimensions(), (16, 8));
+        assert_eq!(lanczos.dimensions(), (16, 8));
+        assert_ne!(nearest.to_rgb8(), lanczos.to_rgb8());
+    }
+
+    /// A `width` x `height` white image with a `content` colored rectangle at `(x, y, w, h)`.
+    fn bordered_image(width: u32, height: u32, (x, y, w, h): (u32, u32, u32, u32)) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |px, py| {
+            if (x..x + w).contains(&px) && (y..y + h).contains(&py) {
+                Rgba([200, 30, 30, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_resize_percent() {
+        let percent = |width_pct, height_pct| {
+            resize_percent(
+                create_test_image(200, 100),
+                &ResizePercentParams {
+                    width_pct,
+                    height_pct,
+                },
+            )
+            .dimensions()
+        };
+        assert_eq!(percent(50.0, None), (100, 50));
+        assert_eq!(percent(50.0, Some(200.0)), (100, 200));
+        // Rounded, and never below one pixel
+        assert_eq!(percent(33.3, None), (67, 33));
+        assert_eq!(percent(0.01, None), (1, 1));
+    }
+
+    #[test]
+    fn test_resize_percent_rejects_non_positive_percentages() {
+        for (width_pct, height_pct) in [(0.0, None), (-50.0, None), (50.0, Some(0.0))] {
+            let params = ResizePercentParams {
+                width_pct,
+                height_pct,
+            };
+            assert!(params.validate().is_err(), "{:?}", params);
+        }
+        let params = ResizePercentParams {
+            width_pct: f32::NAN,
+            height_pct: None,
+        };
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn test_trim_crops_to_the_content() {
+        let image = bordered_image(40, 30, (7, 5, 12, 9));
+        let trimmed = trim(image.clone(), &TrimParams::default());
+        assert_eq!(trimmed.dimensions(), (12, 9));
+        assert_eq!(trimmed, image.crop_imm(7, 5, 12, 9));
+    }
+
+    #[test]
+    fn test_trim_honors_tolerance_and_background() {
+        // Off-white specks in the margin are trimmed away only within the tolerance
+        let mut image = bordered_image(40, 30, (7, 5, 12, 9)).to_rgba8();
+        image.put_pixel(1, 1, Rgba([250, 250, 250, 255]));
+        let image = DynamicImage::ImageRgba8(image);
+        let trim_with = |tolerance, background| {
+            trim(
+                image.clone(),
+                &TrimParams {
+                    tolerance,
+                    background,
+                },
+            )
+            .dimensions()
+        };
+        assert_eq!(trim_with(0, None), (18, 13));
+        assert_eq!(trim_with(10, None), (12, 9));
+        // With the content color as background, the white margins are what is kept
+        assert_eq!(trim_with(0, Some([200, 30, 30])), (40, 30));
+    }
+
+    #[test]
+    fn test_trim_leaves_uniform_images_unchanged() {
+        let image = create_test_image(16, 8);
+        assert_eq!(trim(image.clone(), &TrimParams::default()), image);
+    }
 }
@@ -2,21 +2,139 @@
 //!
 //! This module provides functions to apply text or image watermarks to images as part of the processing pipeline.
 
+use crate::http::errors::AppError;
 use crate::image::params::{WatermarkImageParams, WatermarkParams, WatermarkPosition};
-use image::{DynamicImage, Rgba};
-use image::{GenericImage, GenericImageView, RgbaImage};
+use crate::server::ServerConfig;
+use image::{imageops, DynamicImage, Rgba};
+use image::{GenericImageView, RgbaImage};
 use imageproc::drawing::draw_text_mut;
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+use once_cell::sync::Lazy;
 use rusttype::{point, Font, Scale};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Largest font file that will be loaded.
+const MAX_FONT_FILE_SIZE: u64 = 32 * 1024 * 1024;
+/// Fonts kept loaded at once; the cache forgets one when it is full.
+const MAX_CACHED_FONTS: usize = 16;
+
+/// Canonical `server.fonts_dir`, or `None` when only the embedded font may be used.
+static FONTS_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Font used when no `font_path` is given.
+pub(crate) static EMBEDDED_FONT: Lazy<Arc<Font<'static>>> = Lazy::new(|| {
+    let font_data = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/fonts/DejaVuSans.ttf"
+    ));
+    Arc::new(Font::try_from_bytes(font_data).expect("embedded DejaVuSans.ttf is a valid font"))
+});
+
+/// Fonts loaded from disk, keyed by their path below the fonts directory.
+static FONT_CACHE: Lazy<Mutex<HashMap<PathBuf, Arc<Font<'static>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Sets the directory `font_path` names are resolved in from `server.fonts_dir`. Later calls
+/// have no effect.
+pub fn init_fonts_dir(config: &ServerConfig) {
+    let dir = (!config.fonts_dir.is_empty()).then(|| {
+        Path::new(&config.fonts_dir)
+            .canonicalize()
+            .map_err(|e| {
+                tracing::warn!(
+                    "Fonts directory '{}' is unusable, only the embedded font is available: {}",
+                    config.fonts_dir,
+                    e
+                )
+            })
+            .ok()
+    });
+    if FONTS_DIR.set(dir.flatten()).is_err() {
+        tracing::warn!("Fonts directory already initialized; keeping the existing one");
+    }
+}
+
+/// Returns the font named by `font_path`, loading and caching it on first use, or the embedded
+/// font.
+fn load_font(font_path: Option<&str>) -> Result<Arc<Font<'static>>, AppError> {
+    match font_path {
+        None => Ok(EMBEDDED_FONT.clone()),
+        Some(name) => load_font_from(FONTS_DIR.get_or_init(|| None).as_deref(), name),
+    }
+}
+
+/// Loads the font `name`, a relative path below `fonts_dir`.
+///
+/// Every failure, including a missing fonts directory, a name escaping it, an oversized file
+/// or invalid font data, is the same `AppError::BadRequest`, so clients learn nothing about
+/// the server's files; the cause is logged.
+fn load_font_from(fonts_dir: Option<&Path>, name: &str) -> Result<Arc<Font<'static>>, AppError> {
+    let unavailable = |reason: &dyn std::fmt::Display| {
+        tracing::warn!("Watermark font '{}' rejected: {}", name, reason);
+        AppError::BadRequest(format!("Watermark font '{}' is not available", name))
+    };
+    let Some(fonts_dir) = fonts_dir else {
+        return Err(unavailable(&"no fonts directory is configured"));
+    };
+    let relative = Path::new(name);
+    if name.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(unavailable(&"not a plain relative path"));
+    }
+    let key: PathBuf = relative.components().collect();
+
+    let mut cache = FONT_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(font) = cache.get(&key) {
+        return Ok(font.clone());
+    }
+    // Symlinks may still point outside the directory
+    let path = fonts_dir
+        .join(&key)
+        .canonicalize()
+        .map_err(|e| unavailable(&e))?;
+    if !path.starts_with(fonts_dir) {
+        return Err(unavailable(&"resolves outside the fonts directory"));
+    }
+    let file = std::fs::File::open(&path).map_err(|e| unavailable(&e))?;
+    let metadata = file.metadata().map_err(|e| unavailable(&e))?;
+    if !metadata.is_file() || metadata.len() > MAX_FONT_FILE_SIZE {
+        return Err(unavailable(&"not a regular file within the size limit"));
+    }
+    let mut data = Vec::with_capacity(metadata.len() as usize);
+    // The file may grow after the check
+    file.take(MAX_FONT_FILE_SIZE)
+        .read_to_end(&mut data)
+        .map_err(|e| unavailable(&e))?;
+    let font = Font::try_from_vec(data).ok_or_else(|| unavailable(&"not a TrueType font"))?;
+    let font = Arc::new(font);
+    if cache.len() >= MAX_CACHED_FONTS {
+        if let Some(evicted) = cache.keys().next().cloned() {
+            cache.remove(&evicted);
+        }
+    }
+    cache.insert(key, font.clone());
+    Ok(font)
+}
 
 /// Applies a text watermark to the image with the specified parameters.
 /// Supports automatic positioning or exact coordinates, opacity, and font customization.
+/// The text is rendered with the font `params.font_path` names below `server.fonts_dir` when
+/// set, otherwise with the embedded DejaVu Sans.
 ///
 /// # Arguments
 /// * `image` - The input image to watermark.
-/// * `params` - The watermark parameters (text, opacity, position, font size, color, x, y).
+/// * `params` - The watermark parameters (text, opacity, position, font size, color, x, y, font path).
 ///
 /// # Returns
-/// A new `DynamicImage` with the watermark applied, or an error if the font cannot be loaded.
+/// A new `DynamicImage` with the watermark applied, or `AppError::BadRequest` if the font cannot be loaded.
 ///
 /// # Examples
 /// # use image::DynamicImage;
@@ -30,19 +148,15 @@ use rusttype::{point, Font, Scale};
 ///     color: [255, 255, 255],
 ///     x: None,
 ///     y: None,
+///     font_path: None,
 /// };
 /// let watermarked = watermark(&img, &wm_params)?;
 /// # Ok(())
 /// # }
-pub fn watermark(image: &DynamicImage, params: &WatermarkParams) -> Result<DynamicImage, String> {
+pub fn watermark(image: &DynamicImage, params: &WatermarkParams) -> Result<DynamicImage, AppError> {
+    let font = load_font(params.font_path.as_deref())?;
     // Always operate on RGBA8
     let mut rgba_image = image.to_rgba8();
-    // Load the font data from a byte array
-    let font_data = include_bytes!(concat!(
-        env!("CARGO_MANIFEST_DIR"),
-        "/assets/fonts/DejaVuSans.ttf"
-    ));
-    let font = Font::try_from_bytes(font_data).ok_or_else(|| "Failed to load font".to_string())?;
 
     let scale = Scale::uniform(params.font_size as f32);
     let color = Rgba([
@@ -58,7 +172,7 @@ pub fn watermark(image: &DynamicImage, params: &WatermarkParams) -> Result<Dynam
     let glyphs_width = glyphs
         .iter()
         .filter_map(|g| g.pixel_bounding_box().map(|bb| bb.max.x as f32))
-        .last()
+        .next_back()
         .unwrap_or(0.0)
         .ceil() as u32;
     let glyphs_height = (v_metrics.ascent - v_metrics.descent).ceil() as u32;
@@ -66,23 +180,17 @@ pub fn watermark(image: &DynamicImage, params: &WatermarkParams) -> Result<Dynam
     let (width, height) = rgba_image.dimensions();
 
     let (x, y) = match (params.x, params.y) {
-        (Some(x), Some(y)) => (x, y),
-        _ => match params.position {
-            WatermarkPosition::TopLeft => (margin, margin + glyphs_height),
-            WatermarkPosition::TopRight => (
-                width.saturating_sub(glyphs_width + margin),
-                margin + glyphs_height,
-            ),
-            WatermarkPosition::BottomLeft => (margin, height.saturating_sub(margin)),
-            WatermarkPosition::BottomRight => (
-                width.saturating_sub(glyphs_width + margin),
-                height.saturating_sub(margin),
-            ),
-            WatermarkPosition::Center => (
-                width.saturating_sub(glyphs_width) / 2,
-                height.saturating_sub(glyphs_height) / 2 + glyphs_height,
-            ),
-        },
+        // Manual coordinates are kept inside the image like computed ones
+        (Some(x), Some(y)) => (
+            x.min(width.saturating_sub(glyphs_width)),
+            y.min(height.saturating_sub(glyphs_height)),
+        ),
+        _ => anchor_position(
+            &params.position,
+            (width, height),
+            (glyphs_width, glyphs_height),
+            margin,
+        ),
     };
 
     draw_text_mut(
@@ -91,61 +199,151 @@ pub fn watermark(image: &DynamicImage, params: &WatermarkParams) -> Result<Dynam
         x as i32,
         y as i32,
         scale,
-        &font,
+        font.as_ref(),
         &params.text,
     );
 
     Ok(DynamicImage::ImageRgba8(rgba_image))
 }
 
-/// Overlays a watermark image onto the base image at the specified position and opacity.
-pub(crate) fn watermark_image(
-    mut image: DynamicImage,
+/// Overlays a placeholder logo (a white block a quarter of the image's size) onto the base
+/// image; used when no watermark image was uploaded.
+pub(crate) fn watermark_image(image: DynamicImage, params: &WatermarkImageParams) -> DynamicImage {
+    let (img_width, img_height) = image.dimensions();
+    let logo = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+        (img_width / 4).max(1),
+        (img_height / 4).max(1),
+        Rgba([255, 255, 255, 255]),
+    ));
+    watermark_image_with_logo(image, &logo, params)
+}
+
+/// Overlays the given logo onto the base image.
+///
+/// When `params.tile` is set, the logo is repeated across the whole image in a
+/// diagonal grid (every other row is shifted by half a step), leaving a gap of one
+/// logo width/height between tiles so the base stays visible. Otherwise the logo is
+/// placed once according to `params.position`. In both cases the logo is rotated by
+/// `params.angle` degrees and blended using its own alpha scaled by `params.opacity`.
+///
+/// # Arguments
+/// * `image` - The base image to watermark.
+/// * `logo` - The watermark image to stamp onto the base.
+/// * `params` - The watermark parameters (opacity, position, tile, angle).
+///
+/// # Returns
+/// A new RGBA `DynamicImage` with the watermark applied.
+pub(crate) fn watermark_image_with_logo(
+    image: DynamicImage,
+    logo: &DynamicImage,
     params: &WatermarkImageParams,
 ) -> DynamicImage {
-    // For demonstration, use a placeholder watermark image (solid color or pattern)
-    // In a real implementation, params would include the watermark image bytes or path
-    let (img_width, img_height) = image.dimensions();
-    let watermark_width = img_width / 4;
-    let watermark_height = img_height / 4;
-    let watermark = RgbaImage::from_pixel(
-        watermark_width,
-        watermark_height,
-        Rgba([255, 255, 255, (params.opacity * 255.0) as u8]),
-    );
+    let mut base = image.to_rgba8();
+    let logo = rotate_logo(&logo.to_rgba8(), params.angle);
+    let (img_width, img_height) = base.dimensions();
+    let (logo_width, logo_height) = logo.dimensions();
+    if logo_width == 0 || logo_height == 0 {
+        return DynamicImage::ImageRgba8(base);
+    }
+
+    if params.tile {
+        let step_x = logo_width.saturating_mul(2);
+        let step_y = logo_height.saturating_mul(2);
+        let mut row = 0u32;
+        let mut y = 0u32;
+        while y < img_height {
+            // Shift odd rows by half a step to produce the diagonal pattern
+            let mut x: i64 = if row % 2 == 1 {
+                -(step_x as i64) / 2
+            } else {
+                0
+            };
+            while x < img_width as i64 {
+                blend_logo(&mut base, &logo, x, y as i64, params.opacity);
+                x += step_x as i64;
+            }
+            y = y.saturating_add(step_y);
+            row += 1;
+        }
+    } else {
+        let (x, y) = anchor_position(
+            &params.position,
+            (img_width, img_height),
+            (logo_width, logo_height),
+            0,
+        );
+        blend_logo(&mut base, &logo, x as i64, y as i64, params.opacity);
+    }
+
+    DynamicImage::ImageRgba8(base)
+}
 
-    // Positioning logic (center by default)
-    let (x, y) = match params.position {
-        WatermarkPosition::TopLeft => (0, 0),
-        WatermarkPosition::TopRight => (img_width - watermark_width, 0),
-        WatermarkPosition::BottomLeft => (0, img_height - watermark_height),
+/// Top-left corner that places an `overlay`-sized box at `position` within `canvas`, `margin`
+/// pixels from the edges.
+///
+/// The result always lies in `0..=canvas - overlay` on each axis, so a box that fits stays fully
+/// inside the canvas (the margin shrinks if needed) and a box larger than the canvas is anchored
+/// at the origin and clipped by the caller.
+fn anchor_position(
+    position: &WatermarkPosition,
+    (canvas_width, canvas_height): (u32, u32),
+    (overlay_width, overlay_height): (u32, u32),
+    margin: u32,
+) -> (u32, u32) {
+    let max_x = canvas_width.saturating_sub(overlay_width);
+    let max_y = canvas_height.saturating_sub(overlay_height);
+    let (x, y) = match position {
+        WatermarkPosition::TopLeft => (margin, margin),
+        WatermarkPosition::TopRight => (max_x.saturating_sub(margin), margin),
+        WatermarkPosition::BottomLeft => (margin, max_y.saturating_sub(margin)),
         WatermarkPosition::BottomRight => {
-            (img_width - watermark_width, img_height - watermark_height)
+            (max_x.saturating_sub(margin), max_y.saturating_sub(margin))
         }
-        WatermarkPosition::Center => (
-            (img_width - watermark_width) / 2,
-            (img_height - watermark_height) / 2,
-        ),
+        WatermarkPosition::Center => (max_x / 2, max_y / 2),
     };
+    (x.min(max_x), y.min(max_y))
+}
 
-    // Blend watermark onto the image
-    for wy in 0..watermark_height {
-        for wx in 0..watermark_width {
-            let px = watermark.get_pixel(wx, wy);
-            let ix = x + wx;
-            let iy = y + wy;
-            if ix < img_width && iy < img_height {
-                let mut base_px = image.get_pixel(ix, iy);
-                // Alpha blend
-                let alpha = px[3] as f32 / 255.0;
-                for c in 0..3 {
-                    base_px[c] = ((1.0 - alpha) * base_px[c] as f32 + alpha * px[c] as f32) as u8;
-                }
-                image.put_pixel(ix, iy, base_px);
-            }
+/// Rotates the logo about its center on a canvas large enough to hold every corner.
+fn rotate_logo(logo: &RgbaImage, angle: f32) -> RgbaImage {
+    if angle % 360.0 == 0.0 {
+        return logo.clone();
+    }
+    let (w, h) = logo.dimensions();
+    let side = ((w as f32).hypot(h as f32)).ceil() as u32;
+    let mut canvas = RgbaImage::from_pixel(side, side, Rgba([0, 0, 0, 0]));
+    imageops::overlay(
+        &mut canvas,
+        logo,
+        ((side - w) / 2) as i64,
+        ((side - h) / 2) as i64,
+    );
+    rotate_about_center(
+        &canvas,
+        angle.to_radians(),
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+    )
+}
+
+/// Alpha-blends `logo` onto `base` with its top-left corner at (`x`, `y`), clipping to the base.
+fn blend_logo(base: &mut RgbaImage, logo: &RgbaImage, x: i64, y: i64, opacity: f32) {
+    let (img_width, img_height) = base.dimensions();
+    for (lx, ly, px) in logo.enumerate_pixels() {
+        let ix = x + lx as i64;
+        let iy = y + ly as i64;
+        if ix < 0 || iy < 0 || ix >= img_width as i64 || iy >= img_height as i64 {
+            continue;
+        }
+        let alpha = (px[3] as f32 / 255.0) * opacity;
+        if alpha <= 0.0 {
+            continue;
+        }
+        let base_px = base.get_pixel_mut(ix as u32, iy as u32);
+        for c in 0..3 {
+            base_px[c] = ((1.0 - alpha) * base_px[c] as f32 + alpha * px[c] as f32) as u8;
         }
     }
-    image
 }
 
 #[cfg(test)]
@@ -173,6 +371,7 @@ mod tests {
             color: [255, 255, 255],
             x: None,
             y: None,
+            font_path: None,
         };
         let result = watermark(&img, &params);
         assert!(result.is_ok());
@@ -189,6 +388,7 @@ mod tests {
             color: [255, 255, 255],
             x: None,
             y: None,
+            font_path: None,
         };
         let result = watermark(&img, &params);
         assert!(result.is_ok());
@@ -205,6 +405,7 @@ mod tests {
             color: [255, 255, 255],
             x: None,
             y: None,
+            font_path: None,
         };
         let result = watermark(&img, &params);
         assert!(result.is_ok());
@@ -221,6 +422,7 @@ mod tests {
             color: [255, 255, 255],
             x: None,
             y: None,
+            font_path: None,
         };
         let result = watermark(&img, &params);
         assert!(result.is_ok());
@@ -237,6 +439,7 @@ mod tests {
             color: [255, 255, 255],
             x: None,
             y: None,
+            font_path: None,
         };
         let result = watermark(&img, &params);
         assert!(result.is_ok());
@@ -253,6 +456,7 @@ mod tests {
             color: [255, 255, 255],
             x: None,
             y: None,
+            font_path: None,
         };
         let result = watermark(&img, &params);
         assert!(result.is_ok());
@@ -269,6 +473,7 @@ mod tests {
             color: [255, 255, 255],
             x: None,
             y: None,
+            font_path: None,
         };
         let result = watermark(&img, &params);
         assert!(result.is_ok());
@@ -285,17 +490,232 @@ mod tests {
             color: [255, 255, 255],
             x: None,
             y: None,
+            font_path: None,
         };
         let result = watermark(&img, &params);
         assert!(result.is_ok());
     }
 
+    fn watermark_params(font_path: Option<String>) -> WatermarkParams {
+        WatermarkParams {
+            text: "Font".to_string(),
+            opacity: 1.0,
+            position: WatermarkPosition::Center,
+            font_size: 32,
+            color: [255, 255, 255],
+            x: None,
+            y: None,
+            font_path,
+        }
+    }
+
+    #[test]
+    fn test_watermark_defaults_to_embedded_font() {
+        let img = create_test_image(200, 100);
+        let result = watermark(&img, &watermark_params(None)).unwrap();
+        assert!(result.pixels().any(|(_, _, px)| px[0] > 0));
+    }
+
+    /// A fonts directory holding `brand.ttf` (unique per test, as the cache is shared).
+    fn fonts_dir_with(name: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::copy(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/assets/fonts/DejaVuSans.ttf"),
+            dir.path().join(name),
+        )
+        .unwrap();
+        let canonical = dir.path().canonicalize().unwrap();
+        (dir, canonical)
+    }
+
+    #[test]
+    fn test_font_is_loaded_from_the_fonts_directory() {
+        let (_dir, fonts_dir) = fonts_dir_with("brand-load.ttf");
+        assert!(load_font_from(Some(&fonts_dir), "brand-load.ttf").is_ok());
+        // Served from the cache once the file is gone
+        std::fs::remove_file(fonts_dir.join("brand-load.ttf")).unwrap();
+        assert!(load_font_from(Some(&fonts_dir), "brand-load.ttf").is_ok());
+    }
+
+    #[test]
+    fn test_fonts_outside_the_fonts_directory_are_rejected() {
+        let (_dir, fonts_dir) = fonts_dir_with("brand-escape.ttf");
+        let absolute = fonts_dir.join("brand-escape.ttf");
+        for name in [
+            "../outside.ttf",
+            "./brand-escape.ttf",
+            absolute.to_str().unwrap(),
+            "",
+            "/dev/zero",
+        ] {
+            assert!(
+                matches!(
+                    load_font_from(Some(&fonts_dir), name),
+                    Err(AppError::BadRequest(_))
+                ),
+                "{}",
+                name
+            );
+        }
+        // Without a fonts directory no name is loaded
+        assert!(load_font_from(None, "brand-escape.ttf").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_out_of_the_fonts_directory_are_rejected() {
+        let (_dir, fonts_dir) = fonts_dir_with("brand-link.ttf");
+        let other = tempfile::tempdir().unwrap();
+        let target = other.path().join("secret.ttf");
+        std::fs::copy(fonts_dir.join("brand-link.ttf"), &target).unwrap();
+        std::os::unix::fs::symlink(&target, fonts_dir.join("link.ttf")).unwrap();
+        std::os::unix::fs::symlink("/dev/zero", fonts_dir.join("zero.ttf")).unwrap();
+        for name in ["link.ttf", "zero.ttf"] {
+            assert!(load_font_from(Some(&fonts_dir), name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_font_errors_do_not_reveal_which_files_exist() {
+        let (_dir, fonts_dir) = fonts_dir_with("brand-errors.ttf");
+        std::fs::write(fonts_dir.join("not-a-font.ttf"), b"definitely not a font").unwrap();
+        std::fs::create_dir(fonts_dir.join("subdir")).unwrap();
+        let message = |name: &str| match load_font_from(Some(&fonts_dir), name) {
+            Err(AppError::BadRequest(msg)) => msg.replace(name, "NAME"),
+            other => panic!("Expected BadRequest for {}, got {:?}", name, other.is_ok()),
+        };
+        let missing = message("missing.ttf");
+        assert_eq!(missing, "Watermark font 'NAME' is not available");
+        for name in ["not-a-font.ttf", "subdir", "../etc/passwd"] {
+            assert_eq!(message(name), missing, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_watermark_font_without_fonts_directory_is_bad_request() {
+        let img = create_test_image(200, 100);
+        let params = watermark_params(Some("brand.ttf".to_string()));
+        assert!(matches!(
+            watermark(&img, &params),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    const ALL_POSITIONS: [WatermarkPosition; 5] = [
+        WatermarkPosition::TopLeft,
+        WatermarkPosition::TopRight,
+        WatermarkPosition::BottomLeft,
+        WatermarkPosition::BottomRight,
+        WatermarkPosition::Center,
+    ];
+
+    #[test]
+    fn test_anchor_position_clamps_into_canvas() {
+        for position in &ALL_POSITIONS {
+            // Overlay larger than the canvas is anchored at the origin
+            assert_eq!(anchor_position(position, (50, 50), (80, 80), 10), (0, 0));
+            // Overlay that fits stays inside even when the margin does not fit
+            let (x, y) = anchor_position(position, (50, 50), (45, 45), 10);
+            assert!(x <= 5 && y <= 5, "{:?} placed at ({}, {})", position, x, y);
+        }
+        assert_eq!(
+            anchor_position(&WatermarkPosition::BottomRight, (200, 100), (50, 20), 10),
+            (140, 70)
+        );
+    }
+
+    #[test]
+    fn test_watermark_text_larger_than_image_every_position() {
+        let img = create_test_image(20, 10);
+        for position in ALL_POSITIONS {
+            let params = WatermarkParams {
+                text: "Much wider than the image".to_string(),
+                opacity: 1.0,
+                position,
+                font_size: 48,
+                color: [255, 255, 255],
+                x: None,
+                y: None,
+                font_path: None,
+            };
+            let result = watermark(&img, &params).unwrap();
+            assert_eq!(result.dimensions(), (20, 10));
+        }
+    }
+
+    #[test]
+    fn test_watermark_text_lands_in_requested_corner() {
+        let img = create_test_image(200, 100);
+        let lit = |image: &DynamicImage, xs: std::ops::Range<u32>, ys: std::ops::Range<u32>| {
+            image
+                .pixels()
+                .any(|(x, y, px)| xs.contains(&x) && ys.contains(&y) && px[0] > 0)
+        };
+        for (position, xs, ys) in [
+            (WatermarkPosition::TopLeft, 0..100, 0..50),
+            (WatermarkPosition::BottomRight, 100..200, 50..100),
+        ] {
+            let params = WatermarkParams {
+                text: "WM".to_string(),
+                opacity: 1.0,
+                position,
+                font_size: 24,
+                color: [255, 255, 255],
+                x: None,
+                y: None,
+                font_path: None,
+            };
+            let result = watermark(&img, &params).unwrap();
+            assert!(lit(&result, xs, ys), "text missing from its corner");
+        }
+    }
+
+    #[test]
+    fn test_watermark_manual_coordinates_out_of_bounds() {
+        let img = create_test_image(50, 50);
+        let params = WatermarkParams {
+            text: "Edge".to_string(),
+            opacity: 1.0,
+            position: WatermarkPosition::Center,
+            font_size: 16,
+            color: [255, 255, 255],
+            x: Some(u32::MAX),
+            y: Some(u32::MAX),
+            font_path: None,
+        };
+        let result = watermark(&img, &params).unwrap();
+        assert!(result.pixels().any(|(_, _, px)| px[0] > 0));
+    }
+
+    #[test]
+    fn test_watermark_image_larger_than_base_every_position() {
+        let img = create_test_image(50, 50);
+        let logo = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            80,
+            80,
+            Rgba([255u8, 255u8, 255u8, 255u8]),
+        ));
+        for position in ALL_POSITIONS {
+            let params = WatermarkImageParams {
+                opacity: 1.0,
+                position,
+                ..Default::default()
+            };
+            let result = watermark_image_with_logo(img.clone(), &logo, &params);
+            assert_eq!(result.dimensions(), (50, 50));
+            // Anchored at the origin, the logo covers the whole base
+            assert_eq!(result.get_pixel(0, 0)[0], 255);
+            assert_eq!(result.get_pixel(49, 49)[0], 255);
+        }
+    }
+
     #[test]
     fn test_watermark_image_center() {
         let img = create_test_image(200, 100);
         let params = WatermarkImageParams {
             opacity: 0.5,
             position: WatermarkPosition::Center,
+            ..Default::default()
         };
         let result = watermark_image(img, &params);
         // Check that the center region is not pure black (watermark applied)
@@ -309,9 +729,66 @@ mod tests {
         let params = WatermarkImageParams {
             opacity: 0.8,
             position: WatermarkPosition::TopLeft,
+            ..Default::default()
         };
         let result = watermark_image(img, &params);
         let px = result.get_pixel(10, 10);
         assert!(px[0] > 0 && px[3] == 255);
     }
+
+    #[test]
+    fn test_watermark_image_tiled_pattern() {
+        let img = create_test_image(100, 100);
+        let logo = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            10,
+            10,
+            Rgba([255u8, 255u8, 255u8, 255u8]),
+        ));
+        let params = WatermarkImageParams {
+            opacity: 1.0,
+            tile: true,
+            ..Default::default()
+        };
+        let result = watermark_image_with_logo(img, &logo, &params);
+        assert_eq!(result.dimensions(), (100, 100));
+        // Logo appears at several grid positions, including the shifted odd rows
+        for (x, y) in [(5, 5), (45, 5), (85, 45), (15, 25), (55, 65)] {
+            assert_eq!(
+                result.get_pixel(x, y)[0],
+                255,
+                "missing tile at ({}, {})",
+                x,
+                y
+            );
+        }
+        // Base remains visible in the gaps between tiles
+        for (x, y) in [(15, 5), (5, 15), (5, 25)] {
+            assert_eq!(
+                result.get_pixel(x, y)[0],
+                0,
+                "gap covered at ({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn test_watermark_image_tiled_rotated_keeps_dimensions() {
+        let img = create_test_image(120, 80);
+        let logo = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            16,
+            8,
+            Rgba([255u8, 255u8, 255u8, 255u8]),
+        ));
+        let params = WatermarkImageParams {
+            opacity: 0.5,
+            tile: true,
+            angle: 45.0,
+            ..Default::default()
+        };
+        let result = watermark_image_with_logo(img, &logo, &params);
+        assert_eq!(result.dimensions(), (120, 80));
+        assert!(result.pixels().any(|(_, _, px)| px[0] > 0));
+    }
 }
@@ -1,5 +1,6 @@
 use crate::http::errors::ImageError;
-use serde::Deserialize;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
 
 /// Trait for validating operation parameters. Implemented by all parameter structs.
 pub trait Validate {
@@ -10,7 +11,7 @@ pub trait Validate {
 /// Parameters for resizing an image.
 /// - width: target width (must be > 0)
 /// - height: target height (must be > 0)
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct ResizeParams {
     #[serde(default = "default_dimension")]
     pub width: u32,
@@ -35,22 +36,48 @@ impl Validate for ResizeParams {
 }
 
 /// Parameters for rotating an image.
-/// - degrees: rotation angle (0 <= degrees < 360)
-#[derive(Debug, Deserialize, Default)]
+/// - degrees: clockwise rotation angle, normalized modulo 360 (so `-90`, `270` and `630` are
+///   equivalent); must be a multiple of 90 unless `keep_canvas` is set
+/// - keep_canvas: rotate about the center within the original bounds, clipping the corners
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct RotateParams {
     #[serde(default = "default_degrees")]
     pub degrees: f32,
+    #[serde(default)]
+    pub keep_canvas: bool,
 }
 
 fn default_degrees() -> f32 {
     90.0
 }
 
+impl RotateParams {
+    /// The angle folded into `0..360`.
+    pub fn normalized_degrees(&self) -> f32 {
+        let degrees = self.degrees.rem_euclid(360.0);
+        // rem_euclid can round up to exactly 360 for tiny negative inputs
+        if degrees >= 360.0 {
+            0.0
+        } else {
+            degrees
+        }
+    }
+
+    /// Whether the rotation swaps width and height of the output.
+    pub fn swaps_dimensions(&self) -> bool {
+        !self.keep_canvas && matches!(self.normalized_degrees() as u32, 90 | 270)
+    }
+}
+
 impl Validate for RotateParams {
     fn validate(&self) -> Result<(), ImageError> {
-        if self.degrees < 0.0 || self.degrees >= 360.0 {
+        if !self.degrees.is_finite() {
+            Err(ImageError::InvalidDegrees(
+                "Degrees must be a finite number.".to_string(),
+            ))
+        } else if !self.keep_canvas && self.normalized_degrees() % 90.0 != 0.0 {
             Err(ImageError::InvalidDegrees(
-                "Degrees must be between 0 and 360.".to_string(),
+                "Degrees must be a multiple of 90 unless keep_canvas is set.".to_string(),
             ))
         } else {
             Ok(())
@@ -58,10 +85,33 @@ impl Validate for RotateParams {
     }
 }
 
+/// Parameters for mirroring an image.
+/// - axis: `horizontal` (left-right, like `flop`), `vertical` (top-bottom, like `flip`) or
+///   `both`
+#[derive(Debug, Deserialize)]
+pub struct MirrorParams {
+    pub axis: MirrorAxis,
+}
+
+/// Which way `mirror` flips an image; unknown axes are rejected when the params are parsed.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MirrorAxis {
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl Validate for MirrorParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        Ok(())
+    }
+}
+
 /// Parameters for cropping an image.
 /// - x, y: top-left corner
 /// - width, height: crop size (must be > 0)
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct CropParams {
     #[serde(default)]
     pub x: u32,
@@ -92,6 +142,8 @@ impl Validate for CropParams {
 /// - font_size: > 0
 /// - color: [R, G, B]
 /// - x, y: optional manual position
+/// - font_path: optional TTF font to use instead of the embedded one, as a relative path below
+///   `server.fonts_dir`
 #[derive(Debug, Deserialize, Default)]
 pub struct WatermarkParams {
     #[serde(default)]
@@ -108,6 +160,9 @@ pub struct WatermarkParams {
     pub x: Option<u32>, // If None, use position for automatic placement
     #[serde(default)]
     pub y: Option<u32>,
+    /// Path of a TTF font on the server; the embedded DejaVu Sans is used when unset.
+    #[serde(default)]
+    pub font_path: Option<String>,
 }
 
 fn default_opacity() -> f32 {
@@ -161,13 +216,32 @@ pub enum WatermarkPosition {
 
 /// Parameters for format conversion.
 /// - format: target format (e.g., "png", "jpeg")
-/// - quality: optional, 0-100
-#[derive(Debug, Deserialize, Default)]
+/// - quality: optional, 0-100 (JPEG)
+/// - progressive: optional, request progressive encoding (JPEG)
+/// - compression: optional, 0-9 (PNG)
+/// - max_bytes: optional byte budget; picks the highest JPEG quality that fits (> 0)
+/// - strict: reject options that don't apply to `format` instead of ignoring them
+/// - background: optional [R, G, B] that transparency is composited over for formats without
+///   alpha (JPEG, BMP); defaults to white
+/// - palette: optional, write an indexed PNG with at most this many colors (2-256)
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct FormatConversionParams {
     #[serde(default = "default_format")]
     pub format: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub quality: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progressive: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>,
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<[u8; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<u16>,
 }
 
 fn default_format() -> String {
@@ -183,6 +257,25 @@ impl Validate for FormatConversionParams {
                 ));
             }
         }
+        if let Some(compression) = self.compression {
+            if compression > 9 {
+                return Err(ImageError::InvalidParameters(
+                    "PNG compression must be between 0 and 9.".to_string(),
+                ));
+            }
+        }
+        if self.max_bytes == Some(0) {
+            return Err(ImageError::InvalidParameters(
+                "max_bytes must be > 0.".to_string(),
+            ));
+        }
+        if let Some(colors) = self.palette {
+            if !(2..=256).contains(&colors) {
+                return Err(ImageError::InvalidParameters(
+                    "Palette size must be between 2 and 256 colors.".to_string(),
+                ));
+            }
+        }
         Ok(())
     }
 }
@@ -190,6 +283,8 @@ impl Validate for FormatConversionParams {
 /// Parameters for smart cropping.
 /// - width, height: target size (must be > 0)
 /// - quality: optional
+/// - strategy: optional, `center`, `entropy` (default) or `attention`
+/// - filter: optional resampling filter for the analysis downscale, see `ResampleFilter`
 #[derive(Debug, Deserialize, Default)]
 pub struct SmartCropParams {
     #[serde(default = "default_dimension")]
@@ -199,6 +294,23 @@ pub struct SmartCropParams {
     #[serde(default)]
     #[allow(dead_code)]
     pub quality: Option<u8>,
+    #[serde(default)]
+    pub strategy: SmartCropStrategy,
+    #[serde(default)]
+    pub filter: ResampleFilter,
+}
+
+/// How `smart_crop` chooses the crop window.
+/// - center: plain center crop
+/// - entropy: the window with the most edge energy (default)
+/// - attention: like entropy, but also favoring saturated regions such as skin and colorful subjects
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmartCropStrategy {
+    Center,
+    #[default]
+    Entropy,
+    Attention,
 }
 
 impl Validate for SmartCropParams {
@@ -243,10 +355,10 @@ impl Validate for AdjustContrastParams {
 /// Parameters for Gaussian blur.
 /// - sigma: blur radius (> 0)
 /// - minampl: optional, minimum amplitude
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct BlurParams {
     pub sigma: f32,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub minampl: Option<f32>,
 }
 
@@ -270,12 +382,58 @@ impl Validate for BlurParams {
 
 /// Parameters for thumbnail creation.
 /// - width, height: target size (must be > 0)
-#[derive(Debug, Deserialize, Default)]
+/// - mode: optional, `fit` (default) or `cover`
+/// - filter: optional resampling filter, see `ResampleFilter`
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct ThumbnailParams {
     #[serde(default = "default_dimension")]
     pub width: u32,
     #[serde(default = "default_dimension")]
     pub height: u32,
+    #[serde(default)]
+    pub mode: ThumbnailMode,
+    #[serde(default)]
+    pub filter: ResampleFilter,
+}
+
+/// Resampling filter for scaling steps, from fastest to sharpest.
+/// - nearest: nearest neighbor, blocky but fastest
+/// - triangle: bilinear
+/// - catmullrom: bicubic
+/// - gaussian: soft
+/// - lanczos3: sharpest and slowest (default)
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResampleFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResampleFilter> for FilterType {
+    fn from(filter: ResampleFilter) -> Self {
+        match filter {
+            ResampleFilter::Nearest => FilterType::Nearest,
+            ResampleFilter::Triangle => FilterType::Triangle,
+            ResampleFilter::CatmullRom => FilterType::CatmullRom,
+            ResampleFilter::Gaussian => FilterType::Gaussian,
+            ResampleFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// How `thumbnail` maps the image onto the requested box.
+/// - fit: scale to fit within the box, keeping the aspect ratio (one side may come out smaller)
+/// - cover: scale to fill the box, then center-crop the overflow to exactly the requested size
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailMode {
+    #[default]
+    Fit,
+    Cover,
 }
 
 impl Validate for ThumbnailParams {
@@ -329,25 +487,85 @@ fn default_zoom_factor() -> f32 {
 
 impl Validate for ZoomParams {
     fn validate(&self) -> Result<(), ImageError> {
-        if self.factor <= 0.0 {
+        if !(self.factor > 0.0 && self.factor.is_finite()) {
             return Err(ImageError::InvalidParameters(
-                "Zoom factor must be > 0".to_string(),
+                "Zoom factor must be a finite number > 0".to_string(),
             ));
         }
         Ok(())
     }
 }
 
+/// Parameters for resizing by percentage of the current size.
+/// - width_pct: target width in percent of the current width (> 0)
+/// - height_pct: target height in percent of the current height (> 0); defaults to `width_pct`
+#[derive(Debug, Deserialize)]
+pub struct ResizePercentParams {
+    pub width_pct: f32,
+    #[serde(default)]
+    pub height_pct: Option<f32>,
+}
+
+impl ResizePercentParams {
+    /// Output size for an image of `width` x `height`, rounded and at least 1x1.
+    pub fn target_dimensions(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let scaled = |side: u32, pct: f32| {
+            (side as f64 * pct as f64 / 100.0)
+                .round()
+                .clamp(1.0, u32::MAX as f64) as u32
+        };
+        (
+            scaled(width, self.width_pct),
+            scaled(height, self.height_pct.unwrap_or(self.width_pct)),
+        )
+    }
+}
+
+impl Validate for ResizePercentParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        for pct in std::iter::once(self.width_pct).chain(self.height_pct) {
+            if !(pct > 0.0 && pct.is_finite()) {
+                return Err(ImageError::InvalidParameters(
+                    "Resize percentages must be finite numbers > 0".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Parameters for image watermarking.
 /// - opacity: 0.0-1.0
-/// - position: WatermarkPosition
+/// - position: WatermarkPosition (ignored when tiling)
+/// - tile: repeat the logo across the whole image in a diagonal grid
+/// - angle: rotation of the logo in degrees (-360..=360)
+/// - asset: name of the uploaded image to use as the logo (default: the `watermark` field, or
+///   a placeholder block when none was uploaded)
+/// - source: index of the uploaded `image` field to use as the logo instead (>= 1)
 #[derive(Debug, Deserialize, Default)]
 pub struct WatermarkImageParams {
     #[serde(default = "default_opacity")]
     pub opacity: f32,
     #[serde(default)]
     pub position: WatermarkPosition,
-    // In a real implementation, you would also have a field for the watermark image itself (e.g., as a path or bytes)
+    #[serde(default)]
+    pub tile: bool,
+    #[serde(default)]
+    pub angle: f32,
+    #[serde(default)]
+    pub asset: Option<String>,
+    #[serde(default)]
+    pub source: Option<usize>,
+}
+
+/// Checks a `source` param: index 0 is the image being processed, so it can't be composited.
+fn validate_source(source: Option<usize>) -> Result<(), ImageError> {
+    if source == Some(0) {
+        return Err(ImageError::InvalidParameters(
+            "source must be >= 1; image 0 is the one being processed".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 impl Validate for WatermarkImageParams {
@@ -357,6 +575,338 @@ impl Validate for WatermarkImageParams {
                 "Opacity must be between 0.0 and 1.0".to_string(),
             ));
         }
+        if !self.angle.is_finite() || self.angle.abs() > 360.0 {
+            return Err(ImageError::InvalidDegrees(
+                "Watermark angle must be between -360 and 360.".to_string(),
+            ));
+        }
+        if self.source.is_some() && self.asset.is_some() {
+            return Err(ImageError::InvalidParameters(
+                "Watermark takes either 'asset' or 'source', not both".to_string(),
+            ));
+        }
+        validate_source(self.source)
+    }
+}
+
+/// Parameters for compositing a second image onto the base image.
+/// - x, y: top-left corner of the overlay on the base, in pixels (parts outside are clipped)
+/// - opacity: 0.0-1.0 (default 1.0), multiplied with the overlay's own alpha
+/// - blend_mode: `normal` (default), `multiply` or `screen`
+/// - url: fetch the overlay from this URL instead of the uploaded `overlay` field
+/// - source: use the uploaded `image` field with this index instead (>= 1)
+#[derive(Debug, Deserialize, Default)]
+pub struct OverlayParams {
+    #[serde(default)]
+    pub x: u32,
+    #[serde(default)]
+    pub y: u32,
+    #[serde(default = "default_overlay_opacity")]
+    pub opacity: f32,
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub source: Option<usize>,
+}
+
+fn default_overlay_opacity() -> f32 {
+    1.0
+}
+
+/// How `overlay` combines each overlay pixel with the base pixel below it.
+/// - normal: the overlay color replaces the base
+/// - multiply: base * overlay, which only darkens
+/// - screen: the inverse of multiplying the inverses, which only lightens
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+}
+
+impl Validate for OverlayParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(ImageError::InvalidOpacity(
+                "Opacity must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+        if self.source.is_some() && self.url.is_some() {
+            return Err(ImageError::InvalidParameters(
+                "Overlay takes either 'url' or 'source', not both".to_string(),
+            ));
+        }
+        validate_source(self.source)
+    }
+}
+
+/// Parameters for flattening transparency onto a solid background.
+/// - background: [R, G, B] (default white)
+#[derive(Debug, Deserialize)]
+pub struct FlattenParams {
+    #[serde(default = "default_background")]
+    pub background: [u8; 3],
+}
+
+impl Default for FlattenParams {
+    fn default() -> Self {
+        Self {
+            background: default_background(),
+        }
+    }
+}
+
+fn default_background() -> [u8; 3] {
+    [255, 255, 255]
+} // White
+
+impl Validate for FlattenParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        Ok(())
+    }
+}
+
+/// Parameters for drawing a background behind transparent areas.
+/// - color_start: [R, G, B], the whole background, or where a gradient starts
+/// - color_end: optional [R, G, B] where a linear gradient ends; solid `color_start` when absent
+/// - direction: `vertical` (default, top to bottom), `horizontal` (left to right) or
+///   `diagonal` (top-left to bottom-right)
+#[derive(Debug, Deserialize)]
+pub struct BackgroundParams {
+    pub color_start: [u8; 3],
+    #[serde(default)]
+    pub color_end: Option<[u8; 3]>,
+    #[serde(default)]
+    pub direction: GradientDirection,
+}
+
+/// Direction of a `background` gradient.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GradientDirection {
+    #[default]
+    Vertical,
+    Horizontal,
+    Diagonal,
+}
+
+impl Validate for BackgroundParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        Ok(())
+    }
+}
+
+/// Parameters for stylistic color filters.
+/// - mode: `sepia`, `invert` or `tint`
+/// - tint: optional [R, G, B] channel multipliers (0.0..=4.0) applied after the mode; required for `tint`
+#[derive(Debug, Deserialize)]
+pub struct ColorizeParams {
+    pub mode: ColorizeMode,
+    #[serde(default)]
+    pub tint: Option<[f32; 3]>,
+}
+
+/// Color filter applied by `colorize`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorizeMode {
+    Sepia,
+    Invert,
+    Tint,
+}
+
+impl Validate for ColorizeParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        match self.tint {
+            Some(tint) if tint.iter().any(|m| !(0.0..=4.0).contains(m)) => {
+                Err(ImageError::InvalidParameters(
+                    "Tint multipliers must be between 0.0 and 4.0".to_string(),
+                ))
+            }
+            None if self.mode == ColorizeMode::Tint => Err(ImageError::InvalidParameters(
+                "Tint mode requires a 'tint' multiplier".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parameters for setting the global opacity of an image.
+/// - opacity: 0.0-1.0, multiplies the alpha channel
+#[derive(Debug, Deserialize)]
+pub struct SetOpacityParams {
+    pub opacity: f32,
+}
+
+impl Validate for SetOpacityParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(ImageError::InvalidOpacity(
+                "Opacity must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parameters for scaling the color channels individually.
+/// - r, g, b: gains >= 0 (default 1.0, unchanged)
+#[derive(Debug, Deserialize)]
+pub struct ChannelGainParams {
+    #[serde(default = "default_gain")]
+    pub r: f32,
+    #[serde(default = "default_gain")]
+    pub g: f32,
+    #[serde(default = "default_gain")]
+    pub b: f32,
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+impl Validate for ChannelGainParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        if [self.r, self.g, self.b]
+            .iter()
+            .any(|gain| !gain.is_finite() || *gain < 0.0)
+        {
+            return Err(ImageError::InvalidParameters(
+                "Channel gains must be >= 0.0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parameters for gamma correction.
+/// - gamma: > 0; values above 1.0 brighten midtones, below 1.0 darken them
+#[derive(Debug, Deserialize)]
+pub struct GammaParams {
+    pub gamma: f32,
+}
+
+impl Validate for GammaParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        if !self.gamma.is_finite() || self.gamma <= 0.0 {
+            return Err(ImageError::InvalidParameters(
+                "Gamma must be > 0.0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parameters for auto-levels.
+/// - clip_percent: 0.0-50.0 (default 0.0), share of the darkest and of the brightest values in
+///   each channel ignored as outliers when finding the range to stretch
+#[derive(Debug, Deserialize, Default)]
+pub struct NormalizeParams {
+    #[serde(default)]
+    pub clip_percent: f32,
+}
+
+impl Validate for NormalizeParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        if !(0.0..50.0).contains(&self.clip_percent) {
+            return Err(ImageError::InvalidParameters(
+                "clip_percent must be at least 0.0 and below 50.0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parameters for rounding the corners of an image.
+/// - radius: corner radius in pixels (> 0), clamped to half the shorter side
+#[derive(Debug, Deserialize)]
+pub struct RoundCornersParams {
+    pub radius: u32,
+}
+
+impl Validate for RoundCornersParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        if self.radius == 0 {
+            return Err(ImageError::InvalidParameters(
+                "Corner radius must be > 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parameters for adding a solid border around an image.
+/// - width: border width in pixels (> 0), added on every side
+/// - color: [R, G, B] (default white)
+#[derive(Debug, Deserialize)]
+pub struct BorderParams {
+    pub width: u32,
+    #[serde(default = "default_color")]
+    pub color: [u8; 3],
+}
+
+impl Validate for BorderParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        if self.width == 0 {
+            return Err(ImageError::InvalidParameters(
+                "Border width must be > 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parameters for trimming uniform borders.
+/// - tolerance: largest per-channel difference from the background still counted as background
+/// - background: [R, G, B]; the top-left pixel's color when absent
+#[derive(Debug, Default, Deserialize)]
+pub struct TrimParams {
+    #[serde(default)]
+    pub tolerance: u8,
+    #[serde(default)]
+    pub background: Option<[u8; 3]>,
+}
+
+impl Validate for TrimParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        Ok(())
+    }
+}
+
+/// Parameters for drawing text at a fixed position.
+/// - text: text to draw (non-empty)
+/// - x, y: top-left corner of the text box, in pixels
+/// - font_size: > 0
+/// - color: [R, G, B]
+#[derive(Debug, Deserialize)]
+pub struct DrawTextParams {
+    pub text: String,
+    #[serde(default)]
+    pub x: u32,
+    #[serde(default)]
+    pub y: u32,
+    #[serde(default = "default_font_size")]
+    pub font_size: u32,
+    #[serde(default = "default_color")]
+    pub color: [u8; 3],
+}
+
+impl Validate for DrawTextParams {
+    fn validate(&self) -> Result<(), ImageError> {
+        if self.text.is_empty() {
+            return Err(ImageError::InvalidParameters(
+                "Text cannot be empty".to_string(),
+            ));
+        }
+        if self.font_size == 0 {
+            return Err(ImageError::InvalidParameters(
+                "Font size must be > 0".to_string(),
+            ));
+        }
         Ok(())
     }
 }
@@ -1,9 +1,135 @@
 use super::operations;
 use super::params::{self, Validate};
-use super::pipeline_types::{PipelineOperationSpec, SupportedOperation};
+use super::pipeline_types::{json_pointer, PipelineOperationSpec, SupportedOperation};
 use crate::http::errors::{AppError, ImageError};
-use image::DynamicImage;
+use crate::server::cpu_pool::run_cpu;
+use image::{DynamicImage, GenericImageView};
+use once_cell::sync::Lazy;
 use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{Instrument, Span};
+
+/// Auxiliary images available to operations, keyed by name (the multipart field they were
+/// uploaded in, e.g. `watermark`).
+pub type PipelineAssets = HashMap<String, DynamicImage>;
+
+/// Asset `watermarkImage` stamps when its params name none.
+pub const WATERMARK_ASSET: &str = "watermark";
+
+/// Asset `overlay` composites when its params give no `url`. Images fetched for a `url` are
+/// stored under the URL itself.
+pub const OVERLAY_ASSET: &str = "overlay";
+
+/// Name of the asset holding the `index`th image of a multipart upload, which `overlay` and
+/// `watermarkImage` select with `source`. Index 0 is the image being processed.
+pub fn source_asset(index: usize) -> String {
+    format!("source:{}", index)
+}
+
+/// Successful and failed runs of each operation since startup, reported by `/metrics`.
+static OPERATION_COUNTS: Lazy<HashMap<SupportedOperation, [AtomicU64; 2]>> = Lazy::new(|| {
+    SupportedOperation::ALL
+        .iter()
+        .map(|&operation| (operation, [AtomicU64::new(0), AtomicU64::new(0)]))
+        .collect()
+});
+
+/// Counts one run of `operation`; failures skipped through `ignore_failure` count as failures.
+fn record_outcome(operation: SupportedOperation, succeeded: bool) {
+    if let Some(counts) = OPERATION_COUNTS.get(&operation) {
+        counts[usize::from(!succeeded)].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// `(operation, succeeded, failed)` run counts since startup, in [`SupportedOperation::ALL`]
+/// order.
+pub fn operation_counts() -> Vec<(SupportedOperation, u64, u64)> {
+    SupportedOperation::ALL
+        .iter()
+        .map(|operation| {
+            let [succeeded, failed] = &OPERATION_COUNTS[operation];
+            (
+                *operation,
+                succeeded.load(Ordering::Relaxed),
+                failed.load(Ordering::Relaxed),
+            )
+        })
+        .collect()
+}
+
+/// Outcome of a pipeline run: the processed image plus which operations ran.
+#[derive(Debug)]
+pub struct PipelineResult {
+    /// The processed image.
+    pub image: DynamicImage,
+    /// Operations that completed successfully, in execution order.
+    pub applied: Vec<SupportedOperation>,
+    /// Operations that failed but were skipped because of `ignore_failure`.
+    pub skipped: Vec<SupportedOperation>,
+    /// Wall-clock time of every executed operation, applied or skipped, in execution order.
+    pub timings: Vec<(SupportedOperation, Duration)>,
+}
+
+impl PipelineResult {
+    fn new(image: DynamicImage) -> Self {
+        Self {
+            image,
+            applied: Vec::new(),
+            skipped: Vec::new(),
+            timings: Vec::new(),
+        }
+    }
+
+    /// Logs the total pipeline time with the per-operation breakdown.
+    fn log_completion(&self, started: Instant) {
+        tracing::info!(
+            total_ms = started.elapsed().as_secs_f64() * 1000.0,
+            timings = %format_timings(&self.timings),
+            "Pipeline execution complete"
+        );
+    }
+
+    /// Comma-separated list of applied operations, followed by `; skipped=...` when any were skipped.
+    ///
+    /// e.g. `resize,blur; skipped=crop`
+    pub fn summary(&self) -> String {
+        let join = |ops: &[SupportedOperation]| {
+            ops.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut summary = join(&self.applied);
+        if !self.skipped.is_empty() {
+            summary.push_str("; skipped=");
+            summary.push_str(&join(&self.skipped));
+        }
+        summary
+    }
+}
+
+/// Comma-separated `operation=milliseconds` pairs, e.g. `resize=12.041ms,blur=3.500ms`.
+pub fn format_timings(timings: &[(SupportedOperation, Duration)]) -> String {
+    timings
+        .iter()
+        .map(|(operation, elapsed)| {
+            format!("{}={:.3}ms", operation, elapsed.as_secs_f64() * 1000.0)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Span covering one pipeline operation; `elapsed_us` is recorded once it finishes.
+fn operation_span(operation: SupportedOperation) -> Span {
+    tracing::info_span!(
+        "pipeline_operation",
+        operation = %operation,
+        elapsed_us = tracing::field::Empty
+    )
+}
 
 /// Executes a sequence of image operations (pipeline) on the given image.
 ///
@@ -12,155 +138,369 @@ use serde_json::Value;
 /// * `operations_spec` - A vector of pipeline operation specifications.
 ///
 /// # Returns
-/// * `Ok(DynamicImage)` with the processed image if all operations succeed (or failures are ignored).
+/// * `Ok(PipelineResult)` with the processed image and the applied/skipped operations if all
+///   operations succeed (or failures are ignored).
 /// * `Err(AppError)` if a non-ignored operation fails.
+///
+/// Each operation takes the image by value; only operations with `ignore_failure` keep a copy
+/// of their input, since that is all a failure needs to carry on.
+#[allow(dead_code)] // Library API; the server runs pipelines with a timeout
 pub fn execute_pipeline(
-    mut image: DynamicImage,
+    image: DynamicImage,
     operations_spec: Vec<PipelineOperationSpec>,
-) -> Result<DynamicImage, AppError> {
+) -> Result<PipelineResult, AppError> {
+    execute_pipeline_with_assets(image, operations_spec, &PipelineAssets::new())
+}
+
+/// Executes a pipeline like [`execute_pipeline`], with `assets` available to operations that
+/// take a second image (e.g. the `watermarkImage` logo).
+#[allow(dead_code)] // Library API; the server runs pipelines with a timeout
+pub fn execute_pipeline_with_assets(
+    image: DynamicImage,
+    operations_spec: Vec<PipelineOperationSpec>,
+    assets: &PipelineAssets,
+) -> Result<PipelineResult, AppError> {
+    let started = Instant::now();
+    let mut result = PipelineResult::new(image);
     for spec in operations_spec {
         let operation_name = spec.operation; // For logging/error messages
+        let span = operation_span(operation_name);
+        let _entered = span.enter();
         tracing::info!(operation = ?operation_name, params = ?spec.params, "Starting operation");
-        match execute_single_operation(image.clone(), &spec) {
+        let fallback = spec.ignore_failure.then(|| result.image.clone());
+        let operation_started = Instant::now();
+        let outcome = execute_operation(result.image, &spec, assets);
+        let elapsed = operation_started.elapsed();
+        span.record("elapsed_us", elapsed.as_micros() as u64);
+        result.timings.push((operation_name, elapsed));
+        record_outcome(operation_name, outcome.is_ok());
+        match outcome {
             Ok(processed_image) => {
                 tracing::info!(operation = ?operation_name, "Operation succeeded");
-                image = processed_image;
+                result.image = processed_image;
+                result.applied.push(operation_name);
             }
             Err(e) => {
-                tracing::error!(operation = ?operation_name, params = ?spec.params, error = %e, "Operation failed");
-                if spec.ignore_failure {
-                    tracing::warn!(operation = ?operation_name, "Operation failed but was ignored");
-                } else {
-                    return Err(match e {
-                        ae @ AppError::BadRequest(_)
-                        | ae @ AppError::ImageProcessingError(_)
-                        | ae @ AppError::InvalidOperation(_) => ae,
-                        _ => AppError::ImageProcessingError(format!(
-                            "Error in operation {:?}: {}",
-                            operation_name, e
-                        )),
-                    });
-                }
+                result.image = handle_operation_failure(&spec, e, fallback)?;
+                result.skipped.push(operation_name);
+            }
+        }
+    }
+    result.log_completion(started);
+    Ok(result)
+}
+
+/// Executes a pipeline like [`execute_pipeline_with_assets`], bounding each operation by
+/// `max_operation_duration`.
+///
+/// Every operation runs on a blocking task; if it does not finish in time the operation is
+/// treated as failed with `AppError::ImageProcessingError` (honoring `ignore_failure`).
+/// A `None` duration disables the bound. Note that a timed-out blocking task cannot be
+/// cancelled and keeps running in the background until it completes.
+pub async fn execute_pipeline_with_timeout(
+    image: DynamicImage,
+    operations_spec: Vec<PipelineOperationSpec>,
+    max_operation_duration: Option<Duration>,
+    assets: Arc<PipelineAssets>,
+) -> Result<PipelineResult, AppError> {
+    let started = Instant::now();
+    let mut result = PipelineResult::new(image);
+    for spec in operations_spec {
+        let operation_name = spec.operation;
+        // The span is not entered across awaits; the blocking task re-enters it instead
+        let span = operation_span(operation_name);
+        span.in_scope(|| {
+            tracing::info!(operation = ?operation_name, params = ?spec.params, "Starting operation");
+        });
+        let fallback = spec.ignore_failure.then(|| result.image.clone());
+        let input = result.image;
+        let task_spec = spec.clone();
+        let task_span = span.clone();
+        let task_assets = assets.clone();
+        let operation_started = Instant::now();
+        let outcome = run_with_timeout(
+            move || task_span.in_scope(|| execute_operation(input, &task_spec, &task_assets)),
+            max_operation_duration,
+            operation_name,
+        )
+        .instrument(span.clone())
+        .await;
+        let elapsed = operation_started.elapsed();
+        span.record("elapsed_us", elapsed.as_micros() as u64);
+        result.timings.push((operation_name, elapsed));
+        record_outcome(operation_name, outcome.is_ok());
+        let _entered = span.enter();
+        match outcome {
+            Ok(processed_image) => {
+                tracing::info!(operation = ?operation_name, "Operation succeeded");
+                result.image = processed_image;
+                result.applied.push(operation_name);
+            }
+            Err(e) => {
+                result.image = handle_operation_failure(&spec, e, fallback)?;
+                result.skipped.push(operation_name);
             }
         }
     }
-    tracing::info!("Pipeline execution complete");
-    Ok(image)
+    result.log_completion(started);
+    Ok(result)
+}
+
+/// Runs a single operation closure on a blocking task, failing if it exceeds `timeout`.
+async fn run_with_timeout<F>(
+    operation: F,
+    timeout: Option<Duration>,
+    operation_name: SupportedOperation,
+) -> Result<DynamicImage, AppError>
+where
+    F: FnOnce() -> Result<DynamicImage, AppError> + Send + 'static,
+{
+    let task_name = format!("Operation {:?}", operation_name);
+    let task = run_cpu(&task_name, operation);
+    match timeout {
+        Some(limit) => tokio::time::timeout(limit, task).await.map_err(|_| {
+            AppError::ImageProcessingError(format!(
+                "Operation {:?} exceeded the maximum duration of {:?}",
+                operation_name, limit
+            ))
+        })?,
+        None => task.await,
+    }
+}
+
+/// Logs a failed operation and decides whether the pipeline may continue.
+///
+/// `fallback` is the operation's input, kept only when `ignore_failure` is set; the pipeline
+/// continues with it, and otherwise fails.
+fn handle_operation_failure(
+    spec: &PipelineOperationSpec,
+    e: AppError,
+    fallback: Option<DynamicImage>,
+) -> Result<DynamicImage, AppError> {
+    let operation_name = spec.operation;
+    tracing::error!(operation = ?operation_name, params = ?spec.params, error = %e, "Operation failed");
+    if let Some(previous) = fallback {
+        tracing::warn!(operation = ?operation_name, "Operation failed but was ignored");
+        Ok(previous)
+    } else {
+        Err(match e {
+            ae @ AppError::BadRequest(_)
+            | ae @ AppError::InvalidParams { .. }
+            | ae @ AppError::InvalidOperationsJson { .. }
+            | ae @ AppError::ImageProcessingError(_)
+            | ae @ AppError::InvalidOperation(_) => ae,
+            _ => AppError::ImageProcessingError(format!(
+                "Error in operation {:?}: {}",
+                operation_name, e
+            )),
+        })
+    }
+}
+
+/// Wraps a parameter validation failure, keeping the underlying `ImageError` for its error code.
+fn invalid_params(operation_name: &str) -> impl FnOnce(ImageError) -> AppError + '_ {
+    move |source| AppError::InvalidParams {
+        context: format!("Invalid {} params", operation_name),
+        source,
+    }
+}
+
+/// Runs an operation on the whole image, or only inside `spec.region` when one is set.
+///
+/// Region operations must preserve the region's size (e.g. blur, sharpen, grayscale); the
+/// processed rectangle is pasted back at the same position.
+fn execute_operation(
+    image: DynamicImage,
+    spec: &PipelineOperationSpec,
+    assets: &PipelineAssets,
+) -> Result<DynamicImage, AppError> {
+    let Some([x, y, width, height]) = spec.region else {
+        return execute_single_operation(image, spec, assets);
+    };
+    check_region_size(spec)?;
+    let op_name = spec.operation.to_string();
+    check_region_bounds(&image, x, y, width, height, &op_name)?;
+
+    let processed = execute_single_operation(image.crop_imm(x, y, width, height), spec, assets)?;
+    if processed.dimensions() != (width, height) {
+        let (new_w, new_h) = processed.dimensions();
+        return Err(AppError::BadRequest(format!(
+            "Operation {} changed the region size from {}x{} to {}x{}; only size-preserving operations can be applied to a region",
+            op_name, width, height, new_w, new_h
+        )));
+    }
+
+    let has_alpha = image.color().has_alpha();
+    let mut canvas = image.into_rgba8();
+    image::imageops::replace(&mut canvas, &processed.to_rgba8(), x as i64, y as i64);
+    let composited = DynamicImage::ImageRgba8(canvas);
+    Ok(if has_alpha {
+        composited
+    } else {
+        DynamicImage::ImageRgb8(composited.into_rgb8())
+    })
 }
 
 fn execute_single_operation(
     image: DynamicImage,
     spec: &PipelineOperationSpec,
+    assets: &PipelineAssets,
 ) -> Result<DynamicImage, AppError> {
     tracing::info!(operation = ?spec.operation, params = ?spec.params, "Executing single operation");
     match spec.operation {
         SupportedOperation::Resize => {
-            let params: params::ResizeParams = parse_params(&spec.params, "Resize")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Resize params: {}", e))
-            })?;
+            let params: params::ResizeParams = parse_valid_params(spec)?;
             Ok(operations::resize(image, &params))
         }
         SupportedOperation::Rotate => {
-            let params: params::RotateParams = parse_params(&spec.params, "Rotate")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Rotate params: {}", e))
-            })?;
+            let params: params::RotateParams = parse_valid_params(spec)?;
             Ok(operations::rotate(image, &params))
         }
         SupportedOperation::Crop => {
-            let params: params::CropParams = parse_params(&spec.params, "Crop")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Crop params: {}", e))
-            })?;
+            let params: params::CropParams = parse_valid_params(spec)?;
+            check_region_bounds(
+                &image,
+                params.x,
+                params.y,
+                params.width,
+                params.height,
+                "Crop",
+            )?;
             Ok(operations::crop(image, &params))
         }
         SupportedOperation::Grayscale => Ok(operations::grayscale(image)),
         SupportedOperation::Blur => {
-            let params: params::BlurParams = parse_params(&spec.params, "Blur")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Blur params: {}", e))
-            })?;
+            let params: params::BlurParams = parse_valid_params(spec)?;
             Ok(operations::blur(image, &params))
         }
         SupportedOperation::Flip => Ok(operations::flip_vertical(image)),
         SupportedOperation::Flop => Ok(operations::flip_horizontal(image)),
+        SupportedOperation::Mirror => {
+            let params: params::MirrorParams = parse_valid_params(spec)?;
+            Ok(operations::mirror(image, &params))
+        }
         SupportedOperation::Convert => {
-            let params: params::FormatConversionParams = parse_params(&spec.params, "Convert")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Convert params: {}", e))
-            })?;
+            let params: params::FormatConversionParams = parse_valid_params(spec)?;
             operations::convert_format(image, &params) // Returns Result<DynamicImage, AppError>
         }
         SupportedOperation::AdjustBrightness => {
-            let params: params::AdjustBrightnessParams =
-                parse_params(&spec.params, "AdjustBrightness")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid AdjustBrightness params: {}", e))
-            })?;
+            let params: params::AdjustBrightnessParams = parse_valid_params(spec)?;
             Ok(operations::adjust_brightness(image, params.value))
         }
         SupportedOperation::AdjustContrast => {
-            let params: params::AdjustContrastParams =
-                parse_params(&spec.params, "AdjustContrast")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid AdjustContrast params: {}", e))
-            })?;
+            let params: params::AdjustContrastParams = parse_valid_params(spec)?;
             Ok(operations::adjust_contrast(image, params.value))
         }
         SupportedOperation::Sharpen => Ok(operations::sharpen(image)),
+        SupportedOperation::Flatten => {
+            let params: params::FlattenParams = parse_valid_params(spec)?;
+            Ok(operations::flatten(image, &params))
+        }
+        SupportedOperation::Background => {
+            let params: params::BackgroundParams = parse_valid_params(spec)?;
+            Ok(operations::background(image, &params))
+        }
+        SupportedOperation::AutoEnhance => Ok(operations::auto_enhance(image)),
+        SupportedOperation::Normalize => {
+            let params: params::NormalizeParams = parse_valid_params(spec)?;
+            Ok(operations::normalize(image, &params))
+        }
+        SupportedOperation::Colorize => {
+            let params: params::ColorizeParams = parse_valid_params(spec)?;
+            Ok(operations::colorize(image, &params))
+        }
         SupportedOperation::Thumbnail => {
-            let params: params::ThumbnailParams = parse_params(&spec.params, "Thumbnail")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Thumbnail params: {}", e))
-            })?;
+            let params: params::ThumbnailParams = parse_valid_params(spec)?;
             Ok(operations::thumbnail(image, &params))
         }
         SupportedOperation::Enlarge => {
             // Enlarge uses ResizeParams, but only allows upscaling
-            let params: params::ResizeParams = parse_params(&spec.params, "Enlarge")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Enlarge params: {}", e))
-            })?;
-            Ok(operations::enlarge(image, &params))
+            let params: params::ResizeParams = parse_valid_params(spec)?;
+            operations::enlarge(image, &params)
         }
         SupportedOperation::Extract => {
-            let params: params::ExtractParams = parse_params(&spec.params, "Extract")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Extract params: {}", e))
-            })?;
+            let params: params::ExtractParams = parse_valid_params(spec)?;
+            check_region_bounds(
+                &image,
+                params.x,
+                params.y,
+                params.width,
+                params.height,
+                "Extract",
+            )?;
             Ok(operations::extract(image, &params))
         }
         SupportedOperation::Autorotate => Ok(operations::autorotate(image)),
         SupportedOperation::Zoom => {
-            let params: params::ZoomParams = parse_params(&spec.params, "Zoom")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Zoom params: {}", e))
-            })?;
+            let params: params::ZoomParams = parse_valid_params(spec)?;
             Ok(operations::zoom(image, &params))
         }
         SupportedOperation::SmartCrop => {
-            let params: params::SmartCropParams = parse_params(&spec.params, "SmartCrop")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid SmartCrop params: {}", e))
-            })?;
+            let params: params::SmartCropParams = parse_valid_params(spec)?;
             Ok(operations::smart_crop(image, &params))
         }
         SupportedOperation::Watermark => {
-            let params: params::WatermarkParams = parse_params(&spec.params, "Watermark")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid Watermark params: {}", e))
-            })?;
+            let params: params::WatermarkParams = parse_valid_params(spec)?;
             operations::watermark::watermark(&image, &params)
-                .map_err(AppError::ImageProcessingError)
         }
         SupportedOperation::WatermarkImage => {
-            let params: params::WatermarkImageParams =
-                parse_params(&spec.params, "WatermarkImage")?;
-            params.validate().map_err(|e: ImageError| {
-                AppError::BadRequest(format!("Invalid WatermarkImage params: {}", e))
+            let params: params::WatermarkImageParams = parse_valid_params(spec)?;
+            Ok(match watermark_asset(&params, assets)? {
+                Some(logo) => {
+                    operations::watermark::watermark_image_with_logo(image, logo, &params)
+                }
+                None => operations::watermark::watermark_image(image, &params),
+            })
+        }
+        SupportedOperation::SetOpacity => {
+            let params: params::SetOpacityParams = parse_valid_params(spec)?;
+            Ok(operations::set_opacity(image, &params))
+        }
+        SupportedOperation::ChannelGain => {
+            let params: params::ChannelGainParams = parse_valid_params(spec)?;
+            Ok(operations::channel_gain(image, &params))
+        }
+        SupportedOperation::Gamma => {
+            let params: params::GammaParams = parse_valid_params(spec)?;
+            Ok(operations::gamma(image, &params))
+        }
+        SupportedOperation::DrawText => {
+            let params: params::DrawTextParams = parse_valid_params(spec)?;
+            Ok(operations::overlay::draw_text(image, &params))
+        }
+        SupportedOperation::RoundCorners => {
+            let params: params::RoundCornersParams = parse_valid_params(spec)?;
+            Ok(operations::round_corners(image, &params))
+        }
+        SupportedOperation::Border => {
+            let params: params::BorderParams = parse_valid_params(spec)?;
+            Ok(operations::border(image, &params))
+        }
+        SupportedOperation::Overlay => {
+            let params: params::OverlayParams = parse_valid_params(spec)?;
+            let name = match (&params.url, params.source) {
+                (Some(url), _) => url.clone(),
+                (None, Some(source)) => source_asset(source),
+                (None, None) => OVERLAY_ASSET.to_string(),
+            };
+            let top = assets.get(&name).ok_or_else(|| {
+                AppError::BadRequest(match (&params.url, params.source) {
+                    (Some(url), _) => format!("Overlay image '{}' was not fetched", url),
+                    (None, Some(source)) => format!("Overlay source {} was not uploaded", source),
+                    (None, None) => {
+                        format!("Overlay requires an image in the '{}' field", OVERLAY_ASSET)
+                    }
+                })
             })?;
-            Ok(operations::watermark::watermark_image(image, &params))
+            Ok(operations::overlay::overlay(image, top, &params))
+        }
+        SupportedOperation::Trim => {
+            let params: params::TrimParams = parse_valid_params(spec)?;
+            Ok(operations::trim(image, &params))
+        }
+        SupportedOperation::ResizePercent => {
+            let params: params::ResizePercentParams = parse_valid_params(spec)?;
+            Ok(operations::resize_percent(image, &params))
         } // Catch any other future variants if SupportedOperation enum expands beyond these
           // _ => Err(AppError::InvalidOperation(format!(
           //     "Unknown or unsupported operation: {:?}.",
@@ -169,15 +509,194 @@ fn execute_single_operation(
     }
 }
 
+/// Parses an operations JSON array, pointing at the offending value when it doesn't fit.
+///
+/// Besides the array itself, the params of every operation without `ignore_failure` are parsed
+/// into their typed form, so a wrong-typed or missing param is reported with its operation's
+/// index (e.g. `/1/params/width`) instead of failing midway through the pipeline. Other
+/// validation failures are left to the executor.
+pub fn parse_operations(json: &str) -> Result<Vec<PipelineOperationSpec>, AppError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    let operations: Vec<PipelineOperationSpec> = serde_path_to_error::deserialize(deserializer)
+        .map_err(|e| AppError::InvalidOperationsJson {
+            pointer: json_pointer(e.path()),
+            message: e.inner().to_string(),
+        })?;
+    check_operation_params(&operations)?;
+    Ok(operations)
+}
+
+/// Fails with `InvalidOperationsJson` for the first operation whose params don't parse, with
+/// a pointer relative to the operations array. Operations with `ignore_failure` are skipped.
+pub fn check_operation_params(operations: &[PipelineOperationSpec]) -> Result<(), AppError> {
+    for (index, spec) in operations.iter().enumerate() {
+        if spec.ignore_failure {
+            continue;
+        }
+        if let Err(AppError::InvalidOperationsJson { pointer, message }) = validate_operation(spec)
+        {
+            return Err(AppError::InvalidOperationsJson {
+                pointer: format!("/{}{}", index, pointer),
+                message: format!("operation {} ({}): {}", index, spec.operation, message),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks an operation's region and parameters without an image.
+///
+/// Runs the same parsing and `Validate` checks as execution, so a pipeline that passes can only
+/// fail on image-dependent errors, such as a crop rectangle outside the image.
+pub fn validate_operation(spec: &PipelineOperationSpec) -> Result<(), AppError> {
+    check_region_size(spec)?;
+    match spec.operation {
+        SupportedOperation::Resize | SupportedOperation::Enlarge => {
+            parse_valid_params::<params::ResizeParams>(spec).map(drop)
+        }
+        SupportedOperation::Rotate => parse_valid_params::<params::RotateParams>(spec).map(drop),
+        SupportedOperation::Crop => parse_valid_params::<params::CropParams>(spec).map(drop),
+        SupportedOperation::Blur => parse_valid_params::<params::BlurParams>(spec).map(drop),
+        SupportedOperation::Convert => {
+            parse_valid_params::<params::FormatConversionParams>(spec).map(drop)
+        }
+        SupportedOperation::AdjustBrightness => {
+            parse_valid_params::<params::AdjustBrightnessParams>(spec).map(drop)
+        }
+        SupportedOperation::AdjustContrast => {
+            parse_valid_params::<params::AdjustContrastParams>(spec).map(drop)
+        }
+        SupportedOperation::Flatten => parse_valid_params::<params::FlattenParams>(spec).map(drop),
+        SupportedOperation::Normalize => {
+            parse_valid_params::<params::NormalizeParams>(spec).map(drop)
+        }
+        SupportedOperation::Background => {
+            parse_valid_params::<params::BackgroundParams>(spec).map(drop)
+        }
+        SupportedOperation::Colorize => {
+            parse_valid_params::<params::ColorizeParams>(spec).map(drop)
+        }
+        SupportedOperation::Thumbnail => {
+            parse_valid_params::<params::ThumbnailParams>(spec).map(drop)
+        }
+        SupportedOperation::Extract => parse_valid_params::<params::ExtractParams>(spec).map(drop),
+        SupportedOperation::Zoom => parse_valid_params::<params::ZoomParams>(spec).map(drop),
+        SupportedOperation::SmartCrop => {
+            parse_valid_params::<params::SmartCropParams>(spec).map(drop)
+        }
+        SupportedOperation::Watermark => {
+            parse_valid_params::<params::WatermarkParams>(spec).map(drop)
+        }
+        SupportedOperation::WatermarkImage => {
+            parse_valid_params::<params::WatermarkImageParams>(spec).map(drop)
+        }
+        SupportedOperation::SetOpacity => {
+            parse_valid_params::<params::SetOpacityParams>(spec).map(drop)
+        }
+        SupportedOperation::ChannelGain => {
+            parse_valid_params::<params::ChannelGainParams>(spec).map(drop)
+        }
+        SupportedOperation::Gamma => parse_valid_params::<params::GammaParams>(spec).map(drop),
+        SupportedOperation::DrawText => {
+            parse_valid_params::<params::DrawTextParams>(spec).map(drop)
+        }
+        SupportedOperation::RoundCorners => {
+            parse_valid_params::<params::RoundCornersParams>(spec).map(drop)
+        }
+        SupportedOperation::Border => parse_valid_params::<params::BorderParams>(spec).map(drop),
+        SupportedOperation::Overlay => parse_valid_params::<params::OverlayParams>(spec).map(drop),
+        SupportedOperation::Trim => parse_valid_params::<params::TrimParams>(spec).map(drop),
+        SupportedOperation::ResizePercent => {
+            parse_valid_params::<params::ResizePercentParams>(spec).map(drop)
+        }
+        SupportedOperation::Mirror => parse_valid_params::<params::MirrorParams>(spec).map(drop),
+        SupportedOperation::Grayscale
+        | SupportedOperation::Flip
+        | SupportedOperation::Flop
+        | SupportedOperation::Sharpen
+        | SupportedOperation::AutoEnhance
+        | SupportedOperation::Autorotate => Ok(()),
+    }
+}
+
+/// Rejects empty regions of interest.
+fn check_region_size(spec: &PipelineOperationSpec) -> Result<(), AppError> {
+    match spec.region {
+        Some([_, _, width, height]) if width == 0 || height == 0 => {
+            Err(AppError::BadRequest(format!(
+                "Invalid {} region: width and height must be greater than zero",
+                spec.operation
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Ensures a rectangular region lies entirely within the image bounds.
+fn check_region_bounds(
+    image: &DynamicImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    op_name: &str,
+) -> Result<(), AppError> {
+    let (img_w, img_h) = image.dimensions();
+    let fits_x = x.checked_add(width).is_some_and(|right| right <= img_w);
+    let fits_y = y.checked_add(height).is_some_and(|bottom| bottom <= img_h);
+    if fits_x && fits_y {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Invalid {} region: {}x{} at ({}, {}) exceeds image bounds {}x{}",
+            op_name, width, height, x, y, img_w, img_h
+        )))
+    }
+}
+
+/// The logo for `watermarkImage`: the uploaded image selected by `params.source` or the asset
+/// named by `params.asset`, which must exist, or else the `watermark` asset when one was
+/// uploaded.
+fn watermark_asset<'a>(
+    params: &params::WatermarkImageParams,
+    assets: &'a PipelineAssets,
+) -> Result<Option<&'a DynamicImage>, AppError> {
+    if let Some(source) = params.source {
+        return assets.get(&source_asset(source)).map(Some).ok_or_else(|| {
+            AppError::BadRequest(format!("Watermark source {} was not uploaded", source))
+        });
+    }
+    match &params.asset {
+        Some(name) => assets.get(name).map(Some).ok_or_else(|| {
+            AppError::BadRequest(format!("Watermark asset '{}' was not provided", name))
+        }),
+        None => Ok(assets.get(WATERMARK_ASSET)),
+    }
+}
+
+/// Parses `spec.params` into the operation's parameter type and validates them.
+fn parse_valid_params<T: serde::de::DeserializeOwned + Validate>(
+    spec: &PipelineOperationSpec,
+) -> Result<T, AppError> {
+    let operation_name = format!("{:?}", spec.operation);
+    let params: T = parse_params(&spec.params, &operation_name)?;
+    params.validate().map_err(invalid_params(&operation_name))?;
+    Ok(params)
+}
+
+/// Parses `value` into the operation's params type; failures point at the offending field,
+/// relative to the operation (e.g. `/params/width`).
 fn parse_params<T: serde::de::DeserializeOwned>(
     value: &Value,
     op_name: &str,
 ) -> Result<T, AppError> {
-    serde_json::from_value(value.clone()).map_err(|e| {
-        AppError::BadRequest(format!(
-            "Failed to parse parameters for {} operation: {}. Value: {}",
-            op_name, e, value
-        ))
+    serde_path_to_error::deserialize(value.clone()).map_err(|e| AppError::InvalidOperationsJson {
+        pointer: format!("/params{}", json_pointer(e.path())),
+        message: format!(
+            "Failed to parse parameters for {} operation: {}",
+            op_name,
+            e.inner()
+        ),
     })
 }
 
@@ -190,6 +709,7 @@ fn parse_params<T: serde::de::DeserializeOwned>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::errors::ErrorCode;
     use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
     use serde_json::json;
 
@@ -212,6 +732,7 @@ mod tests {
                     "width": 50,
                     "height": 50
                 }),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Blur,
@@ -220,6 +741,7 @@ mod tests {
                     "sigma": 1.0,
                     "minampl": 0.1
                 }),
+                region: None,
             },
         ];
 
@@ -229,7 +751,7 @@ mod tests {
             "Pipeline failed at resize or blur: {:?}",
             result
         );
-        let processed = result.unwrap();
+        let processed = result.unwrap().image;
         assert_eq!(
             processed.dimensions(),
             (50, 50),
@@ -252,6 +774,7 @@ mod tests {
                 "x": null,
                 "y": null
             }),
+            region: None,
         }];
 
         let result = execute_pipeline(image, operations);
@@ -259,7 +782,7 @@ mod tests {
             println!("Watermark pipeline error: {:?}", result.as_ref().err());
         }
         assert!(result.is_ok(), "Watermark pipeline failed: {:?}", result);
-        let processed = result.unwrap();
+        let processed = result.unwrap().image;
         assert_eq!(
             processed.dimensions(),
             (100, 100),
@@ -278,6 +801,7 @@ mod tests {
                     "width": -50, // Invalid parameter
                     "height": 50
                 }),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Blur,
@@ -286,6 +810,7 @@ mod tests {
                     "sigma": 1.0,
                     "minampl": 0.1
                 }),
+                region: None,
             },
         ];
 
@@ -297,6 +822,189 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_operation_counters_record_successes_and_failures() {
+        let counts = |operation| {
+            operation_counts()
+                .into_iter()
+                .find(|&(counted, _, _)| counted == operation)
+                .map(|(_, succeeded, failed)| (succeeded, failed))
+                .unwrap()
+        };
+        let (gamma_ok, gamma_failed) = counts(SupportedOperation::Gamma);
+        let (flop_ok, flop_failed) = counts(SupportedOperation::Flop);
+
+        let operations = vec![
+            PipelineOperationSpec {
+                operation: SupportedOperation::Gamma,
+                ignore_failure: true,
+                params: json!({"gamma": -1.0}),
+                region: None,
+            },
+            PipelineOperationSpec {
+                operation: SupportedOperation::Flop,
+                ignore_failure: false,
+                params: json!({}),
+                region: None,
+            },
+        ];
+        let result = execute_pipeline(create_test_image(10, 10), operations).unwrap();
+        assert_eq!(result.skipped, vec![SupportedOperation::Gamma]);
+
+        // Other tests run pipelines concurrently, so the counters can only be checked as lower bounds
+        let (gamma_ok_after, gamma_failed_after) = counts(SupportedOperation::Gamma);
+        let (flop_ok_after, flop_failed_after) = counts(SupportedOperation::Flop);
+        assert!(gamma_failed_after > gamma_failed);
+        assert!(flop_ok_after > flop_ok);
+        assert!(gamma_ok_after >= gamma_ok && flop_failed_after >= flop_failed);
+    }
+
+    /// Runs a pipeline the way `execute_pipeline` used to: every operation gets a clone.
+    fn execute_pipeline_cloning(
+        image: DynamicImage,
+        operations: &[PipelineOperationSpec],
+    ) -> DynamicImage {
+        let mut image = image;
+        for spec in operations {
+            match execute_operation(image.clone(), spec, &PipelineAssets::new()) {
+                Ok(processed) => image = processed,
+                Err(e) => assert!(spec.ignore_failure, "unexpected failure: {}", e),
+            }
+        }
+        image
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_without_clones_matches_cloning_path() {
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_fn(160, 120, |x, y| {
+            image::Rgb([x as u8, y as u8, (x * y % 256) as u8])
+        }));
+        let spec = |operation, params, ignore_failure| PipelineOperationSpec {
+            operation,
+            params,
+            ignore_failure,
+            region: None,
+        };
+        let operations = vec![
+            spec(
+                SupportedOperation::Resize,
+                json!({"width": 80, "height": 60}),
+                false,
+            ),
+            // Out of bounds after the resize: fails and is skipped
+            spec(
+                SupportedOperation::Crop,
+                json!({"x": 70, "y": 50, "width": 40, "height": 40}),
+                true,
+            ),
+            spec(SupportedOperation::Blur, json!({"sigma": 1.5}), false),
+            spec(
+                SupportedOperation::AdjustBrightness,
+                json!({"value": 20}),
+                false,
+            ),
+            spec(SupportedOperation::Sharpen, json!({}), false),
+        ];
+
+        let expected = execute_pipeline_cloning(image.clone(), &operations);
+        let result = execute_pipeline(image.clone(), operations.clone()).unwrap();
+        assert_eq!(
+            result.summary(),
+            "resize,blur,adjustBrightness,sharpen; skipped=crop"
+        );
+        assert_eq!(result.image.dimensions(), expected.dimensions());
+        assert_eq!(result.image.as_bytes(), expected.as_bytes());
+
+        let timed = execute_pipeline_with_timeout(image, operations, None, Arc::default())
+            .await
+            .unwrap();
+        assert_eq!(timed.image.as_bytes(), expected.as_bytes());
+    }
+
+    /// Collects the `operation` field of every `pipeline_operation` span.
+    #[derive(Clone, Default)]
+    struct OperationSpans(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for OperationSpans {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            struct OperationField(Option<String>);
+            impl tracing::field::Visit for OperationField {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    if field.name() == "operation" {
+                        self.0 = Some(format!("{:?}", value));
+                    }
+                }
+            }
+            if attrs.metadata().name() == "pipeline_operation" {
+                let mut visitor = OperationField(None);
+                attrs.record(&mut visitor);
+                self.0.lock().unwrap().extend(visitor.0);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_emits_a_span_per_operation() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let spans = OperationSpans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let operations = vec![
+            PipelineOperationSpec {
+                operation: SupportedOperation::Resize,
+                ignore_failure: false,
+                params: json!({"width": 50, "height": 50}),
+                region: None,
+            },
+            // Out of bounds: fails, is skipped, and is still timed
+            PipelineOperationSpec {
+                operation: SupportedOperation::Crop,
+                ignore_failure: true,
+                params: json!({"x": 40, "y": 40, "width": 20, "height": 20}),
+                region: None,
+            },
+            PipelineOperationSpec {
+                operation: SupportedOperation::Grayscale,
+                ignore_failure: false,
+                params: json!({}),
+                region: None,
+            },
+        ];
+        let expected = ["resize", "crop", "grayscale"];
+
+        let result = execute_pipeline(create_test_image(100, 100), operations.clone()).unwrap();
+        assert_eq!(*spans.0.lock().unwrap(), expected);
+        let timed: Vec<String> = result
+            .timings
+            .iter()
+            .map(|(operation, _)| operation.to_string())
+            .collect();
+        assert_eq!(timed, expected);
+        assert_eq!(format_timings(&result.timings).matches("ms").count(), 3);
+
+        spans.0.lock().unwrap().clear();
+        let result = execute_pipeline_with_timeout(
+            create_test_image(100, 100),
+            operations,
+            None,
+            Arc::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(*spans.0.lock().unwrap(), expected);
+        assert_eq!(result.timings.len(), 3);
+    }
+
     #[test]
     fn test_pipeline_error_handling() {
         let image = create_test_image(100, 100);
@@ -307,6 +1015,7 @@ mod tests {
                 "width": -50, // Invalid parameter
                 "height": 50
             }),
+            region: None,
         }];
 
         let result = execute_pipeline(image, operations);
@@ -331,6 +1040,7 @@ mod tests {
                 "x": 5,
                 "y": 5
             }),
+            region: None,
         }];
         let result = execute_pipeline(image, operations);
         assert!(
@@ -338,7 +1048,7 @@ mod tests {
             "Watermark with custom position and color failed: {:?}",
             result
         );
-        let processed = result.unwrap();
+        let processed = result.unwrap().image;
         assert_eq!(
             processed.dimensions(),
             (100, 100),
@@ -361,6 +1071,7 @@ mod tests {
                 "x": 5,
                 "y": 5
             }),
+            region: None,
         }];
         let result = execute_pipeline(image.clone(), operations);
         assert!(result.is_err(), "Watermark missing text should error");
@@ -378,6 +1089,7 @@ mod tests {
                 "x": 5,
                 "y": 5
             }),
+            region: None,
         }];
         let result = execute_pipeline(image.clone(), operations);
         assert!(result.is_err(), "Watermark with invalid color should error");
@@ -395,6 +1107,7 @@ mod tests {
                 "x": 5,
                 "y": 5
             }),
+            region: None,
         }];
         let result = execute_pipeline(image, operations);
         assert!(
@@ -411,6 +1124,7 @@ mod tests {
                 operation: SupportedOperation::Grayscale,
                 ignore_failure: false,
                 params: json!({}),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Watermark,
@@ -422,6 +1136,7 @@ mod tests {
                     "font_size": 18,
                     "color": [255, 0, 0],
                 }),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Convert,
@@ -430,6 +1145,7 @@ mod tests {
                     "format": "jpeg",
                     "quality": 80
                 }),
+                region: None,
             },
         ];
         let result = execute_pipeline(image, operations);
@@ -438,7 +1154,7 @@ mod tests {
             "Pipeline grayscale->watermark->convert failed: {:?}",
             result
         );
-        let processed = result.unwrap();
+        let processed = result.unwrap().image;
         assert_eq!(
             processed.dimensions(),
             (100, 100),
@@ -454,9 +1170,10 @@ mod tests {
             operation: SupportedOperation::Resize,
             params: json!({"width": 50, "height": 75}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
         let processed = result.unwrap();
         assert_eq!(processed.dimensions(), (50, 75));
@@ -469,9 +1186,10 @@ mod tests {
             operation: SupportedOperation::Resize,
             params: json!({"width": -10, "height": 50}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_err());
     }
 
@@ -482,9 +1200,10 @@ mod tests {
             operation: SupportedOperation::Grayscale,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
@@ -495,9 +1214,10 @@ mod tests {
             operation: SupportedOperation::Blur,
             params: json!({"sigma": 2.0}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
@@ -508,9 +1228,10 @@ mod tests {
             operation: SupportedOperation::Blur,
             params: json!({"sigma": -1.0}), // Invalid negative sigma
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_err());
     }
 
@@ -521,9 +1242,10 @@ mod tests {
             operation: SupportedOperation::Crop,
             params: json!({"x": 10, "y": 10, "width": 50, "height": 50}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
         let processed = result.unwrap();
         assert_eq!(processed.dimensions(), (50, 50));
@@ -536,10 +1258,48 @@ mod tests {
             operation: SupportedOperation::Crop,
             params: json!({"x": 0, "y": 0, "width": 0, "height": 50}), // zero width should fail
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
-        assert!(result.is_err());
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
+        let err = result.unwrap_err();
+        assert_eq!(err.error_code(), ErrorCode::InvalidDimensions);
+    }
+
+    #[test]
+    fn test_execute_single_operation_crop_out_of_bounds() {
+        let image = create_test_image(100, 100);
+        let spec = PipelineOperationSpec {
+            operation: SupportedOperation::Crop,
+            params: json!({"x": 0, "y": 0, "width": 200, "height": 200}),
+            ignore_failure: false,
+            region: None,
+        };
+
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_execute_single_operation_extract_bounds() {
+        let image = create_test_image(100, 100);
+        let in_bounds = PipelineOperationSpec {
+            operation: SupportedOperation::Extract,
+            params: json!({"x": 50, "y": 60, "width": 50, "height": 40}),
+            ignore_failure: false,
+            region: None,
+        };
+        let result = execute_single_operation(image.clone(), &in_bounds, &PipelineAssets::new());
+        assert_eq!(result.unwrap().dimensions(), (50, 40));
+
+        let out_of_bounds = PipelineOperationSpec {
+            operation: SupportedOperation::Extract,
+            params: json!({"x": 60, "y": 0, "width": 50, "height": 40}),
+            ignore_failure: false,
+            region: None,
+        };
+        let result = execute_single_operation(image, &out_of_bounds, &PipelineAssets::new());
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
     }
 
     #[test]
@@ -549,9 +1309,10 @@ mod tests {
             operation: SupportedOperation::Rotate,
             params: json!({"degrees": 90}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
@@ -562,9 +1323,10 @@ mod tests {
             operation: SupportedOperation::Flip,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
@@ -575,9 +1337,10 @@ mod tests {
             operation: SupportedOperation::Flop,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
@@ -588,9 +1351,10 @@ mod tests {
             operation: SupportedOperation::AdjustBrightness,
             params: json!({"value": 20}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
@@ -601,9 +1365,10 @@ mod tests {
             operation: SupportedOperation::AdjustContrast,
             params: json!({"value": 1.2}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
@@ -614,12 +1379,216 @@ mod tests {
             operation: SupportedOperation::Sharpen,
             params: json!({}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_execute_single_operation_flatten() {
+        let image = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
+            10,
+            10,
+            Rgba([0u8, 0u8, 0u8, 0u8]), // Fully transparent
+        ));
+        let spec = PipelineOperationSpec {
+            operation: SupportedOperation::Flatten,
+            params: json!({"background": [10, 20, 30]}),
+            ignore_failure: false,
+            region: None,
+        };
+
+        let processed = execute_single_operation(image, &spec, &PipelineAssets::new()).unwrap();
+        assert_eq!(processed.get_pixel(5, 5), Rgba([10, 20, 30, 255]));
+    }
+
+    #[test]
+    fn test_execute_single_operation_auto_enhance() {
+        let image = create_test_image(100, 100);
+        let spec = PipelineOperationSpec {
+            operation: SupportedOperation::AutoEnhance,
+            params: json!({}),
+            ignore_failure: false,
+            region: None,
+        };
+
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
+        assert_eq!(result.unwrap().dimensions(), (100, 100));
+    }
+
+    #[test]
+    fn test_execute_single_operation_colorize() {
+        let image = create_test_image(10, 10);
+        let spec = PipelineOperationSpec {
+            operation: SupportedOperation::Colorize,
+            params: json!({"mode": "sepia", "tint": [1.0, 1.0, 0.8]}),
+            ignore_failure: false,
+            region: None,
+        };
+        assert_eq!(
+            execute_single_operation(image.clone(), &spec, &PipelineAssets::new())
+                .unwrap()
+                .dimensions(),
+            (10, 10)
+        );
+
+        let missing_tint = PipelineOperationSpec {
+            operation: SupportedOperation::Colorize,
+            params: json!({"mode": "tint"}),
+            ignore_failure: false,
+            region: None,
+        };
+        assert!(matches!(
+            execute_single_operation(image, &missing_tint, &PipelineAssets::new()),
+            Err(AppError::InvalidParams { .. })
+        ));
+    }
+
+    #[test]
+    fn test_execute_single_operation_draw_text() {
+        let image = create_test_image(100, 50);
+        let spec = PipelineOperationSpec {
+            operation: SupportedOperation::DrawText,
+            params: json!({"text": "Hi", "x": 10, "y": 5, "font_size": 16, "color": [255, 0, 0]}),
+            ignore_failure: false,
+            region: None,
+        };
+        assert_eq!(
+            execute_single_operation(image.clone(), &spec, &PipelineAssets::new())
+                .unwrap()
+                .dimensions(),
+            (100, 50)
+        );
+
+        for params in [json!({"text": ""}), json!({"text": "Hi", "font_size": 0})] {
+            let invalid = PipelineOperationSpec {
+                operation: SupportedOperation::DrawText,
+                params,
+                ignore_failure: false,
+                region: None,
+            };
+            assert!(matches!(
+                execute_single_operation(image.clone(), &invalid, &PipelineAssets::new()),
+                Err(AppError::InvalidParams { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_execute_single_operation_set_opacity_and_channel_gain() {
+        let image = create_test_image(10, 10);
+        let opacity = PipelineOperationSpec {
+            operation: SupportedOperation::SetOpacity,
+            params: json!({"opacity": 0.5}),
+            ignore_failure: false,
+            region: None,
+        };
+        let result =
+            execute_single_operation(image.clone(), &opacity, &PipelineAssets::new()).unwrap();
+        assert_eq!(result.get_pixel(5, 5)[3], 128);
+
+        let gain = PipelineOperationSpec {
+            operation: SupportedOperation::ChannelGain,
+            params: json!({"r": 0.0}),
+            ignore_failure: false,
+            region: None,
+        };
+        let result =
+            execute_single_operation(image.clone(), &gain, &PipelineAssets::new()).unwrap();
+        assert_eq!(result.get_pixel(5, 5)[0], 0);
+
+        for invalid in [
+            PipelineOperationSpec {
+                operation: SupportedOperation::SetOpacity,
+                params: json!({"opacity": 1.5}),
+                ignore_failure: false,
+                region: None,
+            },
+            PipelineOperationSpec {
+                operation: SupportedOperation::ChannelGain,
+                params: json!({"g": -1.0}),
+                ignore_failure: false,
+                region: None,
+            },
+        ] {
+            assert!(matches!(
+                execute_single_operation(image.clone(), &invalid, &PipelineAssets::new()),
+                Err(AppError::InvalidParams { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_execute_single_operation_gamma() {
+        let image = create_test_image(10, 10);
+        let spec = |params| PipelineOperationSpec {
+            operation: SupportedOperation::Gamma,
+            params,
+            ignore_failure: false,
+            region: None,
+        };
+        let result = execute_single_operation(
+            image.clone(),
+            &spec(json!({"gamma": 2.2})),
+            &PipelineAssets::new(),
+        )
+        .unwrap();
+        // Pure red is a fixed point of every gamma curve
+        assert_eq!(result.get_pixel(5, 5), Rgba([255, 0, 0, 255]));
+
+        for invalid in [json!({"gamma": 0.0}), json!({"gamma": -1.0})] {
+            assert!(matches!(
+                execute_single_operation(image.clone(), &spec(invalid), &PipelineAssets::new()),
+                Err(AppError::InvalidParams { .. })
+            ));
+        }
+        assert!(matches!(
+            execute_single_operation(image, &spec(json!({})), &PipelineAssets::new()),
+            Err(AppError::InvalidOperationsJson { .. })
+        ));
+    }
+
+    #[test]
+    fn test_execute_single_operation_round_corners_and_border() {
+        let image = create_test_image(40, 40);
+        let rounded = PipelineOperationSpec {
+            operation: SupportedOperation::RoundCorners,
+            params: json!({"radius": 20}),
+            ignore_failure: false,
+            region: None,
+        };
+        let result =
+            execute_single_operation(image.clone(), &rounded, &PipelineAssets::new()).unwrap();
+        assert_eq!(result.get_pixel(0, 0)[3], 0);
+        assert_eq!(result.get_pixel(20, 20)[3], 255);
+
+        let bordered = PipelineOperationSpec {
+            operation: SupportedOperation::Border,
+            params: json!({"width": 4, "color": [0, 0, 0]}),
+            ignore_failure: false,
+            region: None,
+        };
+        assert_eq!(
+            execute_single_operation(image.clone(), &bordered, &PipelineAssets::new())
+                .unwrap()
+                .dimensions(),
+            (48, 48)
+        );
+
+        let zero_radius = PipelineOperationSpec {
+            operation: SupportedOperation::RoundCorners,
+            params: json!({"radius": 0}),
+            ignore_failure: false,
+            region: None,
+        };
+        assert!(matches!(
+            execute_single_operation(image, &zero_radius, &PipelineAssets::new()),
+            Err(AppError::InvalidParams { .. })
+        ));
+    }
+
     #[test]
     fn test_execute_single_operation_convert() {
         let image = create_test_image(100, 100);
@@ -627,9 +1596,10 @@ mod tests {
             operation: SupportedOperation::Convert,
             params: json!({"format": "jpeg", "quality": 85}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_ok());
     }
 
@@ -640,9 +1610,10 @@ mod tests {
             operation: SupportedOperation::Convert,
             params: json!({"format": "invalid_format"}),
             ignore_failure: false,
+            region: None,
         };
 
-        let result = execute_single_operation(image, &spec);
+        let result = execute_single_operation(image, &spec, &PipelineAssets::new());
         assert!(result.is_err());
     }
 
@@ -654,26 +1625,31 @@ mod tests {
                 operation: SupportedOperation::Resize,
                 ignore_failure: false,
                 params: json!({"width": 150, "height": 150}),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Crop,
                 ignore_failure: false,
                 params: json!({"x": 25, "y": 25, "width": 100, "height": 100}),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Rotate,
                 ignore_failure: false,
-                params: json!({"degrees": 45}),
+                params: json!({"degrees": 45, "keep_canvas": true}),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Blur,
                 ignore_failure: false,
                 params: json!({"sigma": 1.5}),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Grayscale,
                 ignore_failure: false,
                 params: json!({}),
+                region: None,
             },
         ];
 
@@ -689,16 +1665,19 @@ mod tests {
                 operation: SupportedOperation::Resize,
                 ignore_failure: false,
                 params: json!({"width": 80, "height": 80}),
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Crop,
                 ignore_failure: true, // This will be ignored if it fails
-                params: json!({"x": 0, "y": 0, "width": 0, "height": 50}), // zero width should fail
+                params: json!({"x": 0, "y": 0, "width": 0, "height": 50}), // zero width should fail,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Blur,
                 ignore_failure: false,
                 params: json!({"sigma": 1.0}),
+                region: None,
             },
         ];
 
@@ -708,7 +1687,7 @@ mod tests {
             "Pipeline with mixed success/ignored failures failed: {:?}",
             result
         );
-        let processed = result.unwrap();
+        let processed = result.unwrap().image;
         assert_eq!(processed.dimensions(), (80, 80)); // Should have resize dimensions
     }
 
@@ -719,12 +1698,14 @@ mod tests {
             PipelineOperationSpec {
                 operation: SupportedOperation::Crop,
                 ignore_failure: true,
-                params: json!({"x": 0, "y": 0, "width": 0, "height": 50}), // zero width should fail
+                params: json!({"x": 0, "y": 0, "width": 0, "height": 50}), // zero width should fail,
+                region: None,
             },
             PipelineOperationSpec {
                 operation: SupportedOperation::Resize,
                 ignore_failure: true,
-                params: json!({"width": 0, "height": 50}), // zero width should fail
+                params: json!({"width": 0, "height": 50}), // zero width should fail,
+                region: None,
             },
         ];
 
@@ -733,7 +1714,7 @@ mod tests {
             result.is_ok(),
             "Pipeline with all ignored failures should succeed"
         );
-        let processed = result.unwrap();
+        let processed = result.unwrap().image;
         assert_eq!(processed.dimensions(), (100, 100)); // Should maintain original dimensions
     }
 
@@ -753,7 +1734,35 @@ mod tests {
         use crate::image::params::ResizeParams;
         let params = json!({"width": "not_a_number", "height": 200});
         let result: Result<ResizeParams, AppError> = parse_params(&params, "resize");
-        assert!(result.is_err());
+        match result {
+            Err(AppError::InvalidOperationsJson { pointer, message }) => {
+                assert_eq!(pointer, "/params/width");
+                assert!(message.contains("invalid type: string"), "{}", message);
+            }
+            other => panic!("Expected InvalidOperationsJson, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resample_filter_names_are_validated() {
+        for operation in [SupportedOperation::Thumbnail, SupportedOperation::SmartCrop] {
+            let spec = |filter: &str| PipelineOperationSpec {
+                operation,
+                ignore_failure: false,
+                params: json!({"width": 10, "height": 10, "filter": filter}),
+                region: None,
+            };
+            for filter in ["nearest", "triangle", "catmullrom", "gaussian", "lanczos3"] {
+                assert!(validate_operation(&spec(filter)).is_ok(), "{}", filter);
+            }
+            match validate_operation(&spec("bicubic")) {
+                Err(AppError::InvalidOperationsJson { pointer, message }) => {
+                    assert_eq!(pointer, "/params/filter");
+                    assert!(message.contains("unknown variant `bicubic`"), "{}", message);
+                }
+                other => panic!("Expected InvalidOperationsJson, got {:?}", other),
+            }
+        }
     }
 
     #[test]
@@ -774,7 +1783,156 @@ mod tests {
 
         let result = execute_pipeline(image, operations);
         assert!(result.is_ok());
-        let processed = result.unwrap();
+        let processed = result.unwrap().image;
         assert_eq!(processed.dimensions(), (100, 100)); // Should be unchanged
     }
+
+    #[tokio::test]
+    async fn test_pipeline_with_timeout_succeeds() {
+        let image = create_test_image(100, 100);
+        let operations = vec![PipelineOperationSpec {
+            operation: SupportedOperation::Resize,
+            ignore_failure: false,
+            params: json!({"width": 40, "height": 30}),
+            region: None,
+        }];
+
+        let result = execute_pipeline_with_timeout(
+            image,
+            operations,
+            Some(Duration::from_secs(5)),
+            Arc::default(),
+        )
+        .await;
+        assert_eq!(result.unwrap().image.dimensions(), (40, 30));
+    }
+
+    #[tokio::test]
+    async fn test_run_with_timeout_fires_for_slow_operation() {
+        let image = create_test_image(10, 10);
+        let slow_operation = move || {
+            std::thread::sleep(Duration::from_millis(500));
+            Ok(image)
+        };
+
+        let result = run_with_timeout(
+            slow_operation,
+            Some(Duration::from_millis(20)),
+            SupportedOperation::Blur,
+        )
+        .await;
+        match result {
+            Err(AppError::ImageProcessingError(msg)) => {
+                assert!(msg.contains("exceeded the maximum duration"))
+            }
+            other => panic!("Expected timeout error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_with_timeout_without_limit() {
+        let image = create_test_image(10, 10);
+        let result = run_with_timeout(move || Ok(image), None, SupportedOperation::Blur).await;
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_pipeline_result_summary() {
+        let image = create_test_image(100, 100);
+        let operations = vec![
+            PipelineOperationSpec {
+                operation: SupportedOperation::Resize,
+                ignore_failure: false,
+                params: json!({"width": 50, "height": 50}),
+                region: None,
+            },
+            PipelineOperationSpec {
+                operation: SupportedOperation::Crop,
+                ignore_failure: true,
+                params: json!({"x": 0, "y": 0, "width": 0, "height": 50}), // zero width should fail,
+                region: None,
+            },
+            PipelineOperationSpec {
+                operation: SupportedOperation::Grayscale,
+                ignore_failure: false,
+                params: json!({}),
+                region: None,
+            },
+        ];
+
+        let result = execute_pipeline(image, operations).unwrap();
+        assert_eq!(
+            result.applied,
+            vec![SupportedOperation::Resize, SupportedOperation::Grayscale]
+        );
+        assert_eq!(result.skipped, vec![SupportedOperation::Crop]);
+        assert_eq!(result.summary(), "resize,grayscale; skipped=crop");
+    }
+
+    fn create_checkerboard(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(width, height, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgb([255, 255, 255])
+            } else {
+                image::Rgb([0, 0, 0])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_region_operation_only_changes_region() {
+        let image = create_checkerboard(40, 40);
+        let operations = vec![PipelineOperationSpec {
+            operation: SupportedOperation::Blur,
+            ignore_failure: false,
+            params: json!({"sigma": 2.0}),
+            region: Some([0, 0, 16, 16]),
+        }];
+
+        let result = execute_pipeline(image.clone(), operations).unwrap().image;
+        assert_eq!(result.dimensions(), (40, 40));
+
+        let mut region_changed = false;
+        for (x, y, pixel) in result.pixels() {
+            if x < 16 && y < 16 {
+                region_changed |= pixel != image.get_pixel(x, y);
+            } else {
+                assert_eq!(pixel, image.get_pixel(x, y), "pixel ({}, {}) changed", x, y);
+            }
+        }
+        assert!(region_changed, "blur should change the region");
+    }
+
+    #[test]
+    fn test_region_operation_rejects_invalid_regions() {
+        let out_of_bounds = PipelineOperationSpec {
+            operation: SupportedOperation::Blur,
+            ignore_failure: false,
+            params: json!({"sigma": 2.0}),
+            region: Some([30, 30, 20, 20]),
+        };
+        assert!(matches!(
+            execute_operation(
+                create_checkerboard(40, 40),
+                &out_of_bounds,
+                &PipelineAssets::new()
+            ),
+            Err(AppError::BadRequest(_))
+        ));
+
+        let resizing = PipelineOperationSpec {
+            operation: SupportedOperation::Resize,
+            ignore_failure: false,
+            params: json!({"width": 5, "height": 5}),
+            region: Some([0, 0, 20, 20]),
+        };
+        assert!(matches!(
+            execute_operation(
+                create_checkerboard(40, 40),
+                &resizing,
+                &PipelineAssets::new()
+            ),
+            Err(AppError::BadRequest(_))
+        ));
+    }
 }
@@ -3,8 +3,9 @@
 //! This module defines the data structures used to specify a sequence of image operations (pipeline)
 //! and the set of operations supported by the pipeline executor.
 
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
+use std::fmt;
 
 // Add other necessary imports if/when they become clear.
 // For now, params.rs might be needed for actual parameter structs,
@@ -12,7 +13,7 @@ use serde_json::Value;
 // use super::params::*; // Example if params were directly embedded
 
 /// Specification for a single operation in an image processing pipeline.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PipelineOperationSpec {
     /// The operation to perform.
@@ -23,10 +24,18 @@ pub struct PipelineOperationSpec {
     /// Parameters for the operation (operation-specific, dynamic).
     #[serde(default)]
     pub params: Value, // Using serde_json::Value for dynamic params
+    /// Optional `[x, y, width, height]` region of interest. When set, the operation is applied
+    /// to that rectangle only and the result is composited back into the full image.
+    #[serde(default)]
+    pub region: Option<[u32; 4]>,
 }
 
 /// Enum of all supported image operations for the pipeline.
-#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+///
+/// The canonical wire form is camelCase (e.g. `smartCrop`, `adjustBrightness`), which is what
+/// responses report. Requests may also spell operations in snake_case (`smart_crop`), PascalCase
+/// (`SmartCrop`) or lowercase (`smartcrop`); matching ignores case and underscores.
+#[derive(Debug, Serialize, PartialEq, Eq, Hash, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub enum SupportedOperation {
     Crop,
@@ -48,7 +57,116 @@ pub enum SupportedOperation {
     AdjustBrightness, // Added from existing imaginary-rs operations
     AdjustContrast,   // Added from existing imaginary-rs operations
     Sharpen,          // Added from existing imaginary-rs operations
-                      // Add other operations as they are implemented and supported in pipeline
+    Flatten,
+    AutoEnhance,
+    Colorize,
+    SetOpacity,
+    ChannelGain,
+    Gamma,
+    DrawText,
+    RoundCorners,
+    Border,
+    Overlay,
+    Trim,
+    ResizePercent,
+    Background,
+    Normalize,
+    Mirror,
+    // Add other operations as they are implemented and supported in pipeline
+}
+
+impl SupportedOperation {
+    /// Every variant, used to resolve the accepted spellings when deserializing.
+    pub const ALL: [SupportedOperation; 34] = [
+        SupportedOperation::Crop,
+        SupportedOperation::SmartCrop,
+        SupportedOperation::Resize,
+        SupportedOperation::Enlarge,
+        SupportedOperation::Extract,
+        SupportedOperation::Rotate,
+        SupportedOperation::Autorotate,
+        SupportedOperation::Flip,
+        SupportedOperation::Flop,
+        SupportedOperation::Thumbnail,
+        SupportedOperation::Zoom,
+        SupportedOperation::Convert,
+        SupportedOperation::Watermark,
+        SupportedOperation::WatermarkImage,
+        SupportedOperation::Blur,
+        SupportedOperation::Grayscale,
+        SupportedOperation::AdjustBrightness,
+        SupportedOperation::AdjustContrast,
+        SupportedOperation::Sharpen,
+        SupportedOperation::Flatten,
+        SupportedOperation::AutoEnhance,
+        SupportedOperation::Colorize,
+        SupportedOperation::SetOpacity,
+        SupportedOperation::ChannelGain,
+        SupportedOperation::Gamma,
+        SupportedOperation::DrawText,
+        SupportedOperation::RoundCorners,
+        SupportedOperation::Border,
+        SupportedOperation::Overlay,
+        SupportedOperation::Trim,
+        SupportedOperation::ResizePercent,
+        SupportedOperation::Background,
+        SupportedOperation::Normalize,
+        SupportedOperation::Mirror,
+    ];
+}
+
+/// Formats a `serde_path_to_error` path as a JSON pointer (RFC 6901), e.g. `/1/params/width`.
+pub fn json_pointer(path: &serde_path_to_error::Path) -> String {
+    use serde_path_to_error::Segment;
+
+    path.iter()
+        .map(|segment| match segment {
+            Segment::Seq { index } => format!("/{}", index),
+            Segment::Map { key } | Segment::Enum { variant: key } => {
+                format!("/{}", key.replace('~', "~0").replace('/', "~1"))
+            }
+            Segment::Unknown => String::new(),
+        })
+        .collect()
+}
+
+/// Lowercases a name and drops underscores so `smart_crop`, `smartCrop` and `SmartCrop` compare equal.
+fn normalize_operation_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl<'de> Deserialize<'de> for SupportedOperation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let normalized = normalize_operation_name(&name);
+        SupportedOperation::ALL
+            .into_iter()
+            .find(|op| normalize_operation_name(&op.to_string()) == normalized)
+            .ok_or_else(|| {
+                let valid: Vec<String> = SupportedOperation::ALL
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+                serde::de::Error::custom(format!(
+                    "unknown operation `{}`, expected one of: {}",
+                    name,
+                    valid.join(", ")
+                ))
+            })
+    }
+}
+
+impl fmt::Display for SupportedOperation {
+    /// Formats the operation using its wire name (e.g. `adjustBrightness`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(Value::String(name)) => f.write_str(&name),
+            _ => write!(f, "{:?}", self),
+        }
+    }
 }
 
 // Consider adding a method to PipelineOperationSpec to try and parse `params`
@@ -59,3 +177,47 @@ pub enum SupportedOperation {
 //     }
 // }
 // This would require specific knowledge of param structs here, or a more generic approach.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str) -> Result<SupportedOperation, serde_json::Error> {
+        serde_json::from_value(Value::String(name.to_string()))
+    }
+
+    #[test]
+    fn test_operation_spellings_are_interchangeable() {
+        for name in ["smartCrop", "smart_crop", "SmartCrop", "smartcrop"] {
+            assert_eq!(
+                parse(name).unwrap(),
+                SupportedOperation::SmartCrop,
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            parse("adjust_brightness").unwrap(),
+            SupportedOperation::AdjustBrightness
+        );
+        assert_eq!(parse("resize").unwrap(), SupportedOperation::Resize);
+    }
+
+    #[test]
+    fn test_unknown_operation_is_rejected() {
+        let err = parse("sepia").unwrap_err().to_string();
+        assert!(err.contains("unknown operation `sepia`"), "{}", err);
+        assert!(
+            err.contains("expected one of: crop, smartCrop, resize"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_all_operations_round_trip_through_canonical_name() {
+        for op in SupportedOperation::ALL {
+            assert_eq!(parse(&op.to_string()).unwrap(), op);
+        }
+    }
+}
@@ -23,9 +23,11 @@ mod utils;
 use crate::http::errors::AppError;
 use crate::http::info::AppInfo;
 use crate::security::{ApiKey, ApiSalt};
+use crate::server::connection::WriteTimeoutAcceptor;
+use arc_swap::ArcSwap;
 use axum_server::tls_rustls::RustlsConfig;
 use std::net::SocketAddr;
-use tokio::sync::Semaphore;
+use std::time::Duration;
 
 use axum_server::Server;
 
@@ -50,6 +52,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize health metrics
     crate::http::handlers::health_handler::init_health_metrics();
 
+    // Probe which image formats this build can decode and encode
+    let format_support = crate::image::format_support::format_support();
+    let supported = |check: fn(&crate::image::format_support::FormatCapability) -> bool| {
+        format_support
+            .formats()
+            .iter()
+            .filter(|c| check(c))
+            .map(|c| crate::image::format_support::format_name(c.format))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    info!("Decodable formats: {}", supported(|c| c.decode));
+    info!("Encodable formats: {}", supported(|c| c.encode));
+
     // Load configuration
     let config = config::load_config(&matches)?;
 
@@ -61,6 +77,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     //}
 
     let config = Arc::new(config);
+    crate::server::cpu_pool::init_cpu_pool(&config.server)?;
+    crate::server::governor::init_pixel_governor(&config.server);
+    crate::image::operations::watermark::init_fonts_dir(&config.server);
 
     // Use the security configuration
     let allow_insecure =
@@ -85,20 +104,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Err(AppError::Unauthorized(format!("Origin {} is not allowed.", origin)).into());
     }
 
-    let data = &config.data;
-    let signature = config.security.generate_signature(data).map_err(|e| {
-        AppError::InternalServerError(format!("Failed to generate signature: {}", e))
-    })?;
-    info!("{}", AppInfo::GeneratedSignature(signature.clone()));
-
-    let is_valid = config
-        .security
-        .validate_signature(data, &signature)
-        .map_err(|e| {
-            AppError::InternalServerError(format!("Failed to validate signature: {}", e))
-        })?;
-    info!("{}", AppInfo::ValidatedSignature(is_valid));
-
     // Print expected and received API keys
     if let Some(expected_api_key) = config.security.key() {
         info!("{}", AppInfo::ExpectedApiKey(expected_api_key.to_string()));
@@ -133,15 +138,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    let concurrency: u32 = *matches.get_one::<u32>("concurrency").unwrap_or(&0);
-    let _semaphore = if concurrency > 0 {
-        Some(Arc::new(Semaphore::new(concurrency as usize)))
-    } else {
-        None
-    };
+    // CORS origins and the API key are re-read from the config on SIGHUP
+    let live_config = Arc::new(ArcSwap::new(config.clone()));
+    #[cfg(unix)]
+    server::reload_on_sighup(matches.clone(), live_config.clone());
 
-    // Pass the semaphore to the server
-    // server::run_server(config, semaphore).await?;
+    // The concurrency limit is applied by the router from the resolved server.concurrency
+    if config.server.concurrency > 0 {
+        info!(
+            "Limiting to {} concurrent requests",
+            config.server.concurrency
+        );
+    } else {
+        info!("Concurrent requests are unlimited");
+    }
 
     let http_version = matches
         .get_one::<String>("http-version")
@@ -160,6 +170,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map(|s| s.as_str())
         .unwrap_or("key.pem");
 
+    // Close connections whose clients stop reading the response for longer than write_timeout
+    let write_timeout_acceptor =
+        WriteTimeoutAcceptor::new(Duration::from_secs(config.server.write_timeout));
+
+    // Stop accepting connections on SIGTERM/SIGINT and let in-flight requests drain
+    let shutdown_handle = axum_server::Handle::new();
+    server::graceful_shutdown_on(
+        server::shutdown_signal(),
+        vec![shutdown_handle.clone()],
+        Duration::from_secs(config.server.shutdown_timeout),
+    );
+
     let cert_exists = std::path::Path::new(cert_path).exists();
     let key_exists = std::path::Path::new(key_path).exists();
 
@@ -194,14 +216,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         // Start HTTPS/2 on 3000
         let addr_https = SocketAddr::from(([0, 0, 0, 0], 3000));
-        let app = server::create_router(config.clone());
+        let app = server::with_security_headers(
+            server::create_live_router(live_config.clone()),
+            config.security.headers(),
+        );
         let config_tls = RustlsConfig::from_pem_file(cert_path, key_path)
             .await
             .unwrap();
         println!("listening on https://{} (HTTP/2 enabled)", addr_https);
+        let https_shutdown_handle = shutdown_handle.clone();
         let https_handle = tokio::spawn(async move {
             axum_server::bind_rustls(addr_https, config_tls)
-                .serve(app.into_make_service())
+                .handle(https_shutdown_handle)
+                .map(|acceptor| acceptor.acceptor(write_timeout_acceptor))
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
                 .await
                 .unwrap();
         });
@@ -229,6 +257,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         );
         let http_handle = tokio::spawn(async move {
             Server::bind(addr_http)
+                .handle(shutdown_handle)
+                .acceptor(write_timeout_acceptor)
                 .serve(redirect_router.into_make_service())
                 .await
                 .unwrap();
@@ -238,13 +268,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     } else {
         // HTTP/1.1 only on 8080
         let addr_http = SocketAddr::from(([0, 0, 0, 0], 8080));
-        let app = server::create_router(config.clone());
+        let app = server::create_live_router(live_config.clone());
         println!("listening on http://{} (HTTP/1.1)", addr_http);
         Server::bind(addr_http)
-            .serve(app.into_make_service())
+            .handle(shutdown_handle)
+            .acceptor(write_timeout_acceptor)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .unwrap();
     }
+    info!("Server stopped");
     Ok(())
 }
 
@@ -2,7 +2,7 @@ use anyhow::Result;
 use hmac::{Hmac, Mac};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize, Serializer};
 use sha2::{Digest, Sha256};
 use std::env;
 use std::fmt;
@@ -28,6 +28,13 @@ impl fmt::Display for ApiKey {
     }
 }
 
+/// Serializes as the redacted placeholder so the key never leaves the process.
+impl Serialize for ApiKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl AsRef<str> for ApiKey {
     fn as_ref(&self) -> &str {
         &self.0
@@ -63,6 +70,13 @@ impl fmt::Display for ApiSalt {
     }
 }
 
+/// Serializes as the redacted placeholder so the salt never leaves the process.
+impl Serialize for ApiSalt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl AsRef<str> for ApiSalt {
     fn as_ref(&self) -> &str {
         &self.0
@@ -82,7 +96,7 @@ impl From<String> for ApiSalt {
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SecurityConfig {
     #[serde(default = "default_key")]
     key: Option<ApiKey>,
@@ -90,6 +104,12 @@ pub struct SecurityConfig {
     salt: Option<ApiSalt>,
     #[serde(default = "default_allowed_origins")]
     allowed_origins: Vec<String>,
+    /// Hosts `?url=` may fetch from; `*.example.com` matches any subdomain. Empty allows any
+    /// public host.
+    #[serde(default)]
+    allowed_fetch_hosts: Vec<String>,
+    #[serde(default)]
+    headers: SecurityHeadersConfig,
 }
 
 impl Default for SecurityConfig {
@@ -98,10 +118,53 @@ impl Default for SecurityConfig {
             key: default_key(),
             salt: default_salt(),
             allowed_origins: default_allowed_origins(),
+            allowed_fetch_hosts: Vec::new(),
+            headers: SecurityHeadersConfig::default(),
+        }
+    }
+}
+
+/// Security response headers added when serving over TLS (`[security.headers]`).
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SecurityHeadersConfig {
+    /// Whether to add the headers at all.
+    #[serde(default = "default_security_headers_enabled")]
+    pub enabled: bool,
+    /// `max-age` (seconds) for `Strict-Transport-Security`.
+    #[serde(default = "default_hsts_max_age")]
+    pub hsts_max_age: u64,
+    /// Whether HSTS also applies to subdomains.
+    #[serde(default)]
+    pub hsts_include_subdomains: bool,
+    /// Value of the `Referrer-Policy` header.
+    #[serde(default = "default_referrer_policy")]
+    pub referrer_policy: String,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_security_headers_enabled(),
+            hsts_max_age: default_hsts_max_age(),
+            hsts_include_subdomains: false,
+            referrer_policy: default_referrer_policy(),
         }
     }
 }
 
+fn default_security_headers_enabled() -> bool {
+    true
+}
+
+/// One year, the commonly recommended HSTS duration.
+fn default_hsts_max_age() -> u64 {
+    31_536_000
+}
+
+fn default_referrer_policy() -> String {
+    "no-referrer".to_string()
+}
+
 /// Returns None for key by default; must be set explicitly for security.
 fn default_key() -> Option<ApiKey> {
     None
@@ -144,6 +207,20 @@ impl SecurityConfig {
     pub fn set_allowed_origins(&mut self, origins: Vec<String>) {
         self.allowed_origins = origins;
     }
+    /// Get the hosts URL fetches are restricted to
+    #[allow(dead_code)]
+    pub fn allowed_fetch_hosts(&self) -> &[String] {
+        &self.allowed_fetch_hosts
+    }
+    /// Set the hosts URL fetches are restricted to
+    #[allow(dead_code)]
+    pub fn set_allowed_fetch_hosts(&mut self, hosts: Vec<String>) {
+        self.allowed_fetch_hosts = hosts;
+    }
+    /// Get the security response header settings
+    pub fn headers(&self) -> &SecurityHeadersConfig {
+        &self.headers
+    }
 
     /// Prepares the key for HMAC operations by SHA256 hashing it.
     /// Uses the configured key, or a default placeholder if none is set.
@@ -163,7 +240,7 @@ impl SecurityConfig {
     /// Returns a clone of the (potentially newly generated) key.
     #[allow(dead_code)]
     pub fn generate_api_key(&mut self) -> ApiKey {
-        if self.key.as_ref().map_or(true, |k| k.0.is_empty()) {
+        if self.key.as_ref().is_none_or(|k| k.0.is_empty()) {
             let generated_key_string: String = thread_rng()
                 .sample_iter(Alphanumeric)
                 .take(32) // Ensure generated key is long enough
@@ -174,6 +251,23 @@ impl SecurityConfig {
         self.key.as_ref().unwrap().clone() // Should be Some now
     }
 
+    /// Checks a client-provided API key against the configured one in constant time.
+    ///
+    /// Returns `false` when no key is configured; an empty key counts as none.
+    pub fn verify_api_key(&self, provided: &str) -> bool {
+        let Some(expected) = self.key.as_ref().filter(|key| !key.0.is_empty()) else {
+            return false;
+        };
+        // Compare digests so timing does not depend on the key length or contents
+        let provided = Sha256::digest(provided.as_bytes());
+        let expected = Sha256::digest(expected.0.as_bytes());
+        provided
+            .iter()
+            .zip(expected.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+
     pub fn validate_signature(&self, data: &[u8], signature: &str) -> Result<bool> {
         let key = self.prepare_key();
         let mut mac = HmacSha256::new_from_slice(&key)?;
@@ -188,6 +282,26 @@ impl SecurityConfig {
             || self.allowed_origins.contains(&"*".to_string())
     }
 
+    /// Whether images may be fetched from `host`.
+    ///
+    /// Names compare case-insensitively and ignore a trailing dot. `*.example.com` matches
+    /// subdomains at any depth but not `example.com` itself. An empty list allows every host.
+    pub fn is_fetch_host_allowed(&self, host: &str) -> bool {
+        if self.allowed_fetch_hosts.is_empty() {
+            return true;
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.allowed_fetch_hosts.iter().any(|pattern| {
+            let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+            match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+                None => host == pattern,
+            }
+        })
+    }
+
     pub fn generate_signature(&self, data: &[u8]) -> Result<String> {
         let key = self.prepare_key();
         let mut mac = HmacSha256::new_from_slice(&key)?;
@@ -296,6 +410,24 @@ mod tests {
         assert!(!config.is_origin_allowed("http://other.com"));
     }
 
+    #[test]
+    fn test_fetch_host_allowlist() {
+        let mut config = SecurityConfig::default();
+        assert!(config.is_fetch_host_allowed("anything.example"));
+
+        config.set_allowed_fetch_hosts(vec![
+            "images.example.com".to_string(),
+            "*.cdn.example".to_string(),
+        ]);
+        assert!(config.is_fetch_host_allowed("images.example.com"));
+        assert!(config.is_fetch_host_allowed("IMAGES.example.com."));
+        assert!(!config.is_fetch_host_allowed("evil.images.example.com"));
+        assert!(config.is_fetch_host_allowed("eu.cdn.example"));
+        assert!(config.is_fetch_host_allowed("a.b.cdn.example"));
+        assert!(!config.is_fetch_host_allowed("cdn.example"));
+        assert!(!config.is_fetch_host_allowed("evilcdn.example"));
+    }
+
     #[test]
     fn test_signature_validation() {
         let mut config = SecurityConfig::default();
@@ -0,0 +1,157 @@
+//! Connection-level I/O guards.
+//!
+//! `WriteTimeoutStream` wraps an accepted connection and fails any write or flush that
+//! stays blocked for longer than the configured `write_timeout`. This stops a slow (or
+//! stalled) consumer from holding a connection open indefinitely while the server waits
+//! to push response bytes. `WriteTimeoutAcceptor` plugs the wrapper into `axum_server`.
+
+use axum_server::accept::Accept;
+use std::future::{ready, Future, Ready};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep, Sleep};
+
+/// Stream wrapper that errors with `io::ErrorKind::TimedOut` when a write stalls too long.
+pub struct WriteTimeoutStream<S> {
+    inner: S,
+    write_timeout: Duration,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> WriteTimeoutStream<S> {
+    pub fn new(inner: S, write_timeout: Duration) -> Self {
+        Self {
+            inner,
+            write_timeout,
+            deadline: None,
+        }
+    }
+
+    /// Arms the deadline on the first pending poll and reports whether it has elapsed.
+    fn deadline_elapsed(&mut self, cx: &mut Context<'_>) -> bool {
+        let write_timeout = self.write_timeout;
+        let deadline = self
+            .deadline
+            .get_or_insert_with(|| Box::pin(sleep(write_timeout)));
+        deadline.as_mut().poll(cx).is_ready()
+    }
+
+    fn poll_guarded<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        match poll {
+            Poll::Ready(result) => {
+                self.deadline = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending if self.deadline_elapsed(cx) => {
+                self.deadline = None;
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("write timed out after {:?}", self.write_timeout),
+                )))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for WriteTimeoutStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for WriteTimeoutStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.poll_guarded(cx, poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.poll_guarded(cx, poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// `axum_server` acceptor wrapping every accepted connection in a [`WriteTimeoutStream`].
+#[derive(Clone, Copy, Debug)]
+pub struct WriteTimeoutAcceptor {
+    write_timeout: Duration,
+}
+
+impl WriteTimeoutAcceptor {
+    pub fn new(write_timeout: Duration) -> Self {
+        Self { write_timeout }
+    }
+}
+
+impl<I, S> Accept<I, S> for WriteTimeoutAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin,
+{
+    type Stream = WriteTimeoutStream<I>;
+    type Service = S;
+    type Future = Ready<io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        ready(Ok((
+            WriteTimeoutStream::new(stream, self.write_timeout),
+            service,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_write_timeout_closes_slow_reader() {
+        // The peer never reads, so the 64-byte pipe fills up and writes block
+        let (server_side, _slow_client) = duplex(64);
+        let mut stream = WriteTimeoutStream::new(server_side, Duration::from_millis(50));
+
+        let payload = vec![0u8; 4096];
+        let result = tokio::time::timeout(Duration::from_secs(5), stream.write_all(&payload))
+            .await
+            .expect("write timeout did not fire");
+        let err = result.expect_err("write to a stalled reader should fail");
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_write_succeeds_with_active_reader() {
+        let (server_side, mut client) = duplex(64);
+        let mut stream = WriteTimeoutStream::new(server_side, Duration::from_millis(200));
+
+        let reader = tokio::spawn(async move {
+            let mut received = Vec::new();
+            client.read_to_end(&mut received).await.unwrap();
+            received.len()
+        });
+
+        stream.write_all(&[1u8; 4096]).await.unwrap();
+        stream.shutdown().await.unwrap();
+        drop(stream);
+        assert_eq!(reader.await.unwrap(), 4096);
+    }
+}
@@ -0,0 +1,188 @@
+//! Thread pool for CPU-bound image work.
+//!
+//! Decoding, operations and encoding run here instead of on tokio's blocking pool, which spawns
+//! a new thread for every task it can't hand to an idle one and so grows to hundreds of threads
+//! under a burst. The pool has `server.concurrency` threads (one per CPU when that is 0) and a
+//! queue of `server.cpu_queue_size` tasks; work submitted while the queue is full is shed with
+//! `AppError::ServiceUnavailable` (503) instead of waiting behind everything else.
+
+use crate::http::errors::AppError;
+use crate::server::ServerConfig;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{oneshot, Semaphore};
+
+static CPU_POOL: OnceLock<CpuPool> = OnceLock::new();
+
+pub struct CpuPool {
+    pool: rayon::ThreadPool,
+    /// One permit per task running or queued; `None` leaves the queue unbounded.
+    slots: Option<Arc<Semaphore>>,
+    capacity: usize,
+}
+
+impl CpuPool {
+    /// A pool of `threads` threads (one per CPU when 0) queueing at most `queue_size` tasks
+    /// beyond those running (unbounded when 0).
+    pub fn new(threads: usize, queue_size: usize) -> Result<Self, AppError> {
+        let threads = match threads {
+            0 => num_cpus::get().max(1),
+            threads => threads,
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("imaginary-cpu-{}", index))
+            .build()
+            .map_err(|e| {
+                AppError::InternalServerError(format!("Failed to start CPU thread pool: {}", e))
+            })?;
+        let capacity = threads + queue_size;
+        Ok(Self {
+            pool,
+            slots: (queue_size > 0).then(|| Arc::new(Semaphore::new(capacity))),
+            capacity,
+        })
+    }
+
+    /// Runs `work` on the pool and waits for its result.
+    ///
+    /// Fails with `AppError::ServiceUnavailable` without running `work` when the queue is full,
+    /// and with `AppError::InternalServerError` if `work` panics; `task` names it in that error.
+    /// Dropping the returned future does not cancel `work` once it has been queued.
+    pub async fn run<T, F>(&self, task: &str, work: F) -> Result<T, AppError>
+    where
+        F: FnOnce() -> Result<T, AppError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = match &self.slots {
+            Some(slots) => Some(slots.clone().try_acquire_owned().map_err(|_| {
+                AppError::ServiceUnavailable(format!(
+                    "CPU work queue is full ({} tasks running or waiting)",
+                    self.capacity
+                ))
+            })?),
+            None => None,
+        };
+        let (sender, receiver) = oneshot::channel();
+        self.pool.spawn(move || {
+            let outcome = catch_unwind(AssertUnwindSafe(work));
+            drop(permit);
+            // The caller may have stopped waiting, e.g. after an operation timeout
+            let _ = sender.send(outcome);
+        });
+        match receiver.await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(AppError::InternalServerError(format!(
+                "{} task failed: panicked",
+                task
+            ))),
+            Err(_) => Err(AppError::InternalServerError(format!(
+                "{} task failed: dropped",
+                task
+            ))),
+        }
+    }
+}
+
+/// Creates the shared pool from the resolved server config. Later calls have no effect.
+pub fn init_cpu_pool(config: &ServerConfig) -> Result<(), AppError> {
+    let pool = CpuPool::new(config.concurrency, config.cpu_queue_size)?;
+    if CPU_POOL.set(pool).is_err() {
+        tracing::warn!("CPU thread pool already initialized; keeping the existing one");
+    }
+    Ok(())
+}
+
+/// Runs `work` on the shared pool, see [`CpuPool::run`].
+///
+/// Without [`init_cpu_pool`] (e.g. in tests) the pool has one thread per CPU and no queue
+/// limit.
+pub async fn run_cpu<T, F>(task: &str, work: F) -> Result<T, AppError>
+where
+    F: FnOnce() -> Result<T, AppError> + Send + 'static,
+    T: Send + 'static,
+{
+    CPU_POOL
+        .get_or_init(|| CpuPool::new(0, 0).expect("CPU thread pool failed to start"))
+        .run(task, work)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use std::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_saturated_pool_sheds_excess_work() {
+        let pool = Arc::new(CpuPool::new(1, 1).unwrap());
+        let (release, gate) = mpsc::channel::<()>();
+        let gate = Arc::new(std::sync::Mutex::new(gate));
+
+        // One task occupies the only thread, a second one waits in the queue
+        let mut admitted = Vec::new();
+        for _ in 0..2 {
+            let pool = pool.clone();
+            let gate = gate.clone();
+            admitted.push(tokio::spawn(async move {
+                pool.run("Test", move || {
+                    gate.lock().unwrap().recv().unwrap();
+                    Ok(())
+                })
+                .await
+            }));
+        }
+        while pool.slots.as_ref().unwrap().available_permits() > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let started = std::time::Instant::now();
+        let shed = pool.run("Test", || Ok(())).await.unwrap_err();
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert!(matches!(shed, AppError::ServiceUnavailable(_)));
+        assert_eq!(
+            shed.into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        for _ in 0..2 {
+            release.send(()).unwrap();
+        }
+        for task in admitted {
+            task.await.unwrap().unwrap();
+        }
+        // Finished tasks give their slots back
+        assert_eq!(pool.run("Test", || Ok(7)).await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_unbounded_queue_never_sheds() {
+        let pool = Arc::new(CpuPool::new(1, 0).unwrap());
+        assert!(pool.slots.is_none());
+        let tasks: Vec<_> = (0..16)
+            .map(|i| {
+                let pool = pool.clone();
+                tokio::spawn(async move { pool.run("Test", move || Ok(i)).await })
+            })
+            .collect();
+        for (i, task) in tasks.into_iter().enumerate() {
+            assert_eq!(task.await.unwrap().unwrap(), i);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_work_becomes_an_internal_error() {
+        let pool = CpuPool::new(1, 1).unwrap();
+        let result: Result<(), AppError> = pool.run("Encoding", || panic!("encoder bug")).await;
+        match result {
+            Err(AppError::InternalServerError(msg)) => {
+                assert!(msg.starts_with("Encoding task failed"), "{}", msg)
+            }
+            other => panic!("Expected InternalServerError, got {:?}", other),
+        }
+        // The thread survives the panic
+        assert_eq!(pool.run("Test", || Ok(1)).await.unwrap(), 1);
+    }
+}
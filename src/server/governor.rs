@@ -0,0 +1,179 @@
+//! Pixel-throughput governor.
+//!
+//! Limits the total number of source pixels processed per second, rather than the number
+//! of requests, so that a handful of huge images cannot monopolize the CPU. It uses a
+//! GCRA-style virtual clock: every admitted request pushes the "theoretical arrival time"
+//! forward by `pixels / rate`. Up to one second of budget may be consumed in a burst;
+//! beyond that, requests are delayed, and requests that would have to wait longer than
+//! [`MAX_THROTTLE_DELAY`] are shed.
+
+use crate::http::errors::AppError;
+use crate::server::ServerConfig;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Longest a request will be held back before it is rejected instead.
+pub const MAX_THROTTLE_DELAY: Duration = Duration::from_secs(2);
+
+/// Budget that may be spent at once when the governor is idle.
+const BURST: Duration = Duration::from_secs(1);
+
+static PIXEL_GOVERNOR: OnceLock<Option<PixelGovernor>> = OnceLock::new();
+
+#[derive(Debug)]
+pub struct PixelGovernor {
+    pixels_per_second: f64,
+    theoretical_arrival: Mutex<Option<Instant>>,
+}
+
+impl PixelGovernor {
+    pub fn new(megapixels_per_second: f64) -> Self {
+        Self {
+            pixels_per_second: megapixels_per_second * 1_000_000.0,
+            theoretical_arrival: Mutex::new(None),
+        }
+    }
+
+    /// The governor for `max_megapixels_per_second`, or `None` when it is 0 (unlimited).
+    pub fn from_config(config: &ServerConfig) -> Option<Self> {
+        (config.max_megapixels_per_second > 0.0)
+            .then(|| Self::new(config.max_megapixels_per_second))
+    }
+
+    /// Reserves budget for `pixels` and returns how long the caller must wait before processing.
+    ///
+    /// Returns `None` (and reserves nothing) if the wait would exceed [`MAX_THROTTLE_DELAY`].
+    pub fn reserve(&self, pixels: u64) -> Option<Duration> {
+        self.reserve_at(pixels, Instant::now())
+    }
+
+    fn reserve_at(&self, pixels: u64, now: Instant) -> Option<Duration> {
+        let cost = Duration::from_secs_f64(pixels as f64 / self.pixels_per_second);
+        let mut tat = self
+            .theoretical_arrival
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let start = tat.map_or(now, |t| t.max(now));
+        let delay = start.saturating_duration_since(now + BURST);
+        if delay > MAX_THROTTLE_DELAY {
+            return None;
+        }
+        *tat = Some(start + cost);
+        Some(delay)
+    }
+}
+
+/// Creates the shared governor from the resolved server config. Later calls have no effect.
+pub fn init_pixel_governor(config: &ServerConfig) {
+    if PIXEL_GOVERNOR
+        .set(PixelGovernor::from_config(config))
+        .is_err()
+    {
+        tracing::warn!("Pixel governor already initialized; keeping the existing one");
+    }
+}
+
+/// Waits until the global pixel budget allows processing `pixels`, or sheds the request.
+///
+/// Without [`init_pixel_governor`] (e.g. in tests), or with `max_megapixels_per_second` at 0,
+/// nothing is throttled.
+pub async fn throttle_pixels(pixels: u64) -> Result<(), AppError> {
+    let Some(governor) = PIXEL_GOVERNOR.get().and_then(Option::as_ref) else {
+        return Ok(());
+    };
+    match governor.reserve(pixels) {
+        Some(delay) if delay.is_zero() => Ok(()),
+        Some(delay) => {
+            tracing::info!(
+                pixels,
+                ?delay,
+                "Pixel throughput budget exceeded; delaying request"
+            );
+            tokio::time::sleep(delay).await;
+            Ok(())
+        }
+        None => Err(AppError::RateLimitExceeded(format!(
+            "Pixel throughput limit of {} megapixels/second exceeded",
+            governor.pixels_per_second / 1_000_000.0
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_MEGAPIXEL: u64 = 1000 * 1000;
+
+    #[test]
+    fn test_large_images_are_throttled_by_pixel_volume() {
+        let governor = PixelGovernor::new(1.0);
+        let now = Instant::now();
+        // The first second of budget is available as a burst
+        assert_eq!(
+            governor.reserve_at(ONE_MEGAPIXEL, now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            governor.reserve_at(ONE_MEGAPIXEL, now),
+            Some(Duration::ZERO)
+        );
+        // Further large images are delayed, then shed once the wait becomes too long
+        assert_eq!(
+            governor.reserve_at(ONE_MEGAPIXEL, now),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            governor.reserve_at(ONE_MEGAPIXEL, now),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(governor.reserve_at(ONE_MEGAPIXEL, now), None);
+    }
+
+    #[test]
+    fn test_many_small_images_are_not_throttled() {
+        let governor = PixelGovernor::new(1.0);
+        let now = Instant::now();
+        // 100 requests of 100x100 pixels are only 1 megapixel in total
+        for _ in 0..100 {
+            assert_eq!(governor.reserve_at(100 * 100, now), Some(Duration::ZERO));
+        }
+    }
+
+    #[test]
+    fn test_governor_follows_the_configured_rate() {
+        let config = |max_megapixels_per_second| ServerConfig {
+            max_megapixels_per_second,
+            ..Default::default()
+        };
+        assert!(PixelGovernor::from_config(&config(0.0)).is_none());
+        let governor = PixelGovernor::from_config(&config(2.0)).unwrap();
+        let now = Instant::now();
+        // Each megapixel costs half a second, so a third one still fits in the burst
+        for _ in 0..3 {
+            assert_eq!(
+                governor.reserve_at(ONE_MEGAPIXEL, now),
+                Some(Duration::ZERO)
+            );
+        }
+        assert_eq!(
+            governor.reserve_at(ONE_MEGAPIXEL, now),
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn test_budget_recovers_over_time() {
+        let governor = PixelGovernor::new(1.0);
+        let now = Instant::now();
+        for _ in 0..4 {
+            governor.reserve_at(ONE_MEGAPIXEL, now);
+        }
+        assert_eq!(governor.reserve_at(ONE_MEGAPIXEL, now), None);
+        let later = now + Duration::from_secs(4);
+        assert_eq!(
+            governor.reserve_at(ONE_MEGAPIXEL, later),
+            Some(Duration::ZERO)
+        );
+    }
+}
@@ -1,13 +1,20 @@
-use crate::config::Config;
+use crate::config::LiveConfig;
 use crate::http::errors::AppError;
 use crate::http::handlers::health_handler::{increment_error_count, increment_request_count};
-use axum::http::{Request, Response};
+use crate::http::handlers::pipeline_handler::verify_url_signature;
+use crate::security::SecurityHeadersConfig;
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, Response};
 use axum::middleware::Next;
 use axum::response::IntoResponse;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::Semaphore;
-use tracing::info;
+use tower::{Layer, ServiceExt};
+use tracing::{info, warn};
 
 #[allow(dead_code)] // For future logging middleware
 pub async fn log_request_and_errors(
@@ -33,47 +40,54 @@ pub async fn log_request_and_errors(
     response
 }
 
-#[allow(dead_code)] // For future authentication middleware
-pub async fn authenticate(
+/// Applies [`cors_layer`](crate::server::cors_layer) built from the current
+/// `security.allowed_origins`, so reloaded origins apply to the next request.
+pub async fn cors_middleware(
+    axum::extract::State(live): axum::extract::State<LiveConfig>,
     req: Request<axum::body::Body>,
     next: Next,
 ) -> Response<axum::body::Body> {
-    let config = match req.extensions().get::<Arc<Config>>() {
-        Some(config) => config,
-        None => {
-            return AppError::InternalServerError("Configuration not found".to_string())
-                .into_response()
-        }
-    };
+    let mut next = Some(next);
+    let inner = tower::service_fn(move |req| {
+        let next = next
+            .take()
+            .expect("CORS runs the inner service at most once");
+        async move { Ok::<_, Infallible>(next.run(req).await) }
+    });
+    match crate::server::cors_layer(&live.load().security)
+        .layer(inner)
+        .oneshot(req)
+        .await
+    {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
 
+/// Requires a matching `x-api-key` header when a security key is configured.
+///
+/// GET requests carrying a valid URL signature (see `/sign`) pass without the header, since
+/// signed URLs exist to be handed to clients that don't hold the key. The key is read from the
+/// live configuration, so a reload rotates it without a restart.
+pub async fn authenticate(
+    axum::extract::State(live): axum::extract::State<LiveConfig>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response<axum::body::Body> {
+    let config = live.load();
+    if req.method() == Method::GET && verify_url_signature(req.uri(), &config.security).is_ok() {
+        return next.run(req).await;
+    }
     // Allow the request to proceed if the API key is not set
-    if let Some(api_key) = config.security.key() {
-        if !api_key.is_empty() {
-            if let Some(request_api_key_header) = req.headers().get("x-api-key") {
-                let request_api_key_str = request_api_key_header.to_str().unwrap_or("");
-
-                // Constant-time comparison to prevent timing attacks
-                let provided_key = request_api_key_str.as_bytes();
-                let expected_key = api_key.as_ref().as_bytes();
-
-                // Always compare the same amount of data to prevent length-based timing attacks
-                let mut result = (provided_key.len() ^ expected_key.len()) as u8;
-                let min_len = std::cmp::min(provided_key.len(), expected_key.len());
-
-                for i in 0..min_len {
-                    result |= provided_key[i] ^ expected_key[i];
-                }
-
-                // Add a small random delay to further obfuscate timing
-                let delay_ms = (result as u64 % 5) + 1;
-                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-
-                if result != 0 {
-                    return AppError::Unauthorized("Invalid API key".to_string()).into_response();
-                }
-            } else {
-                return AppError::Unauthorized("API key not provided".to_string()).into_response();
-            }
+    if config.security.key().is_some_and(|key| !key.is_empty()) {
+        let Some(provided) = req.headers().get("x-api-key") else {
+            return AppError::Unauthorized("API key not provided".to_string()).into_response();
+        };
+        if !config
+            .security
+            .verify_api_key(provided.to_str().unwrap_or(""))
+        {
+            return AppError::Unauthorized("Invalid API key".to_string()).into_response();
         }
     }
 
@@ -99,7 +113,7 @@ pub async fn metrics_middleware(
     response
 }
 
-#[allow(dead_code)]
+/// Middleware that holds a semaphore permit for the duration of each request.
 pub async fn concurrency_limit_middleware(
     axum::extract::State(semaphore): axum::extract::State<Arc<Semaphore>>,
     req: axum::http::Request<axum::body::Body>,
@@ -110,3 +124,405 @@ pub async fn concurrency_limit_middleware(
     drop(permit);
     res
 }
+
+/// Header carrying the ID that `SetRequestIdLayer` assigns to every request.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Adds the request's `x-request-id` to JSON error bodies as `request_id`, so a client can
+/// quote it when reporting an error and it can be matched to the server logs.
+///
+/// Must run inside `SetRequestIdLayer`, which assigns the ID, and inside compression, so the
+/// body it rewrites is still plain JSON.
+pub async fn request_id_middleware(
+    req: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let response = next.run(req).await;
+    let Some(request_id) = request_id else {
+        return response;
+    };
+    let is_json_error = (response.status().is_client_error()
+        || response.status().is_server_error())
+        && response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+    if !is_json_error {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!(
+                "Failed to read error body for request {}: {}",
+                request_id, e
+            );
+            parts.headers.remove(header::CONTENT_LENGTH);
+            return Response::from_parts(parts, axum::body::Body::empty());
+        }
+    };
+    match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut body)) => {
+            body.insert("request_id".to_string(), request_id.into());
+            parts.headers.remove(header::CONTENT_LENGTH);
+            let body = serde_json::Value::Object(body).to_string();
+            Response::from_parts(parts, axum::body::Body::from(body))
+        }
+        _ => Response::from_parts(parts, axum::body::Body::from(bytes)),
+    }
+}
+
+/// Number of tracked clients above which idle (fully refilled) buckets are evicted.
+const RATE_LIMIT_PRUNE_THRESHOLD: usize = 10_000;
+/// Shortest time between two evictions, so a flood of clients doesn't make every request
+/// scan all buckets.
+const RATE_LIMIT_PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+/// Most clients tracked at once; new clients beyond it are limited until buckets go idle.
+const RATE_LIMIT_MAX_CLIENTS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    clients: HashMap<IpAddr, TokenBucket>,
+    last_prune: Option<Instant>,
+}
+
+/// Per-client token-bucket rate limiter.
+///
+/// Each client IP gets a bucket holding up to `burst` tokens that refills at
+/// `requests_per_second`; every request consumes one token.
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    trust_forwarded_for: bool,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32, trust_forwarded_for: bool) -> Self {
+        Self {
+            requests_per_second,
+            burst: f64::from(burst.max(1)),
+            trust_forwarded_for,
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                last_prune: None,
+            }),
+        }
+    }
+
+    /// Takes a token for `client`, or returns how long it must wait for the next one.
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let prune_due = buckets
+            .last_prune
+            .is_none_or(|last| now.saturating_duration_since(last) >= RATE_LIMIT_PRUNE_INTERVAL);
+        if buckets.clients.len() > RATE_LIMIT_PRUNE_THRESHOLD && prune_due {
+            buckets
+                .clients
+                .retain(|_, bucket| self.refilled(bucket, now) < self.burst);
+            buckets.last_prune = Some(now);
+        }
+        if buckets.clients.len() >= RATE_LIMIT_MAX_CLIENTS && !buckets.clients.contains_key(&client)
+        {
+            return Err(RATE_LIMIT_PRUNE_INTERVAL);
+        }
+        let bucket = buckets.clients.entry(client).or_insert(TokenBucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.last_refill = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.requests_per_second,
+            ))
+        }
+    }
+
+    fn refilled(&self, bucket: &TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        (bucket.tokens + elapsed.as_secs_f64() * self.requests_per_second).min(self.burst)
+    }
+
+    /// Identifies the client: the last `X-Forwarded-For` entry when trusted, else the peer IP.
+    ///
+    /// The trusted proxy appends the address it saw to whatever the client sent, so only the
+    /// last entry can't be chosen by the client.
+    fn client_ip(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+        if self.trust_forwarded_for {
+            let forwarded = headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+        peer.map(|addr| addr.ip())
+    }
+}
+
+/// Middleware that rejects clients exceeding their request rate with 429 and `Retry-After`.
+///
+/// Requests whose client cannot be identified (no connection info) are not limited.
+pub async fn rate_limit_middleware(
+    axum::extract::State(limiter): axum::extract::State<Arc<RateLimiter>>,
+    req: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let Some(client) = limiter.client_ip(req.headers(), peer) else {
+        return next.run(req).await;
+    };
+    match limiter.check(client) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => {
+            warn!(%client, ?retry_after, "Rate limit exceeded");
+            let mut response =
+                AppError::RateLimitExceeded(format!("Too many requests from {}", client))
+                    .into_response();
+            let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+            response
+        }
+    }
+}
+
+/// Builds the security headers (HSTS, nosniff, Referrer-Policy) from configuration.
+pub fn security_headers(config: &SecurityHeadersConfig) -> Vec<(HeaderName, HeaderValue)> {
+    let mut hsts = format!("max-age={}", config.hsts_max_age);
+    if config.hsts_include_subdomains {
+        hsts.push_str("; includeSubDomains");
+    }
+    let mut headers = vec![(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    )];
+    match HeaderValue::from_str(&hsts) {
+        Ok(value) => headers.push((header::STRICT_TRANSPORT_SECURITY, value)),
+        Err(e) => warn!("Invalid Strict-Transport-Security value '{}': {}", hsts, e),
+    }
+    match HeaderValue::from_str(&config.referrer_policy) {
+        Ok(value) => headers.push((header::REFERRER_POLICY, value)),
+        Err(e) => warn!(
+            "Invalid Referrer-Policy value '{}': {}",
+            config.referrer_policy, e
+        ),
+    }
+    headers
+}
+
+/// Middleware that adds the configured security headers to every response.
+pub async fn security_headers_middleware(
+    axum::extract::State(headers): axum::extract::State<Arc<Vec<(HeaderName, HeaderValue)>>>,
+    req: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let mut response = next.run(req).await;
+    for (name, value) in headers.iter() {
+        response.headers_mut().insert(name.clone(), value.clone());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_security_headers_present_when_enabled() {
+        let config = SecurityHeadersConfig {
+            hsts_max_age: 600,
+            hsts_include_subdomains: true,
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::new(security_headers(&config)),
+                security_headers_middleware,
+            ));
+
+        let response = app
+            .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=600; includeSubDomains"
+        );
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+    }
+
+    fn rate_limited_app(burst: u32, trust_forwarded_for: bool) -> Router {
+        Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::new(RateLimiter::new(1.0, burst, trust_forwarded_for)),
+                rate_limit_middleware,
+            ))
+    }
+
+    fn request_from(peer: &str, forwarded_for: Option<&str>) -> Request<Body> {
+        let mut builder = Request::get("/health");
+        if let Some(forwarded_for) = forwarded_for {
+            builder = builder.header("x-forwarded-for", forwarded_for);
+        }
+        let mut req = builder.body(Body::empty()).unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        req
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_rejects_request_after_burst() {
+        let app = rate_limited_app(3, false);
+        for _ in 0..3 {
+            let response = app
+                .clone()
+                .oneshot(request_from("10.0.0.1:4000", None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+        }
+        let response = app
+            .clone()
+            .oneshot(request_from("10.0.0.1:4001", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        // Other clients have their own bucket
+        let response = app
+            .oneshot(request_from("10.0.0.2:4000", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_keys_by_forwarded_for_when_trusted() {
+        let app = rate_limited_app(1, true);
+        let first = app
+            .clone()
+            .oneshot(request_from("10.0.0.1:4000", Some("203.0.113.7")))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), axum::http::StatusCode::OK);
+        // Same proxy peer, different forwarded client
+        let other = app
+            .clone()
+            .oneshot(request_from("10.0.0.1:4000", Some("203.0.113.8")))
+            .await
+            .unwrap();
+        assert_eq!(other.status(), axum::http::StatusCode::OK);
+        let repeat = app
+            .oneshot(request_from("10.0.0.1:4000", Some("203.0.113.7")))
+            .await
+            .unwrap();
+        assert_eq!(repeat.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_ignores_spoofed_forwarded_for_entries() {
+        let app = rate_limited_app(1, true);
+        // The proxy appends the real client after whatever the client sent
+        let first = app
+            .clone()
+            .oneshot(request_from(
+                "10.0.0.1:4000",
+                Some("198.51.100.1, 203.0.113.7"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), axum::http::StatusCode::OK);
+        let spoofed = app
+            .oneshot(request_from(
+                "10.0.0.1:4000",
+                Some("198.51.100.2, 203.0.113.7"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(spoofed.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn test_rate_limit_prunes_at_most_once_per_interval_and_caps_clients() {
+        let limiter = RateLimiter::new(2.0, 1, false);
+        let now = Instant::now();
+        let client = |i: usize| IpAddr::from((i as u128).to_be_bytes());
+        for i in 0..RATE_LIMIT_MAX_CLIENTS {
+            assert!(limiter.check_at(client(i), now).is_ok());
+        }
+        let tracked = |limiter: &RateLimiter| limiter.buckets.lock().unwrap().clients.len();
+        assert_eq!(tracked(&limiter), RATE_LIMIT_MAX_CLIENTS);
+        // The buckets aren't idle yet, so the first prune keeps them and the map is full
+        assert_eq!(
+            limiter.check_at(client(RATE_LIMIT_MAX_CLIENTS), now),
+            Err(RATE_LIMIT_PRUNE_INTERVAL)
+        );
+        // Known clients keep their own bucket
+        assert_eq!(
+            limiter.check_at(client(0), now),
+            Err(Duration::from_millis(500))
+        );
+
+        // Once they have refilled, the next prune after the interval evicts them
+        let later = now + Duration::from_secs(2);
+        assert!(limiter
+            .check_at(client(RATE_LIMIT_MAX_CLIENTS), later)
+            .is_ok());
+        assert_eq!(tracked(&limiter), 1);
+    }
+
+    #[test]
+    fn test_token_bucket_refills_over_time() {
+        let limiter = RateLimiter::new(2.0, 1, false);
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let now = Instant::now();
+        assert!(limiter.check_at(client, now).is_ok());
+        assert_eq!(
+            limiter.check_at(client, now),
+            Err(Duration::from_millis(500))
+        );
+        assert!(limiter
+            .check_at(client, now + Duration::from_millis(500))
+            .is_ok());
+    }
+}
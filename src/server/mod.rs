@@ -15,42 +15,65 @@
 //! It is designed to produce an `Infallible` service from the router's perspective, meaning
 //! its own errors are either handled internally by Axum (e.g., 404s) or are panics caught by `CatchPanicLayer`.
 //!
-//! `BoxCloneService` is utilized for type erasure to simplify service types, making them suitable
-//! for use with `axum::serve`.
+//! The timeout and error-handling wrappers stay generic over the request body so the composed
+//! service can be served by `axum_server`, whose connections are wrapped in a
+//! [`connection::WriteTimeoutStream`] to enforce the configured `write_timeout`.
 
-use crate::config::Config;
+use crate::config::{Config, LiveConfig};
 use crate::http::errors::AppError;
-use crate::http::handlers::health_handler::{health_check, metrics, readiness_check};
+use crate::http::handlers::analyze_handler::analyze_image;
+use crate::http::handlers::batch_handler::{process_batch, process_batch_stream};
+use crate::http::handlers::capabilities_handler::capabilities;
+use crate::http::handlers::compare_handler::compare_images;
+use crate::http::handlers::config_handler::effective_config;
+use crate::http::handlers::health_handler::{
+    health_check, metrics, readiness_check, HealthChecksConfig,
+};
 use crate::http::handlers::pipeline_handler::process_pipeline;
-use crate::server::middleware::{concurrency_limit_middleware, metrics_middleware};
+use crate::http::handlers::sign_handler::sign_payload;
+use crate::http::handlers::srcset_handler::create_srcset;
+use crate::http::handlers::validate_handler::validate_pipeline;
+use crate::http::handlers::version_handler::version;
+use crate::security::{SecurityConfig, SecurityHeadersConfig};
+use crate::server::connection::WriteTimeoutAcceptor;
+use crate::server::middleware::{
+    authenticate, concurrency_limit_middleware, cors_middleware, metrics_middleware,
+    rate_limit_middleware, request_id_middleware, security_headers, security_headers_middleware,
+    RateLimiter, REQUEST_ID_HEADER,
+};
+use arc_swap::ArcSwap;
 use axum::{
     body::Body,
-    http::{HeaderName, Response, StatusCode},
+    extract::DefaultBodyLimit,
+    http::{HeaderName, HeaderValue, Response, StatusCode},
     response::IntoResponse,
-    routing::{get, post},
-    BoxError, Json, Router, ServiceExt,
+    routing::{any, get, post},
+    BoxError, Extension, Json, Router, ServiceExt,
 };
-use serde::Deserialize;
+use axum_server::Handle;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::future::Future;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
-use tokio::net::TcpListener;
 use tokio::sync::Semaphore;
-use tower::util::BoxCloneService;
 use tower::ServiceBuilder;
 use tower_http::{
     catch_panic::CatchPanicLayer,
     compression::CompressionLayer,
-    cors::{Any, CorsLayer},
-    request_id::{MakeRequestUuid, SetRequestIdLayer},
+    cors::{AllowOrigin, Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer},
 };
 use tracing::{info, Level};
 
+pub mod connection;
+pub mod cpu_pool;
+pub mod governor;
 pub mod middleware;
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Default)]
 pub struct ServerConfig {
     #[serde(default = "default_port")]
     #[allow(dead_code)]
@@ -60,14 +83,106 @@ pub struct ServerConfig {
     #[serde(default = "default_read_timeout")]
     #[allow(dead_code)]
     pub read_timeout: u64,
+    /// Seconds a connection may stay blocked writing a response before it is closed.
     #[serde(default = "default_write_timeout")]
-    #[allow(dead_code)]
     pub write_timeout: u64,
+    /// Maximum number of requests processed concurrently (0 = unlimited).
+    /// Overridden by the `--concurrency` CLI flag when given.
     #[serde(default = "default_concurrency")]
-    #[allow(dead_code)]
     pub concurrency: usize,
+    /// Image work (decoding, operations, encoding) that may wait for a thread of the CPU pool,
+    /// which has `concurrency` threads; more is rejected with 503 (0 = unlimited).
+    #[serde(default = "default_cpu_queue_size")]
+    pub cpu_queue_size: usize,
     #[serde(default = "default_max_body_size")]
     pub max_body_size: usize,
+    /// Maximum time in seconds a single pipeline operation may run (0 = unlimited).
+    #[serde(default = "default_max_operation_duration")]
+    pub max_operation_duration: u64,
+    /// How endpoints that only handle static images treat animated inputs.
+    #[serde(default)]
+    pub animated_input_policy: AnimatedInputPolicy,
+    /// Largest input, in pixels, that will be decoded (0 = unlimited). Checked against the
+    /// dimensions declared in the image header, before decoding.
+    #[serde(default = "default_max_image_pixels")]
+    pub max_input_pixels: u64,
+    /// Most frames of an animated GIF that will be decoded (0 = unlimited). All frames
+    /// together must also stay within `max_input_pixels`.
+    #[serde(default = "default_max_frames")]
+    pub max_frames: usize,
+    /// Largest image, in pixels, any pipeline operation may produce (0 = unlimited).
+    #[serde(default = "default_max_image_pixels")]
+    pub max_output_pixels: u64,
+    /// Largest width or height any pipeline operation may produce (0 = unlimited).
+    #[serde(default = "default_max_output_dimension")]
+    pub max_output_dimension: u32,
+    /// Source megapixels processed per second across all requests (0 = unlimited).
+    #[serde(default)]
+    pub max_megapixels_per_second: f64,
+    /// Sustained requests per second allowed per client IP (0 = unlimited).
+    #[serde(default)]
+    pub rate_limit_per_second: f64,
+    /// Requests a client may make in a burst before being limited.
+    #[serde(default = "default_rate_limit_burst")]
+    pub rate_limit_burst: u32,
+    /// Key clients by the last `X-Forwarded-For` address, the one the proxy appended, instead
+    /// of the peer address. Only enable this behind a single trusted reverse proxy.
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+    /// Add `X-Imaginary-Timings` with per-operation durations to pipeline responses. Meant for
+    /// debugging, as it exposes processing costs to clients.
+    #[serde(default)]
+    pub timings_header: bool,
+    /// Seconds in-flight requests get to finish after SIGTERM/SIGINT before connections are
+    /// closed.
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+    /// Redirects followed when fetching an image URL; every hop gets the SSRF checks.
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+    /// Extra attempts for an image URL fetch that failed to connect, timed out or got a 5xx
+    /// response; 0 disables retries.
+    #[serde(default = "default_fetch_retries")]
+    pub fetch_retries: u32,
+    /// Delay before the first fetch retry, in milliseconds; it doubles for every further retry.
+    #[serde(default = "default_fetch_retry_backoff_ms")]
+    pub fetch_retry_backoff_ms: u64,
+    /// Image URL fetches (inputs and overlays) in flight at once, across all requests; a fetch
+    /// that can't get a slot quickly is rejected with 503 (0 = unlimited).
+    #[serde(default = "default_max_concurrent_fetches")]
+    pub max_concurrent_fetches: usize,
+    /// Slots for `max_concurrent_fetches`, created on first use.
+    #[serde(skip)]
+    pub fetch_slots: OnceLock<Arc<Semaphore>>,
+    /// Most operations one pipeline may contain; 0 disables the limit.
+    #[serde(default = "default_max_pipeline_operations")]
+    pub max_pipeline_operations: usize,
+    /// Input formats accepted, by file extension (e.g. `jpg`, `tiff`). Checked against the
+    /// detected format before decoding; an empty list accepts any format this build decodes.
+    #[serde(default = "default_allowed_input_formats")]
+    pub allowed_input_formats: Vec<String>,
+    /// Reject uploaded images whose multipart `Content-Type` names a different format than
+    /// their content (e.g. polyglot files labelled `text/html`).
+    #[serde(default)]
+    pub strict_content_type: bool,
+    /// Directory holding the TTF fonts a `watermark` may name in `font_path`; empty allows only
+    /// the embedded font.
+    #[serde(default)]
+    pub fonts_dir: String,
+    /// System checks performed by `/ready` (`[server.health_checks]`).
+    #[serde(default)]
+    pub health_checks: HealthChecksConfig,
+}
+
+/// Policy for animated inputs (multi-frame GIF, APNG) on endpoints that only process one frame.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimatedInputPolicy {
+    /// Reject the request with a 400 Bad Request.
+    Reject,
+    /// Process only the first frame and flag the response with `X-First-Frame-Only`.
+    #[default]
+    FirstFrame,
 }
 
 fn default_port() -> u16 {
@@ -85,16 +200,70 @@ fn default_write_timeout() -> u64 {
 fn default_concurrency() -> usize {
     4
 }
+fn default_cpu_queue_size() -> usize {
+    64
+}
 fn default_max_body_size() -> usize {
     10 * 1024 * 1024
 }
+fn default_max_operation_duration() -> u64 {
+    10
+}
+fn default_max_image_pixels() -> u64 {
+    100_000_000
+}
+fn default_max_frames() -> usize {
+    200
+}
+fn default_max_output_dimension() -> u32 {
+    16_384
+}
+fn default_rate_limit_burst() -> u32 {
+    20
+}
+fn default_shutdown_timeout() -> u64 {
+    30
+}
+fn default_max_redirects() -> usize {
+    5
+}
+fn default_fetch_retries() -> u32 {
+    2
+}
+fn default_max_concurrent_fetches() -> usize {
+    32
+}
+fn default_fetch_retry_backoff_ms() -> u64 {
+    100
+}
+fn default_max_pipeline_operations() -> usize {
+    25
+}
+fn default_allowed_input_formats() -> Vec<String> {
+    ["png", "jpeg", "gif", "webp", "bmp", "tiff"]
+        .map(str::to_string)
+        .to_vec()
+}
 
 pub fn create_router(config: Arc<Config>) -> Router {
+    create_live_router(Arc::new(ArcSwap::new(config)))
+}
+
+/// [`create_router`] that reads the CORS origins and the API key from `live` on every request,
+/// so a [`reload_config`](crate::config::reload_config) takes effect without a restart.
+///
+/// Handlers and the remaining middleware keep the configuration loaded when the router was built.
+pub fn create_live_router(live: LiveConfig) -> Router {
+    let config = live.load_full();
     let common_middleware = ServiceBuilder::new()
         .layer(SetRequestIdLayer::new(
-            HeaderName::from_static("x-request-id"),
+            HeaderName::from_static(REQUEST_ID_HEADER),
             MakeRequestUuid,
         ))
+        // Echoes the ID on every response, including those CORS and the layers below answer
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            REQUEST_ID_HEADER,
+        )))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(
@@ -109,20 +278,132 @@ pub fn create_router(config: Arc<Config>) -> Router {
                         .latency_unit(tower_http::LatencyUnit::Micros),
                 ),
         )
-        .layer(CorsLayer::new().allow_origin(Any))
+        .layer(axum::middleware::from_fn_with_state(
+            live.clone(),
+            cors_middleware,
+        ))
         .layer(CompressionLayer::new())
+        .layer(axum::middleware::from_fn(request_id_middleware))
         .layer(CatchPanicLayer::new());
 
-    Router::new()
+    // Processing endpoints require the API key (or, for GET, a valid URL signature); health
+    // and metrics stay open for probes
+    let protected = Router::new()
+        // Every method reaches the handler, which serves GET and POST and answers the rest
+        // with 405 and an `Allow` header
+        .route("/pipeline", any(process_pipeline))
+        .route("/pipeline/validate", post(validate_pipeline))
+        .route("/batch", post(process_batch))
+        .route("/batch/stream", post(process_batch_stream))
+        .route("/analyze", post(analyze_image))
+        .route("/compare", post(compare_images))
+        .route("/srcset", post(create_srcset))
+        .route("/sign", post(sign_payload))
+        .route_layer(axum::middleware::from_fn_with_state(
+            live.clone(),
+            authenticate,
+        ));
+
+    let mut router = Router::new()
         .route("/health", get(health_check))
         .route("/ready", get(readiness_check))
         .route("/metrics", get(metrics))
-        .route("/pipeline", post(process_pipeline))
+        .route("/config", get(effective_config))
+        .route("/capabilities", get(capabilities))
+        .route("/version", get(version))
+        .merge(protected);
+
+    // Bodies are cut off at the configured size while they are read, so an oversized upload
+    // fails with 413 instead of being buffered before the handlers' own size checks
+    router = router.layer(DefaultBodyLimit::max(config.server.max_body_size));
+
+    if config.server.rate_limit_per_second > 0.0 {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            Arc::new(RateLimiter::new(
+                config.server.rate_limit_per_second,
+                config.server.rate_limit_burst,
+                config.server.trust_forwarded_for,
+            )),
+            rate_limit_middleware,
+        ));
+    }
+
+    with_concurrency_limit(router, &config.server)
         .layer(axum::middleware::from_fn(metrics_middleware))
+        .layer(Extension(live))
         .layer(common_middleware)
         .with_state(config)
 }
 
+/// CORS layer allowing the origins in `security.allowed_origins`; `"*"` allows any origin.
+///
+/// Responses to other origins carry no `Access-Control-Allow-Origin` header, so browsers block
+/// them.
+pub fn cors_layer(security: &SecurityConfig) -> CorsLayer {
+    let origins = security.allowed_origins();
+    if origins.iter().any(|origin| origin == "*") {
+        return CorsLayer::new().allow_origin(Any);
+    }
+    let origins: Vec<HeaderValue> = origins
+        .iter()
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                tracing::warn!(origin = %origin, "Ignoring invalid CORS origin");
+                None
+            }
+        })
+        .collect();
+    CorsLayer::new().allow_origin(AllowOrigin::list(origins))
+}
+
+impl ServerConfig {
+    /// The semaphore limiting concurrent URL fetches, or `None` when they are unlimited.
+    pub fn fetch_slots(&self) -> Option<&Arc<Semaphore>> {
+        (self.max_concurrent_fetches > 0).then(|| {
+            self.fetch_slots
+                .get_or_init(|| Arc::new(Semaphore::new(self.max_concurrent_fetches)))
+        })
+    }
+}
+
+/// Builds the request-concurrency semaphore from the resolved `server.concurrency` value.
+///
+/// Returns `None` when concurrency is unlimited (0).
+pub fn concurrency_semaphore(config: &ServerConfig) -> Option<Arc<Semaphore>> {
+    (config.concurrency > 0).then(|| Arc::new(Semaphore::new(config.concurrency)))
+}
+
+/// Limits how many requests the router handles at once to `server.concurrency`.
+///
+/// Requests beyond the limit wait for a permit rather than being rejected. Applied by
+/// [`create_router`], so both the HTTP/1.1 and HTTP/2 listeners honor it.
+pub fn with_concurrency_limit<S>(router: Router<S>, config: &ServerConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    match concurrency_semaphore(config) {
+        Some(semaphore) => router.layer(axum::middleware::from_fn_with_state(
+            semaphore,
+            concurrency_limit_middleware,
+        )),
+        None => router,
+    }
+}
+
+/// Adds HSTS, `X-Content-Type-Options` and `Referrer-Policy` headers to every response.
+///
+/// Intended for the TLS listener; returns the router unchanged when the headers are disabled.
+pub fn with_security_headers(router: Router, config: &SecurityHeadersConfig) -> Router {
+    if !config.enabled {
+        return router;
+    }
+    router.layer(axum::middleware::from_fn_with_state(
+        Arc::new(security_headers(config)),
+        security_headers_middleware,
+    ))
+}
+
 // Define the error handler as a standalone async function
 #[allow(dead_code)]
 async fn outer_error_handler(err: BoxError) -> Response<Body> {
@@ -142,11 +423,84 @@ async fn outer_error_handler(err: BoxError) -> Response<Body> {
     }
 }
 
+/// Resolves when the process receives Ctrl+C (SIGINT) or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
+
+/// Reloads the configuration into `live` every time the process receives SIGHUP.
+///
+/// A configuration that fails to load is logged and the running one is kept.
+#[cfg(unix)]
+pub fn reload_on_sighup(
+    matches: clap::ArgMatches,
+    live: LiveConfig,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to install SIGHUP handler; config reload disabled");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            match crate::config::reload_config(&matches, &live) {
+                Ok(()) => info!("Received SIGHUP, configuration reloaded"),
+                Err(e) => tracing::error!(
+                    error = %e,
+                    "Received SIGHUP but the configuration failed to load; keeping the current one"
+                ),
+            }
+        }
+    })
+}
+
+/// Gracefully shuts down every server in `handles` once `signal` resolves.
+///
+/// The servers stop accepting connections right away; in-flight requests get `drain_timeout`
+/// to finish before the remaining connections are closed.
+pub fn graceful_shutdown_on<F>(
+    signal: F,
+    handles: Vec<Handle>,
+    drain_timeout: Duration,
+) -> tokio::task::JoinHandle<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        signal.await;
+        info!(
+            "Shutting down, draining in-flight requests for up to {:?}",
+            drain_timeout
+        );
+        for handle in handles {
+            handle.graceful_shutdown(Some(drain_timeout));
+        }
+    })
+}
+
 #[allow(dead_code)]
-pub async fn run_server(
-    config: Arc<Config>,
-    semaphore: Option<Arc<Semaphore>>,
-) -> Result<(), AppError> {
+pub async fn run_server(config: Arc<Config>) -> Result<(), AppError> {
     let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port)
         .parse()
         .map_err(|_| AppError::InternalServerError("Failed to parse address".to_string()))?;
@@ -157,58 +511,14 @@ pub async fn run_server(
     std_listener.set_nonblocking(true).map_err(|e| {
         AppError::InternalServerError(format!("Failed to set std listener to non-blocking: {}", e))
     })?;
-    let listener = TcpListener::from_std(std_listener).map_err(|e| {
-        AppError::InternalServerError(format!(
-            "Failed to convert std listener to tokio listener: {}",
-            e
-        ))
-    })?;
 
-    let common_middleware = ServiceBuilder::new()
-        .layer(SetRequestIdLayer::new(
-            HeaderName::from_static("x-request-id"),
-            MakeRequestUuid,
-        ))
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(
-                    DefaultMakeSpan::new()
-                        .level(Level::INFO)
-                        .include_headers(true),
-                )
-                .on_request(DefaultOnRequest::new().level(Level::INFO))
-                .on_response(
-                    DefaultOnResponse::new()
-                        .level(Level::INFO)
-                        .latency_unit(tower_http::LatencyUnit::Micros),
-                ),
-        )
-        .layer(CorsLayer::new().allow_origin(Any))
-        .layer(CompressionLayer::new())
-        .layer(CatchPanicLayer::new());
-
-    let mut router = Router::new()
-        .route("/health", get(health_check))
-        .route("/ready", get(readiness_check))
-        .route("/metrics", get(metrics))
-        .route("/pipeline", post(process_pipeline))
-        .layer(axum::middleware::from_fn(metrics_middleware))
-        .layer(common_middleware)
-        .with_state(config.clone());
-
-    if let Some(semaphore) = semaphore {
-        router = router.layer(axum::middleware::from_fn_with_state(
-            semaphore,
-            concurrency_limit_middleware,
-        ));
-    }
+    let router = create_router(config.clone());
 
-    let app_service_inner = BoxCloneService::new(router);
     let timeout_duration = config.server.read_timeout;
 
     // Explicitly compose Timeout and HandleError services
     let timed_service = tower::timeout::Timeout::new(
-        app_service_inner, // This is BoxCloneService<..., Infallible>
+        router, // Router is Infallible; only the timeout can produce a BoxError
         Duration::from_secs(timeout_duration),
     );
 
@@ -216,12 +526,309 @@ pub async fn run_server(
         timed_service,
         outer_error_handler, // Takes BoxError, returns Response<Body>
     );
-    let boxed_final_service = BoxCloneService::new(final_service_logic);
+
+    let handle = Handle::new();
+    graceful_shutdown_on(
+        shutdown_signal(),
+        vec![handle.clone()],
+        Duration::from_secs(config.server.shutdown_timeout),
+    );
 
     info!("Starting server on {}", addr);
-    axum::serve(listener, boxed_final_service.into_make_service())
+    axum_server::from_tcp(std_listener)
+        .handle(handle)
+        .acceptor(WriteTimeoutAcceptor::new(Duration::from_secs(
+            config.server.write_timeout,
+        )))
+        .serve(
+            ServiceExt::<axum::http::Request<Body>>::into_make_service_with_connect_info::<
+                SocketAddr,
+            >(final_service_logic),
+        )
         .await
         .map_err(|e| AppError::InternalServerError(format!("Server failed: {}", e)))?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use tokio::sync::{mpsc, Notify};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_concurrency_limit_makes_second_request_wait() {
+        let (started_tx, mut started_rx) = mpsc::unbounded_channel();
+        let release = Arc::new(Notify::new());
+        let handler_release = release.clone();
+        let router = Router::new().route(
+            "/slow",
+            get(move || {
+                let started_tx = started_tx.clone();
+                let release = handler_release.clone();
+                async move {
+                    started_tx.send(()).unwrap();
+                    release.notified().await;
+                    "done"
+                }
+            }),
+        );
+        let config = ServerConfig {
+            concurrency: 1,
+            ..Default::default()
+        };
+        let app = with_concurrency_limit(router, &config);
+        let request = || Request::get("/slow").body(Body::empty()).unwrap();
+
+        let first = tokio::spawn(app.clone().oneshot(request()));
+        started_rx.recv().await.unwrap();
+        let second = tokio::spawn(app.oneshot(request()));
+
+        // The second request cannot reach the handler while the first holds the only permit
+        let blocked = tokio::time::timeout(Duration::from_millis(100), started_rx.recv()).await;
+        assert!(blocked.is_err(), "second request should wait for a permit");
+
+        release.notify_one();
+        assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::OK);
+        started_rx.recv().await.unwrap();
+        release.notify_one();
+        assert_eq!(second.await.unwrap().unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_drains_in_flight_requests() {
+        let (started_tx, mut started_rx) = mpsc::unbounded_channel();
+        let release = Arc::new(Notify::new());
+        let handler_release = release.clone();
+        let router = Router::new().route(
+            "/slow",
+            get(move || {
+                let started_tx = started_tx.clone();
+                let release = handler_release.clone();
+                async move {
+                    started_tx.send(()).unwrap();
+                    release.notified().await;
+                    "done"
+                }
+            }),
+        );
+
+        let handle = Handle::new();
+        let server = tokio::spawn(
+            axum_server::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .handle(handle.clone())
+                .serve(router.into_make_service()),
+        );
+        let addr = handle
+            .listening()
+            .await
+            .expect("server should be listening");
+        let url = format!("http://{}/slow", addr);
+
+        let in_flight = tokio::spawn(reqwest::Client::new().get(url.clone()).send());
+        started_rx.recv().await.unwrap();
+
+        let (signal_tx, signal_rx) = tokio::sync::oneshot::channel::<()>();
+        graceful_shutdown_on(
+            async {
+                signal_rx.await.ok();
+            },
+            vec![handle],
+            Duration::from_secs(5),
+        );
+        signal_tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // New connections are no longer served once shutdown has started
+        let late = tokio::spawn(
+            reqwest::Client::new()
+                .get(url)
+                .timeout(Duration::from_secs(2))
+                .send(),
+        );
+
+        // The request that was already running completes normally
+        release.notify_one();
+        let response = in_flight.await.unwrap().unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "done");
+
+        server.await.unwrap().unwrap();
+        assert!(
+            late.await.unwrap().is_err(),
+            "late request should be refused"
+        );
+        assert!(
+            started_rx.try_recv().is_err(),
+            "late request reached a handler"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_pipeline_method_is_405_with_allow_header() {
+        let response = create_router(Arc::new(Config::default()))
+            .oneshot(Request::delete("/pipeline").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["allow"], "GET, POST");
+    }
+
+    async fn allow_origin_header(config: Config, origin: &str) -> Option<HeaderValue> {
+        let response = create_router(Arc::new(config))
+            .oneshot(
+                Request::get("/health")
+                    .header("Origin", origin)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response
+            .headers()
+            .get("access-control-allow-origin")
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn test_cors_only_allows_configured_origins() {
+        let config = || {
+            let mut config = Config::default();
+            config
+                .security
+                .set_allowed_origins(vec!["https://app.example".to_string()]);
+            config
+        };
+        assert_eq!(
+            allow_origin_header(config(), "https://app.example").await,
+            Some(HeaderValue::from_static("https://app.example"))
+        );
+        assert_eq!(
+            allow_origin_header(config(), "https://evil.example").await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_wildcard_allows_any_origin() {
+        let mut config = Config::default();
+        config.security.set_allowed_origins(vec!["*".to_string()]);
+        assert_eq!(
+            allow_origin_header(config, "https://anything.example").await,
+            Some(HeaderValue::from_static("*"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_origins_apply_without_rebuilding_the_router() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let write_origin = |origin: &str| {
+            std::fs::write(
+                &path,
+                format!("[security]\nallowed_origins = [\"{}\"]\n", origin),
+            )
+            .unwrap();
+        };
+        write_origin("https://old.example");
+        let matches = crate::config::cli::build_cli()
+            .try_get_matches_from(["imaginary-rs", "--config", path.to_str().unwrap()])
+            .unwrap();
+        let live = Arc::new(ArcSwap::from_pointee(
+            crate::config::load_config(&matches).unwrap(),
+        ));
+        let router = create_live_router(live.clone());
+        let allow_origin = |origin: &'static str| {
+            let router = router.clone();
+            async move {
+                router
+                    .oneshot(
+                        Request::get("/health")
+                            .header("Origin", origin)
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap()
+                    .headers()
+                    .get("access-control-allow-origin")
+                    .cloned()
+            }
+        };
+        assert_eq!(allow_origin("https://new.example").await, None);
+
+        write_origin("https://new.example");
+        crate::config::reload_config(&matches, &live).unwrap();
+
+        assert_eq!(
+            allow_origin("https://new.example").await,
+            Some(HeaderValue::from_static("https://new.example"))
+        );
+        assert_eq!(allow_origin("https://old.example").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_key_signs_and_is_reported() {
+        use crate::security::ApiKey;
+
+        let config_with_key = |key: &str| {
+            let mut config = Config {
+                server: ServerConfig {
+                    max_body_size: 1024 * 1024,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            config.security.set_key(ApiKey::from(key.to_string()));
+            config
+        };
+        let old_key = "old-key-0123456789abcdef0123456789";
+        let new_key = "new-key-0123456789abcdef0123456789";
+        let live = Arc::new(ArcSwap::from_pointee(config_with_key(old_key)));
+        let router = create_live_router(live.clone());
+        live.store(Arc::new(config_with_key(new_key)));
+
+        let payload = "/pipeline?url=https://example.com/a.png";
+        let response = router
+            .clone()
+            .oneshot(
+                Request::post("/sign")
+                    .header("x-api-key", new_key)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(json!({ "payload": payload }).to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let expected = config_with_key(new_key)
+            .security
+            .generate_signature(payload.as_bytes())
+            .unwrap();
+        assert_eq!(body["signature"], expected);
+
+        // /config accepts the new key only
+        for (key, status) in [
+            (new_key, StatusCode::OK),
+            (old_key, StatusCode::UNAUTHORIZED),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::get("/config")
+                        .header("x-api-key", key)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status);
+        }
+    }
+}
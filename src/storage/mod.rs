@@ -1,13 +1,13 @@
 use anyhow::Result;
 use cached::proc_macro::cached;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use tracing::info;
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct StorageConfig {
     #[serde(default = "default_temp_dir")]
     pub temp_dir: PathBuf,
@@ -61,6 +61,7 @@ pub fn get_metadata_hash(
     None
 }
 
+#[allow(dead_code)] // For future cache management features
 pub fn check_cached_metadata(
     filename: &str,
     content_length: usize,
@@ -84,6 +85,7 @@ pub fn check_cached_metadata(
 }
 
 // Generate operation hash
+#[allow(dead_code)] // For future cache management features
 pub fn generate_operation_hash(image_path: &Path, operation: &str, params: &str) -> Result<String> {
     let mut hasher = Sha256::new();
 
@@ -100,6 +102,7 @@ pub fn generate_operation_hash(image_path: &Path, operation: &str, params: &str)
     Ok(format!("{:x}", hasher.finalize()))
 }
 
+#[allow(dead_code)] // For future cache management features
 pub fn cache_result(image_path: &Path, operation: &str, params: &str, _result_path: &Path) {
     if let Ok(hash) = generate_operation_hash(image_path, operation, params) {
         let cached = get_cached_result(image_path.to_path_buf(), operation, params);
@@ -109,6 +112,7 @@ pub fn cache_result(image_path: &Path, operation: &str, params: &str, _result_pa
     }
 }
 
+#[allow(dead_code)] // For future cache management features
 pub fn get_result(image_path: &Path, operation: &str, params: &str) -> Option<PathBuf> {
     get_cached_result(image_path.to_path_buf(), operation, params)
 }
@@ -1,7 +1,10 @@
-use crate::http::errors;
+use crate::http::errors::{self, AppError};
+use crate::image::format_support::decode_error;
 use crate::image::params::Validate;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
 use image::io::Reader as ImageReader;
-use image::{DynamicImage, GenericImageView, ImageFormat};
+use image::{AnimationDecoder, DynamicImage, GenericImageView, ImageFormat};
 use std::fs::File;
 use std::io::Cursor;
 
@@ -49,6 +52,31 @@ pub fn save_image_to_bytes(image: &DynamicImage, format: ImageFormat) -> Result<
     Ok(buffer)
 }
 
+/// Reads the width and height from the image header without decoding any pixels.
+///
+/// Size limits run on this before the full decode, so a small file declaring huge dimensions
+/// (a decompression bomb) is rejected before its buffer is allocated.
+pub fn probe_dimensions(image_bytes: &[u8], format: ImageFormat) -> Result<(u32, u32), AppError> {
+    ImageReader::with_format(Cursor::new(image_bytes), format)
+        .into_dimensions()
+        .map_err(|e| decode_error(format, e))
+}
+
+/// Returns true if the encoded image contains more than one frame (animated GIF or APNG).
+///
+/// Formats without animation support, or data that cannot be decoded, report `false`.
+pub fn is_animated(image_bytes: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(image_bytes))
+            .map(|decoder| decoder.into_frames().take(2).count() > 1)
+            .unwrap_or(false),
+        ImageFormat::Png => PngDecoder::new(Cursor::new(image_bytes))
+            .map(|decoder| decoder.is_apng())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
 // Add a new function to validate parameters
 #[allow(dead_code)]
 pub fn validate_params<T: Validate>(params: &T) -> Result<(), errors::ImageError> {
@@ -75,6 +103,15 @@ mod tests {
         assert_eq!(dimensions, Some((100, 100)));
     }
 
+    #[test]
+    fn test_probe_dimensions_reads_the_header() {
+        assert_eq!(
+            probe_dimensions(&create_test_image(), ImageFormat::Png).unwrap(),
+            (100, 100)
+        );
+        assert!(probe_dimensions(b"not an image", ImageFormat::Png).is_err());
+    }
+
     #[test]
     fn test_get_image_format() {
         let image_bytes = create_test_image();
@@ -103,4 +140,30 @@ mod tests {
         let bytes = result.unwrap();
         assert!(!bytes.is_empty());
     }
+
+    #[test]
+    fn test_is_animated() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, RgbaImage};
+
+        let mut animated = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut animated);
+            let frames = [Rgba([255u8, 0, 0, 255]), Rgba([0u8, 0, 255, 255])]
+                .into_iter()
+                .map(|color| {
+                    Frame::from_parts(
+                        RgbaImage::from_pixel(10, 10, color),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(100, 1),
+                    )
+                });
+            encoder.encode_frames(frames).unwrap();
+        }
+        assert!(is_animated(&animated, ImageFormat::Gif));
+
+        let still = create_test_image();
+        assert!(!is_animated(&still, ImageFormat::Png));
+    }
 }
@@ -1,2 +1,3 @@
 pub mod image_utils;
 pub mod logger;
+pub mod single_flight;
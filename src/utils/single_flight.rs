@@ -0,0 +1,159 @@
+//! Single-flight request coalescing.
+//!
+//! When several callers ask for the same key at the same time, only the first one runs the
+//! computation; the others wait for it and receive a clone of its result. Entries are removed
+//! as soon as the computation finishes, or once every caller waiting on it is gone, so results
+//! are not cached beyond the in-flight window.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+pub struct SingleFlight<T> {
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs `compute` for `key`, or joins a computation already in progress for it.
+    ///
+    /// If the caller running the computation is cancelled, one of the waiting callers takes
+    /// over and runs its own `compute`.
+    pub async fn run<F, Fut>(&self, key: &str, compute: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let flight = Flight {
+            flights: self,
+            key,
+            cell: self
+                .lock()
+                .entry(key.to_string())
+                .or_insert_with(|| Arc::new(OnceCell::new()))
+                .clone(),
+        };
+        flight.cell.get_or_init(compute).await.clone()
+    }
+}
+
+impl<T> SingleFlight<T> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<OnceCell<T>>>> {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// One caller's share of an in-flight computation.
+///
+/// Dropping it, when the computation finishes or the caller is cancelled, removes the entry
+/// once it has a result or no other caller is left waiting on it.
+struct Flight<'a, T> {
+    flights: &'a SingleFlight<T>,
+    key: &'a str,
+    cell: Arc<OnceCell<T>>,
+}
+
+impl<T> Drop for Flight<'_, T> {
+    fn drop(&mut self) {
+        let mut in_flight = self.flights.lock();
+        // The map holds one reference and this caller another; callers clone it under the lock
+        let abandoned = Arc::strong_count(&self.cell) == 2;
+        if (self.cell.initialized() || abandoned)
+            && in_flight
+                .get(self.key)
+                .is_some_and(|current| Arc::ptr_eq(current, &self.cell))
+        {
+            in_flight.remove(self.key);
+        }
+    }
+}
+
+impl<T: Clone> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_identical_requests_share_one_computation() {
+        let flights = Arc::new(SingleFlight::<Arc<Vec<u8>>>::new());
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let flights = flights.clone();
+                let runs = runs.clone();
+                tokio::spawn(async move {
+                    flights
+                        .run("resize:200x200", || async move {
+                            runs.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            Arc::new(vec![1, 2, 3])
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            assert_eq!(*task.await.unwrap(), vec![1, 2, 3]);
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_finished_computations_are_not_reused() {
+        let flights = SingleFlight::<usize>::new();
+        assert_eq!(flights.run("key", || async { 1 }).await, 1);
+        assert_eq!(flights.run("key", || async { 2 }).await, 2);
+        assert_eq!(flights.run("other", || async { 3 }).await, 3);
+        assert!(flights.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_computations_are_removed() {
+        let flights = SingleFlight::<usize>::new();
+        let abandoned = tokio::time::timeout(
+            Duration::from_millis(10),
+            flights.run("key", std::future::pending::<usize>),
+        )
+        .await;
+        assert!(abandoned.is_err());
+        assert!(flights.lock().is_empty());
+        assert_eq!(flights.run("key", || async { 1 }).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_waiting_caller_takes_over_a_cancelled_computation() {
+        let flights = Arc::new(SingleFlight::<usize>::new());
+        let first = tokio::spawn({
+            let flights = flights.clone();
+            async move { flights.run("key", std::future::pending::<usize>).await }
+        });
+        while flights.lock().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        let second = tokio::spawn({
+            let flights = flights.clone();
+            async move { flights.run("key", || async { 2 }).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        first.abort();
+        assert_eq!(second.await.unwrap(), 2);
+        assert!(flights.lock().is_empty());
+    }
+}
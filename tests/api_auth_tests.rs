@@ -0,0 +1,166 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use imaginary::config::Config;
+use imaginary::security::ApiKey;
+use imaginary::server::{create_router, ServerConfig};
+use std::io::Cursor;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const API_KEY: &str = "integration-test-key-0123456789abcdef";
+const BOUNDARY: &str = "imaginary-auth-boundary";
+
+fn test_config() -> Config {
+    Config {
+        server: ServerConfig {
+            max_body_size: 1024 * 1024,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn app_with_key() -> axum::Router {
+    let mut config = test_config();
+    config.security.set_key(ApiKey::from(API_KEY.to_string()));
+    create_router(Arc::new(config))
+}
+
+/// Builds a multipart `/pipeline` request with a small PNG and a grayscale operation.
+fn pipeline_request(api_key: Option<&str>) -> Request<Body> {
+    let mut png = Vec::new();
+    image::DynamicImage::new_rgb8(8, 8)
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"test.png\"\r\nContent-Type: image/png\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(&png);
+    body.extend_from_slice(
+        format!(
+            "\r\n--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"operations\"\r\n\r\n[{{\"operation\": \"grayscale\"}}]\r\n--{BOUNDARY}--\r\n"
+        )
+        .as_bytes(),
+    );
+
+    let mut builder = Request::post("/pipeline").header(
+        "Content-Type",
+        format!("multipart/form-data; boundary={BOUNDARY}"),
+    );
+    if let Some(api_key) = api_key {
+        builder = builder.header("x-api-key", api_key);
+    }
+    builder.body(Body::from(body)).unwrap()
+}
+
+#[tokio::test]
+async fn test_pipeline_without_api_key_is_unauthorized() {
+    let response = app_with_key()
+        .oneshot(pipeline_request(None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_pipeline_with_wrong_api_key_is_unauthorized() {
+    let response = app_with_key()
+        .oneshot(pipeline_request(Some("not-the-configured-key")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_pipeline_with_truncated_api_key_is_unauthorized() {
+    // A prefix whose length differs from the key's by a multiple of 256
+    let key = "k".repeat(257);
+    let mut config = test_config();
+    config.security.set_key(ApiKey::from(key.clone()));
+    let response = create_router(Arc::new(config))
+        .oneshot(pipeline_request(Some(&key[..1])))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_pipeline_with_correct_api_key_succeeds() {
+    let response = app_with_key()
+        .oneshot(pipeline_request(Some(API_KEY)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/png");
+}
+
+#[tokio::test]
+async fn test_health_does_not_require_api_key() {
+    let response = app_with_key()
+        .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_pipeline_is_open_without_configured_key() {
+    let response = create_router(Arc::new(test_config()))
+        .oneshot(pipeline_request(None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_sign_requires_api_key() {
+    let sign_request = |api_key: Option<&str>| {
+        let mut builder = Request::post("/sign").header("Content-Type", "application/json");
+        if let Some(api_key) = api_key {
+            builder = builder.header("x-api-key", api_key);
+        }
+        builder.body(Body::from(r#"{"payload": "hello"}"#)).unwrap()
+    };
+
+    let response = app_with_key().oneshot(sign_request(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app_with_key()
+        .oneshot(sign_request(Some(API_KEY)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// A GET `/pipeline` URL for an image on a private address, which the SSRF checks refuse, so
+/// requests that get past authentication end with `400` instead of a fetch.
+const SIGNED_PATH: &str =
+    "/pipeline?url=http://127.0.0.1/a.png&operations=%5B%7B%22operation%22%3A%22grayscale%22%7D%5D";
+
+#[tokio::test]
+async fn test_signed_get_does_not_need_the_api_key() {
+    let mut config = test_config();
+    config.security.set_key(ApiKey::from(API_KEY.to_string()));
+    let signature = config
+        .security
+        .generate_signature(SIGNED_PATH.as_bytes())
+        .unwrap();
+    let app = create_router(Arc::new(config));
+
+    let signed = Request::get(format!("{SIGNED_PATH}&sign={signature}"))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(signed).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    for uri in [SIGNED_PATH.to_string(), format!("{SIGNED_PATH}&sign=0000")] {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
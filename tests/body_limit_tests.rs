@@ -0,0 +1,69 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use imaginary::config::Config;
+use imaginary::server::{create_router, ServerConfig};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+const BOUNDARY: &str = "imaginary-limit-boundary";
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+fn app() -> axum::Router {
+    create_router(Arc::new(Config {
+        server: ServerConfig {
+            max_body_size: MAX_BODY_SIZE,
+            ..Default::default()
+        },
+        ..Default::default()
+    }))
+}
+
+/// Builds a multipart request whose `operations` field is `size` bytes of padding.
+///
+/// The handlers never size-check `operations`, so only the router's body limit can turn this
+/// into a `413`.
+fn padded_request(path: &str, size: usize) -> Request<Body> {
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"operations\"\r\n\r\n[{{\"operation\": \"grayscale\"}}]"
+    )
+    .into_bytes();
+    body.resize(body.len() + size, b' ');
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+    Request::post(path)
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header("Content-Length", body.len())
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_oversized_multipart_body_is_rejected_with_413() {
+    for path in [
+        "/pipeline",
+        "/batch",
+        "/batch/stream",
+        "/analyze",
+        "/compare",
+        "/srcset",
+    ] {
+        let response = app()
+            .oneshot(padded_request(path, MAX_BODY_SIZE * 4))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_body_within_limit_reaches_the_handler() {
+    // No image field: the handler itself rejects the request, not the body limit
+    let response = app()
+        .oneshot(padded_request("/pipeline", 1024))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
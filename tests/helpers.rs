@@ -49,5 +49,5 @@ pub fn save_test_image(img: &DynamicImage, filename: &str) -> std::io::Result<()
     std::fs::create_dir_all(&path)?;
     path.push(filename);
     img.save(path)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+        .map_err(|e| std::io::Error::other(e.to_string()))
 }
@@ -19,11 +19,13 @@ fn test_complete_pipeline_with_real_image() {
                 "width": original_dimensions.0 / 2,
                 "height": original_dimensions.1 / 2
             }),
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Grayscale,
             ignore_failure: false,
             params: json!({}),
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Watermark,
@@ -35,12 +37,13 @@ fn test_complete_pipeline_with_real_image() {
                 "font_size": 24,
                 "color": [255, 255, 255]
             }),
+            region: None,
         },
     ];
 
     let result = execute_pipeline(image, operations);
     assert!(result.is_ok());
-    let processed = result.unwrap();
+    let processed = result.unwrap().image;
     assert_eq!(
         processed.dimensions(),
         (original_dimensions.0 / 2, original_dimensions.1 / 2)
@@ -58,6 +61,7 @@ fn test_format_conversion_pipeline() {
             "format": "jpeg",
             "quality": 85
         }),
+        region: None,
     }];
 
     let result = execute_pipeline(image, operations);
@@ -78,12 +82,14 @@ fn test_complex_pipeline_with_error_handling() {
                 "width": 0,  // Invalid width
                 "height": original_dimensions.1 / 2
             }),
+            region: None,
         },
         // This operation should succeed
         PipelineOperationSpec {
             operation: SupportedOperation::Grayscale,
             ignore_failure: false,
             params: json!({}),
+            region: None,
         },
         // This operation should succeed
         PipelineOperationSpec {
@@ -92,12 +98,13 @@ fn test_complex_pipeline_with_error_handling() {
             params: json!({
                 "sigma": 1.0
             }),
+            region: None,
         },
     ];
 
     let result = execute_pipeline(image, operations);
     assert!(result.is_ok());
-    let processed = result.unwrap();
+    let processed = result.unwrap().image;
     // Image should maintain original dimensions since resize failed but was ignored
     assert_eq!(processed.dimensions(), original_dimensions);
 }
@@ -114,6 +121,7 @@ fn test_pipeline_with_different_image_formats() {
                 "width": 100,
                 "height": 100
             }),
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Convert,
@@ -122,12 +130,13 @@ fn test_pipeline_with_different_image_formats() {
                 "format": "png",
                 "quality": 90
             }),
+            region: None,
         },
     ];
 
     let result = execute_pipeline(tiff_image, operations);
     assert!(result.is_ok());
-    let processed = result.unwrap();
+    let processed = result.unwrap().image;
     assert_eq!(processed.dimensions(), (100, 100));
 }
 
@@ -143,6 +152,7 @@ fn test_pipeline_with_rotation_and_blur() {
             params: json!({
                 "degrees": 90
             }),
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Blur,
@@ -150,12 +160,13 @@ fn test_pipeline_with_rotation_and_blur() {
             params: json!({
                 "sigma": 2.0
             }),
+            region: None,
         },
     ];
 
     let result = execute_pipeline(image, operations);
     assert!(result.is_ok());
-    let processed = result.unwrap();
+    let processed = result.unwrap().image;
     // After 90-degree rotation, dimensions should be swapped
     assert_eq!(
         processed.dimensions(),
@@ -173,12 +184,13 @@ fn test_resize_pipeline() {
             "width": 50,
             "height": 50
         }),
+        region: None,
     }];
 
     let result = execute_pipeline(image, operations);
     assert!(result.is_ok());
 
-    let processed = result.unwrap();
+    let processed = result.unwrap().image;
     assert_eq!(processed.dimensions(), (50, 50));
 }
 
@@ -191,6 +203,7 @@ fn test_blur_pipeline() {
         params: json!({
             "sigma": 1.0
         }),
+        region: None,
     }];
 
     let result = execute_pipeline(image, operations);
@@ -210,6 +223,7 @@ fn test_complex_pipeline() {
                 "width": original_dimensions.0 / 2,
                 "height": original_dimensions.1 / 2
             }),
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Blur,
@@ -217,6 +231,7 @@ fn test_complex_pipeline() {
             params: json!({
                 "sigma": 0.5
             }),
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Rotate,
@@ -224,13 +239,14 @@ fn test_complex_pipeline() {
             params: json!({
                 "degrees": 90.0
             }),
+            region: None,
         },
     ];
 
     let result = execute_pipeline(image, operations);
     assert!(result.is_ok());
 
-    let processed = result.unwrap();
+    let processed = result.unwrap().image;
     // After 90-degree rotation, dimensions should be swapped
     assert_eq!(
         processed.dimensions(),
@@ -252,6 +268,7 @@ fn test_pipeline_with_ignored_failures() {
                 "width": -50, // Invalid parameter
                 "height": 50
             }),
+            region: None,
         },
         PipelineOperationSpec {
             operation: SupportedOperation::Blur,
@@ -259,6 +276,7 @@ fn test_pipeline_with_ignored_failures() {
             params: json!({
                 "sigma": 1.0
             }),
+            region: None,
         },
     ];
 
@@ -276,6 +294,7 @@ fn test_pipeline_error_handling() {
             "width": -50, // Invalid parameter
             "height": 50
         }),
+        region: None,
     }];
 
     let result = execute_pipeline(image, operations);
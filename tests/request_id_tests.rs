@@ -0,0 +1,73 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use imaginary::config::Config;
+use imaginary::security::ApiKey;
+use imaginary::server::{create_router, ServerConfig};
+use serde_json::Value;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn app_with_key() -> axum::Router {
+    let mut config = Config {
+        server: ServerConfig {
+            max_body_size: 1024 * 1024,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    config.security.set_key(ApiKey::from(
+        "request-id-test-key-0123456789abcdef".to_string(),
+    ));
+    create_router(Arc::new(config))
+}
+
+async fn body_json(response: axum::response::Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_error_body_request_id_matches_header() {
+    // Unauthenticated, so the request fails before reaching the handler
+    let response = app_with_key()
+        .oneshot(Request::post("/pipeline").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let header = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(!header.is_empty());
+    let body = body_json(response).await;
+    assert_eq!(body["request_id"], header.as_str());
+    assert_eq!(body["error_code"], "unauthorized");
+}
+
+#[tokio::test]
+async fn test_client_request_id_is_echoed() {
+    let response = app_with_key()
+        .oneshot(
+            Request::post("/pipeline")
+                .header("x-request-id", "client-chosen-id")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "client-chosen-id");
+    assert_eq!(body_json(response).await["request_id"], "client-chosen-id");
+}
+
+#[tokio::test]
+async fn test_successful_response_carries_request_id() {
+    let response = app_with_key()
+        .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key("x-request-id"));
+}